@@ -87,31 +87,151 @@ impl DVIFileWriter {
         }
     }
 
-    fn add_box(&mut self, tex_box: &TeXBox) {
+    // Pushes the current position onto the DVI stack, keeping track of the
+    // deepest the stack gets for the postamble. Note that the current font
+    // isn't part of the DVI stack, so a font change made before a pop is
+    // still in effect afterwards. This means that `curr_font_num` doesn't
+    // need to be saved or restored here.
+    fn push_stack(&mut self) {
         self.commands.push(DVICommand::Push);
         self.curr_stack_depth += 1;
         if self.curr_stack_depth > self.max_stack_depth {
             self.max_stack_depth = self.curr_stack_depth;
         }
+    }
+
+    fn pop_stack(&mut self) {
+        self.commands.push(DVICommand::Pop);
+        self.curr_stack_depth -= 1;
+    }
+
+    fn add_box(&mut self, tex_box: &TeXBox) {
+        self.push_stack();
 
         match tex_box {
             TeXBox::HorizontalBox(hbox) => {
-                for elem in &hbox.list {
-                    self.add_horizontal_list_elem(&elem, &hbox.glue_set_ratio);
-                }
+                self.add_horizontal_list(
+                    &hbox.list,
+                    &hbox.glue_set_ratio,
+                    hbox.height,
+                    hbox.depth,
+                );
             }
             TeXBox::VerticalBox(vbox) => {
                 self.commands
                     .push(DVICommand::Down4(-vbox.height.as_scaled_points()));
 
-                for elem in &vbox.list {
-                    self.add_vertical_list_elem(&elem, &vbox.glue_set_ratio);
-                }
+                self.add_vertical_list(
+                    &vbox.list,
+                    &vbox.glue_set_ratio,
+                    vbox.width,
+                );
             }
         }
 
-        self.commands.push(DVICommand::Pop);
-        self.curr_stack_depth -= 1;
+        self.pop_stack();
+    }
+
+    // Adds the elements of a horizontal list with the given height and depth.
+    // Rules with running heights or depths extend to the height and depth of
+    // the list.
+    fn add_horizontal_list(
+        &mut self,
+        list: &[HorizontalListElem],
+        glue_set_ratio: &Option<GlueSetRatio>,
+        height: Dimen,
+        depth: Dimen,
+    ) {
+        for elem in list {
+            if let HorizontalListElem::Rule {
+                height: rule_height,
+                depth: rule_depth,
+                width: rule_width,
+            } = elem
+            {
+                self.add_horizontal_rule(
+                    rule_height.unwrap_or(height),
+                    rule_depth.unwrap_or(depth),
+                    rule_width.unwrap_or_else(Dimen::zero),
+                );
+            } else {
+                self.add_horizontal_list_elem(elem, glue_set_ratio);
+            }
+        }
+    }
+
+    // Adds the elements of a vertical list with the given width. Rules with
+    // running widths extend to the width of the list.
+    fn add_vertical_list(
+        &mut self,
+        list: &[VerticalListElem],
+        glue_set_ratio: &Option<GlueSetRatio>,
+        width: Dimen,
+    ) {
+        for elem in list {
+            if let VerticalListElem::Rule {
+                height: rule_height,
+                depth: rule_depth,
+                width: rule_width,
+            } = elem
+            {
+                self.add_vertical_rule(
+                    rule_height.unwrap_or_else(Dimen::zero),
+                    rule_depth.unwrap_or_else(Dimen::zero),
+                    rule_width.unwrap_or(width),
+                );
+            } else {
+                self.add_vertical_list_elem(elem, glue_set_ratio);
+            }
+        }
+    }
+
+    // Draws a rule whose baseline is at the current position, and moves right
+    // by its width. Rules with non-positive total height or width aren't
+    // drawn, but still take up space.
+    fn add_horizontal_rule(
+        &mut self,
+        height: Dimen,
+        depth: Dimen,
+        width: Dimen,
+    ) {
+        let total_height = height + depth;
+
+        if total_height > Dimen::zero() && width > Dimen::zero() {
+            // DVI rules are drawn upwards from the current position, so we
+            // move to the bottom of the rule first.
+            if depth != Dimen::zero() {
+                self.commands
+                    .push(DVICommand::Down4(depth.as_scaled_points()));
+            }
+            self.commands.push(DVICommand::SetRule {
+                height: total_height.as_scaled_points(),
+                width: width.as_scaled_points(),
+            });
+            if depth != Dimen::zero() {
+                self.commands
+                    .push(DVICommand::Down4(-depth.as_scaled_points()));
+            }
+        } else {
+            self.commands
+                .push(DVICommand::Right4(width.as_scaled_points()));
+        }
+    }
+
+    // Moves down past a rule whose top is at the current position, drawing
+    // it if it is visible.
+    fn add_vertical_rule(&mut self, height: Dimen, depth: Dimen, width: Dimen) {
+        let total_height = height + depth;
+
+        self.commands
+            .push(DVICommand::Down4(total_height.as_scaled_points()));
+
+        if total_height > Dimen::zero() && width > Dimen::zero() {
+            self.commands.push(DVICommand::PutRule {
+                height: total_height.as_scaled_points(),
+                width: width.as_scaled_points(),
+            });
+        }
     }
 
     fn add_vertical_list_elem(
@@ -131,16 +251,37 @@ impl DVIFileWriter {
                     .push(DVICommand::Down4(move_amount.as_scaled_points()));
             }
 
+            VerticalListElem::Kern(dimen) => {
+                self.commands
+                    .push(DVICommand::Down4(dimen.as_scaled_points()));
+            }
+
+            // File operations are carried out by the parser when the page
+            // is shipped out, and don't show up in the DVI file.
+            VerticalListElem::Penalty(_) | VerticalListElem::Whatsit(_) => {}
+
+            // Outside of add_vertical_list(), there's no list width to extend
+            // running widths to, so they are treated as zero.
+            VerticalListElem::Rule {
+                height,
+                depth,
+                width,
+            } => self.add_vertical_rule(
+                height.unwrap_or_else(Dimen::zero),
+                depth.unwrap_or_else(Dimen::zero),
+                width.unwrap_or_else(Dimen::zero),
+            ),
+
             VerticalListElem::Box { tex_box, shift } => {
                 self.commands.push(DVICommand::Down4(
                     tex_box.height().as_scaled_points(),
                 ));
                 if shift != &Dimen::zero() {
-                    self.commands.push(DVICommand::Push);
+                    self.push_stack();
                     self.commands
                         .push(DVICommand::Right4(shift.as_scaled_points()));
                     self.add_box(tex_box);
-                    self.commands.push(DVICommand::Pop);
+                    self.pop_stack();
                 } else {
                     self.add_box(tex_box);
                 }
@@ -179,13 +320,43 @@ impl DVIFileWriter {
                     .push(DVICommand::Right4(move_amount.as_scaled_points()));
             }
 
+            HorizontalListElem::Kern(dimen) => {
+                self.commands
+                    .push(DVICommand::Right4(dimen.as_scaled_points()));
+            }
+
+            // Outside of add_horizontal_list(), there's no list height or
+            // depth to extend running dimensions to, so they are treated as
+            // zero.
+            HorizontalListElem::Rule {
+                height,
+                depth,
+                width,
+            } => self.add_horizontal_rule(
+                height.unwrap_or_else(Dimen::zero),
+                depth.unwrap_or_else(Dimen::zero),
+                width.unwrap_or_else(Dimen::zero),
+            ),
+
+            // See the VerticalListElem::Whatsit case above.
+            HorizontalListElem::Whatsit(_) => {}
+
+            // When a line is broken at a discretionary, its pre-break and
+            // post-break lists are put directly into the lines, so any
+            // discretionaries left over weren't broken at.
+            HorizontalListElem::Discretionary { replace, .. } => {
+                for replace_elem in replace {
+                    self.add_horizontal_list_elem(replace_elem, glue_set_ratio);
+                }
+            }
+
             HorizontalListElem::Box { tex_box, shift } => {
                 if shift != &Dimen::zero() {
-                    self.commands.push(DVICommand::Push);
+                    self.push_stack();
                     self.commands
                         .push(DVICommand::Down4(-shift.as_scaled_points()));
                     self.add_box(tex_box);
-                    self.commands.push(DVICommand::Pop);
+                    self.pop_stack();
                 } else {
                     self.add_box(tex_box);
                 }
@@ -220,13 +391,38 @@ impl DVIFileWriter {
         });
 
         self.curr_font_num = -1;
-        for elem in elems {
-            self.add_vertical_list_elem(elem, glue_set_ratio);
-        }
+
+        // Running rule widths on the page extend to the width of the widest
+        // element.
+        let width = elems
+            .iter()
+            .map(|elem| elem.get_size().2)
+            .max()
+            .unwrap_or_else(Dimen::zero);
+        self.add_vertical_list(elems, glue_set_ratio, width);
 
         self.commands.push(DVICommand::Eop);
     }
 
+    /// Adds a page containing a box, like \shipout does. The contents of a
+    /// vertical box are put directly on the page, and a horizontal box is put
+    /// on the page by itself.
+    pub fn add_box_page(&mut self, tex_box: &TeXBox, cs: [i32; 10]) {
+        match tex_box {
+            TeXBox::VerticalBox(vbox) => {
+                self.add_page(&vbox.list, &vbox.glue_set_ratio, cs)
+            }
+            TeXBox::HorizontalBox(_) => self.add_page(
+                &[VerticalListElem::Box {
+                    tex_box: tex_box.clone(),
+                    shift: Dimen::zero(),
+                }],
+                &None,
+                cs,
+            ),
+        }
+    }
+
     pub fn start(&mut self, unit_frac: (u32, u32), mag: u32, comment: Vec<u8>) {
         let (num, den) = unit_frac;
 
@@ -289,6 +485,8 @@ mod tests {
 
     use crate::boxes::{GlueSetRatioKind, HorizontalBox, VerticalBox};
     use crate::dimension::{Dimen, FilDimen, FilKind, SpringDimen, Unit};
+    use crate::dvi::interpret_dvi_file;
+    use crate::dvi::interpreter::DVIOutputElement;
     use crate::glue::Glue;
 
     static CMR10: Lazy<Font> = Lazy::new(|| Font {
@@ -1701,4 +1899,272 @@ mod tests {
             ],
         );
     }
+
+    #[test]
+    fn it_counts_shifted_boxes_in_the_max_stack_depth() {
+        let mut writer = DVIFileWriter::new();
+
+        with_parser(&[r"\hbox{\raise2pt\hbox{a}}%"], |parser| {
+            let hbox = parser.parse_box().unwrap();
+            writer.add_vertical_list_elem(
+                &VerticalListElem::Box {
+                    tex_box: hbox,
+                    shift: Dimen::from_unit(1.0, Unit::Point),
+                },
+                &None,
+            );
+        });
+
+        // One push for the vertical shift, one for the outer box, one for the
+        // raise, and one for the inner box.
+        assert_eq!(writer.max_stack_depth, 4);
+        assert_eq!(writer.curr_stack_depth, 0);
+    }
+
+    #[test]
+    fn it_switches_fonts_back_after_shifted_boxes_with_font_changes() {
+        let mut writer = DVIFileWriter::new();
+
+        with_parser(
+            &[
+                r"\font\seven=cmr7%",
+                r"\font\ten=cmr10%",
+                r"\hbox{a\raise2pt\hbox{\seven b\lower1pt\hbox{\ten d}e}c}%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+                let hbox = parser.parse_box().unwrap();
+                writer.add_horizontal_list_elem(
+                    &HorizontalListElem::Box {
+                        tex_box: hbox,
+                        shift: Dimen::zero(),
+                    },
+                    &None,
+                );
+            },
+        );
+
+        let font_and_char_commands: Vec<DVICommand> = writer
+            .commands
+            .into_iter()
+            .filter(|command| {
+                matches!(command, DVICommand::Fnt4(_) | DVICommand::SetCharN(_))
+            })
+            .collect();
+
+        assert_eq!(
+            font_and_char_commands,
+            vec![
+                DVICommand::Fnt4(0),
+                DVICommand::SetCharN(97),
+                DVICommand::Fnt4(1),
+                DVICommand::SetCharN(98),
+                DVICommand::Fnt4(0),
+                DVICommand::SetCharN(100),
+                DVICommand::Fnt4(1),
+                DVICommand::SetCharN(101),
+                DVICommand::Fnt4(0),
+                DVICommand::SetCharN(99),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_interprets_chars_after_shifted_boxes_with_the_right_fonts() {
+        let mut writer = DVIFileWriter::new();
+        writer.start((25400000, 473628672), 1000, vec![]);
+
+        with_parser(
+            &[r"\font\seven=cmr7%", r"\hbox{a\raise2pt\hbox{\seven b}c}%"],
+            |parser| {
+                parser.parse_assignment();
+                let hbox = parser.parse_box().unwrap();
+                writer.add_page(
+                    &[VerticalListElem::Box {
+                        tex_box: hbox,
+                        shift: Dimen::zero(),
+                    }],
+                    &None,
+                    [1, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                );
+            },
+        );
+
+        writer.end();
+
+        let pages = interpret_dvi_file(writer.to_file());
+        assert_eq!(pages.len(), 1);
+
+        let mut chars: Vec<(i32, String)> = pages[0]
+            .values()
+            .flatten()
+            .filter_map(|elem| match elem {
+                DVIOutputElement::Character { char, font } => {
+                    Some((*char, font.to_string()))
+                }
+                _ => None,
+            })
+            .collect();
+        chars.sort();
+
+        assert_eq!(
+            chars,
+            vec![
+                (97, "cmr10".to_string()),
+                (98, "cmr7".to_string()),
+                (99, "cmr10".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_outputs_horizontal_rules_with_running_widths() {
+        let mut writer = DVIFileWriter::new();
+        writer.start((25400000, 473628672), 1000, vec![]);
+
+        with_parser(
+            &[r"\vbox{\hbox{\vrule width 10pt height 1pt}\hrule}%"],
+            |parser| {
+                let vbox = parser.parse_box().unwrap();
+                writer.add_page(
+                    &[VerticalListElem::Box {
+                        tex_box: vbox,
+                        shift: Dimen::zero(),
+                    }],
+                    &None,
+                    [1, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                );
+            },
+        );
+
+        writer.end();
+
+        let pages = interpret_dvi_file(writer.to_file());
+        assert_eq!(pages.len(), 1);
+
+        let one_pt = Dimen::from_unit(1.0, Unit::Point).as_scaled_points();
+        let rule_thickness =
+            Dimen::from_unit(0.4, Unit::Point).as_scaled_points();
+        let ten_pt = Dimen::from_unit(10.0, Unit::Point).as_scaled_points();
+
+        assert_eq!(pages[0].len(), 2);
+        // The \vrule is drawn up from the baseline of the \hbox
+        assert!(pages[0][&(0, one_pt)].contains(&DVIOutputElement::Rule {
+            height: one_pt,
+            width: ten_pt,
+        }));
+        // The \hrule is drawn up from its bottom edge, and is as wide as the
+        // \vbox.
+        assert!(pages[0][&(0, one_pt + rule_thickness)].contains(
+            &DVIOutputElement::Rule {
+                height: rule_thickness,
+                width: ten_pt,
+            }
+        ));
+    }
+
+    #[test]
+    fn it_outputs_vertical_rules_with_running_heights() {
+        let mut writer = DVIFileWriter::new();
+
+        with_parser(&[r"\hbox{A\vrule}%"], |parser| {
+            let hbox = parser.parse_box().unwrap();
+            writer.add_box(&hbox);
+        });
+
+        let metrics = FontMetrics::from_font(&CMR10).unwrap();
+        let rule_commands: Vec<DVICommand> = writer
+            .commands
+            .into_iter()
+            .filter(|command| matches!(command, DVICommand::SetRule { .. }))
+            .collect();
+
+        assert_eq!(
+            rule_commands,
+            vec![DVICommand::SetRule {
+                height: metrics.get_height('A').as_scaled_points(),
+                width: Dimen::from_unit(0.4, Unit::Point).as_scaled_points(),
+            }]
+        );
+    }
+
+    #[test]
+    fn it_skips_drawing_invisible_rules() {
+        let mut writer = DVIFileWriter::new();
+        writer.add_horizontal_list_elem(
+            &HorizontalListElem::Rule {
+                height: Some(Dimen::zero()),
+                depth: Some(Dimen::zero()),
+                width: Some(Dimen::from_unit(2.0, Unit::Point)),
+            },
+            &None,
+        );
+
+        assert_eq!(
+            writer.commands,
+            vec![DVICommand::Right4(
+                Dimen::from_unit(2.0, Unit::Point).as_scaled_points()
+            )]
+        );
+    }
+
+    #[test]
+    fn it_outputs_replacement_text_for_unbroken_discretionaries() {
+        let mut writer = DVIFileWriter::new();
+        writer.add_horizontal_list_elem(
+            &HorizontalListElem::Discretionary {
+                pre: vec![HorizontalListElem::Char {
+                    chr: '-',
+                    font: CMR10.clone(),
+                }],
+                post: vec![],
+                replace: vec![HorizontalListElem::Char {
+                    chr: 'x',
+                    font: CMR10.clone(),
+                }],
+            },
+            &None,
+        );
+
+        // The commands start with a fnt_def4 and fnt4 command for the 'x'.
+        assert_eq!(&writer.commands[2..], &[DVICommand::SetCharN(120)]);
+    }
+
+    fn get_rule_commands_for_box(lines: &[&str]) -> Vec<DVICommand> {
+        let mut writer = DVIFileWriter::new();
+
+        with_parser(lines, |parser| {
+            let vbox = parser.parse_box().unwrap();
+            writer.add_box(&vbox);
+        });
+
+        writer
+            .commands
+            .into_iter()
+            .filter(|command| matches!(command, DVICommand::SetRule { .. }))
+            .collect()
+    }
+
+    #[test]
+    fn it_outputs_overfull_rules_after_overfull_lines() {
+        let metrics = FontMetrics::from_font(&CMR10).unwrap();
+
+        assert_eq!(
+            get_rule_commands_for_box(&[
+                r"\vbox{\hsize=10pt \overfullrule=3pt \noindent AAA A}%"
+            ]),
+            vec![DVICommand::SetRule {
+                height: metrics.get_height('A').as_scaled_points(),
+                width: Dimen::from_unit(3.0, Unit::Point).as_scaled_points(),
+            }]
+        );
+
+        assert_eq!(
+            get_rule_commands_for_box(&[
+                r"\vbox{\hsize=10pt \overfullrule=0pt \noindent AAA A}%"
+            ]),
+            vec![]
+        );
+    }
 }
@@ -1,9 +1,13 @@
 use std::fmt;
 
-use crate::dimension::{Dimen, FilDimen, FilKind, SpringDimen};
+use crate::diagnostics::DiagnosticKind;
+use crate::dimension::{format_scaled, Dimen, FilDimen, FilKind, SpringDimen};
+use crate::font::Font;
 use crate::glue::Glue;
+use crate::layout_tree::box_to_layout_tree;
 use crate::list::{HorizontalListElem, VerticalListElem};
-use crate::state::TeXState;
+use crate::output_files::FileOperation;
+use crate::state::{DimenParameter, TeXState};
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum GlueSetRatioKind {
@@ -57,6 +61,31 @@ impl GlueSetRatio {
         }
     }
 
+    // Whether the glue in the box isn't stretched or shrunk at all.
+    pub fn is_zero(&self) -> bool {
+        self.stretch == 0
+    }
+
+    // Formats the ratio the way that TeX does in \showbox, like "0.5fil" for
+    // stretching or "- 0.5" for shrinking. Like TeX, very large ratios are
+    // shown as ">20000.0" instead of the exact value.
+    pub fn to_tex_string(&self) -> String {
+        let order = match self.kind {
+            GlueSetRatioKind::Finite => "",
+            GlueSetRatioKind::Fil => "fil",
+            GlueSetRatioKind::Fill => "fill",
+            GlueSetRatioKind::Filll => "filll",
+        };
+        let sign = if self.stretch < 0 { "- " } else { "" };
+
+        let ratio = self.stretch.abs();
+        if ratio > 20000 * 65536 {
+            format!("{}>{}{}", sign, format_scaled(20000 * 65536), order)
+        } else {
+            format!("{}{}{}", sign, format_scaled(ratio), order)
+        }
+    }
+
     fn multiply_spring_dimen(&self, spring_dimen: &SpringDimen) -> Dimen {
         match (&self.kind, spring_dimen) {
             (&GlueSetRatioKind::Finite, SpringDimen::Dimen(dimen)) => {
@@ -174,6 +203,19 @@ impl GlueSetResult {
             GlueSetResult::GlueSetRatio(glue_set_ratio) => glue_set_ratio,
         }
     }
+
+    // Returns the badness of a box set with this result. Like TeX, we use a
+    // badness of 1000000 for boxes that are overfull.
+    pub fn get_badness(&self) -> u64 {
+        match self {
+            GlueSetResult::InsufficientShrink => 1000000,
+            GlueSetResult::ZeroStretch => 10000,
+            GlueSetResult::ZeroShrink => 1000000,
+            GlueSetResult::GlueSetRatio(glue_set_ratio) => {
+                glue_set_ratio.get_badness()
+            }
+        }
+    }
 }
 
 // Given the amount of stretch/shrink needed to set a given box and the amount
@@ -250,6 +292,17 @@ pub enum BoxLayout {
     Spread(Dimen),
 }
 
+/// Returns the badness of setting a box with the given layout, which is what
+/// \badness reports after the box is built.
+pub fn get_badness_for_layout(glue: &Glue, layout: &BoxLayout) -> u64 {
+    let glue_set = match layout {
+        BoxLayout::Natural => return 0,
+        BoxLayout::Fixed(final_dimen) => set_glue_for_dimen(final_dimen, glue),
+        BoxLayout::Spread(spread) => set_glue_for_spread(spread, glue),
+    };
+    glue_set.get_badness()
+}
+
 /// Based on the layout of a box and the stretchable dimension, return the
 /// resulting true dimension and the needed glue set ratio.
 pub fn get_set_dimen_and_ratio(
@@ -321,6 +374,16 @@ impl HorizontalBox {
             .flat_map(|elem| match elem {
                 HorizontalListElem::Char { chr: ch, font: _ } => vec![*ch],
                 HorizontalListElem::HSkip(_) => vec![' '],
+                HorizontalListElem::Kern(_) => vec![],
+                HorizontalListElem::Rule { .. } => vec![],
+                HorizontalListElem::Whatsit(_) => vec![],
+                HorizontalListElem::Discretionary { replace, .. } => {
+                    HorizontalBox {
+                        list: replace.clone(),
+                        ..HorizontalBox::empty()
+                    }
+                    .to_chars()
+                }
                 HorizontalListElem::Box { tex_box, shift: _ } => {
                     tex_box.to_chars()
                 }
@@ -340,7 +403,7 @@ impl HorizontalBox {
     }
 
     pub fn create_from_horizontal_list_with_layout(
-        list: Vec<HorizontalListElem>,
+        mut list: Vec<HorizontalListElem>,
         layout: &BoxLayout,
         state: &TeXState,
     ) -> HorizontalBox {
@@ -366,6 +429,41 @@ impl HorizontalBox {
             width = width + elem_width;
         }
 
+        // If there isn't enough finite shrink to get the list down to the
+        // requested width, the box sticks out past its width.
+        let overfull_by = match (layout, &width.shrink) {
+            (BoxLayout::Natural, _) => Dimen::zero(),
+            (BoxLayout::Fixed(final_width), SpringDimen::Dimen(shrink)) => {
+                width.space - *shrink - *final_width
+            }
+            (BoxLayout::Spread(spread), SpringDimen::Dimen(shrink)) => {
+                *spread * -1 - *shrink
+            }
+            (_, SpringDimen::FilDimen(_)) => Dimen::zero(),
+        };
+        if overfull_by > state.get_dimen_parameter(&DimenParameter::HFuzz) {
+            state.with_diagnostics(|diagnostics| {
+                diagnostics.report(DiagnosticKind::OverfullHBox { overfull_by })
+            });
+
+            // Like TeX, we put a rule at the end of overfull boxes so that
+            // they are easy to spot in the output. Since the rule is added
+            // after the width of the list is measured, it sticks out past the
+            // end of the box.
+            let overfull_rule =
+                state.get_dimen_parameter(&DimenParameter::OverfullRule);
+            if overfull_rule > Dimen::zero() {
+                list.push(HorizontalListElem::Rule {
+                    height: None,
+                    depth: None,
+                    width: Some(overfull_rule),
+                });
+            }
+        }
+
+        state.set_last_badness(get_badness_for_layout(&width, layout));
+        state.with_stats(|stats| stats.boxes_built += 1);
+
         // Figure out the final width and glue set needed.
         let (set_width, set_ratio) = get_set_dimen_and_ratio(width, layout);
 
@@ -400,6 +498,10 @@ impl VerticalBox {
             // between each element here.
             .flat_map(|elem| match elem {
                 VerticalListElem::VSkip(_) => vec![],
+                VerticalListElem::Kern(_) => vec![],
+                VerticalListElem::Penalty(_) => vec![],
+                VerticalListElem::Rule { .. } => vec![],
+                VerticalListElem::Whatsit(_) => vec![],
                 VerticalListElem::Box { tex_box, shift: _ } => {
                     let mut vec = tex_box.to_chars();
                     vec.push('\n');
@@ -459,6 +561,122 @@ impl TeXBox {
         }
     }
 
+    /// Dumps the box and everything inside of it with one element per line,
+    /// in the same format as TeX's \showbox.
+    pub fn to_layout_tree(&self, state: &TeXState) -> String {
+        box_to_layout_tree(self, state)
+    }
+
+    /// Returns an iterator over the characters in the box and the fonts that
+    /// they are set in, in the order that they appear in the box. This
+    /// includes the characters inside of any boxes nested in this one.
+    pub fn glyphs(&self) -> Box<dyn Iterator<Item = (char, &Font)> + '_> {
+        match self {
+            TeXBox::HorizontalBox(hbox) => {
+                Box::new(hbox.list.iter().flat_map(|elem| match elem {
+                    HorizontalListElem::Char { chr, font } => {
+                        Box::new(std::iter::once((*chr, font)))
+                    }
+                    HorizontalListElem::Box { tex_box, .. } => tex_box.glyphs(),
+                    _ => Box::new(std::iter::empty()),
+                }))
+            }
+            TeXBox::VerticalBox(vbox) => {
+                Box::new(vbox.list.iter().flat_map(|elem| match elem {
+                    VerticalListElem::Box { tex_box, .. } => tex_box.glyphs(),
+                    _ => Box::new(std::iter::empty()),
+                }))
+            }
+        }
+    }
+
+    /// Returns the file operations in the box in the order that TeX carries
+    /// them out when the box is shipped out (left to right, top to bottom),
+    /// along with where they are. Positions are measured right and down from
+    /// the top left corner of this box.
+    pub fn file_operations(
+        &self,
+        state: &TeXState,
+    ) -> Vec<(&FileOperation, Dimen, Dimen)> {
+        let mut operations = Vec::new();
+        self.add_file_operations(
+            state,
+            Dimen::zero(),
+            *self.height(),
+            &mut operations,
+        );
+        operations
+    }
+
+    // Adds the file operations in the box to `operations`, where (h, v) is
+    // the position of the box's reference point. This moves through the box
+    // the same way that the box is added to the DVI file.
+    fn add_file_operations<'b>(
+        &'b self,
+        state: &TeXState,
+        h: Dimen,
+        v: Dimen,
+        operations: &mut Vec<(&'b FileOperation, Dimen, Dimen)>,
+    ) {
+        match self {
+            TeXBox::HorizontalBox(hbox) => {
+                let mut h = h;
+                for elem in &hbox.list {
+                    match elem {
+                        HorizontalListElem::Whatsit(operation) => {
+                            operations.push((operation, h, v))
+                        }
+                        // Our shifts in horizontal lists move boxes up.
+                        HorizontalListElem::Box { tex_box, shift } => tex_box
+                            .add_file_operations(
+                                state,
+                                h,
+                                v - *shift,
+                                operations,
+                            ),
+                        _ => (),
+                    }
+
+                    h = h + match (elem, &hbox.glue_set_ratio) {
+                        (HorizontalListElem::HSkip(glue), Some(set_ratio)) => {
+                            set_ratio.apply_to_glue(glue)
+                        }
+                        _ => elem.get_size(state).2.space,
+                    };
+                }
+            }
+            TeXBox::VerticalBox(vbox) => {
+                let mut v = v - vbox.height;
+                for elem in &vbox.list {
+                    match elem {
+                        VerticalListElem::Whatsit(operation) => {
+                            operations.push((operation, h, v))
+                        }
+                        VerticalListElem::Box { tex_box, shift } => tex_box
+                            .add_file_operations(
+                                state,
+                                h + *shift,
+                                v + *tex_box.height(),
+                                operations,
+                            ),
+                        _ => (),
+                    }
+
+                    let (height, depth, _) = elem.get_size();
+                    v = v
+                        + depth
+                        + match (elem, &vbox.glue_set_ratio) {
+                            (
+                                VerticalListElem::VSkip(glue),
+                                Some(set_ratio),
+                            ) => set_ratio.apply_to_glue(glue),
+                            _ => height.space,
+                        };
+                }
+            }
+        }
+    }
+
     // For early testing, we're not actually going to outputting a DVI file
     // with the correctly formatted text. So to test things, we'll just pull
     // out the contents of the box as a list of characters.
@@ -478,7 +696,6 @@ mod tests {
     use once_cell::sync::Lazy;
 
     use crate::dimension::Unit;
-    use crate::font::Font;
     use crate::glue::Glue;
 
     static CMR10: Lazy<Font> = Lazy::new(|| Font {
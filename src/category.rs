@@ -1,4 +1,3 @@
-#[allow(dead_code)] // TODO: remove this once all of these are used
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 pub enum Category {
     Escape,       // 0
@@ -18,3 +17,32 @@ pub enum Category {
     Comment,      // 14
     Invalid,      // 15
 }
+
+impl Category {
+    // The category code number, as used by \catcode.
+    pub fn to_number(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_number(number: u8) -> Category {
+        match number {
+            0 => Category::Escape,
+            1 => Category::BeginGroup,
+            2 => Category::EndGroup,
+            3 => Category::MathShift,
+            4 => Category::AlignmentTab,
+            5 => Category::EndOfLine,
+            6 => Category::Parameter,
+            7 => Category::Superscript,
+            8 => Category::Subscript,
+            9 => Category::Ignored,
+            10 => Category::Space,
+            11 => Category::Letter,
+            12 => Category::Other,
+            13 => Category::Active,
+            14 => Category::Comment,
+            15 => Category::Invalid,
+            _ => panic!("Invalid category code: {}", number),
+        }
+    }
+}
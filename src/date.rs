@@ -0,0 +1,77 @@
+//! Figuring out the values of \time, \day, \month, and \year at the start of
+//! the job.
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Returns the time that the job should use, in seconds since the Unix epoch.
+/// Like many other tools, we use the value of the SOURCE_DATE_EPOCH
+/// environment variable (which is passed in here) if it is set so that builds
+/// can be reproducible, and otherwise use the current time.
+pub fn get_job_epoch_seconds(source_date_epoch: Option<&str>) -> i64 {
+    if let Some(epoch) = source_date_epoch {
+        return epoch.trim().parse().unwrap_or_else(|_| {
+            panic!("Invalid SOURCE_DATE_EPOCH: {}", epoch);
+        });
+    }
+
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(err) => -(err.duration().as_secs() as i64),
+    }
+}
+
+/// Converts a time in seconds since the Unix epoch into the values of \year,
+/// \month, \day, and \time (which is the number of minutes since midnight),
+/// in UTC.
+pub fn get_date_and_time(epoch_seconds: i64) -> (i32, i32, i32, i32) {
+    let days = epoch_seconds.div_euclid(86400);
+    let minutes = epoch_seconds.rem_euclid(86400) / 60;
+
+    // This converts the number of days since 1970-01-01 into a date in the
+    // proleptic Gregorian calendar, using the algorithm from
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524
+        - day_of_era / 146096)
+        / 365;
+    let day_of_year =
+        day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // This month counts starting from March, so that the leap day is at the
+    // end of the year.
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year as i32, month as i32, day as i32, minutes as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_converts_epoch_seconds_to_dates() {
+        assert_eq!(get_date_and_time(0), (1970, 1, 1, 0));
+        assert_eq!(get_date_and_time(1700000000), (2023, 11, 14, 22 * 60 + 13));
+        assert_eq!(get_date_and_time(951825600), (2000, 2, 29, 12 * 60));
+        assert_eq!(get_date_and_time(-60), (1969, 12, 31, 23 * 60 + 59));
+    }
+
+    #[test]
+    fn it_uses_source_date_epoch_when_it_is_set() {
+        assert_eq!(get_job_epoch_seconds(Some("1700000000")), 1700000000);
+
+        let (year, month, day, time) =
+            get_date_and_time(get_job_epoch_seconds(None));
+        assert!(year >= 2020);
+        assert!((1..=12).contains(&month));
+        assert!((1..=31).contains(&day));
+        assert!((0..24 * 60).contains(&time));
+    }
+}
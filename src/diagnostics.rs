@@ -0,0 +1,341 @@
+//! Reporting of warnings and errors.
+//!
+//! Every diagnostic is printed to the terminal, and can also be written as
+//! JSON, one object per line, to a separate writer (like the file given with
+//! `--diagnostics-json`) so that editors and CI can consume them. Each JSON
+//! object has these fields:
+//!
+//!  * `severity`: either `"warning"` or `"error"`
+//!  * `kind`: one of the kinds below
+//!  * `message`: the same message that is printed to the terminal
+//!  * `file`: the name of the file being read with `\input`, or `null` when
+//!    reading the main input from stdin
+//!  * `line`: the line of the input that was being read, starting from 1
+//!
+//! along with fields specific to the kind of diagnostic:
+//!
+//!  * `overfull_hbox` (warning): `overfull_by_sp`, the number of scaled
+//!    points that the box is too wide by
+//!  * `missing_character` (warning): `character` and `font`, the character
+//!    that was dropped and the name of the font that didn't contain it
+//!  * `missing_font` (error): `font`, the name of the font whose metrics
+//!    couldn't be loaded, and `control_sequence`, the name of the control
+//!    sequence it was being assigned to (or `null`)
+//!  * `undefined_control_sequence` (error): `control_sequence`, the name of
+//!    the control sequence without the escape character
+//!  * `invalid_character` (error): `character`, the character with category
+//!    15 that was skipped
+//!  * `insufficient_math_font` (error): `font` and `family`, a font that
+//!    doesn't have enough parameters to be used for math family 2 or 3
+//!  * `missing_box` (error): no extra fields; something other than a box
+//!    came after `\setbox<n>=`
+use std::io::Write;
+
+use crate::dimension::Dimen;
+
+#[derive(Debug, PartialEq)]
+pub enum DiagnosticKind {
+    OverfullHBox {
+        overfull_by: Dimen,
+    },
+    MissingCharacter {
+        chr: char,
+        font_name: String,
+    },
+    MissingFont {
+        control_sequence: Option<String>,
+        font_name: String,
+    },
+    UndefinedControlSequence {
+        name: String,
+    },
+    InvalidCharacter {
+        chr: char,
+    },
+    InsufficientMathFont {
+        font_name: String,
+        family: u8,
+    },
+    MissingBox,
+}
+
+impl DiagnosticKind {
+    fn is_error(&self) -> bool {
+        matches!(
+            self,
+            DiagnosticKind::MissingFont { .. }
+                | DiagnosticKind::UndefinedControlSequence { .. }
+                | DiagnosticKind::InvalidCharacter { .. }
+                | DiagnosticKind::InsufficientMathFont { .. }
+                | DiagnosticKind::MissingBox
+        )
+    }
+
+    fn get_name(&self) -> &'static str {
+        match self {
+            DiagnosticKind::OverfullHBox { .. } => "overfull_hbox",
+            DiagnosticKind::MissingCharacter { .. } => "missing_character",
+            DiagnosticKind::MissingFont { .. } => "missing_font",
+            DiagnosticKind::UndefinedControlSequence { .. } => {
+                "undefined_control_sequence"
+            }
+            DiagnosticKind::InvalidCharacter { .. } => "invalid_character",
+            DiagnosticKind::InsufficientMathFont { .. } => {
+                "insufficient_math_font"
+            }
+            DiagnosticKind::MissingBox => "missing_box",
+        }
+    }
+
+    /// The human-readable message for this diagnostic, which matches what
+    /// TeX prints where possible.
+    pub fn get_message(&self) -> String {
+        match self {
+            DiagnosticKind::OverfullHBox { overfull_by } => format!(
+                "Overfull \\hbox ({} too wide)",
+                overfull_by.to_pt_string()
+            ),
+            DiagnosticKind::MissingCharacter { chr, font_name } => format!(
+                "Missing character: There is no {} in font {}!",
+                chr, font_name
+            ),
+            DiagnosticKind::MissingFont {
+                control_sequence,
+                font_name,
+            } => {
+                let font = match control_sequence {
+                    Some(name) => format!("\\{}={}", name, font_name),
+                    None => font_name.clone(),
+                };
+                format!(
+                    "Font {} not loadable: Metric (TFM) file not found",
+                    font
+                )
+            }
+            DiagnosticKind::UndefinedControlSequence { name } => {
+                format!("Undefined control sequence \\{}", name)
+            }
+            DiagnosticKind::InvalidCharacter { .. } => {
+                "Text line contains an invalid character".to_string()
+            }
+            DiagnosticKind::InsufficientMathFont { font_name, family } => {
+                // Family 2 is the symbol font and family 3 is the extension
+                // font, which are the only ones that need extra parameters.
+                if *family == 2 {
+                    format!("Font {} is not a symbol font", font_name)
+                } else {
+                    format!("Font {} is not an extension font", font_name)
+                }
+            }
+            DiagnosticKind::MissingBox => {
+                "A <box> was supposed to be here".to_string()
+            }
+        }
+    }
+
+    // Returns the kind-specific fields of the JSON object, as pairs of names
+    // and already-encoded JSON values.
+    fn get_json_fields(&self) -> Vec<(&'static str, String)> {
+        match self {
+            DiagnosticKind::OverfullHBox { overfull_by } => vec![(
+                "overfull_by_sp",
+                overfull_by.as_scaled_points().to_string(),
+            )],
+            DiagnosticKind::MissingCharacter { chr, font_name } => vec![
+                ("character", json_string(&chr.to_string())),
+                ("font", json_string(font_name)),
+            ],
+            DiagnosticKind::MissingFont {
+                control_sequence,
+                font_name,
+            } => vec![
+                ("font", json_string(font_name)),
+                (
+                    "control_sequence",
+                    match control_sequence {
+                        Some(name) => json_string(name),
+                        None => "null".to_string(),
+                    },
+                ),
+            ],
+            DiagnosticKind::UndefinedControlSequence { name } => {
+                vec![("control_sequence", json_string(name))]
+            }
+            DiagnosticKind::InvalidCharacter { chr } => {
+                vec![("character", json_string(&chr.to_string()))]
+            }
+            DiagnosticKind::InsufficientMathFont { font_name, family } => vec![
+                ("font", json_string(font_name)),
+                ("family", family.to_string()),
+            ],
+            DiagnosticKind::MissingBox => vec![],
+        }
+    }
+}
+
+fn json_string(string: &str) -> String {
+    let mut result = String::from("\"");
+    for ch in string.chars() {
+        match ch {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => {
+                result.push_str(&format!("\\u{:04x}", ch as u32))
+            }
+            ch => result.push(ch),
+        }
+    }
+    result.push('"');
+    result
+}
+
+pub struct Diagnostics {
+    // The \input file that is currently being read, or None when reading the
+    // main input.
+    file: Option<String>,
+    // The line of the input that is currently being read, which is attached
+    // to each diagnostic.
+    line: usize,
+    json_writer: Option<Box<dyn Write>>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Diagnostics {
+            file: None,
+            line: 0,
+            json_writer: None,
+        }
+    }
+
+    pub fn set_file(&mut self, file: Option<String>) {
+        self.file = file;
+    }
+
+    pub fn set_line(&mut self, line: usize) {
+        self.line = line;
+    }
+
+    /// Sets where the JSON lines for each diagnostic are written.
+    pub fn set_json_writer(&mut self, writer: Box<dyn Write>) {
+        self.json_writer = Some(writer);
+    }
+
+    fn to_json(&self, kind: &DiagnosticKind) -> String {
+        let mut fields = vec![
+            (
+                "severity",
+                json_string(if kind.is_error() { "error" } else { "warning" }),
+            ),
+            ("kind", json_string(kind.get_name())),
+            ("message", json_string(&kind.get_message())),
+            (
+                "file",
+                match &self.file {
+                    Some(file) => json_string(file),
+                    None => "null".to_string(),
+                },
+            ),
+            ("line", self.line.to_string()),
+        ];
+        fields.extend(kind.get_json_fields());
+
+        let fields: Vec<String> = fields
+            .into_iter()
+            .map(|(name, value)| format!("{}:{}", json_string(name), value))
+            .collect();
+        format!("{{{}}}", fields.join(","))
+    }
+
+    pub fn report(&mut self, kind: DiagnosticKind) {
+        println!("{} (line {})", kind.get_message(), self.line);
+
+        let json = self.to_json(&kind);
+        if let Some(writer) = &mut self.json_writer {
+            writeln!(writer, "{}", json).unwrap_or_else(|err| {
+                panic!("Error writing diagnostics: {}", err)
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::dimension::Unit;
+    use crate::testing::SharedWriter;
+
+    #[test]
+    fn it_escapes_json_strings() {
+        assert_eq!(json_string("a\"b\\c\n\u{1}"), r#""a\"b\\c\n\u0001""#);
+    }
+
+    #[test]
+    fn it_serializes_diagnostics_to_json() {
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.set_line(3);
+
+        assert_eq!(
+            diagnostics.to_json(&DiagnosticKind::OverfullHBox {
+                overfull_by: Dimen::from_unit(1.5, Unit::Point),
+            }),
+            concat!(
+                r#"{"severity":"warning","kind":"overfull_hbox","#,
+                r#""message":"Overfull \\hbox (1.5pt too wide)","#,
+                r#""file":null,"line":3,"overfull_by_sp":98304}"#,
+            )
+        );
+        assert_eq!(
+            diagnostics.to_json(&DiagnosticKind::UndefinedControlSequence {
+                name: "foo".to_string(),
+            }),
+            concat!(
+                r#"{"severity":"error","kind":"undefined_control_sequence","#,
+                r#""message":"Undefined control sequence \\foo","#,
+                r#""file":null,"line":3,"control_sequence":"foo"}"#,
+            )
+        );
+
+        diagnostics.set_file(Some("macros.tex".to_string()));
+        assert_eq!(
+            diagnostics.to_json(&DiagnosticKind::MissingBox),
+            concat!(
+                r#"{"severity":"error","kind":"missing_box","#,
+                r#""message":"A <box> was supposed to be here","#,
+                r#""file":"macros.tex","line":3}"#,
+            )
+        );
+    }
+
+    #[test]
+    fn it_writes_one_line_per_diagnostic() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.set_json_writer(Box::new(SharedWriter(output.clone())));
+
+        diagnostics.report(DiagnosticKind::MissingFont {
+            control_sequence: None,
+            font_name: "nofont".to_string(),
+        });
+        diagnostics.set_line(2);
+        diagnostics.report(DiagnosticKind::MissingCharacter {
+            chr: '"',
+            font_name: "cmr10".to_string(),
+        });
+
+        let output = String::from_utf8(output.borrow().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains(r#""kind":"missing_font""#));
+        assert!(lines[0].contains(r#""font":"nofont""#));
+        assert!(lines[1].contains(r#""line":2"#));
+        assert!(lines[1].contains(r#""character":"\"""#));
+    }
+}
@@ -35,6 +35,41 @@ fn get_scale(unit: Unit) -> (f64, f64) {
     }
 }
 
+// Formats a number of 65536ths of a unit the way that TeX does, printing the
+// shortest decimal which rounds back to the same number. This is the algorithm
+// from print_scaled in tex.web.
+pub fn format_scaled(scaled: i32) -> String {
+    let unity = 65536;
+    let mut result = String::new();
+
+    let mut s = scaled;
+    if s < 0 {
+        result.push('-');
+        s = -s;
+    }
+
+    result.push_str(&(s / unity).to_string());
+    result.push('.');
+
+    s = 10 * (s % unity) + 5;
+    let mut delta = 10;
+    loop {
+        if delta > unity {
+            // Round the last digit
+            s += 0o100000 - 50000;
+        }
+        result.push(std::char::from_digit((s / unity) as u32, 10).unwrap());
+        s = 10 * (s % unity);
+        delta *= 10;
+
+        if s <= delta {
+            break;
+        }
+    }
+
+    result
+}
+
 // Represents a dimension in terms of a number of scaled points.
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Dimen(i32);
@@ -75,6 +110,17 @@ impl Dimen {
         self.0
     }
 
+    // Formats the Dimen in points the way that TeX does, e.g. "3.0pt".
+    pub fn to_pt_string(self) -> String {
+        self.to_tex_string_with_unit("pt")
+    }
+
+    // Formats the Dimen with the given unit after it, which can be empty like
+    // in \showbox output, e.g. "3.0".
+    pub fn to_tex_string_with_unit(self, unit: &str) -> String {
+        format!("{}{}", format_scaled(self.0), unit)
+    }
+
     pub fn abs(&self) -> Dimen {
         if *self < Dimen::zero() {
             *self * -1
@@ -226,12 +272,53 @@ impl Mul<i32> for FilDimen {
     }
 }
 
+impl Div<i32> for FilDimen {
+    type Output = FilDimen;
+
+    fn div(self, other: i32) -> FilDimen {
+        FilDimen(self.0, self.1 / other)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum SpringDimen {
     Dimen(Dimen),
     FilDimen(FilDimen),
 }
 
+impl SpringDimen {
+    pub fn is_zero(&self) -> bool {
+        match self {
+            SpringDimen::Dimen(dimen) => *dimen == Dimen::zero(),
+            SpringDimen::FilDimen(fil_dimen) => fil_dimen.is_zero(),
+        }
+    }
+
+    // Formats the stretch or shrink the way TeX does, like "2.0pt" or
+    // "1.0fill".
+    #[cfg(test)]
+    pub fn to_tex_string(&self) -> String {
+        self.to_tex_string_with_unit("pt")
+    }
+
+    // Formats the stretch or shrink with the given unit after finite
+    // dimensions, which is what TeX's print_glue does. \showbox doesn't use
+    // a unit, so it shows things like "2.0" or "1.0fill".
+    pub fn to_tex_string_with_unit(&self, unit: &str) -> String {
+        match self {
+            SpringDimen::Dimen(dimen) => dimen.to_tex_string_with_unit(unit),
+            SpringDimen::FilDimen(FilDimen(kind, value)) => {
+                let unit = match kind {
+                    FilKind::Fil => "fil",
+                    FilKind::Fill => "fill",
+                    FilKind::Filll => "filll",
+                };
+                format!("{}{}", format_scaled(*value), unit)
+            }
+        }
+    }
+}
+
 impl Add for SpringDimen {
     type Output = SpringDimen;
 
@@ -265,6 +352,17 @@ impl Mul<i32> for SpringDimen {
     }
 }
 
+impl Div<i32> for SpringDimen {
+    type Output = SpringDimen;
+
+    fn div(self, other: i32) -> SpringDimen {
+        match self {
+            SpringDimen::FilDimen(fil) => SpringDimen::FilDimen(fil / other),
+            SpringDimen::Dimen(dimen) => SpringDimen::Dimen(dimen / other),
+        }
+    }
+}
+
 /// Represents a math dimension in terms of a number of 1/65536 of an mu. These
 ///  are converted to em in math modes by dividing by 18.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -358,6 +456,55 @@ mod tests {
         assert_eq!(Dimen(12345) / 2, Dimen(6172));
     }
 
+    #[test]
+    fn it_formats_scaled_points_with_at_most_five_decimal_places() {
+        assert_eq!(format_scaled(65536), "1.0");
+        assert_eq!(format_scaled(1), "0.00002");
+        assert_eq!(format_scaled(32768), "0.5");
+        // 0.1pt isn't a whole number of scaled points, but it is the
+        // shortest decimal that rounds to the nearest one.
+        assert_eq!(format_scaled(6554), "0.1");
+        assert_eq!(format_scaled(65535), "0.99998");
+        assert_eq!(format_scaled(-65536 * 12 - 1), "-12.00002");
+    }
+
+    #[test]
+    fn it_formats_dimens_in_points() {
+        assert_eq!(Dimen(0).to_pt_string(), "0.0pt");
+        assert_eq!(Dimen(65536 * 3).to_pt_string(), "3.0pt");
+        assert_eq!(Dimen(655361).to_pt_string(), "10.00002pt");
+        assert_eq!(Dimen(1).to_pt_string(), "0.00002pt");
+        assert_eq!(Dimen(-98304).to_pt_string(), "-1.5pt");
+    }
+
+    #[test]
+    fn it_formats_the_largest_dimens_in_points() {
+        let max = Dimen::from_scaled_points(0o7777777777);
+        max.validate();
+        assert_eq!(max.to_pt_string(), "16383.99998pt");
+        assert_eq!((max * -1).to_pt_string(), "-16383.99998pt");
+        assert_eq!((max * -1).to_tex_string_with_unit(""), "-16383.99998");
+        assert_eq!(Dimen::zero().to_tex_string_with_unit(""), "0.0");
+    }
+
+    #[test]
+    fn it_formats_spring_dimens() {
+        assert_eq!(
+            SpringDimen::Dimen(Dimen(65536 * 2)).to_tex_string(),
+            "2.0pt"
+        );
+        assert_eq!(
+            SpringDimen::FilDimen(FilDimen(FilKind::Fil, 65536))
+                .to_tex_string(),
+            "1.0fil"
+        );
+        assert_eq!(
+            SpringDimen::FilDimen(FilDimen(FilKind::Filll, -32768))
+                .to_tex_string(),
+            "-0.5filll"
+        );
+    }
+
     #[test]
     #[should_panic(expected = "Dimension too large")]
     fn it_checks_large_dimensions() {
@@ -72,6 +72,38 @@ impl DVICommand {
     ) -> io::Result<()> {
         match self {
             DVICommand::SetCharN(n) => writer.write_1_byte_unsigned(*n),
+            DVICommand::Set1(c) => {
+                writer.write_1_byte_unsigned(128)?;
+                writer.write_1_byte_unsigned(*c)
+            }
+            DVICommand::Set2(c) => {
+                writer.write_1_byte_unsigned(129)?;
+                writer.write_2_bytes_unsigned(*c)
+            }
+            DVICommand::Set3(c) => {
+                writer.write_1_byte_unsigned(130)?;
+                writer.write_3_bytes_unsigned(*c)
+            }
+            DVICommand::Set4(c) => {
+                writer.write_1_byte_unsigned(131)?;
+                writer.write_4_bytes_signed(*c)
+            }
+            DVICommand::Put1(c) => {
+                writer.write_1_byte_unsigned(133)?;
+                writer.write_1_byte_unsigned(*c)
+            }
+            DVICommand::Put2(c) => {
+                writer.write_1_byte_unsigned(134)?;
+                writer.write_2_bytes_unsigned(*c)
+            }
+            DVICommand::Put3(c) => {
+                writer.write_1_byte_unsigned(135)?;
+                writer.write_3_bytes_unsigned(*c)
+            }
+            DVICommand::Put4(c) => {
+                writer.write_1_byte_unsigned(136)?;
+                writer.write_4_bytes_signed(*c)
+            }
             DVICommand::Bop { cs, pointer } => {
                 writer.write_1_byte_unsigned(139)?;
                 for c in cs {
@@ -539,4 +571,48 @@ mod tests {
                 223, 223, 223, 223, 223, 223,
             ]);
     }
+
+    #[test]
+    fn it_round_trips_set_and_put_commands() {
+        let file = DVIFile {
+            commands: vec![
+                DVICommand::Set1(200),
+                DVICommand::Set2(0x1234),
+                DVICommand::Set3(0x123456),
+                DVICommand::Set4(-2),
+                DVICommand::Put1(200),
+                DVICommand::Put2(0x1234),
+                DVICommand::Put3(0x123456),
+                DVICommand::Put4(-2),
+            ],
+        };
+
+        let mut output: Vec<u8> = Vec::new();
+        file.write_to(&mut output).unwrap();
+
+        #[rustfmt::skip]
+        assert_eq!(
+            output,
+            vec![
+                // set1
+                128, 200,
+                // set2
+                129, 0x12, 0x34,
+                // set3
+                130, 0x12, 0x34, 0x56,
+                // set4
+                131, 0xff, 0xff, 0xff, 0xfe,
+                // put1
+                133, 200,
+                // put2
+                134, 0x12, 0x34,
+                // put3
+                135, 0x12, 0x34, 0x56,
+                // put4
+                136, 0xff, 0xff, 0xff, 0xfe,
+            ]
+        );
+
+        assert_eq!(DVIFile::new(&output[..]).unwrap(), file);
+    }
 }
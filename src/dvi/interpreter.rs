@@ -9,6 +9,8 @@ use crate::font_metrics::FontMetrics;
 #[derive(Debug, Hash, PartialEq, Eq)]
 pub enum DVIOutputElement {
     Character { char: i32, font: String },
+    // Rules are placed with their bottom left corner at the position.
+    Rule { height: i32, width: i32 },
 }
 
 pub type DVIPageOutput = HashMap<(i32, i32), HashSet<DVIOutputElement>>;
@@ -101,6 +103,41 @@ fn add_to_page(
     }
 }
 
+// Adds a character in the current font to the page at the current position.
+// The set commands then move right by the width of the character, but the put
+// commands leave the position where it was.
+fn add_char_to_page(
+    state: &mut DVIState,
+    page: &mut DVIPageOutput,
+    chr: i32,
+    move_right: bool,
+) {
+    let font_name = {
+        let (_, font_name) = state.current_font();
+        font_name.to_string()
+    };
+    let stack = state.curr_stack();
+
+    add_to_page(
+        page,
+        (stack.h, stack.v),
+        DVIOutputElement::Character {
+            char: chr,
+            font: font_name,
+        },
+    );
+
+    if move_right {
+        let shift_width = {
+            let (metrics, _) = state.current_font();
+            let chr = std::char::from_u32(chr as u32)
+                .unwrap_or_else(|| panic!("Invalid character: {}", chr));
+            metrics.get_width(chr)
+        };
+        state.curr_stack().h += shift_width.as_scaled_points();
+    }
+}
+
 fn interpret_page<'a, I>(
     state: &mut DVIState,
     commands: &mut Peekable<I>,
@@ -183,29 +220,56 @@ where
             DVICommand::Fnt4(f) => {
                 state.f = Some(*f);
             }
-            DVICommand::SetCharN(n) => {
-                {
-                    let font_name = {
-                        let (_, font_name) = state.current_font();
-                        font_name.to_string()
-                    };
-                    let stack = state.curr_stack();
-
+            DVICommand::SetCharN(c) | DVICommand::Set1(c) => {
+                add_char_to_page(state, &mut page, *c as i32, true);
+            }
+            DVICommand::Set2(c) => {
+                add_char_to_page(state, &mut page, *c as i32, true);
+            }
+            DVICommand::Set3(c) => {
+                add_char_to_page(state, &mut page, *c as i32, true);
+            }
+            DVICommand::Set4(c) => {
+                add_char_to_page(state, &mut page, *c, true);
+            }
+            DVICommand::Put1(c) => {
+                add_char_to_page(state, &mut page, *c as i32, false);
+            }
+            DVICommand::Put2(c) => {
+                add_char_to_page(state, &mut page, *c as i32, false);
+            }
+            DVICommand::Put3(c) => {
+                add_char_to_page(state, &mut page, *c as i32, false);
+            }
+            DVICommand::Put4(c) => {
+                add_char_to_page(state, &mut page, *c, false);
+            }
+            DVICommand::SetRule { height, width } => {
+                let stack = state.curr_stack();
+                if *height > 0 && *width > 0 {
                     add_to_page(
                         &mut page,
                         (stack.h, stack.v),
-                        DVIOutputElement::Character {
-                            char: *n as i32,
-                            font: font_name,
+                        DVIOutputElement::Rule {
+                            height: *height,
+                            width: *width,
+                        },
+                    );
+                }
+                stack.h += width;
+            }
+            DVICommand::PutRule { height, width } => {
+                let stack = state.curr_stack();
+                if *height > 0 && *width > 0 {
+                    add_to_page(
+                        &mut page,
+                        (stack.h, stack.v),
+                        DVIOutputElement::Rule {
+                            height: *height,
+                            width: *width,
                         },
                     );
                 }
-
-                let shift_width = {
-                    let (metrics, _) = state.current_font();
-                    metrics.get_width(*n as char)
-                };
-                state.curr_stack().h += shift_width.as_scaled_points();
             }
             other => panic!("unknown command: {:?}", other),
         }
@@ -469,6 +533,65 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_doesnt_add_char_width_when_putting_chars() {
+        let page = interpret_page_from_commands(vec![
+            DVICommand::Bop {
+                cs: [1, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                pointer: -1,
+            },
+            DVICommand::FntDef1 {
+                font_num: 0,
+                checksum: 305419896,
+                scale: 655360,
+                design_size: 655360,
+                area: 0,
+                length: 5,
+                font_name: "cmr10".to_string(),
+            },
+            DVICommand::FntNumN(0),
+            DVICommand::Put1(67),
+            DVICommand::Set2(68),
+            DVICommand::Put4(69),
+            DVICommand::Set3(70),
+            DVICommand::Eop,
+        ]);
+
+        let metrics = FontMetrics::from_font(&Font {
+            font_name: "cmr10".to_string(),
+            scale: Dimen::from_unit(10.0, Unit::Point),
+        })
+        .unwrap();
+
+        assert_eq!(page.len(), 2);
+        assert_eq!(
+            page.get(&(0, 0)),
+            Some(&set![
+                DVIOutputElement::Character {
+                    char: 67,
+                    font: "cmr10".to_string(),
+                },
+                DVIOutputElement::Character {
+                    char: 68,
+                    font: "cmr10".to_string(),
+                }
+            ])
+        );
+        assert_eq!(
+            page.get(&(metrics.get_width('D').as_scaled_points(), 0)),
+            Some(&set![
+                DVIOutputElement::Character {
+                    char: 69,
+                    font: "cmr10".to_string(),
+                },
+                DVIOutputElement::Character {
+                    char: 70,
+                    font: "cmr10".to_string(),
+                }
+            ])
+        );
+    }
+
     #[test]
     fn it_handles_movement_commands() {
         let page = interpret_page_from_commands(vec![
@@ -568,6 +691,50 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_interprets_rules() {
+        let page = interpret_page_from_commands(vec![
+            DVICommand::Bop {
+                cs: [1, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                pointer: -1,
+            },
+            DVICommand::SetRule {
+                height: 100,
+                width: 200,
+            },
+            DVICommand::PutRule {
+                height: 300,
+                width: 400,
+            },
+            // Invisible rules still move, but aren't drawn
+            DVICommand::SetRule {
+                height: 0,
+                width: 500,
+            },
+            DVICommand::PutRule {
+                height: 600,
+                width: 0,
+            },
+            DVICommand::Eop,
+        ]);
+
+        assert_eq!(page.len(), 2);
+        assert_eq!(
+            page.get(&(0, 0)),
+            Some(&set![DVIOutputElement::Rule {
+                height: 100,
+                width: 200
+            }])
+        );
+        assert_eq!(
+            page.get(&(200, 0)),
+            Some(&set![DVIOutputElement::Rule {
+                height: 300,
+                width: 400
+            }])
+        );
+    }
+
     #[test]
     fn it_handles_pushing_and_popping() {
         let page = interpret_page_from_commands(vec![
@@ -1,7 +1,7 @@
 mod file;
 mod file_reader;
 mod file_writer;
-mod interpreter;
+pub mod interpreter;
 mod parser;
 
 pub use file::{DVICommand, DVIFile};
@@ -28,6 +28,46 @@ impl DVICommand {
         match opcode {
             // set_char_n
             n if n <= 127 => Ok(Some(DVICommand::SetCharN(n))),
+            // set1
+            128 => {
+                let c = reader.read_1_byte_unsigned()?;
+                Ok(Some(DVICommand::Set1(c)))
+            }
+            // set2
+            129 => {
+                let c = reader.read_2_bytes_unsigned()?;
+                Ok(Some(DVICommand::Set2(c)))
+            }
+            // set3
+            130 => {
+                let c = reader.read_3_bytes_unsigned()?;
+                Ok(Some(DVICommand::Set3(c)))
+            }
+            // set4
+            131 => {
+                let c = reader.read_4_bytes_signed()?;
+                Ok(Some(DVICommand::Set4(c)))
+            }
+            // put1
+            133 => {
+                let c = reader.read_1_byte_unsigned()?;
+                Ok(Some(DVICommand::Put1(c)))
+            }
+            // put2
+            134 => {
+                let c = reader.read_2_bytes_unsigned()?;
+                Ok(Some(DVICommand::Put2(c)))
+            }
+            // put3
+            135 => {
+                let c = reader.read_3_bytes_unsigned()?;
+                Ok(Some(DVICommand::Put3(c)))
+            }
+            // put4
+            136 => {
+                let c = reader.read_4_bytes_signed()?;
+                Ok(Some(DVICommand::Put4(c)))
+            }
             // bop
             139 => {
                 let mut cs = [0; 10];
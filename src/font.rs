@@ -5,3 +5,18 @@ pub struct Font {
     pub font_name: String,
     pub scale: Dimen,
 }
+
+impl Font {
+    /// TeX's \nullfont, which has no characters and no metrics file. Fonts
+    /// whose metrics can't be loaded are replaced with this.
+    pub fn null_font() -> Font {
+        Font {
+            font_name: "nullfont".to_string(),
+            scale: Dimen::zero(),
+        }
+    }
+
+    pub fn is_null_font(&self) -> bool {
+        *self == Font::null_font()
+    }
+}
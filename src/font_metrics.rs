@@ -1,13 +1,19 @@
+use std::collections::HashMap;
+
 use crate::dimension::{Dimen, Unit};
 use crate::font::Font;
 use crate::paths::get_path_to_font;
-use crate::tfm::TFMFile;
+use crate::tfm::{ExtensibleRecipe, LigKernAction, TFMFile};
 
 #[derive(Debug)]
 pub struct FontMetrics {
     tfm_file: TFMFile,
     design_size: Dimen,
     scale: Dimen,
+    // The result of the font's lig/kern program for each pair of characters,
+    // with kerns already scaled. This is computed when the font is loaded so
+    // that adding characters to a list doesn't need to walk the program.
+    lig_kern_table: HashMap<(char, char), LigKernAction>,
 }
 
 impl FontMetrics {
@@ -23,12 +29,26 @@ impl FontMetrics {
         }?;
 
         let design_size = file.get_design_size();
+        let unscaled_lig_kern_table = file.get_lig_kern_table();
 
-        Some(FontMetrics {
+        let mut metrics = FontMetrics {
             tfm_file: file,
             design_size: Dimen::from_unit(design_size, Unit::Point),
             scale: font.scale,
-        })
+            lig_kern_table: HashMap::new(),
+        };
+
+        metrics.lig_kern_table = unscaled_lig_kern_table
+            .into_iter()
+            .map(|(pair, action)| match action {
+                LigKernAction::Kern(kern) => {
+                    (pair, LigKernAction::Kern(metrics.scale_dimen(kern)))
+                }
+                ligature => (pair, ligature),
+            })
+            .collect();
+
+        Some(metrics)
     }
 
     pub fn get_design_size(&self) -> f64 {
@@ -48,6 +68,10 @@ impl FontMetrics {
         )
     }
 
+    pub fn has_char(&self, chr: char) -> bool {
+        self.tfm_file.has_char(chr)
+    }
+
     pub fn get_width(&self, chr: char) -> Dimen {
         self.scale_dimen(self.tfm_file.get_width(chr))
     }
@@ -60,6 +84,10 @@ impl FontMetrics {
         self.scale_dimen(self.tfm_file.get_depth(chr))
     }
 
+    pub fn get_num_font_dimensions(&self) -> usize {
+        self.tfm_file.get_num_font_dimensions()
+    }
+
     pub fn get_font_dimension(&self, dimen_number: usize) -> Dimen {
         self.scale_dimen(self.tfm_file.get_font_dimension(dimen_number))
     }
@@ -67,6 +95,24 @@ impl FontMetrics {
     pub fn get_successor(&self, chr: char) -> char {
         self.tfm_file.get_successor(chr)
     }
+
+    pub fn get_extensible_recipe(&self, chr: char) -> Option<ExtensibleRecipe> {
+        self.tfm_file.get_extensible_recipe(chr)
+    }
+
+    pub fn get_ligature(&self, left: char, right: char) -> Option<char> {
+        match self.lig_kern_table.get(&(left, right)) {
+            Some(LigKernAction::Ligature(chr)) => Some(*chr),
+            _ => None,
+        }
+    }
+
+    pub fn get_kern(&self, left: char, right: char) -> Option<Dimen> {
+        match self.lig_kern_table.get(&(left, right)) {
+            Some(LigKernAction::Kern(kern)) => Some(*kern),
+            _ => None,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -120,6 +166,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_uses_scaled_kerns_from_the_lig_kern_table() {
+        let twentypt_metrics = FontMetrics::from_font(&Font {
+            font_name: "cmr10".to_string(),
+            scale: Dimen::from_unit(20.0, Unit::Point),
+        })
+        .unwrap();
+
+        assert_eq!(
+            twentypt_metrics.get_kern('A', 'V'),
+            twentypt_metrics
+                .tfm_file
+                .get_kern('A', 'V')
+                .map(|kern| kern * 2)
+        );
+        assert_eq!(
+            twentypt_metrics.get_ligature('f', 'i'),
+            twentypt_metrics.tfm_file.get_ligature('f', 'i')
+        );
+        assert_eq!(twentypt_metrics.get_ligature('A', 'V'), None);
+        assert_eq!(twentypt_metrics.get_kern('f', 'i'), None);
+    }
+
     #[test]
     fn it_scales_font_dimensions() {
         let twentypt_metrics = FontMetrics::from_font(&Font {
@@ -1,4 +1,4 @@
-use std::ops::{Add, Mul, Sub};
+use std::ops::{Add, Div, Mul, Sub};
 
 use crate::dimension::{Dimen, MuDimen, SpringDimen};
 
@@ -21,6 +21,28 @@ impl Glue {
             shrink: SpringDimen::Dimen(Dimen::zero()),
         }
     }
+
+    // Formats the glue the way TeX does, only including the stretch and
+    // shrink if they are non-zero, like "3.0pt plus 1.0fil".
+    pub fn to_tex_string(&self) -> String {
+        self.to_tex_string_with_unit("pt")
+    }
+
+    // Formats the glue with the given unit after finite dimensions, which is
+    // what TeX's print_spec does. \showbox doesn't use a unit, so it shows
+    // glue like "3.0 plus 1.0fil".
+    pub fn to_tex_string_with_unit(&self, unit: &str) -> String {
+        let mut result = self.space.to_tex_string_with_unit(unit);
+        if !self.stretch.is_zero() {
+            result.push_str(" plus ");
+            result.push_str(&self.stretch.to_tex_string_with_unit(unit));
+        }
+        if !self.shrink.is_zero() {
+            result.push_str(" minus ");
+            result.push_str(&self.shrink.to_tex_string_with_unit(unit));
+        }
+        result
+    }
 }
 
 impl Add for Glue {
@@ -59,6 +81,17 @@ impl Mul<i32> for Glue {
     }
 }
 
+impl Div<i32> for Glue {
+    type Output = Glue;
+
+    fn div(mut self, other: i32) -> Glue {
+        self.space = self.space / other;
+        self.stretch = self.stretch / other;
+        self.shrink = self.shrink / other;
+        self
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct MuGlue {
     pub space: MuDimen,
@@ -75,3 +108,33 @@ impl MuGlue {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dimension::{FilDimen, FilKind, Unit};
+
+    #[test]
+    fn it_formats_glue_like_tex() {
+        let glue = Glue {
+            space: Dimen::from_unit(2.0, Unit::Point),
+            stretch: SpringDimen::FilDimen(FilDimen::new(FilKind::Fil, 1.0)),
+            shrink: SpringDimen::Dimen(Dimen::from_unit(2.0, Unit::Point)),
+        };
+        assert_eq!(glue.to_tex_string(), "2.0pt plus 1.0fil minus 2.0pt");
+        assert_eq!(
+            glue.to_tex_string_with_unit(""),
+            "2.0 plus 1.0fil minus 2.0"
+        );
+    }
+
+    #[test]
+    fn it_leaves_out_zero_stretch_and_shrink() {
+        let mut glue = Glue::from_dimen(Dimen::from_scaled_points(1));
+        assert_eq!(glue.to_tex_string(), "0.00002pt");
+
+        glue.shrink = SpringDimen::Dimen(Dimen::from_scaled_points(98304));
+        assert_eq!(glue.to_tex_string(), "0.00002pt minus 1.5pt");
+    }
+}
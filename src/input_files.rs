@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+// A file opened with \openin, along with how far into it we've read.
+struct InputStream {
+    lines: Vec<String>,
+    next_line: usize,
+}
+
+/// Keeps track of the files opened with \openin, which are read a line at a
+/// time with \read.
+pub struct InputFiles {
+    streams: HashMap<u8, InputStream>,
+}
+
+impl InputFiles {
+    pub fn new() -> Self {
+        InputFiles {
+            streams: HashMap::new(),
+        }
+    }
+
+    /// Opens the file at the given path for reading on a stream. Like in TeX,
+    /// if the file can't be read then the stream is just left closed, which
+    /// can be checked for with \ifeof.
+    pub fn open(&mut self, stream: u8, path: &Path) {
+        match fs::read_to_string(path) {
+            Ok(contents) => {
+                let lines = contents.lines().map(|s| s.to_string()).collect();
+                self.streams.insert(
+                    stream,
+                    InputStream {
+                        lines,
+                        next_line: 0,
+                    },
+                );
+            }
+            Err(_) => self.close(stream),
+        }
+    }
+
+    pub fn close(&mut self, stream: u8) {
+        self.streams.remove(&stream);
+    }
+
+    pub fn get_num_open(&self) -> usize {
+        self.streams.len()
+    }
+
+    pub fn is_open(&self, stream: u8) -> bool {
+        self.streams.contains_key(&stream)
+    }
+
+    /// Returns the next line from a stream. When there aren't any lines left,
+    /// the stream is closed and None is returned, which TeX treats like
+    /// reading an empty line.
+    pub fn read_line(&mut self, stream: u8) -> Option<String> {
+        let input_stream = self.streams.get_mut(&stream)?;
+
+        match input_stream.lines.get(input_stream.next_line) {
+            Some(line) => {
+                input_stream.next_line += 1;
+                Some(line.clone())
+            }
+            None => {
+                self.close(stream);
+                None
+            }
+        }
+    }
+}
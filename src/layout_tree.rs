@@ -0,0 +1,467 @@
+/// Utilities for turning lists into a stable textual layout tree, and for
+/// comparing two of those trees. These are used by \showbox, and by the
+/// regression corpus tests, where the expected output is stored on disk and
+/// reviewed by hand.
+///
+/// The layout tree uses the same format as TeX's \showbox, so that it can be
+/// compared directly against the output of real TeX.
+use crate::boxes::TeXBox;
+use crate::dimension::Dimen;
+use crate::font::Font;
+use crate::list::{HorizontalListElem, VerticalListElem};
+use crate::output_files::FileOperation;
+use crate::state::TeXState;
+use crate::token::{stringify, Token};
+
+// Formats a dimension the way \showbox does, without any units.
+fn format_dimen(dimen: &Dimen) -> String {
+    dimen.to_tex_string_with_unit("")
+}
+
+// Formats an optional rule dimension, where None means that the dimension is
+// running. This matches how TeX shows running dimensions in \showbox.
+fn format_rule_dimen(dimen: &Option<Dimen>) -> String {
+    match dimen {
+        Some(dimen) => format_dimen(dimen),
+        None => "*".to_string(),
+    }
+}
+
+fn format_rule(
+    height: &Option<Dimen>,
+    depth: &Option<Dimen>,
+    width: &Option<Dimen>,
+) -> String {
+    format!(
+        "\\rule({}+{})x{}",
+        format_rule_dimen(height),
+        format_rule_dimen(depth),
+        format_rule_dimen(width),
+    )
+}
+
+// TeX shows the amount that a box is moved down (in horizontal lists) or
+// right (in vertical lists).
+fn format_shift(shift: &Dimen) -> String {
+    if *shift == Dimen::zero() {
+        "".to_string()
+    } else {
+        format!(", shifted {}", format_dimen(shift))
+    }
+}
+
+// Formats a whatsit the way TeX does. Like TeX, write streams that are out of
+// range are shown as `*` for the terminal or `-` for the log.
+fn format_file_operation(operation: &FileOperation) -> String {
+    match operation {
+        FileOperation::Open { stream, file_name } => {
+            format!("\\openout{}={}", stream, file_name)
+        }
+        FileOperation::Write { stream, tokens } => {
+            let stream = match *stream {
+                0..=15 => stream.to_string(),
+                16.. => "*".to_string(),
+                _ => "-".to_string(),
+            };
+            format!("\\write{}{{{}}}", stream, stringify(tokens, '\\' as i32))
+        }
+        FileOperation::Close { stream } => format!("\\closeout{}", stream),
+    }
+}
+
+struct LayoutTreeWriter<'a> {
+    state: &'a TeXState,
+    out: String,
+}
+
+impl<'a> LayoutTreeWriter<'a> {
+    // Each line is prefixed with one `.` per level of nesting, like in TeX's
+    // \showbox output.
+    fn write_line(&mut self, prefix: &str, line: &str) {
+        self.out.push_str(prefix);
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    // Characters are shown with the control sequence that their font was
+    // defined with. The default font wasn't defined with \font, so we use its
+    // file name instead.
+    fn format_font(&self, font: &Font) -> String {
+        match self.state.get_font_identifier(font) {
+            Some(Token::ControlSequence(name)) => format!("\\{}", name),
+            _ => format!("\\{}", font.font_name),
+        }
+    }
+
+    fn write_box(&mut self, tex_box: &TeXBox, shift: &Dimen, prefix: &str) {
+        let (kind, glue_set_ratio) = match tex_box {
+            TeXBox::HorizontalBox(hbox) => ("hbox", &hbox.glue_set_ratio),
+            TeXBox::VerticalBox(vbox) => ("vbox", &vbox.glue_set_ratio),
+        };
+
+        let glue_set = match glue_set_ratio {
+            Some(ratio) if !ratio.is_zero() => {
+                format!(", glue set {}", ratio.to_tex_string())
+            }
+            _ => "".to_string(),
+        };
+
+        self.write_line(
+            prefix,
+            &format!(
+                "\\{}({}+{})x{}{}{}",
+                kind,
+                format_dimen(tex_box.height()),
+                format_dimen(tex_box.depth()),
+                format_dimen(tex_box.width()),
+                glue_set,
+                format_shift(shift),
+            ),
+        );
+
+        let inner_prefix = format!("{}.", prefix);
+        match tex_box {
+            TeXBox::HorizontalBox(hbox) => {
+                self.write_horizontal_list(&hbox.list, &inner_prefix)
+            }
+            TeXBox::VerticalBox(vbox) => {
+                self.write_vertical_list(&vbox.list, &inner_prefix)
+            }
+        }
+    }
+
+    fn write_horizontal_list(
+        &mut self,
+        list: &[HorizontalListElem],
+        prefix: &str,
+    ) {
+        for elem in list {
+            match elem {
+                HorizontalListElem::Char { chr, font } => {
+                    let line = format!(
+                        "{} {}",
+                        self.format_font(font),
+                        chr.escape_default()
+                    );
+                    self.write_line(prefix, &line)
+                }
+                HorizontalListElem::HSkip(glue) => self.write_line(
+                    prefix,
+                    &format!("\\glue {}", glue.to_tex_string_with_unit("")),
+                ),
+                HorizontalListElem::Kern(dimen) => self.write_line(
+                    prefix,
+                    &format!("\\kern {}", format_dimen(dimen)),
+                ),
+                // Our shifts in horizontal lists move boxes up, but TeX shows
+                // how far they are moved down.
+                HorizontalListElem::Box { tex_box, shift } => {
+                    self.write_box(tex_box, &(*shift * -1), prefix)
+                }
+                HorizontalListElem::Rule {
+                    height,
+                    depth,
+                    width,
+                } => {
+                    self.write_line(prefix, &format_rule(height, depth, width))
+                }
+                // TeX keeps the replaced elements after the discretionary in
+                // the list, and shows the pre-break list nested with `.`s and
+                // the post-break list nested with `|`s.
+                HorizontalListElem::Discretionary { pre, post, replace } => {
+                    if replace.is_empty() {
+                        self.write_line(prefix, "\\discretionary");
+                    } else {
+                        self.write_line(
+                            prefix,
+                            &format!(
+                                "\\discretionary replacing {}",
+                                replace.len()
+                            ),
+                        );
+                    }
+                    self.write_horizontal_list(pre, &format!("{}.", prefix));
+                    self.write_horizontal_list(post, &format!("{}|", prefix));
+                    self.write_horizontal_list(replace, prefix);
+                }
+                HorizontalListElem::Whatsit(operation) => {
+                    self.write_line(prefix, &format_file_operation(operation))
+                }
+            }
+        }
+    }
+
+    fn write_vertical_list(&mut self, list: &[VerticalListElem], prefix: &str) {
+        for elem in list {
+            match elem {
+                VerticalListElem::Box { tex_box, shift } => {
+                    self.write_box(tex_box, shift, prefix)
+                }
+                VerticalListElem::VSkip(glue) => self.write_line(
+                    prefix,
+                    &format!("\\glue {}", glue.to_tex_string_with_unit("")),
+                ),
+                VerticalListElem::Kern(dimen) => self.write_line(
+                    prefix,
+                    &format!("\\kern {}", format_dimen(dimen)),
+                ),
+                VerticalListElem::Penalty(penalty) => {
+                    self.write_line(prefix, &format!("\\penalty {}", penalty))
+                }
+                VerticalListElem::Rule {
+                    height,
+                    depth,
+                    width,
+                } => {
+                    self.write_line(prefix, &format_rule(height, depth, width))
+                }
+                VerticalListElem::Whatsit(operation) => {
+                    self.write_line(prefix, &format_file_operation(operation))
+                }
+            }
+        }
+    }
+}
+
+/// Serializes a horizontal list into a layout tree with one element per
+/// line. All of the dimensions are printed exactly, so two lists have the same
+/// layout tree exactly when they are laid out identically.
+#[cfg(test)]
+pub fn horizontal_list_to_layout_tree(
+    list: &[HorizontalListElem],
+    state: &TeXState,
+) -> String {
+    let mut writer = LayoutTreeWriter {
+        state,
+        out: String::new(),
+    };
+    writer.write_horizontal_list(list, "");
+    writer.out
+}
+
+/// Serializes a box into a layout tree, which is the same as what TeX's
+/// \showbox shows for the box.
+pub fn box_to_layout_tree(tex_box: &TeXBox, state: &TeXState) -> String {
+    let mut writer = LayoutTreeWriter {
+        state,
+        out: String::new(),
+    };
+    writer.write_box(tex_box, &Dimen::zero(), "");
+    writer.out
+}
+
+/// Compares two layout trees line by line. Returns None if they are the same,
+/// and otherwise a diff of the lines, where lines only in the expected tree
+/// are prefixed with `-` and lines only in the actual tree are prefixed with
+/// `+`.
+#[cfg(test)]
+pub fn diff_layout_trees(expected: &str, actual: &str) -> Option<String> {
+    if expected == actual {
+        return None;
+    }
+
+    let expected_lines: Vec<&str> = expected.lines().collect();
+    let actual_lines: Vec<&str> = actual.lines().collect();
+
+    // We find the longest common subsequence of lines between the two trees,
+    // where common_lengths[i][j] is the length of the longest common
+    // subsequence of expected_lines[i..] and actual_lines[j..].
+    let mut common_lengths =
+        vec![vec![0; actual_lines.len() + 1]; expected_lines.len() + 1];
+    for i in (0..expected_lines.len()).rev() {
+        for j in (0..actual_lines.len()).rev() {
+            common_lengths[i][j] = if expected_lines[i] == actual_lines[j] {
+                common_lengths[i + 1][j + 1] + 1
+            } else {
+                std::cmp::max(
+                    common_lengths[i + 1][j],
+                    common_lengths[i][j + 1],
+                )
+            };
+        }
+    }
+
+    let mut diff = String::new();
+    let mut i = 0;
+    let mut j = 0;
+    while i < expected_lines.len() || j < actual_lines.len() {
+        if i < expected_lines.len()
+            && j < actual_lines.len()
+            && expected_lines[i] == actual_lines[j]
+        {
+            diff.push_str(&format!("  {}\n", expected_lines[i]));
+            i += 1;
+            j += 1;
+        } else if i < expected_lines.len()
+            && (j == actual_lines.len()
+                || common_lengths[i + 1][j] >= common_lengths[i][j + 1])
+        {
+            diff.push_str(&format!("- {}\n", expected_lines[i]));
+            i += 1;
+        } else {
+            diff.push_str(&format!("+ {}\n", actual_lines[j]));
+            j += 1;
+        }
+    }
+
+    Some(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::boxes::HorizontalBox;
+    use crate::dimension::Unit;
+    use crate::font::Font;
+    use crate::glue::Glue;
+    use crate::testing::with_parser;
+
+    #[test]
+    fn it_serializes_nested_lists() {
+        let font = Font {
+            font_name: "cmr10".to_string(),
+            scale: Dimen::from_unit(10.0, Unit::Point),
+        };
+
+        let list = vec![
+            HorizontalListElem::Char {
+                chr: 'a',
+                font: font.clone(),
+            },
+            HorizontalListElem::Kern(Dimen::from_unit(1.5, Unit::Point)),
+            HorizontalListElem::Box {
+                tex_box: TeXBox::HorizontalBox(HorizontalBox {
+                    height: Dimen::from_unit(2.0, Unit::Point),
+                    depth: Dimen::zero(),
+                    width: Dimen::from_unit(3.0, Unit::Point),
+                    list: vec![HorizontalListElem::Char { chr: 'b', font }],
+                    glue_set_ratio: None,
+                }),
+                shift: Dimen::from_unit(-1.0, Unit::Point),
+            },
+            HorizontalListElem::HSkip(Glue::from_dimen(Dimen::from_unit(
+                4.0,
+                Unit::Point,
+            ))),
+            HorizontalListElem::Rule {
+                height: None,
+                depth: None,
+                width: Some(Dimen::from_unit(0.4, Unit::Point)),
+            },
+        ];
+
+        assert_eq!(
+            horizontal_list_to_layout_tree(&list, &TeXState::new()),
+            [
+                r"\cmr10 a",
+                r"\kern 1.5",
+                r"\hbox(2.0+0.0)x3.0, shifted 1.0",
+                r".\cmr10 b",
+                r"\glue 4.0",
+                r"\rule(*+*)x0.4",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    // Builds each box in box register 0, and checks that its layout tree is
+    // exactly what real TeX shows for \showbox0 after the same setup.
+    fn assert_boxes_match_showbox(cases: &[(&str, &[&str])]) {
+        for (setbox, expected) in cases {
+            with_parser(&[r"\font\tenrm=cmr10 \tenrm%", setbox], |parser| {
+                // The \font, selecting \tenrm, and the \setbox.
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                let tex_box = parser.state.get_box(0).unwrap();
+                assert_eq!(
+                    box_to_layout_tree(&tex_box, parser.state),
+                    expected.join("\n") + "\n"
+                );
+            });
+        }
+    }
+
+    #[test]
+    fn it_matches_real_tex_showbox_output() {
+        assert_boxes_match_showbox(&[
+            (
+                r"\setbox0=\hbox to 20pt{a\hskip 0pt plus 1fil}%",
+                &[
+                    r"\hbox(4.30554+0.0)x20.0, glue set 14.99998fil",
+                    r".\tenrm a",
+                    r".\glue 0.0 plus 1.0fil",
+                ],
+            ),
+            (
+                r"\setbox0=\hbox{\raise2pt\hbox{a}\kern1pt\vrule width 0.4pt}%",
+                &[
+                    r"\hbox(6.30554+0.0)x6.40001",
+                    r".\hbox(4.30554+0.0)x5.00002, shifted -2.0",
+                    r"..\tenrm a",
+                    r".\kern 1.0",
+                    r".\rule(*+*)x0.4",
+                ],
+            ),
+            (
+                r"\setbox0=\vbox to 10pt{\hrule height 12pt\vskip 0pt minus 4pt\penalty 100}%",
+                &[
+                    r"\vbox(10.0+0.0)x0.0, glue set - 0.5",
+                    r".\rule(12.0+0.0)x*",
+                    r".\glue 0.0 minus 4.0",
+                    r".\penalty 100",
+                ],
+            ),
+            (
+                r"\setbox0=\vbox{\moveright 3pt\hbox{a}}%",
+                &[
+                    r"\vbox(4.30554+0.0)x8.00002",
+                    r".\hbox(4.30554+0.0)x5.00002, shifted 3.0",
+                    r"..\tenrm a",
+                ],
+            ),
+        ]);
+    }
+
+    #[test]
+    fn it_shows_discretionaries_like_tex() {
+        let font = Font {
+            font_name: "cmr10".to_string(),
+            scale: Dimen::from_unit(10.0, Unit::Point),
+        };
+        let char_elem = |chr| HorizontalListElem::Char {
+            chr,
+            font: font.clone(),
+        };
+
+        let list = vec![HorizontalListElem::Discretionary {
+            pre: vec![char_elem('-')],
+            post: vec![char_elem('b')],
+            replace: vec![char_elem('c')],
+        }];
+
+        assert_eq!(
+            horizontal_list_to_layout_tree(&list, &TeXState::new()),
+            [
+                r"\discretionary replacing 1",
+                r".\cmr10 -",
+                r"|\cmr10 b",
+                r"\cmr10 c",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn it_diffs_layout_trees() {
+        assert_eq!(diff_layout_trees("a\nb\n", "a\nb\n"), None);
+        assert_eq!(
+            diff_layout_trees("a\nb\nc\n", "a\nx\nc\nd\n"),
+            Some("  a\n- b\n+ x\n  c\n+ d\n".to_string())
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use crate::category::Category;
-use crate::state::TeXState;
+use crate::diagnostics::DiagnosticKind;
+use crate::state::{IntegerParameter, TeXState};
 use crate::token::Token;
 
 #[derive(Debug, PartialEq, Eq)]
@@ -30,11 +31,64 @@ fn hex_value(ch: char) -> u8 {
     }
 }
 
+// The position in a source that we stopped reading from when we started
+// reading from a file with \input, so that we can go back to it when the file
+// runs out.
+struct SuspendedInput {
+    source: Vec<Vec<char>>,
+    file_name: Option<String>,
+    row: usize,
+    col: usize,
+    lex_state: LexState,
+    end_line_char: Option<char>,
+
+    // Tokens that had already been read from the source before it was
+    // suspended, which come before the rest of its characters.
+    upcoming_tokens: Vec<Token>,
+}
+
+fn lines_to_source<T>(lines: &[T]) -> Vec<Vec<char>>
+where
+    T: AsRef<str>,
+    T: std::string::ToString,
+{
+    lines
+        .iter()
+        .map(|s| {
+            // Each line ends with a placeholder, which is replaced with the
+            // \endlinechar when it is read.
+            let mut line = s.to_string();
+            line.push('\n');
+            line.chars().collect()
+        })
+        .collect()
+}
+
 pub struct Lexer<'a> {
     source: Vec<Vec<char>>,
+    // The name of the file that the source was read from, or None for the
+    // lines that we started with.
+    file_name: Option<String>,
     row: usize,
     col: usize,
     lex_state: LexState,
+    // The \endlinechar at the time that we started reading the current line,
+    // or None if it was outside of 0-255 and nothing should be added to the
+    // end of the line.
+    end_line_char: Option<char>,
+
+    // The sources that have been suspended by \input, with the most recent
+    // one last.
+    input_stack: Vec<SuspendedInput>,
+    // Tokens from a suspended source that we went back to, which are returned
+    // before lexing anything else. Like the parser's upcoming tokens, the next
+    // token is at the end.
+    resumed_tokens: Vec<Token>,
+
+    // Whether the line that we're reading should be attached to diagnostics.
+    // This is turned off for lexers that read lines for \read, which aren't
+    // part of the input.
+    tracks_diagnostics_line: bool,
 
     state: &'a TeXState,
 }
@@ -45,22 +99,151 @@ impl<'a> Lexer<'a> {
         T: AsRef<str>,
         T: std::string::ToString,
     {
-        let source = lines
-            .iter()
-            .map(|s| {
-                let mut line = s.to_string();
-                line.push('\n');
-                line.chars().collect()
-            })
-            .collect();
-
-        Lexer {
-            source,
+        let mut lexer = Lexer {
+            source: lines_to_source(lines),
+            file_name: None,
             row: 0,
             col: 0,
             lex_state: LexState::BeginningLine,
+            end_line_char: None,
+            input_stack: Vec::new(),
+            resumed_tokens: Vec::new(),
+            tracks_diagnostics_line: true,
             state,
+        };
+        lexer.start_line();
+        lexer
+    }
+
+    /// Lexes all of the tokens in a single line using the current category
+    /// codes, including the \endlinechar at the end of it, like for \read.
+    pub fn lex_line(line: &str, state: &'a TeXState) -> Vec<Token> {
+        let mut lexer = Lexer {
+            source: lines_to_source(&[line]),
+            file_name: None,
+            row: 0,
+            col: 0,
+            lex_state: LexState::BeginningLine,
+            end_line_char: None,
+            input_stack: Vec::new(),
+            resumed_tokens: Vec::new(),
+            tracks_diagnostics_line: false,
+            state,
+        };
+        lexer.start_line();
+
+        let mut tokens = Vec::new();
+        while let Some(token) = lexer.lex_token() {
+            tokens.push(token);
         }
+        tokens
+    }
+
+    /// The number of sources that have been suspended to read from other
+    /// sources, which is how many \input files are currently being read.
+    pub fn get_input_depth(&self) -> usize {
+        self.input_stack.len()
+    }
+
+    /// Starts reading from the given lines of the named file, like for
+    /// \input. Once they run out, we go back to reading the current source,
+    /// starting with the given upcoming tokens (which have the next token at
+    /// the end).
+    pub fn push_source<T>(
+        &mut self,
+        file_name: &str,
+        lines: &[T],
+        upcoming_tokens: Vec<Token>,
+    ) where
+        T: AsRef<str>,
+        T: std::string::ToString,
+    {
+        let mut saved_tokens = std::mem::take(&mut self.resumed_tokens);
+        saved_tokens.extend(upcoming_tokens);
+
+        let suspended = SuspendedInput {
+            source: std::mem::replace(&mut self.source, lines_to_source(lines)),
+            file_name: self.file_name.replace(file_name.to_string()),
+            row: self.row,
+            col: self.col,
+            lex_state: std::mem::replace(
+                &mut self.lex_state,
+                LexState::BeginningLine,
+            ),
+            end_line_char: self.end_line_char,
+            upcoming_tokens: saved_tokens,
+        };
+        self.input_stack.push(suspended);
+
+        self.row = 0;
+        self.col = 0;
+        self.update_diagnostics_file();
+        self.start_line();
+    }
+
+    /// Stops reading the current source once the current line is finished,
+    /// like for \endinput.
+    pub fn end_source_after_line(&mut self) {
+        self.source.truncate(self.row + 1);
+    }
+
+    // Goes back to the most recently suspended source, returning false if
+    // there isn't one.
+    fn pop_source(&mut self) -> bool {
+        match self.input_stack.pop() {
+            Some(suspended) => {
+                self.source = suspended.source;
+                self.file_name = suspended.file_name;
+                self.row = suspended.row;
+                self.col = suspended.col;
+                self.lex_state = suspended.lex_state;
+                self.end_line_char = suspended.end_line_char;
+                self.resumed_tokens = suspended.upcoming_tokens;
+                self.update_diagnostics_file();
+                self.update_diagnostics_line();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Keeps the file attached to diagnostics in sync with the source that
+    // we're reading.
+    fn update_diagnostics_file(&self) {
+        if !self.tracks_diagnostics_line {
+            return;
+        }
+
+        let file_name = self.file_name.clone();
+        self.state
+            .with_diagnostics(|diagnostics| diagnostics.set_file(file_name));
+    }
+
+    // Keeps the line attached to diagnostics in sync with the line that we're
+    // reading.
+    fn update_diagnostics_line(&self) {
+        if !self.tracks_diagnostics_line {
+            return;
+        }
+
+        let line = self.row + 1;
+        self.state
+            .with_diagnostics(|diagnostics| diagnostics.set_line(line));
+    }
+
+    // Like TeX, we decide what character goes at the end of a line when we
+    // start reading it, so changes to \endlinechar only affect the lines
+    // after the one that they're made on.
+    fn start_line(&mut self) {
+        let end_line_char = self
+            .state
+            .get_integer_parameter(&IntegerParameter::EndLineChar);
+        self.end_line_char = if (0..256).contains(&end_line_char) {
+            Some((end_line_char as u8) as char)
+        } else {
+            None
+        };
+        self.update_diagnostics_line();
     }
 
     fn get_plain_char(&mut self) -> PlainLexResult {
@@ -73,12 +256,22 @@ impl<'a> Lexer<'a> {
         if self.col == line.len() {
             self.row += 1;
             self.col = 0;
+            self.start_line();
             return PlainLexResult::Eol;
         }
 
         let ch = line[self.col];
         self.col += 1;
-        PlainLexResult::Char(ch)
+
+        if self.col == line.len() {
+            // This is the placeholder at the end of the line.
+            match self.end_line_char {
+                Some(end_line_char) => PlainLexResult::Char(end_line_char),
+                None => self.get_plain_char(),
+            }
+        } else {
+            PlainLexResult::Char(ch)
+        }
     }
 
     fn unget_plain_char(&mut self, ch: &PlainLexResult) {
@@ -87,6 +280,7 @@ impl<'a> Lexer<'a> {
             PlainLexResult::Eol => {
                 self.row -= 1;
                 self.col = self.source[self.row].len() - 1;
+                self.update_diagnostics_line();
             }
             PlainLexResult::Eof => (),
         }
@@ -158,14 +352,32 @@ impl<'a> Lexer<'a> {
     }
 
     pub fn lex_token(&mut self) -> Option<Token> {
+        if let Some(token) = self.resumed_tokens.pop() {
+            return Some(token);
+        }
+
         match self.get_char() {
-            PlainLexResult::Eof => None,
+            PlainLexResult::Eof => {
+                if self.pop_source() {
+                    self.lex_token()
+                } else {
+                    None
+                }
+            }
             PlainLexResult::Eol => {
                 self.lex_state = LexState::BeginningLine;
                 self.lex_token()
             }
             PlainLexResult::Char(c) => match self.state.get_category(c) {
-                Category::Invalid => panic!("Invalid character: '{}'", c),
+                Category::Invalid => {
+                    // Like TeX, we complain and then act like the character
+                    // wasn't there.
+                    self.state.with_diagnostics(|diagnostics| {
+                        diagnostics
+                            .report(DiagnosticKind::InvalidCharacter { chr: c })
+                    });
+                    self.lex_token()
+                }
                 Category::Escape => {
                     self.lex_state = LexState::SkippingBlanks;
 
@@ -198,7 +410,13 @@ impl<'a> Lexer<'a> {
 
                             Some(Token::ControlSequence(sequence))
                         }
+                        Category::Space => {
+                            Some(Token::ControlSequence(first_char.to_string()))
+                        }
+                        // Spaces are only skipped after control words and
+                        // control spaces, not after other control symbols.
                         _ => {
+                            self.lex_state = LexState::MiddleLine;
                             Some(Token::ControlSequence(first_char.to_string()))
                         }
                     }
@@ -221,6 +439,8 @@ impl<'a> Lexer<'a> {
                     }
                 }
                 Category::Comment => {
+                    // This skips the \endlinechar too, so nothing is added
+                    // after a comment.
                     self.col = self.source[self.row].len();
                     self.lex_token()
                 }
@@ -238,6 +458,11 @@ impl<'a> Lexer<'a> {
 mod tests {
     use super::*;
 
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::testing::SharedWriter;
+
     fn assert_lexes_to_with_state(
         lines: &[&str],
         expected_toks: &[Token],
@@ -295,9 +520,25 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "Invalid character:")]
-    fn it_panics_on_invalid_tokens() {
-        assert_lexes_to(&["\u{00ff}"], &[]);
+    fn it_reports_and_skips_invalid_characters() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let state = TeXState::new();
+        state.with_diagnostics(|diagnostics| {
+            diagnostics.set_json_writer(Box::new(SharedWriter(output.clone())))
+        });
+
+        assert_lexes_to_with_state(
+            &["a\u{00ff}b%"],
+            &[
+                Token::Char('a', Category::Letter),
+                Token::Char('b', Category::Letter),
+            ],
+            &state,
+        );
+
+        let output = String::from_utf8(output.borrow().clone()).unwrap();
+        assert!(output.contains(r#""kind":"invalid_character""#));
+        assert!(output.contains("\"character\":\"\u{00ff}\""));
     }
 
     #[test]
@@ -357,6 +598,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_keeps_space_after_control_symbols() {
+        assert_lexes_to(
+            &["\\\\ \\, %"],
+            &[
+                Token::ControlSequence("\\".to_string()),
+                Token::Char(' ', Category::Space),
+                Token::ControlSequence(",".to_string()),
+                Token::Char(' ', Category::Space),
+            ],
+        );
+    }
+
     #[test]
     fn it_condenses_multiple_spaces_into_one_space() {
         assert_lexes_to(
@@ -386,6 +640,58 @@ mod tests {
         assert_lexes_to(&["a%b"], &[Token::Char('a', Category::Letter)]);
     }
 
+    #[test]
+    fn it_does_not_add_end_line_chars_after_comments() {
+        assert_lexes_to(
+            &["a%comment", "b%"],
+            &[
+                Token::Char('a', Category::Letter),
+                Token::Char('b', Category::Letter),
+            ],
+        );
+    }
+
+    #[test]
+    fn it_adds_the_end_line_char_to_the_end_of_lines() {
+        let state = TeXState::new();
+        state.set_integer_parameter(
+            false,
+            &IntegerParameter::EndLineChar,
+            '.' as i32,
+        );
+        assert_lexes_to_with_state(
+            &["a", "b%"],
+            &[
+                Token::Char('a', Category::Letter),
+                Token::Char('.', Category::Other),
+                Token::Char('b', Category::Letter),
+            ],
+            &state,
+        );
+
+        state.set_integer_parameter(false, &IntegerParameter::EndLineChar, -1);
+        assert_lexes_to_with_state(
+            &["a", "b"],
+            &[
+                Token::Char('a', Category::Letter),
+                Token::Char('b', Category::Letter),
+            ],
+            &state,
+        );
+    }
+
+    #[test]
+    fn it_only_uses_new_end_line_chars_on_later_lines() {
+        let state = TeXState::new();
+        let mut lexer = Lexer::new(&["a", "b"], &state);
+
+        assert_eq!(lexer.lex_token(), Some(Token::Char('a', Category::Letter)));
+        state.set_integer_parameter(false, &IntegerParameter::EndLineChar, -1);
+        assert_eq!(lexer.lex_token(), Some(Token::Char(' ', Category::Space)));
+        assert_eq!(lexer.lex_token(), Some(Token::Char('b', Category::Letter)));
+        assert_eq!(lexer.lex_token(), None);
+    }
+
     #[test]
     fn it_uses_real_state() {
         let state = TeXState::new();
@@ -0,0 +1,121 @@
+//! Limits on how many resources a job can use, so that a program embedding
+//! the engine can stop documents that would otherwise run forever or use up
+//! all of its memory. By default there aren't any limits.
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceLimit {
+    // The total number of elements added to horizontal and vertical lists.
+    ListElements,
+    // How many \hbox and \vbox groups can be nested inside of each other.
+    BoxNesting,
+    // The number of pages that are shipped out.
+    Pages,
+    // The number of macros that are expanded.
+    ExpansionSteps,
+    // The number of files open at once, counting files being read with
+    // \input, files opened with \openin, and files opened with \openout.
+    OpenFiles,
+}
+
+impl ResourceLimit {
+    fn name(&self) -> &'static str {
+        match self {
+            ResourceLimit::ListElements => "list elements",
+            ResourceLimit::BoxNesting => "box nesting",
+            ResourceLimit::Pages => "pages",
+            ResourceLimit::ExpansionSteps => "expansion steps",
+            ResourceLimit::OpenFiles => "open files",
+        }
+    }
+}
+
+/// The error for when a job tries to go past one of its limits. The message
+/// is modeled after the one TeX gives when it runs out of memory.
+#[derive(Debug, PartialEq)]
+pub struct ResourceLimitExceeded {
+    pub limit: ResourceLimit,
+    pub max: usize,
+}
+
+impl fmt::Display for ResourceLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "TeX capacity exceeded, sorry [{}={}]",
+            self.limit.name(),
+            self.max
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ResourceLimits {
+    maximums: HashMap<ResourceLimit, usize>,
+}
+
+impl ResourceLimits {
+    pub fn unlimited() -> Self {
+        ResourceLimits {
+            maximums: HashMap::new(),
+        }
+    }
+
+    // The command line doesn't set any limits, so this is only used by tests
+    // until there's a way to embed the engine.
+    #[allow(dead_code)]
+    pub fn with_max(mut self, limit: ResourceLimit, max: usize) -> Self {
+        self.maximums.insert(limit, max);
+        self
+    }
+
+    /// Checks that the given amount of a resource is allowed.
+    pub fn check(
+        &self,
+        limit: ResourceLimit,
+        amount: usize,
+    ) -> Result<(), ResourceLimitExceeded> {
+        match self.maximums.get(&limit) {
+            Some(&max) if amount > max => {
+                Err(ResourceLimitExceeded { limit, max })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_only_limits_resources_with_maximums() {
+        let limits = ResourceLimits::unlimited()
+            .with_max(ResourceLimit::Pages, 2)
+            .with_max(ResourceLimit::BoxNesting, 0);
+
+        assert_eq!(limits.check(ResourceLimit::Pages, 2), Ok(()));
+        assert_eq!(
+            limits.check(ResourceLimit::Pages, 3),
+            Err(ResourceLimitExceeded {
+                limit: ResourceLimit::Pages,
+                max: 2
+            })
+        );
+        assert!(limits.check(ResourceLimit::BoxNesting, 1).is_err());
+        assert_eq!(limits.check(ResourceLimit::ExpansionSteps, 1000), Ok(()));
+    }
+
+    #[test]
+    fn it_describes_the_exceeded_limit() {
+        let error = ResourceLimitExceeded {
+            limit: ResourceLimit::ExpansionSteps,
+            max: 100,
+        };
+        assert_eq!(
+            error.to_string(),
+            "TeX capacity exceeded, sorry [expansion steps=100]"
+        );
+    }
+}
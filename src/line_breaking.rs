@@ -6,10 +6,19 @@ use crate::glue::Glue;
 use crate::list::HorizontalListElem;
 use crate::state::TeXState;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 pub struct LineBreakingParams {
     pub hsize: Dimen,
+    // The indentation and width of the first lines of the paragraph, with the
+    // last entry being used for all of the lines after that. If this is empty,
+    // every line is \hsize wide and isn't indented. This comes from
+    // \parshape or \hangindent.
+    pub par_shape: Vec<(Dimen, Dimen)>,
+    // The glue put at the left and right of every line, from \leftskip and
+    // \rightskip.
+    pub left_skip: Glue,
+    pub right_skip: Glue,
     pub tolerance: i32,
     pub visual_incompatibility_demerits: i32,
 
@@ -18,6 +27,41 @@ pub struct LineBreakingParams {
     pub should_log: bool,
 }
 
+impl LineBreakingParams {
+    // Lines after the last entry in the paragraph shape all have the same
+    // shape, so we only need to distinguish between lines up to that point.
+    // This returns the class of the line that comes after `lines_before`
+    // lines, where lines in the same class have the same shape.
+    fn get_line_class(&self, lines_before: usize) -> usize {
+        lines_before.min(self.par_shape.len().saturating_sub(1))
+    }
+
+    // Returns the indentation and width of the line in the given class.
+    fn get_line_shape(&self, line_class: usize) -> (Dimen, Dimen) {
+        self.par_shape
+            .get(line_class)
+            .copied()
+            .unwrap_or((Dimen::zero(), self.hsize))
+    }
+
+    // Surrounds the elements of a line with the \leftskip and \rightskip glue.
+    // Like TeX, we leave out the glue when it is zero.
+    fn add_margin_glue(
+        &self,
+        line_list: Vec<HorizontalListElem>,
+    ) -> Vec<HorizontalListElem> {
+        let mut result = Vec::new();
+        if self.left_skip != Glue::zero() {
+            result.push(HorizontalListElem::HSkip(self.left_skip.clone()));
+        }
+        result.extend(line_list);
+        if self.right_skip != Glue::zero() {
+            result.push(HorizontalListElem::HSkip(self.right_skip.clone()));
+        }
+        result
+    }
+}
+
 #[derive(Debug, PartialEq)]
 struct LineBreakingResult {
     total_demerits: i64,
@@ -59,6 +103,13 @@ fn get_list_indices_for_breaks(
 ) -> Option<(usize, usize)> {
     let start_index = match start {
         LineBreakPoint::Start => Some(0),
+        // After a break at a discretionary, the next line starts with the
+        // discretionary's post-break material, so nothing is discarded.
+        LineBreakPoint::BreakAtIndex(index)
+            if is_discretionary_at_index(list, *index) =>
+        {
+            Some(index + 1)
+        }
         LineBreakPoint::BreakAtIndex(index) => Some(
             list.iter()
                 .skip(*index)
@@ -77,6 +128,50 @@ fn get_list_indices_for_breaks(
     Some((start_index, end_index))
 }
 
+fn is_discretionary_at_index(
+    list: &[HorizontalListElem],
+    index: usize,
+) -> bool {
+    matches!(
+        list.get(index),
+        Some(HorizontalListElem::Discretionary { .. })
+    )
+}
+
+// Returns the elements in the line between two breaks. If the line starts
+// after a break at a discretionary, it begins with the discretionary's
+// post-break list, and if it ends at a discretionary, it ends with the
+// discretionary's pre-break list.
+fn get_line_elems<'a>(
+    list: &'a Vec<HorizontalListElem>,
+    start: &LineBreakPoint,
+    end: &LineBreakPoint,
+) -> Option<impl Iterator<Item = &'a HorizontalListElem>> {
+    let (start_index, end_index) =
+        get_list_indices_for_breaks(list, start, end)?;
+
+    let post: &[HorizontalListElem] = match start {
+        LineBreakPoint::BreakAtIndex(index) => match &list[*index] {
+            HorizontalListElem::Discretionary { post, .. } => post,
+            _ => &[],
+        },
+        _ => &[],
+    };
+    let pre: &[HorizontalListElem] = match end {
+        LineBreakPoint::BreakAtIndex(index) => match &list[*index] {
+            HorizontalListElem::Discretionary { pre, .. } => pre,
+            _ => &[],
+        },
+        _ => &[],
+    };
+
+    Some(
+        post.iter()
+            .chain(list.get(start_index..end_index)?.iter())
+            .chain(pre.iter()),
+    )
+}
+
 #[derive(Debug, Clone, Copy)]
 enum VisualClassification {
     VeryLoose = 0,
@@ -121,9 +216,21 @@ impl VisualClassification {
     }
 }
 
+// A node in the line breaking graph. Since lines can have different widths
+// depending on how many lines come before them, the same break point can be
+// reached with different numbers of lines before it, and those need to be
+// kept track of separately.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+struct LineBreakNode {
+    point: LineBreakPoint,
+    // The class (from LineBreakingParams::get_line_class) of the line that
+    // comes after this break.
+    line_class: usize,
+}
+
 #[derive(Debug, Clone)]
 struct LineBreakBacktrace {
-    prev_break: Option<LineBreakPoint>,
+    prev_break: Option<LineBreakNode>,
     total_demerits: i64,
     prev_line_classification: VisualClassification,
 }
@@ -132,9 +239,14 @@ struct LineBreakBacktrace {
 struct LineBreakGraph {
     // A list of backtraces from a given breakpoint to the best break before it.
     // Each value corresponds to an entry in break_nodes
-    best_path_to: HashMap<LineBreakPoint, LineBreakBacktrace>,
+    best_path_to: HashMap<LineBreakNode, LineBreakBacktrace>,
 }
 
+const START_NODE: LineBreakNode = LineBreakNode {
+    point: LineBreakPoint::Start,
+    line_class: 0,
+};
+
 impl LineBreakGraph {
     // Set up an empty line breaking graph given a list of indices.
     fn new() -> Self {
@@ -143,7 +255,7 @@ impl LineBreakGraph {
         };
 
         graph.best_path_to.insert(
-            LineBreakPoint::Start,
+            START_NODE,
             LineBreakBacktrace {
                 prev_break: None,
                 total_demerits: 0,
@@ -155,7 +267,7 @@ impl LineBreakGraph {
     }
 
     // Find the best demerits from the start to a given node, if one exists
-    fn get_best_demerits_to_node(&self, to: &LineBreakPoint) -> Option<i64> {
+    fn get_best_demerits_to_node(&self, to: &LineBreakNode) -> Option<i64> {
         self.best_path_to
             .get(to)
             .map(|backtrace| backtrace.total_demerits)
@@ -163,7 +275,7 @@ impl LineBreakGraph {
 
     fn get_classification_of_best_line_before_node(
         &self,
-        to: &LineBreakPoint,
+        to: &LineBreakNode,
     ) -> Option<VisualClassification> {
         self.best_path_to
             .get(to)
@@ -173,8 +285,8 @@ impl LineBreakGraph {
     // Update the best path to a given node
     fn update_best_path_to_node(
         &mut self,
-        to: &LineBreakPoint,
-        from: &LineBreakPoint,
+        to: &LineBreakNode,
+        from: &LineBreakNode,
         demerits: i64,
         prev_line_classification: VisualClassification,
     ) {
@@ -188,21 +300,25 @@ impl LineBreakGraph {
         );
     }
 
-    // Return the best list of breaks to the end node
-    fn get_best_breaks_to_end(&self) -> Option<LineBreakingResult> {
-        let end_demerits =
-            self.get_best_demerits_to_node(&LineBreakPoint::End)?;
+    // Return the best list of breaks to any of the end nodes
+    fn get_best_breaks_to_end(
+        &self,
+        params: &LineBreakingParams,
+    ) -> Option<LineBreakingResult> {
+        let end_node = (0..=params.get_line_class(params.par_shape.len()))
+            .map(|line_class| LineBreakNode {
+                point: LineBreakPoint::End,
+                line_class,
+            })
+            .filter(|node| self.best_path_to.contains_key(node))
+            .min_by_key(|node| self.get_best_demerits_to_node(node).unwrap())?;
+
+        let end_demerits = self.get_best_demerits_to_node(&end_node)?;
         let mut all_breaks = vec![LineBreakPoint::End];
-        let mut curr_break_backtrace = if let Some(backtrace) =
-            self.best_path_to.get(&LineBreakPoint::End)
-        {
-            backtrace
-        } else {
-            return None;
-        };
+        let mut curr_break_backtrace = self.best_path_to.get(&end_node)?;
 
         while let Some(prev_break) = curr_break_backtrace.prev_break {
-            all_breaks.insert(0, prev_break);
+            all_breaks.insert(0, prev_break.point);
             curr_break_backtrace =
                 if let Some(backtrace) = &self.best_path_to.get(&prev_break) {
                     backtrace
@@ -226,7 +342,23 @@ fn get_available_break_indices(
     available_break_indices.push(LineBreakPoint::Start);
     for (i, curr) in list.iter().enumerate() {
         match curr {
-            HorizontalListElem::HSkip(_) => {
+            // Glue is a legal breakpoint if the thing before it isn't
+            // discardable.
+            HorizontalListElem::HSkip(_)
+                if i > 0 && !list[i - 1].is_discardable() =>
+            {
+                available_break_indices.push(LineBreakPoint::BreakAtIndex(i));
+            }
+            // Kerns are only legal breakpoints if they are immediately
+            // followed by glue.
+            HorizontalListElem::Kern(_) => {
+                if let Some(HorizontalListElem::HSkip(_)) = list.get(i + 1) {
+                    available_break_indices
+                        .push(LineBreakPoint::BreakAtIndex(i));
+                }
+            }
+            // Discretionaries are always legal breakpoints.
+            HorizontalListElem::Discretionary { .. } => {
                 available_break_indices.push(LineBreakPoint::BreakAtIndex(i));
             }
             _ => (),
@@ -254,6 +386,7 @@ fn get_demerits_for_line_between(
     state: &TeXState,
     start: &LineBreakPoint,
     end: &LineBreakPoint,
+    desired_width: &Dimen,
     previous_classification: Option<VisualClassification>,
 ) -> Option<DemeritResult> {
     let (start_index, end_index) =
@@ -263,12 +396,12 @@ fn get_demerits_for_line_between(
         return None;
     }
 
-    let line_width = list
-        .get(start_index..end_index)?
-        .iter()
-        .fold(Glue::zero(), |width, elem| width + elem.get_size(state).2);
+    let line_width = get_line_elems(list, start, end)?.fold(
+        params.left_skip.clone() + params.right_skip.clone(),
+        |width, elem| width + elem.get_size(state).2,
+    );
 
-    let glue_set = set_glue_for_dimen(&params.hsize, &line_width);
+    let glue_set = set_glue_for_dimen(desired_width, &line_width);
     let badness = match glue_set {
         GlueSetResult::GlueSetRatio(glue_set_ratio) => {
             glue_set_ratio.get_badness()
@@ -290,7 +423,7 @@ fn get_demerits_for_line_between(
 
     let visual_classification = VisualClassification::from_badness(
         badness,
-        params.hsize < line_width.space,
+        *desired_width < line_width.space,
     );
     let adjacent_classification_demerits =
         if let Some(previous_classification) = previous_classification {
@@ -339,32 +472,41 @@ fn generate_best_list_break_option_with_params(
 
     // Keep track of previous breakpoints that we've looked at already, that are
     // still reachable from the current break without being overfull.
-    let mut reachable_previous_breaks: Vec<LineBreakPoint> =
-        Vec::from([LineBreakPoint::Start]);
+    let mut reachable_previous_breaks: Vec<LineBreakNode> =
+        Vec::from([START_NODE]);
 
-    // For logging, we don't want to refer to our `LineBreakPoint`s using our
+    // For logging, we don't want to refer to our `LineBreakNode`s using our
     // internal representation, so we sequentially number the feasible
     // breakpoints we find, with the start referring to 0.
     let mut next_feasible_line_break_number = 1;
-    let mut feasible_line_break_numbers: HashMap<LineBreakPoint, usize> =
+    let mut feasible_line_break_numbers: HashMap<LineBreakNode, usize> =
         HashMap::new();
-    feasible_line_break_numbers.insert(LineBreakPoint::Start, 0);
+    feasible_line_break_numbers.insert(START_NODE, 0);
 
     for line_break in line_breaks.iter().skip(1) {
-        let mut maybe_best_backwards_path: Option<LineBreakPoint> = None;
-        let mut best_classification: Option<VisualClassification> = None;
-        let mut best_total_demerits: i64 = 0;
+        // The best way to reach this break, for each class of line that could
+        // come after it. This stores the previous break, the classification
+        // of the line before this break, and the total demerits.
+        let mut best_backwards_paths: BTreeMap<
+            usize,
+            (LineBreakNode, VisualClassification, i64),
+        > = BTreeMap::new();
         for previous_break in reachable_previous_breaks.clone().iter() {
             let previous_demerits =
                 graph.get_best_demerits_to_node(previous_break).unwrap();
             let previous_classification = graph
                 .get_classification_of_best_line_before_node(previous_break);
+            let (_, desired_width) =
+                params.get_line_shape(previous_break.line_class);
+            let line_class =
+                params.get_line_class(previous_break.line_class + 1);
             if let Some(demerits) = get_demerits_for_line_between(
                 list,
                 params,
                 state,
-                previous_break,
+                &previous_break.point,
                 line_break,
+                &desired_width,
                 previous_classification,
             ) {
                 match demerits {
@@ -405,12 +547,16 @@ fn generate_best_list_break_option_with_params(
                                     feasible_line_break_numbers[previous_break]
                                 );
                             }
-                            maybe_best_backwards_path = Some(*previous_break);
-                            best_classification =
-                                Some(VisualClassification::Tight);
                             // When this happens, even though this is a very bad
                             // situation, we add no demerits.
-                            best_total_demerits = previous_demerits;
+                            best_backwards_paths.insert(
+                                line_class,
+                                (
+                                    *previous_break,
+                                    VisualClassification::Tight,
+                                    previous_demerits,
+                                ),
+                            );
                         }
                     }
                     DemeritResult::TooLargeBadness => {} // ignore
@@ -427,13 +573,23 @@ fn generate_best_list_break_option_with_params(
                                 demerits
                             );
                         }
-                        if maybe_best_backwards_path.is_none()
-                            || demerits + previous_demerits
-                                <= best_total_demerits
-                        {
-                            maybe_best_backwards_path = Some(*previous_break);
-                            best_classification = Some(classification);
-                            best_total_demerits = demerits + previous_demerits;
+                        let total_demerits = demerits + previous_demerits;
+                        let is_best =
+                            match best_backwards_paths.get(&line_class) {
+                                Some((_, _, best_total_demerits)) => {
+                                    total_demerits <= *best_total_demerits
+                                }
+                                None => true,
+                            };
+                        if is_best {
+                            best_backwards_paths.insert(
+                                line_class,
+                                (
+                                    *previous_break,
+                                    classification,
+                                    total_demerits,
+                                ),
+                            );
                         }
                     }
                 }
@@ -445,9 +601,17 @@ fn generate_best_list_break_option_with_params(
             }
         }
 
-        if let Some(best_backwards_path) = maybe_best_backwards_path {
+        for (
+            line_class,
+            (best_backwards_path, best_classification, best_total_demerits),
+        ) in best_backwards_paths
+        {
+            let node = LineBreakNode {
+                point: *line_break,
+                line_class,
+            };
             feasible_line_break_numbers
-                .insert(*line_break, next_feasible_line_break_number);
+                .insert(node, next_feasible_line_break_number);
             next_feasible_line_break_number += 1;
 
             if params.should_log {
@@ -455,30 +619,33 @@ fn generate_best_list_break_option_with_params(
                 // node to print here.
                 println!(
                     "@@{:?}: line x.{} t={} -> @@{:?}",
-                    feasible_line_break_numbers[line_break],
-                    best_classification.unwrap() as u8,
+                    feasible_line_break_numbers[&node],
+                    best_classification as u8,
                     best_total_demerits,
                     feasible_line_break_numbers[&best_backwards_path]
                 );
             }
-            reachable_previous_breaks.push(*line_break);
+            reachable_previous_breaks.push(node);
             graph.update_best_path_to_node(
-                line_break,
+                &node,
                 &best_backwards_path,
                 best_total_demerits,
-                best_classification.unwrap(),
+                best_classification,
             );
         }
     }
 
-    graph.get_best_breaks_to_end()
+    graph.get_best_breaks_to_end(params)
 }
 
+/// Breaks a horizontal list into lines. Each line is returned along with the
+/// amount that it should be shifted to the right, which comes from the
+/// indentation in the paragraph shape.
 pub fn break_horizontal_list_to_lines_with_params(
     list: &Vec<HorizontalListElem>,
     params: LineBreakingParams,
     state: &TeXState,
-) -> Option<Vec<TeXBox>> {
+) -> Option<Vec<(TeXBox, Dimen)>> {
     let best_option =
         generate_best_list_break_option_with_params(&list, &params, state)?;
 
@@ -487,17 +654,20 @@ pub fn break_horizontal_list_to_lines_with_params(
         .iter()
         .zip(best_option.all_breaks.iter().skip(1));
     let line_boxes = break_pairs
-        .map(|(start, end)| {
-            let (start_index, end_index) =
-                get_list_indices_for_breaks(list, &start, &end).unwrap();
-            let line_list = &list.get(start_index..end_index).unwrap();
+        .enumerate()
+        .map(|(lines_before, (start, end))| {
+            let (indent, width) =
+                params.get_line_shape(params.get_line_class(lines_before));
+            let line_list = params.add_margin_glue(
+                get_line_elems(list, start, end).unwrap().cloned().collect(),
+            );
             let line_box =
                 HorizontalBox::create_from_horizontal_list_with_layout(
-                    Vec::from(*line_list),
-                    &BoxLayout::Fixed(params.hsize),
+                    line_list,
+                    &BoxLayout::Fixed(width),
                     state,
                 );
-            TeXBox::HorizontalBox(line_box)
+            (TeXBox::HorizontalBox(line_box), indent)
         })
         .collect::<Vec<_>>();
 
@@ -519,7 +689,7 @@ mod tests {
     ) {
         with_parser(lines, |parser| {
             while parser.is_assignment_head() {
-                parser.parse_assignment(None);
+                parser.parse_assignment();
             }
             let mut expected_lines = Vec::new();
             while parser.is_box_head() {
@@ -538,12 +708,16 @@ mod tests {
 
                 assert_eq!(best_break.total_demerits, expected_demerits);
 
-                let actual_boxes = break_horizontal_list_to_lines_with_params(
-                    &hlist,
-                    params,
-                    parser.state,
-                )
-                .unwrap();
+                let actual_boxes: Vec<TeXBox> =
+                    break_horizontal_list_to_lines_with_params(
+                        &hlist,
+                        params,
+                        parser.state,
+                    )
+                    .unwrap()
+                    .into_iter()
+                    .map(|(tex_box, _)| tex_box)
+                    .collect();
 
                 // If the assert below fails, the log isn't going to be super
                 // helpful. Add in a slightly nicer check beforehand to give a
@@ -586,6 +760,9 @@ mod tests {
             ],
             LineBreakingParams {
                 hsize: Dimen::from_unit(150.0, Unit::Point),
+                par_shape: vec![],
+                left_skip: Glue::zero(),
+                right_skip: Glue::zero(),
                 tolerance: 10000,
                 visual_incompatibility_demerits: 0,
                 should_log: true,
@@ -608,6 +785,9 @@ mod tests {
             ],
             LineBreakingParams {
                 hsize: Dimen::from_unit(105.0, Unit::Point),
+                par_shape: vec![],
+                left_skip: Glue::zero(),
+                right_skip: Glue::zero(),
                 tolerance: 10000,
                 visual_incompatibility_demerits: 0,
                 should_log: true,
@@ -637,6 +817,9 @@ mod tests {
             ],
             LineBreakingParams {
                 hsize: Dimen::from_unit(105.0, Unit::Point),
+                par_shape: vec![],
+                left_skip: Glue::zero(),
+                right_skip: Glue::zero(),
                 tolerance: 10000,
                 visual_incompatibility_demerits: 10000,
                 should_log: true,
@@ -674,6 +857,9 @@ mod tests {
             ],
             LineBreakingParams {
                 hsize: Dimen::from_unit(400.0, Unit::Point),
+                par_shape: vec![],
+                left_skip: Glue::zero(),
+                right_skip: Glue::zero(),
                 tolerance: 10000,
                 visual_incompatibility_demerits: 10000,
                 should_log: true,
@@ -697,6 +883,9 @@ mod tests {
             ],
             LineBreakingParams {
                 hsize: Dimen::from_unit(80.0, Unit::Point),
+                par_shape: vec![],
+                left_skip: Glue::zero(),
+                right_skip: Glue::zero(),
                 tolerance: 10000,
                 visual_incompatibility_demerits: 0,
                 should_log: true,
@@ -726,6 +915,9 @@ mod tests {
             ],
             LineBreakingParams {
                 hsize: Dimen::from_unit(110.0, Unit::Point),
+                par_shape: vec![],
+                left_skip: Glue::zero(),
+                right_skip: Glue::zero(),
                 tolerance: 2700,
                 visual_incompatibility_demerits: 0,
                 should_log: true,
@@ -745,6 +937,9 @@ mod tests {
             ],
             LineBreakingParams {
                 hsize: Dimen::from_unit(110.0, Unit::Point),
+                par_shape: vec![],
+                left_skip: Glue::zero(),
+                right_skip: Glue::zero(),
                 tolerance: 2600,
                 visual_incompatibility_demerits: 0,
                 should_log: true,
@@ -774,6 +969,9 @@ mod tests {
             ],
             LineBreakingParams {
                 hsize: Dimen::from_unit(120.0, Unit::Point),
+                par_shape: vec![],
+                left_skip: Glue::zero(),
+                right_skip: Glue::zero(),
                 tolerance: 9999,
                 visual_incompatibility_demerits: 0,
                 should_log: true,
@@ -803,6 +1001,9 @@ mod tests {
             ],
             LineBreakingParams {
                 hsize: Dimen::from_unit(120.0, Unit::Point),
+                par_shape: vec![],
+                left_skip: Glue::zero(),
+                right_skip: Glue::zero(),
                 tolerance: 10000,
                 visual_incompatibility_demerits: 10000,
                 should_log: true,
@@ -840,6 +1041,9 @@ mod tests {
             ],
             LineBreakingParams {
                 hsize: Dimen::from_unit(90.0, Unit::Point),
+                par_shape: vec![],
+                left_skip: Glue::zero(),
+                right_skip: Glue::zero(),
                 tolerance: 100,
                 visual_incompatibility_demerits: 0,
                 should_log: true,
@@ -862,6 +1066,9 @@ mod tests {
             ],
             LineBreakingParams {
                 hsize: Dimen::from_unit(90.0, Unit::Point),
+                par_shape: vec![],
+                left_skip: Glue::zero(),
+                right_skip: Glue::zero(),
                 tolerance: 100,
                 visual_incompatibility_demerits: 100,
                 should_log: true,
@@ -869,4 +1076,69 @@ mod tests {
             9150,
         );
     }
+
+    #[test]
+    fn it_only_breaks_at_kerns_followed_by_glue() {
+        with_parser(
+            &[r"a\kern1pt b\kern1pt\hskip1pt c\hskip1pt\hskip1pt d%"],
+            |parser| {
+                let hlist = parser.parse_horizontal_list(true, false);
+
+                assert_eq!(
+                    get_available_break_indices(&hlist),
+                    vec![
+                        LineBreakPoint::Start,
+                        LineBreakPoint::BreakAtIndex(3),
+                        LineBreakPoint::BreakAtIndex(6),
+                        LineBreakPoint::End,
+                    ]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_breaks_lines_at_discretionaries() {
+        with_parser(
+            &[
+                r"\setbox1=\hbox to20pt{x}%",
+                r"\copy1\copy1\-\copy1\copy1\hskip0pt plus1fil%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                let hlist = parser.parse_horizontal_list(true, false);
+
+                assert_eq!(
+                    get_available_break_indices(&hlist),
+                    vec![
+                        LineBreakPoint::Start,
+                        LineBreakPoint::BreakAtIndex(2),
+                        LineBreakPoint::BreakAtIndex(5),
+                        LineBreakPoint::End,
+                    ]
+                );
+
+                let lines = break_horizontal_list_to_lines_with_params(
+                    &hlist,
+                    LineBreakingParams {
+                        hsize: Dimen::from_unit(50.0, Unit::Point),
+                        par_shape: vec![],
+                        left_skip: Glue::zero(),
+                        right_skip: Glue::zero(),
+                        tolerance: 10000,
+                        visual_incompatibility_demerits: 0,
+                        should_log: false,
+                    },
+                    parser.state,
+                )
+                .unwrap();
+
+                let line_chars: Vec<String> = lines
+                    .iter()
+                    .map(|(line, _)| line.to_chars().into_iter().collect())
+                    .collect();
+                assert_eq!(line_chars, vec!["xx-", "xx "]);
+            },
+        );
+    }
 }
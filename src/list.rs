@@ -2,13 +2,39 @@ use crate::boxes::TeXBox;
 use crate::dimension::Dimen;
 use crate::font::Font;
 use crate::glue::Glue;
+use crate::output_files::FileOperation;
 use crate::state::TeXState;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum HorizontalListElem {
-    Char { chr: char, font: Font },
+    Char {
+        chr: char,
+        font: Font,
+    },
     HSkip(Glue),
-    Box { tex_box: TeXBox, shift: Dimen },
+    Kern(Dimen),
+    Box {
+        tex_box: TeXBox,
+        shift: Dimen,
+    },
+    // A rule's dimensions are None when they are "running", which means that
+    // they extend to the edges of the box that the rule ends up in.
+    Rule {
+        height: Option<Dimen>,
+        depth: Option<Dimen>,
+        width: Option<Dimen>,
+    },
+    // A place where a line can be broken. When the line is broken here, the
+    // `pre` list goes at the end of the line and the `post` list goes at the
+    // beginning of the next line. Otherwise, the `replace` list is used.
+    Discretionary {
+        pre: Vec<HorizontalListElem>,
+        post: Vec<HorizontalListElem>,
+        replace: Vec<HorizontalListElem>,
+    },
+    // A file operation that is carried out when the page it ends up on is
+    // shipped out.
+    Whatsit(FileOperation),
 }
 
 impl HorizontalListElem {
@@ -28,6 +54,10 @@ impl HorizontalListElem {
                 (Dimen::zero(), Dimen::zero(), glue.clone())
             }
 
+            HorizontalListElem::Kern(dimen) => {
+                (Dimen::zero(), Dimen::zero(), Glue::from_dimen(*dimen))
+            }
+
             HorizontalListElem::Box { tex_box, shift } => (
                 if *tex_box.height() + *shift < Dimen::zero() {
                     Dimen::zero()
@@ -41,6 +71,37 @@ impl HorizontalListElem {
                 },
                 Glue::from_dimen(*tex_box.width()),
             ),
+
+            // Running dimensions don't contribute to the size of the box.
+            HorizontalListElem::Rule {
+                height,
+                depth,
+                width,
+            } => (
+                height.unwrap_or_else(Dimen::zero),
+                depth.unwrap_or_else(Dimen::zero),
+                Glue::from_dimen(width.unwrap_or_else(Dimen::zero)),
+            ),
+
+            HorizontalListElem::Whatsit(_) => {
+                (Dimen::zero(), Dimen::zero(), Glue::zero())
+            }
+
+            // Discretionaries that aren't broken at are the size of their
+            // replacement text.
+            HorizontalListElem::Discretionary { replace, .. } => replace
+                .iter()
+                .map(|elem| elem.get_size(state))
+                .fold(
+                    (Dimen::zero(), Dimen::zero(), Glue::zero()),
+                    |(height, depth, width), (elem_height, elem_depth, elem_width)| {
+                        (
+                            height.max(elem_height),
+                            depth.max(elem_depth),
+                            width + elem_width,
+                        )
+                    },
+                ),
         }
     }
 
@@ -48,15 +109,34 @@ impl HorizontalListElem {
         match self {
             HorizontalListElem::Char { .. } => false,
             HorizontalListElem::HSkip(_) => true,
+            HorizontalListElem::Kern(_) => true,
             HorizontalListElem::Box { .. } => false,
+            HorizontalListElem::Rule { .. } => false,
+            HorizontalListElem::Discretionary { .. } => false,
+            HorizontalListElem::Whatsit(_) => false,
         }
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum VerticalListElem {
-    Box { tex_box: TeXBox, shift: Dimen },
+    Box {
+        tex_box: TeXBox,
+        shift: Dimen,
+    },
     VSkip(Glue),
+    Kern(Dimen),
+    // See HorizontalListElem::Rule for what None dimensions mean.
+    Rule {
+        height: Option<Dimen>,
+        depth: Option<Dimen>,
+        width: Option<Dimen>,
+    },
+    // The cost of breaking a page here, where 10000 or more means never break
+    // and -10000 or less means always break.
+    Penalty(i32),
+    // See HorizontalListElem::Whatsit.
+    Whatsit(FileOperation),
 }
 
 impl VerticalListElem {
@@ -75,6 +155,35 @@ impl VerticalListElem {
             VerticalListElem::VSkip(glue) => {
                 (glue.clone(), Dimen::zero(), Dimen::zero())
             }
+
+            VerticalListElem::Kern(dimen) => {
+                (Glue::from_dimen(*dimen), Dimen::zero(), Dimen::zero())
+            }
+
+            VerticalListElem::Penalty(_) | VerticalListElem::Whatsit(_) => {
+                (Glue::zero(), Dimen::zero(), Dimen::zero())
+            }
+
+            VerticalListElem::Rule {
+                height,
+                depth,
+                width,
+            } => (
+                Glue::from_dimen(height.unwrap_or_else(Dimen::zero)),
+                depth.unwrap_or_else(Dimen::zero),
+                width.unwrap_or_else(Dimen::zero),
+            ),
+        }
+    }
+
+    pub fn is_discardable(&self) -> bool {
+        match self {
+            VerticalListElem::Box { .. } => false,
+            VerticalListElem::VSkip(_) => true,
+            VerticalListElem::Kern(_) => true,
+            VerticalListElem::Penalty(_) => true,
+            VerticalListElem::Rule { .. } => false,
+            VerticalListElem::Whatsit(_) => false,
         }
     }
 }
@@ -3,20 +3,29 @@
 mod box_to_dvi;
 mod boxes;
 mod category;
+mod date;
+mod diagnostics;
 mod dimension;
 mod dvi;
 mod font;
 mod font_metrics;
 mod glue;
+mod input_files;
+mod layout_tree;
 mod lexer;
+mod limits;
 mod line_breaking;
 mod list;
 mod makro;
 mod math_code;
 mod math_list;
+mod output_files;
+mod page_breaking;
 mod parser;
 mod paths;
+mod primitive;
 mod state;
+mod stats;
 mod tfm;
 mod token;
 mod variable;
@@ -26,15 +35,36 @@ mod testing;
 #[cfg(test)]
 mod tests;
 
+use std::env;
 use std::fs;
 use std::io;
 use std::io::prelude::*;
 
 use crate::box_to_dvi::DVIFileWriter;
+use crate::date::get_job_epoch_seconds;
 use crate::parser::Parser;
 use crate::state::TeXState;
+use crate::stats::report_stats_at_end;
 
 fn main() -> io::Result<()> {
+    // With `--diagnostics-json <file>`, warnings and errors are also written
+    // to the given file as JSON lines.
+    let args: Vec<String> = env::args().collect();
+    let diagnostics_json_path =
+        match args.iter().position(|arg| arg == "--diagnostics-json") {
+            Some(index) => match args.get(index + 1) {
+                Some(path) => Some(path),
+                None => {
+                    eprintln!("Missing file for --diagnostics-json");
+                    eprintln!(
+                    "Usage: xymostex [--diagnostics-json <file>] < input.tex"
+                );
+                    std::process::exit(1);
+                }
+            },
+            None => None,
+        };
+
     let mut lines: Vec<String> = Vec::new();
 
     // Read in every line of stdin. This currently doesn't let us do parsing as
@@ -45,6 +75,17 @@ fn main() -> io::Result<()> {
     }
 
     let state = TeXState::new();
+    state.set_date_and_time(get_job_epoch_seconds(
+        env::var("SOURCE_DATE_EPOCH").ok().as_deref(),
+    ));
+
+    if let Some(path) = diagnostics_json_path {
+        let file = fs::File::create(path)?;
+        state.with_diagnostics(|diagnostics| {
+            diagnostics.set_json_writer(Box::new(file))
+        });
+    }
+
     let mut parser = Parser::new(&lines[..], &state);
 
     let mut file_writer = DVIFileWriter::new();
@@ -54,11 +95,16 @@ fn main() -> io::Result<()> {
         b"Made by XymosTeX".to_vec(),
     );
 
-    let result = parser.parse_outer_vertical_box();
-    file_writer.add_page(&result.list, &None, [1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    for (index, page) in parser.parse_pages().iter().enumerate() {
+        file_writer
+            .add_box_page(page, [index as i32 + 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        state.with_stats(|stats| stats.add_shipped_page(page));
+    }
 
     file_writer.end();
 
+    report_stats_at_end(&state, &mut io::stdout())?;
+
     let file = file_writer.to_file();
 
     let output = fs::File::create("texput.dvi")?;
@@ -3,7 +3,7 @@
 
 use std::collections::HashMap;
 
-use crate::token::Token;
+use crate::token::{stringify, Token};
 
 #[derive(PartialEq, Eq, Debug)]
 pub enum MacroListElem {
@@ -82,6 +82,28 @@ impl Macro {
             })
             .collect()
     }
+
+    /// Returns what \meaning prints for this macro, like `macro:#1->#1#1`.
+    pub fn to_meaning_string(&self, escapechar: i32) -> String {
+        let list_to_string = |list: &[MacroListElem]| {
+            list.iter()
+                .map(|elem| match elem {
+                    MacroListElem::Token(tok) => {
+                        stringify(std::slice::from_ref(tok), escapechar)
+                    }
+                    MacroListElem::Parameter(param_num) => {
+                        format!("#{}", param_num)
+                    }
+                })
+                .collect::<String>()
+        };
+
+        format!(
+            "macro:{}->{}",
+            list_to_string(&self.parameter_list),
+            list_to_string(&self.replacement_list)
+        )
+    }
 }
 
 #[cfg(test)]
@@ -31,7 +31,7 @@ impl MathClass {
 pub struct MathCode {
     pub class: MathClass,
     pub family: u8,
-    pub position: u8,
+    pub position: u32,
 }
 
 impl MathCode {
@@ -50,7 +50,7 @@ impl MathCode {
 
         let class = (num / 0x1000) as u8;
         let family = ((num / 0x100) % 0x10) as u8;
-        let position = (num % 0x100) as u8;
+        let position = num % 0x100;
 
         MathCode {
             class: MathClass::from_number(class),
@@ -58,4 +58,40 @@ impl MathCode {
             position,
         }
     }
+
+    /// Builds a math code from the parts of a \Umathchar, which can refer to
+    /// any of 256 families and any Unicode position.
+    pub fn from_umath_parts(class: u8, family: u8, position: u32) -> MathCode {
+        if class > 7 {
+            panic!(
+                "Invalid math class: {}, should be in the range 0..8",
+                class
+            );
+        }
+        if std::char::from_u32(position).is_none() {
+            panic!("Invalid math char position: {}", position);
+        }
+
+        MathCode {
+            class: MathClass::from_number(class),
+            family,
+            position,
+        }
+    }
+
+    /// Whether this math code can be written as a 15-bit \mathchar number.
+    pub fn is_narrow(&self) -> bool {
+        self.class == MathClass::Active
+            || (self.family < 0x10 && self.position < 0x100)
+    }
+
+    pub fn to_number(&self) -> u32 {
+        if self.class == MathClass::Active {
+            return 0x8000;
+        }
+
+        (self.class.clone() as u32) * 0x1000
+            + (self.family as u32) * 0x100
+            + self.position
+    }
 }
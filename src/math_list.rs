@@ -1,6 +1,7 @@
 use crate::boxes::TeXBox;
 use crate::dimension::Dimen;
 use crate::math_code::{MathClass, MathCode};
+use crate::output_files::FileOperation;
 
 #[derive(Debug, PartialEq, Clone, Hash, Eq, Copy)]
 pub enum AtomKind {
@@ -21,7 +22,6 @@ pub enum AtomKind {
     Acc,
     #[allow(dead_code)]
     Rad,
-    #[allow(dead_code)]
     Vcent,
 }
 
@@ -44,7 +44,7 @@ impl AtomKind {
 #[derive(Debug, PartialEq)]
 pub struct MathSymbol {
     pub family_number: u8,
-    pub position_number: u8,
+    pub position_number: u32,
 }
 
 impl MathSymbol {
@@ -56,6 +56,10 @@ impl MathSymbol {
             position_number: math_code.position,
         }
     }
+
+    pub fn get_char(&self) -> char {
+        std::char::from_u32(self.position_number).unwrap()
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -65,12 +69,23 @@ pub enum MathField {
     MathList(MathList),
 }
 
+// Where the scripts on an Op atom are placed, as set by \displaylimits,
+// \limits, and \nolimits. Normal places them above and below the operator
+// only in display styles.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OpLimits {
+    Normal,
+    Limits,
+    NoLimits,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct MathAtom {
     pub kind: AtomKind,
     pub nucleus: Option<MathField>,
     pub superscript: Option<MathField>,
     pub subscript: Option<MathField>,
+    pub limits: OpLimits,
 }
 
 impl MathAtom {
@@ -80,6 +95,7 @@ impl MathAtom {
             nucleus: None,
             superscript: None,
             subscript: None,
+            limits: OpLimits::Normal,
         }
     }
 
@@ -91,6 +107,7 @@ impl MathAtom {
             nucleus: Some(MathField::Symbol(symbol)),
             superscript: None,
             subscript: None,
+            limits: OpLimits::Normal,
         }
     }
 
@@ -100,6 +117,7 @@ impl MathAtom {
             nucleus: Some(MathField::MathList(math_list)),
             superscript: None,
             subscript: None,
+            limits: OpLimits::Normal,
         }
     }
 
@@ -109,6 +127,7 @@ impl MathAtom {
             nucleus: Some(MathField::TeXBox(tex_box)),
             superscript: None,
             subscript: None,
+            limits: OpLimits::Normal,
         }
     }
 
@@ -222,6 +241,9 @@ pub enum BoundaryKind {
 #[derive(Debug, PartialEq)]
 pub enum MathListElem {
     Atom(MathAtom),
+    // A file operation, which ends up in the horizontal list that the math
+    // list is converted to.
+    Whatsit(FileOperation),
     #[allow(dead_code)]
     StyleChange(MathStyle),
     #[allow(dead_code)]
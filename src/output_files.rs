@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+
+#[cfg(test)]
+use crate::dimension::Dimen;
+use crate::token::Token;
+
+/// An operation on an output file from \openout, \write, or \closeout. In
+/// TeX, these are whatsits that are put into the current list and carried out
+/// when the page that they're on is shipped out, unless they are \immediate.
+/// The tokens for a \write are kept unexpanded, since they're expanded when
+/// the write is carried out.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileOperation {
+    Open { stream: u8, file_name: String },
+    Write { stream: i32, tokens: Vec<Token> },
+    Close { stream: u8 },
+}
+
+/// Where a file operation was on the page that it was shipped out with. The
+/// position is measured right and down from the top left corner of the page.
+#[cfg(test)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PagePosition {
+    pub page: usize,
+    pub h: Dimen,
+    pub v: Dimen,
+}
+
+/// A record of one file operation that was carried out. In tests, every
+/// operation is recorded in a journal so that we can check what order they
+/// happened in. Operations that were carried out immediately don't have a
+/// position.
+#[cfg(test)]
+#[derive(Debug, PartialEq)]
+pub struct JournalEntry {
+    pub kind: &'static str,
+    pub stream: u8,
+    pub content: String,
+    pub position: Option<PagePosition>,
+}
+
+/// Keeps track of the files opened with \openout, and controls where the
+/// document is allowed to write them.
+pub struct OutputFiles {
+    // The directory that output file names are relative to.
+    directory: PathBuf,
+    // When this is set, file names that would refer to somewhere outside of
+    // the output directory (absolute paths or paths containing `..`) aren't
+    // allowed. This is on by default so that a document can't write to
+    // arbitrary places.
+    restricted: bool,
+    streams: HashMap<u8, File>,
+    // Where \message and writes to streams that aren't open go.
+    terminal: Box<dyn Write>,
+    #[cfg(test)]
+    journal: Vec<JournalEntry>,
+    // The position of the operations that are being carried out, while a
+    // page is being shipped out.
+    #[cfg(test)]
+    journal_position: Option<PagePosition>,
+}
+
+impl OutputFiles {
+    pub fn new() -> Self {
+        OutputFiles {
+            directory: PathBuf::from("."),
+            restricted: true,
+            streams: HashMap::new(),
+            terminal: Box::new(io::stdout()),
+            #[cfg(test)]
+            journal: Vec::new(),
+            #[cfg(test)]
+            journal_position: None,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn set_directory(&mut self, directory: &Path) {
+        self.directory = directory.to_path_buf();
+    }
+
+    #[cfg(test)]
+    pub fn set_restricted(&mut self, restricted: bool) {
+        self.restricted = restricted;
+    }
+
+    #[cfg(test)]
+    pub fn set_terminal(&mut self, terminal: Box<dyn Write>) {
+        self.terminal = terminal;
+    }
+
+    /// Returns the path that a file name from the document should be written
+    /// to, or an error if the file name isn't allowed.
+    pub fn get_path(&self, file_name: &str) -> Result<PathBuf, String> {
+        let path = Path::new(file_name);
+
+        if self.restricted
+            && path.components().any(|component| {
+                !matches!(component, Component::Normal(_) | Component::CurDir)
+            })
+        {
+            return Err(format!(
+                "Not allowed to write to {}: it is outside of the output directory",
+                file_name
+            ));
+        }
+
+        Ok(self.directory.join(path))
+    }
+
+    pub fn open(&mut self, stream: u8, file_name: &str) {
+        let path = self
+            .get_path(file_name)
+            .unwrap_or_else(|err| panic!("{}", err));
+        let file = File::create(&path).unwrap_or_else(|err| {
+            panic!("Error opening {}: {}", path.display(), err)
+        });
+
+        // If there was already a file open for this stream, it is closed when
+        // it gets dropped here.
+        self.streams.insert(stream, file);
+
+        #[cfg(test)]
+        self.journal.push(JournalEntry {
+            kind: "openout",
+            stream,
+            content: file_name.to_string(),
+            position: self.journal_position,
+        });
+    }
+
+    pub fn get_num_open(&self) -> usize {
+        self.streams.len()
+    }
+
+    /// Writes a line to a stream. Like in TeX, writing to a stream that
+    /// isn't open (including streams 16 and above) goes to the terminal
+    /// instead. TeX only writes negative streams to the log file, but we don't
+    /// have one, so those go to the terminal too.
+    pub fn write(&mut self, stream: i32, text: &str) {
+        let file = if (0..16).contains(&stream) {
+            self.streams.get_mut(&(stream as u8))
+        } else {
+            None
+        };
+
+        match file {
+            Some(file) => {
+                writeln!(file, "{}", text).unwrap_or_else(|err| {
+                    panic!("Error writing to stream {}: {}", stream, err)
+                });
+
+                #[cfg(test)]
+                self.journal.push(JournalEntry {
+                    kind: "write",
+                    stream: stream as u8,
+                    content: text.to_string(),
+                    position: self.journal_position,
+                });
+            }
+            None => self.print(text),
+        }
+    }
+
+    /// Prints a line to the terminal, like for \message.
+    pub fn print(&mut self, text: &str) {
+        writeln!(self.terminal, "{}", text).unwrap_or_else(|err| {
+            panic!("Error writing to the terminal: {}", err)
+        });
+    }
+
+    pub fn close(&mut self, stream: u8) {
+        self.streams.remove(&stream);
+
+        #[cfg(test)]
+        self.journal.push(JournalEntry {
+            kind: "closeout",
+            stream,
+            content: String::new(),
+            position: self.journal_position,
+        });
+    }
+
+    /// Sets the position that is recorded in the journal for the operations
+    /// that are carried out next.
+    #[cfg(test)]
+    pub fn set_journal_position(&mut self, position: Option<PagePosition>) {
+        self.journal_position = position;
+    }
+
+    /// Returns the operations carried out since the journal was last taken.
+    #[cfg(test)]
+    pub fn take_journal(&mut self) -> Vec<JournalEntry> {
+        std::mem::take(&mut self.journal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_puts_files_in_the_output_directory() {
+        let mut output_files = OutputFiles::new();
+        output_files.set_directory(Path::new("/tmp/output"));
+
+        assert_eq!(
+            output_files.get_path("texput.aux"),
+            Ok(PathBuf::from("/tmp/output/texput.aux"))
+        );
+        assert_eq!(
+            output_files.get_path("./sub/texput.aux"),
+            Ok(PathBuf::from("/tmp/output/./sub/texput.aux"))
+        );
+    }
+
+    #[test]
+    fn it_restricts_files_to_the_output_directory() {
+        let output_files = OutputFiles::new();
+
+        assert!(output_files.get_path("../escape.tex").is_err());
+        assert!(output_files.get_path("sub/../../escape.tex").is_err());
+        assert!(output_files.get_path("/etc/escape.tex").is_err());
+    }
+
+    #[test]
+    fn it_allows_files_outside_of_the_output_directory_when_unrestricted() {
+        let mut output_files = OutputFiles::new();
+        output_files.set_directory(Path::new("/tmp/output"));
+        output_files.set_restricted(false);
+
+        assert_eq!(
+            output_files.get_path("../escape.tex"),
+            Ok(PathBuf::from("/tmp/output/../escape.tex"))
+        );
+    }
+}
@@ -0,0 +1,458 @@
+use std::io;
+use std::io::Write;
+
+use crate::dimension::{format_scaled, Dimen, FilDimen, FilKind, SpringDimen};
+use crate::glue::Glue;
+use crate::list::VerticalListElem;
+
+// These are the special costs and penalties from tex.web.
+const INF_BAD: i32 = 10000;
+const EJECT_PENALTY: i32 = -10000;
+const DEPLORABLE: i32 = 100000;
+const AWFUL_BAD: i32 = 0o7777777777;
+
+pub struct PageBreakingParams {
+    pub vsize: Dimen,
+    pub max_depth: Dimen,
+
+    // Whether we should log each of the page breaks that we consider. Set by
+    // \tracingpages
+    pub should_log: bool,
+}
+
+// TeX's badness function, which approximates 100(t/s)^3 using only integers.
+// This is from tex.web, which says that all implementations of TeX should use
+// precisely this method.
+fn badness(t: i32, s: i32) -> i32 {
+    if t == 0 {
+        return 0;
+    }
+    if s <= 0 {
+        return INF_BAD;
+    }
+
+    let r = if t <= 7230584 {
+        ((t as i64) * 297 / (s as i64)) as i32
+    } else if s >= 1663497 {
+        t / (s / 297)
+    } else {
+        t
+    };
+
+    if r > 1290 {
+        INF_BAD
+    } else {
+        (r * r * r + 0o400000) / 0o1000000
+    }
+}
+
+// The running totals of the material on the current page, which is what TeX
+// calls page_so_far. The stretch is kept separately for each order of
+// infinity, with finite stretch first.
+struct PageTotals {
+    height: Dimen,
+    depth: Dimen,
+    stretch: [i32; 4],
+    shrink: Dimen,
+}
+
+impl PageTotals {
+    fn new() -> PageTotals {
+        PageTotals {
+            height: Dimen::zero(),
+            depth: Dimen::zero(),
+            stretch: [0; 4],
+            shrink: Dimen::zero(),
+        }
+    }
+
+    fn add_elem(&mut self, elem: &VerticalListElem, max_depth: &Dimen) {
+        match elem {
+            VerticalListElem::Box { .. } | VerticalListElem::Rule { .. } => {
+                let (height, depth, _) = elem.get_size();
+                self.height = self.height + self.depth + height.space;
+                self.depth = depth;
+
+                // The depth of the page can't be more than \maxdepth, so any
+                // extra depth is moved into the height.
+                if self.depth > *max_depth {
+                    self.height = self.height + self.depth - *max_depth;
+                    self.depth = *max_depth;
+                }
+            }
+            VerticalListElem::VSkip(glue) => self.add_glue(glue),
+            VerticalListElem::Kern(dimen) => {
+                self.height = self.height + self.depth + *dimen;
+                self.depth = Dimen::zero();
+            }
+            VerticalListElem::Penalty(_) | VerticalListElem::Whatsit(_) => {}
+        }
+    }
+
+    fn add_glue(&mut self, glue: &Glue) {
+        self.height = self.height + self.depth + glue.space;
+        self.depth = Dimen::zero();
+
+        match &glue.stretch {
+            SpringDimen::Dimen(dimen) => {
+                self.stretch[0] += dimen.as_scaled_points()
+            }
+            SpringDimen::FilDimen(FilDimen(kind, value)) => {
+                let order = match kind {
+                    FilKind::Fil => 1,
+                    FilKind::Fill => 2,
+                    FilKind::Filll => 3,
+                };
+                self.stretch[order] += value;
+            }
+        }
+
+        match &glue.shrink {
+            SpringDimen::Dimen(dimen) => self.shrink = self.shrink + *dimen,
+            SpringDimen::FilDimen(fil_dimen) => {
+                if !fil_dimen.is_zero() {
+                    panic!("Infinite glue shrinkage found on current page");
+                }
+            }
+        }
+    }
+
+    // Returns the badness of breaking the page here, or AWFUL_BAD if the page
+    // is too full even after shrinking as much as possible.
+    fn get_badness(&self, goal: &Dimen) -> i32 {
+        if self.height < *goal {
+            if self.stretch[1..].iter().any(|stretch| *stretch != 0) {
+                0
+            } else {
+                badness(
+                    (*goal - self.height).as_scaled_points(),
+                    self.stretch[0],
+                )
+            }
+        } else if self.height - *goal > self.shrink {
+            AWFUL_BAD
+        } else {
+            badness(
+                (self.height - *goal).as_scaled_points(),
+                self.shrink.as_scaled_points(),
+            )
+        }
+    }
+
+    // Formats the totals like print_totals in tex.web, e.g.
+    // "20.0 plus 2.0 plus 1.0fil minus 3.0".
+    fn to_tex_string(&self) -> String {
+        let mut result = format_scaled(self.height.as_scaled_points());
+        for (stretch, unit) in
+            self.stretch.iter().zip(&["", "fil", "fill", "filll"])
+        {
+            if *stretch != 0 {
+                result.push_str(&format!(
+                    " plus {}{}",
+                    format_scaled(*stretch),
+                    unit
+                ));
+            }
+        }
+        if self.shrink != Dimen::zero() {
+            result.push_str(&format!(
+                " minus {}",
+                format_scaled(self.shrink.as_scaled_points())
+            ));
+        }
+        result
+    }
+}
+
+// Returns the penalty for breaking the page before the element at the given
+// index, or None if we can't break there. The end of the list is always a
+// forced break.
+fn get_break_penalty(list: &[VerticalListElem], index: usize) -> Option<i32> {
+    match list.get(index) {
+        None => Some(EJECT_PENALTY),
+        // We can break at glue if the thing before it isn't discardable.
+        Some(VerticalListElem::VSkip(_)) => {
+            if index > 0 && !list[index - 1].is_discardable() {
+                Some(0)
+            } else {
+                None
+            }
+        }
+        // We can break at a kern if it's followed by glue.
+        Some(VerticalListElem::Kern(_)) => match list.get(index + 1) {
+            Some(VerticalListElem::VSkip(_)) => Some(0),
+            _ => None,
+        },
+        // Penalties of 10000 or more forbid breaking.
+        Some(VerticalListElem::Penalty(penalty)) => {
+            if *penalty < INF_BAD {
+                Some(*penalty)
+            } else {
+                None
+            }
+        }
+        Some(_) => None,
+    }
+}
+
+// Finds the best place to end a page that starts at the given index, which
+// should be the index of a box or rule. Like TeX, we add elements to the page
+// until a break would make the page overfull (or we reach a forced break), and
+// then use the cheapest break that we've seen so far.
+fn find_best_page_break(
+    list: &[VerticalListElem],
+    start: usize,
+    params: &PageBreakingParams,
+    log: &mut dyn Write,
+) -> usize {
+    if params.should_log {
+        writeln!(
+            log,
+            "%% goal height={}, max depth={}",
+            format_scaled(params.vsize.as_scaled_points()),
+            format_scaled(params.max_depth.as_scaled_points())
+        )
+        .unwrap();
+    }
+
+    let mut totals = PageTotals::new();
+    let mut best_break = start;
+    let mut least_cost = AWFUL_BAD;
+
+    for index in start..=list.len() {
+        if let Some(penalty) = get_break_penalty(list, index) {
+            let badness = totals.get_badness(&params.vsize);
+            let cost = if badness == AWFUL_BAD {
+                AWFUL_BAD
+            } else if penalty <= EJECT_PENALTY {
+                penalty
+            } else if badness < INF_BAD {
+                badness + penalty
+            } else {
+                DEPLORABLE
+            };
+
+            if params.should_log {
+                let format_cost = |cost: i32| {
+                    if cost == AWFUL_BAD {
+                        "*".to_string()
+                    } else {
+                        cost.to_string()
+                    }
+                };
+                writeln!(
+                    log,
+                    "% t={} g={} b={} p={} c={}{}",
+                    totals.to_tex_string(),
+                    format_scaled(params.vsize.as_scaled_points()),
+                    format_cost(badness),
+                    penalty,
+                    format_cost(cost),
+                    if cost <= least_cost { "#" } else { "" }
+                )
+                .unwrap();
+            }
+
+            if cost <= least_cost {
+                best_break = index;
+                least_cost = cost;
+            }
+
+            if cost == AWFUL_BAD || penalty <= EJECT_PENALTY {
+                return best_break;
+            }
+        }
+
+        totals.add_elem(&list[index], &params.max_depth);
+    }
+
+    unreachable!("The end of the list is always a forced break");
+}
+
+/// Breaks a vertical list into pages that are at most \vsize tall. The
+/// discardable elements at each break (and at the very top of the list) are
+/// dropped, like TeX does at the top of each page.
+pub fn break_vertical_list_into_pages(
+    list: &[VerticalListElem],
+    params: &PageBreakingParams,
+    log: &mut dyn Write,
+) -> Vec<Vec<VerticalListElem>> {
+    let mut pages = Vec::new();
+    let mut start = 0;
+
+    loop {
+        start = match list[start..].iter().position(|e| !e.is_discardable()) {
+            Some(offset) => start + offset,
+            None => return pages,
+        };
+
+        let end = find_best_page_break(list, start, params, log);
+        pages.push(list[start..end].to_vec());
+        start = end;
+    }
+}
+
+/// Finds where to split a vertical list so that the material before the split
+/// fits in the given height, like \vsplit does. TeX's page builder is based on
+/// the same procedure, so this uses the same costs as page breaking. Returns
+/// the index of the first element after the split.
+pub fn find_vertical_list_split(
+    list: &[VerticalListElem],
+    height: Dimen,
+    max_depth: Dimen,
+) -> usize {
+    let params = PageBreakingParams {
+        vsize: height,
+        max_depth,
+        should_log: false,
+    };
+    find_best_page_break(list, 0, &params, &mut io::sink())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dimension::Unit;
+
+    fn pt(points: f64) -> Dimen {
+        Dimen::from_unit(points, Unit::Point)
+    }
+
+    fn rule(height: f64) -> VerticalListElem {
+        VerticalListElem::Rule {
+            height: Some(pt(height)),
+            depth: Some(Dimen::zero()),
+            width: None,
+        }
+    }
+
+    fn skip(space: f64, stretch: f64) -> VerticalListElem {
+        VerticalListElem::VSkip(Glue {
+            space: pt(space),
+            stretch: SpringDimen::Dimen(pt(stretch)),
+            shrink: SpringDimen::Dimen(Dimen::zero()),
+        })
+    }
+
+    fn get_two_page_list() -> Vec<VerticalListElem> {
+        vec![
+            rule(10.0),
+            skip(5.0, 2.0),
+            rule(10.0),
+            skip(5.0, 2.0),
+            rule(10.0),
+            skip(5.0, 0.0),
+            rule(10.0),
+        ]
+    }
+
+    #[test]
+    fn it_computes_badness_like_tex() {
+        assert_eq!(badness(0, 0), 0);
+        assert_eq!(badness(65536, 0), INF_BAD);
+        assert_eq!(badness(65536, 65536), 100);
+        assert_eq!(badness(65536, 2 * 65536), 12);
+        assert_eq!(badness(2 * 65536, 65536), 800);
+        assert_eq!(badness(100 * 65536, 65536), INF_BAD);
+    }
+
+    #[test]
+    fn it_breaks_vertical_lists_into_pages() {
+        let list = get_two_page_list();
+        let mut log = Vec::new();
+        let pages = break_vertical_list_into_pages(
+            &list,
+            &PageBreakingParams {
+                vsize: pt(25.0),
+                max_depth: pt(2.0),
+                should_log: false,
+            },
+            &mut log,
+        );
+
+        // The glue at the break is dropped.
+        assert_eq!(pages, vec![list[0..3].to_vec(), list[4..7].to_vec()]);
+        assert!(log.is_empty());
+    }
+
+    #[test]
+    fn it_logs_page_break_costs() {
+        let mut log = Vec::new();
+        break_vertical_list_into_pages(
+            &get_two_page_list(),
+            &PageBreakingParams {
+                vsize: pt(25.0),
+                max_depth: pt(2.0),
+                should_log: true,
+            },
+            &mut log,
+        );
+
+        assert_eq!(
+            String::from_utf8(log).unwrap(),
+            [
+                "%% goal height=25.0, max depth=2.0",
+                // There's no stretch yet, so this page would be infinitely
+                // bad.
+                "% t=10.0 g=25.0 b=10000 p=0 c=100000#",
+                "% t=25.0 plus 2.0 g=25.0 b=0 p=0 c=0#",
+                // This is too full, so we break at the best place so far.
+                "% t=40.0 plus 4.0 g=25.0 b=* p=0 c=*",
+                "%% goal height=25.0, max depth=2.0",
+                "% t=10.0 g=25.0 b=10000 p=0 c=100000#",
+                // The end of the list is a forced break.
+                "% t=25.0 g=25.0 b=0 p=-10000 c=-10000#",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn it_breaks_pages_at_penalties() {
+        let params = PageBreakingParams {
+            vsize: pt(25.0),
+            max_depth: pt(2.0),
+            should_log: false,
+        };
+
+        // A forced break makes a page even though everything would fit.
+        let list =
+            vec![rule(10.0), VerticalListElem::Penalty(-10000), rule(10.0)];
+        let pages =
+            break_vertical_list_into_pages(&list, &params, &mut Vec::new());
+        assert_eq!(pages, vec![list[0..1].to_vec(), list[2..3].to_vec()]);
+
+        // A penalty of 10000 stops us from breaking at the perfect place after
+        // the second rule (and at the glue after it), so we have to use the
+        // worse break after the first rule.
+        let list = vec![
+            rule(10.0),
+            skip(5.0, 20.0),
+            rule(10.0),
+            VerticalListElem::Penalty(10000),
+            skip(5.0, 20.0),
+            rule(10.0),
+        ];
+        let pages =
+            break_vertical_list_into_pages(&list, &params, &mut Vec::new());
+        assert_eq!(pages, vec![list[0..1].to_vec(), list[2..6].to_vec()]);
+    }
+
+    #[test]
+    fn it_moves_depth_past_the_max_depth_into_the_height() {
+        let mut totals = PageTotals::new();
+        totals.add_elem(
+            &VerticalListElem::Rule {
+                height: Some(pt(10.0)),
+                depth: Some(pt(5.0)),
+                width: None,
+            },
+            &pt(2.0),
+        );
+
+        assert_eq!(totals.height, pt(13.0));
+        assert_eq!(totals.depth, pt(2.0));
+    }
+}
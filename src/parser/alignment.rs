@@ -0,0 +1,892 @@
+use crate::boxes::{BoxLayout, HorizontalBox, TeXBox, VerticalBox};
+use crate::category::Category;
+use crate::dimension::Dimen;
+use crate::glue::Glue;
+use crate::list::{HorizontalListElem, VerticalListElem};
+use crate::parser::Parser;
+use crate::primitive::Primitive;
+use crate::state::GlueParameter;
+use crate::token::Token;
+
+/// The primitives used to build alignments.
+#[cfg(test)]
+pub const HANDLED_PRIMITIVES: &[Primitive] = &[
+    Primitive::HAlign,
+    Primitive::VAlign,
+    Primitive::Cr,
+    Primitive::TabSkip,
+    Primitive::NoAlign,
+];
+
+// One column of an alignment preamble. The text of each cell in the column is
+// put in between the `before` and `after` tokens (the <u> and <v> parts of
+// the template) when the cell is typeset. `tabskip` is the value of \tabskip
+// at the end of the template, which is the glue that goes after the column.
+struct AlignmentTemplate {
+    before: Vec<Token>,
+    after: Vec<Token>,
+    tabskip: Glue,
+}
+
+// The rows of an alignment, along with any material from \noalign that goes
+// between them. For a \valign, the "rows" are the columns of the result and
+// the \noalign material is horizontal material, so this is generic over the
+// type of the cells and the elements of the \noalign lists.
+enum AlignmentRow<Cell, Elem> {
+    Cells(Vec<Cell>),
+    NoAlign(Vec<Elem>),
+}
+
+// Everything parsed from inside of the braces of an \halign or \valign.
+struct Alignment<Cell, Elem> {
+    initial_tabskip: Glue,
+    templates: Vec<AlignmentTemplate>,
+    rows: Vec<AlignmentRow<Cell, Elem>>,
+}
+
+impl<'a> Parser<'a> {
+    pub fn is_alignment_head(&mut self) -> bool {
+        self.is_next_expanded_token_in_set_of_primitives(&[Primitive::HAlign])
+    }
+
+    pub fn is_vertical_alignment_head(&mut self) -> bool {
+        self.is_next_expanded_token_in_set_of_primitives(&[Primitive::VAlign])
+    }
+
+    fn is_alignment_tab_or_cr(&self, token: &Token) -> bool {
+        match token {
+            Token::Char(_, Category::AlignmentTab) => true,
+            _ => self.state.is_token_equal_to_prim(token, Primitive::Cr),
+        }
+    }
+
+    // Parses the templates in the preamble of an alignment, up to and
+    // including the first \cr. The tokens in the preamble aren't expanded,
+    // except that assignments to \tabskip are carried out so that each column
+    // can have different glue after it.
+    fn parse_alignment_preamble(
+        &mut self,
+        head: Primitive,
+    ) -> Vec<AlignmentTemplate> {
+        let mut templates = Vec::new();
+
+        let mut before = Vec::new();
+        let mut after = Vec::new();
+        let mut seen_parameter = false;
+
+        loop {
+            let token = match self.lex_unexpanded_token() {
+                Some(token) => token,
+                None => {
+                    panic!(r"EOF found while parsing \{} preamble", head.name())
+                }
+            };
+
+            if self.is_alignment_tab_or_cr(&token) {
+                if !seen_parameter {
+                    panic!("Missing # inserted in alignment preamble");
+                }
+
+                templates.push(AlignmentTemplate {
+                    before,
+                    after,
+                    tabskip: self
+                        .state
+                        .get_glue_parameter(&GlueParameter::TabSkip),
+                });
+                before = Vec::new();
+                after = Vec::new();
+                seen_parameter = false;
+
+                if self.state.is_token_equal_to_prim(&token, Primitive::Cr) {
+                    return templates;
+                }
+            } else if self
+                .state
+                .is_token_equal_to_prim(&token, Primitive::TabSkip)
+            {
+                self.parse_equals_expanded();
+                let glue = self.parse_glue();
+                self.state.set_glue_parameter(
+                    false,
+                    &GlueParameter::TabSkip,
+                    &glue,
+                );
+            } else {
+                match token {
+                    Token::Char(_, Category::Parameter) => {
+                        if seen_parameter {
+                            panic!("Only one # is allowed per tab");
+                        }
+                        seen_parameter = true;
+                    }
+                    // Spaces at the beginning of a template are ignored.
+                    Token::Char(_, Category::Space) if before.is_empty() => {}
+                    _ => {
+                        if seen_parameter {
+                            after.push(token);
+                        } else {
+                            before.push(token);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Skips over any spaces before the start of a cell or row, and returns the
+    // next unexpanded token.
+    fn peek_non_space_unexpanded_token(&mut self) -> Option<Token> {
+        loop {
+            match self.peek_unexpanded_token() {
+                Some(Token::Char(_, Category::Space)) => {
+                    self.lex_unexpanded_token();
+                }
+                token => return token,
+            }
+        }
+    }
+
+    // Reads the tokens in a single cell of an alignment, up to the & or \cr
+    // that ends the cell. Returns the tokens and whether the cell was ended
+    // with a \cr. Alignment tabs inside of groups (like in a nested
+    // alignment) don't end the cell.
+    fn parse_alignment_cell_tokens(
+        &mut self,
+        head: Primitive,
+    ) -> (Vec<Token>, bool) {
+        let mut tokens = Vec::new();
+        let mut group_level = 0;
+
+        loop {
+            let token = match self.lex_unexpanded_token() {
+                Some(token) => token,
+                None => panic!(r"EOF found while parsing \{}", head.name()),
+            };
+
+            if group_level == 0 && self.is_alignment_tab_or_cr(&token) {
+                let is_cr =
+                    self.state.is_token_equal_to_prim(&token, Primitive::Cr);
+                return (tokens, is_cr);
+            }
+
+            match token {
+                Token::Char(_, Category::BeginGroup) => group_level += 1,
+                Token::Char(_, Category::EndGroup) => {
+                    if group_level == 0 {
+                        panic!("Missing \\cr inserted");
+                    }
+                    group_level -= 1;
+                }
+                _ => {}
+            }
+
+            tokens.push(token);
+        }
+    }
+
+    // Puts the tokens of a cell inside of its template and starts a group for
+    // the cell. The caller parses the cell's list and then calls
+    // finish_alignment_cell().
+    fn start_alignment_cell(
+        &mut self,
+        template: &AlignmentTemplate,
+        tokens: Vec<Token>,
+    ) {
+        let mut cell_tokens = template.before.clone();
+        cell_tokens.extend(tokens);
+        cell_tokens.extend(template.after.iter().cloned());
+        cell_tokens.push(Token::Char('}', Category::EndGroup));
+        self.add_upcoming_tokens(cell_tokens);
+
+        self.state.push_state();
+    }
+
+    fn finish_alignment_cell(&mut self) {
+        match self.lex_unexpanded_token() {
+            Some(Token::Char(_, Category::EndGroup)) => {}
+            _ => panic!("Unexpected end of alignment cell"),
+        }
+
+        self.end_group();
+    }
+
+    // Typesets the tokens of a cell inside of its template as a restricted
+    // horizontal list, in its own group.
+    fn parse_alignment_cell(
+        &mut self,
+        template: &AlignmentTemplate,
+        tokens: Vec<Token>,
+    ) -> HorizontalBox {
+        self.start_alignment_cell(template, tokens);
+        let list = self.parse_horizontal_list(true, false);
+        self.finish_alignment_cell();
+
+        HorizontalBox::create_from_horizontal_list_with_layout(
+            list,
+            &BoxLayout::Natural,
+            self.state,
+        )
+    }
+
+    // Typesets the tokens of a cell in a \valign inside of its template as an
+    // internal vertical list, in its own group.
+    fn parse_vertical_alignment_cell(
+        &mut self,
+        template: &AlignmentTemplate,
+        tokens: Vec<Token>,
+    ) -> VerticalBox {
+        self.start_alignment_cell(template, tokens);
+        let list = self.parse_vertical_list(true);
+        self.finish_alignment_cell();
+
+        self.create_vertical_alignment_cell(list, None)
+    }
+
+    // Packages the list of a \valign cell into a vbox. Like in TeX, the depth
+    // of the cell becomes part of its height, so that the cells in a column
+    // don't overlap. If a height is given, the cell's glue is set so that the
+    // cell is exactly that tall.
+    fn create_vertical_alignment_cell(
+        &mut self,
+        list: Vec<VerticalListElem>,
+        height: Option<Dimen>,
+    ) -> VerticalBox {
+        let natural =
+            self.create_vertical_box_with_layout(list, &BoxLayout::Natural);
+
+        let vbox = match height {
+            Some(height) => self.create_vertical_box_with_layout(
+                natural.list,
+                &BoxLayout::Fixed(height - natural.depth),
+            ),
+            None => natural,
+        };
+
+        VerticalBox {
+            height: vbox.height + vbox.depth,
+            depth: Dimen::zero(),
+            ..vbox
+        }
+    }
+
+    // Parses the material in a \noalign, which goes into the vertical list
+    // between two rows of the alignment.
+    fn parse_no_align(&mut self) -> Vec<VerticalListElem> {
+        self.start_no_align();
+
+        // The elements are returned without any interline glue, since that
+        // is added when they're put into the list along with the rows.
+        let mut list = Vec::new();
+        let mut group_level = 0;
+        while let Some(elems) =
+            self.parse_vertical_list_elems(&mut group_level, true)
+        {
+            list.extend(elems);
+        }
+
+        self.finish_no_align();
+        list
+    }
+
+    // Parses the material in a \noalign in a \valign, which is horizontal
+    // material that goes between two of the columns.
+    fn parse_vertical_no_align(&mut self) -> Vec<HorizontalListElem> {
+        self.start_no_align();
+        let list = self.parse_horizontal_list(true, false);
+        self.finish_no_align();
+        list
+    }
+
+    fn start_no_align(&mut self) {
+        let tok = self.lex_unexpanded_token().unwrap();
+        if !self.state.is_token_equal_to_prim(&tok, Primitive::NoAlign) {
+            panic!("Invalid noalign head: {:?}", tok);
+        }
+
+        if !self.parse_left_brace_expanded() {
+            panic!("{}", "Missing { inserted");
+        }
+
+        self.state.push_state();
+    }
+
+    fn finish_no_align(&mut self) {
+        match self.lex_expanded_token() {
+            Some(Token::Char(_, Category::EndGroup)) => (),
+            _ => panic!("{}", r"Expected } when parsing \noalign"),
+        }
+
+        self.end_group();
+    }
+
+    // Parses an alignment starting with the given head (\halign or \valign),
+    // including the preamble and the braces around it. The cells are
+    // typeset with the given function, but aren't made the same size yet.
+    fn parse_alignment_rows<Cell, Elem>(
+        &mut self,
+        head: Primitive,
+        parse_cell: fn(&mut Self, &AlignmentTemplate, Vec<Token>) -> Cell,
+        parse_no_align: fn(&mut Self) -> Vec<Elem>,
+    ) -> Alignment<Cell, Elem> {
+        let tok = self.lex_expanded_token().unwrap();
+        if !self.state.is_token_equal_to_prim(&tok, head) {
+            panic!("Invalid alignment head: {:?}", tok);
+        }
+
+        if !self.parse_left_brace_expanded() {
+            panic!("{}", "Missing { inserted");
+        }
+
+        self.state.push_state();
+
+        let initial_tabskip =
+            self.state.get_glue_parameter(&GlueParameter::TabSkip);
+        let templates = self.parse_alignment_preamble(head);
+
+        let mut rows = Vec::new();
+        loop {
+            match self.peek_non_space_unexpanded_token() {
+                Some(Token::Char(_, Category::EndGroup)) => {
+                    self.lex_unexpanded_token();
+                    break;
+                }
+                Some(ref token)
+                    if self
+                        .state
+                        .is_token_equal_to_prim(token, Primitive::NoAlign) =>
+                {
+                    rows.push(AlignmentRow::NoAlign(parse_no_align(self)));
+                    continue;
+                }
+                None => panic!(r"EOF found while parsing \{}", head.name()),
+                _ => {}
+            }
+
+            let mut row = Vec::new();
+            loop {
+                if row.len() == templates.len() {
+                    panic!("Extra alignment tab has been changed to \\cr");
+                }
+
+                self.peek_non_space_unexpanded_token();
+                let (tokens, is_cr) = self.parse_alignment_cell_tokens(head);
+                row.push(parse_cell(self, &templates[row.len()], tokens));
+
+                if is_cr {
+                    break;
+                }
+            }
+            rows.push(AlignmentRow::Cells(row));
+        }
+
+        self.end_group();
+
+        Alignment {
+            initial_tabskip,
+            templates,
+            rows,
+        }
+    }
+
+    /// Parses an \halign and returns the rows of the alignment. Each cell is
+    /// set to the natural width of the widest cell in its column, and each
+    /// row is an hbox of the cells with \tabskip glue around them.
+    pub fn parse_alignment(&mut self) -> Vec<VerticalListElem> {
+        let Alignment {
+            initial_tabskip,
+            templates,
+            rows,
+        } = self.parse_alignment_rows(
+            Primitive::HAlign,
+            Self::parse_alignment_cell,
+            Self::parse_no_align,
+        );
+
+        let mut column_widths = vec![Dimen::zero(); templates.len()];
+        for row in &rows {
+            if let AlignmentRow::Cells(cells) = row {
+                for (column, cell) in cells.iter().enumerate() {
+                    if cell.width > column_widths[column] {
+                        column_widths[column] = cell.width;
+                    }
+                }
+            }
+        }
+
+        let mut result = Vec::new();
+        for row in rows {
+            let cells = match row {
+                AlignmentRow::Cells(cells) => cells,
+                AlignmentRow::NoAlign(list) => {
+                    result.extend(list);
+                    continue;
+                }
+            };
+
+            let mut row_list =
+                vec![HorizontalListElem::HSkip(initial_tabskip.clone())];
+            for ((cell, width), template) in
+                cells.into_iter().zip(&column_widths).zip(&templates)
+            {
+                row_list.push(HorizontalListElem::Box {
+                    tex_box: TeXBox::HorizontalBox(
+                        HorizontalBox::create_from_horizontal_list_with_layout(
+                            cell.list,
+                            &BoxLayout::Fixed(*width),
+                            self.state,
+                        ),
+                    ),
+                    shift: Dimen::zero(),
+                });
+                row_list
+                    .push(HorizontalListElem::HSkip(template.tabskip.clone()));
+            }
+
+            result.push(VerticalListElem::Box {
+                tex_box: TeXBox::HorizontalBox(
+                    HorizontalBox::create_from_horizontal_list_with_layout(
+                        row_list,
+                        &BoxLayout::Natural,
+                        self.state,
+                    ),
+                ),
+                shift: Dimen::zero(),
+            });
+        }
+        result
+    }
+
+    /// Parses a \valign and returns the columns of the alignment. This is
+    /// \halign turned on its side: each cell is a vbox set to the height of
+    /// the tallest cell in its row, and each column is a vbox of the cells
+    /// with \tabskip glue above and below them.
+    pub fn parse_vertical_alignment(&mut self) -> Vec<HorizontalListElem> {
+        let Alignment {
+            initial_tabskip,
+            templates,
+            rows: columns,
+        } = self.parse_alignment_rows(
+            Primitive::VAlign,
+            Self::parse_vertical_alignment_cell,
+            Self::parse_vertical_no_align,
+        );
+
+        let mut row_heights = vec![Dimen::zero(); templates.len()];
+        for column in &columns {
+            if let AlignmentRow::Cells(cells) = column {
+                for (row, cell) in cells.iter().enumerate() {
+                    if cell.height > row_heights[row] {
+                        row_heights[row] = cell.height;
+                    }
+                }
+            }
+        }
+
+        let mut result = Vec::new();
+        for column in columns {
+            let cells = match column {
+                AlignmentRow::Cells(cells) => cells,
+                AlignmentRow::NoAlign(list) => {
+                    result.extend(list);
+                    continue;
+                }
+            };
+
+            let mut column_list =
+                vec![VerticalListElem::VSkip(initial_tabskip.clone())];
+            for ((cell, height), template) in
+                cells.into_iter().zip(&row_heights).zip(&templates)
+            {
+                let cell = self
+                    .create_vertical_alignment_cell(cell.list, Some(*height));
+                column_list.push(VerticalListElem::Box {
+                    tex_box: TeXBox::VerticalBox(cell),
+                    shift: Dimen::zero(),
+                });
+                column_list
+                    .push(VerticalListElem::VSkip(template.tabskip.clone()));
+            }
+
+            result.push(HorizontalListElem::Box {
+                tex_box: TeXBox::VerticalBox(
+                    self.create_vertical_box_with_layout(
+                        column_list,
+                        &BoxLayout::Natural,
+                    ),
+                ),
+                shift: Dimen::zero(),
+            });
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dimension::Unit;
+    use crate::font::Font;
+    use crate::glue::Glue;
+    use crate::testing::with_parser;
+
+    // Returns the cells in a row of an alignment, skipping over the \tabskip
+    // glue in between them.
+    fn get_row_cells(elem: &VerticalListElem) -> Vec<HorizontalBox> {
+        match elem {
+            VerticalListElem::Box {
+                tex_box: TeXBox::HorizontalBox(row),
+                ..
+            } => row
+                .list
+                .iter()
+                .filter(|elem| !matches!(elem, HorizontalListElem::HSkip(_)))
+                .map(|cell| match cell {
+                    HorizontalListElem::Box {
+                        tex_box: TeXBox::HorizontalBox(cell),
+                        ..
+                    } => cell.clone(),
+                    _ => panic!("Invalid alignment cell: {:?}", cell),
+                })
+                .collect(),
+            _ => panic!("Invalid alignment row: {:?}", elem),
+        }
+    }
+
+    fn cell_chars(cell: &HorizontalBox) -> String {
+        cell.to_chars().into_iter().collect()
+    }
+
+    #[test]
+    fn it_aligns_columns_to_the_widest_cell() {
+        with_parser(
+            &[
+                r"\def\hfil{\hskip0pt plus1fil}%",
+                r"\halign{#\hfil&\hskip10pt#\hfil\cr a&bb\cr ccc&d\cr}%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+
+                let rows = parser.parse_alignment();
+                assert_eq!(rows.len(), 2);
+
+                let metrics = parser
+                    .state
+                    .get_metrics_for_font(&Font {
+                        font_name: "cmr10".to_string(),
+                        scale: Dimen::from_unit(10.0, Unit::Point),
+                    })
+                    .unwrap();
+                let first_width = metrics.get_width('c') * 3;
+                let second_width = Dimen::from_unit(10.0, Unit::Point)
+                    + metrics.get_width('b') * 2;
+
+                let first_row = get_row_cells(&rows[0]);
+                let second_row = get_row_cells(&rows[1]);
+
+                assert_eq!(cell_chars(&first_row[0]), "a ");
+                assert_eq!(cell_chars(&first_row[1]), " bb ");
+                assert_eq!(cell_chars(&second_row[0]), "ccc ");
+                assert_eq!(cell_chars(&second_row[1]), " d ");
+
+                for row in &[&first_row, &second_row] {
+                    assert_eq!(row[0].width, first_width);
+                    assert_eq!(row[1].width, second_width);
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn it_puts_cells_inside_of_their_templates() {
+        with_parser(&[r"\halign{(#)& [#]\cr a&b\cr c\cr}%"], |parser| {
+            let rows = parser.parse_alignment();
+
+            let first_row = get_row_cells(&rows[0]);
+            assert_eq!(cell_chars(&first_row[0]), "(a)");
+            assert_eq!(cell_chars(&first_row[1]), "[b]");
+
+            // Rows can have fewer cells than there are templates.
+            let second_row = get_row_cells(&rows[1]);
+            assert_eq!(second_row.len(), 1);
+            assert_eq!(cell_chars(&second_row[0]), "(c)");
+        });
+    }
+
+    #[test]
+    fn it_adds_interline_glue_between_alignment_rows() {
+        with_parser(&[r"\halign{#\cr a\cr g\cr}%"], |parser| {
+            let list = parser.parse_vertical_list(true);
+
+            assert_eq!(list.len(), 3);
+            match &list[1] {
+                VerticalListElem::VSkip(glue) => {
+                    assert_ne!(*glue, Glue::zero())
+                }
+                elem => panic!("Expected interline glue, got {:?}", elem),
+            }
+        });
+    }
+
+    #[test]
+    fn it_scopes_assignments_in_alignment_cells() {
+        with_parser(
+            &[r"\halign{#\cr\count1=2 a\cr\number\count1\cr}%"],
+            |parser| {
+                let rows = parser.parse_alignment();
+
+                assert_eq!(cell_chars(&get_row_cells(&rows[1])[0]), "0");
+            },
+        );
+    }
+
+    #[test]
+    fn it_parses_nested_alignments() {
+        with_parser(
+            &[r"\halign{#&#\cr\vbox{\halign{#&#\cr a&b\cr}}&c\cr}%"],
+            |parser| {
+                let rows = parser.parse_alignment();
+                assert_eq!(rows.len(), 1);
+
+                let cells = get_row_cells(&rows[0]);
+                assert_eq!(cells.len(), 2);
+                // The inner row has \tabskip glue around each of its cells.
+                assert_eq!(cell_chars(&cells[0]), " a b \n");
+                assert_eq!(cell_chars(&cells[1]), "c");
+            },
+        );
+    }
+
+    #[test]
+    fn it_puts_tabskip_glue_between_columns() {
+        with_parser(
+            &[
+                r"\tabskip=1pt%",
+                r"\halign{#\tabskip=2pt&#\tabskip3pt\cr a&bb\cr ccc&d\cr}%",
+                r"\skip0=\tabskip%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                let rows = parser.parse_alignment();
+                parser.parse_assignment();
+
+                let metrics = parser
+                    .state
+                    .get_metrics_for_font(&Font {
+                        font_name: "cmr10".to_string(),
+                        scale: Dimen::from_unit(10.0, Unit::Point),
+                    })
+                    .unwrap();
+                let first_width = metrics.get_width('c') * 3;
+                let second_width = metrics.get_width('b') * 2;
+
+                let box_width = |elem: &HorizontalListElem| match elem {
+                    HorizontalListElem::Box { tex_box, .. } => *tex_box.width(),
+                    _ => panic!("Invalid alignment cell: {:?}", elem),
+                };
+                let pt = |points| {
+                    HorizontalListElem::HSkip(Glue::from_dimen(
+                        Dimen::from_unit(points, Unit::Point),
+                    ))
+                };
+
+                for row in &rows {
+                    let row = match row {
+                        VerticalListElem::Box {
+                            tex_box: TeXBox::HorizontalBox(row),
+                            ..
+                        } => row,
+                        _ => panic!("Invalid alignment row: {:?}", row),
+                    };
+
+                    // The columns line up, since every row has the same
+                    // glue and cell widths.
+                    assert_eq!(row.list.len(), 5);
+                    assert_eq!(row.list[0], pt(1.0));
+                    assert_eq!(box_width(&row.list[1]), first_width);
+                    assert_eq!(row.list[2], pt(2.0));
+                    assert_eq!(box_width(&row.list[3]), second_width);
+                    assert_eq!(row.list[4], pt(3.0));
+                    assert_eq!(
+                        row.width,
+                        first_width
+                            + second_width
+                            + Dimen::from_unit(6.0, Unit::Point)
+                    );
+                }
+
+                // The assignments in the preamble are local to the
+                // alignment.
+                assert_eq!(
+                    parser.state.get_skip(0),
+                    Glue::from_dimen(Dimen::from_unit(1.0, Unit::Point))
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_puts_noalign_material_between_rows() {
+        with_parser(
+            &[r"\halign{#\cr a\cr\noalign{\hrule\kern2pt}b\cr}%"],
+            |parser| {
+                let rows = parser.parse_alignment();
+
+                assert_eq!(rows.len(), 4);
+                assert_eq!(cell_chars(&get_row_cells(&rows[0])[0]), "a");
+                assert!(matches!(rows[1], VerticalListElem::Rule { .. }));
+                assert_eq!(
+                    rows[2],
+                    VerticalListElem::Kern(Dimen::from_unit(2.0, Unit::Point))
+                );
+                assert_eq!(cell_chars(&get_row_cells(&rows[3])[0]), "b");
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r"Extra alignment tab has been changed to \cr")]
+    fn it_fails_on_too_many_cells_in_a_row() {
+        with_parser(&[r"\halign{#\cr a&b\cr}%"], |parser| {
+            parser.parse_alignment();
+        });
+    }
+
+    // Returns the cells in a column of a \valign, skipping over the \tabskip
+    // glue in between them.
+    fn get_column_cells(elem: &HorizontalListElem) -> Vec<VerticalBox> {
+        match elem {
+            HorizontalListElem::Box {
+                tex_box: TeXBox::VerticalBox(column),
+                ..
+            } => column
+                .list
+                .iter()
+                .filter(|elem| !matches!(elem, VerticalListElem::VSkip(_)))
+                .map(|cell| match cell {
+                    VerticalListElem::Box {
+                        tex_box: TeXBox::VerticalBox(cell),
+                        ..
+                    } => cell.clone(),
+                    _ => panic!("Invalid alignment cell: {:?}", cell),
+                })
+                .collect(),
+            _ => panic!("Invalid alignment column: {:?}", elem),
+        }
+    }
+
+    #[test]
+    fn it_aligns_vertical_cells_to_the_tallest_cell() {
+        with_parser(
+            &[
+                r"\tabskip=1pt%",
+                r"\valign{#&\kern3pt#\cr",
+                r"\hbox{a}&\hbox{b}\cr",
+                r"\hbox{g}\kern5pt&\hbox{y}\cr}%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                let columns = parser.parse_vertical_alignment();
+                assert_eq!(columns.len(), 2);
+
+                let first_column = get_column_cells(&columns[0]);
+                let second_column = get_column_cells(&columns[1]);
+                assert_eq!(first_column.len(), 2);
+                assert_eq!(second_column.len(), 2);
+
+                assert_eq!(first_column[0].to_chars(), vec!['a', '\n']);
+                assert_eq!(second_column[0].to_chars(), vec!['g', '\n']);
+
+                // The depth of each cell is part of its height, and the cells
+                // in each row are as tall as the tallest one in the row.
+                let metrics = parser
+                    .state
+                    .get_metrics_for_font(&Font {
+                        font_name: "cmr10".to_string(),
+                        scale: Dimen::from_unit(10.0, Unit::Point),
+                    })
+                    .unwrap();
+                let first_height = metrics.get_height('g')
+                    + metrics.get_depth('g')
+                    + Dimen::from_unit(5.0, Unit::Point);
+                // b is taller than y, including y's depth.
+                let second_height = Dimen::from_unit(3.0, Unit::Point)
+                    + metrics.get_height('b');
+
+                for column in &[&first_column, &second_column] {
+                    assert_eq!(column[0].height, first_height);
+                    assert_eq!(column[0].depth, Dimen::zero());
+                    assert_eq!(column[1].height, second_height);
+                    assert_eq!(column[1].depth, Dimen::zero());
+                }
+
+                // Each column is a vbox of its cells with \tabskip glue in
+                // between them.
+                match &columns[0] {
+                    HorizontalListElem::Box {
+                        tex_box: TeXBox::VerticalBox(column),
+                        ..
+                    } => {
+                        assert_eq!(column.list.len(), 5);
+                        assert_eq!(
+                            column.height,
+                            first_height
+                                + second_height
+                                + Dimen::from_unit(3.0, Unit::Point)
+                        );
+                    }
+                    elem => panic!("Invalid alignment column: {:?}", elem),
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn it_puts_valign_columns_into_horizontal_lists() {
+        with_parser(
+            &[r"\hbox{\valign{#\cr\hbox{a}\cr\noalign{b}\hbox{c}\cr}}%"],
+            |parser| {
+                let tex_box = parser.parse_box().unwrap();
+                let hbox = match tex_box {
+                    TeXBox::HorizontalBox(hbox) => hbox,
+                    _ => panic!("Expected an hbox: {:?}", tex_box),
+                };
+
+                assert_eq!(hbox.list.len(), 3);
+                assert_eq!(
+                    get_column_cells(&hbox.list[0])[0].to_chars(),
+                    vec!['a', '\n']
+                );
+                assert!(matches!(
+                    hbox.list[1],
+                    HorizontalListElem::Char { chr: 'b', .. }
+                ));
+                assert_eq!(
+                    get_column_cells(&hbox.list[2])[0].to_chars(),
+                    vec!['c', '\n']
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_starts_paragraphs_for_valign() {
+        with_parser(&[r"\valign{#\cr\hbox{a}\cr}%"], |parser| {
+            let list = parser.parse_vertical_list(true);
+            assert_eq!(list.len(), 1);
+
+            // The paragraph's only line has the indentation box and then the
+            // column of the alignment.
+            match &list[0] {
+                VerticalListElem::Box {
+                    tex_box: TeXBox::HorizontalBox(line),
+                    ..
+                } => {
+                    assert_eq!(get_column_cells(&line.list[1]).len(), 1);
+                }
+                elem => panic!("Expected a paragraph line: {:?}", elem),
+            }
+        });
+    }
+}
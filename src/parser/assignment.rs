@@ -1,54 +1,118 @@
 use std::rc::Rc;
 
 use crate::category::Category;
+use crate::diagnostics::DiagnosticKind;
 use crate::dimension::Dimen;
 use crate::font::Font;
 use crate::font_metrics::FontMetrics;
+use crate::makro::{Macro, MacroListElem};
 use crate::math_code::MathCode;
+use crate::math_list::MathStyle;
 use crate::parser::Parser;
+use crate::primitive::Primitive;
+use crate::state::RegisterKind;
 use crate::token::Token;
 
+/// The primitives that start assignments, other than the variables in
+/// variable.rs.
+#[cfg(test)]
+pub const HANDLED_PRIMITIVES: &[Primitive] = &[
+    Primitive::Def,
+    Primitive::EDef,
+    Primitive::GDef,
+    Primitive::XDef,
+    Primitive::Let,
+    Primitive::FutureLet,
+    Primitive::Read,
+    Primitive::Advance,
+    Primitive::Multiply,
+    Primitive::Divide,
+    Primitive::MathCharDef,
+    Primitive::UMathCharDef,
+    Primitive::CharDef,
+    Primitive::CountDef,
+    Primitive::DimenDef,
+    Primitive::SkipDef,
+    Primitive::CatCode,
+    Primitive::MathCode,
+    Primitive::LcCode,
+    Primitive::UcCode,
+    Primitive::Font,
+    Primitive::TextFont,
+    Primitive::ScriptFont,
+    Primitive::ScriptScriptFont,
+    Primitive::ParShape,
+    Primitive::PrevDepth,
+    Primitive::Global,
+    Primitive::AfterAssignment,
+    Primitive::AfterGroup,
+    Primitive::SetBox,
+];
+
 enum AtClause {
     Natural,
     Scaled(u16),
     At(Dimen),
 }
 
-pub struct SpecialVariables<'a> {
-    pub prev_depth: Option<&'a mut Dimen>,
-}
-
 impl<'a> Parser<'a> {
     fn is_variable_assignment_head(&mut self) -> bool {
         self.is_integer_variable_head()
             || self.is_dimen_variable_head()
             || self.is_glue_variable_head()
+            || self.is_token_list_variable_head()
     }
 
     fn is_macro_assignment_head(&mut self) -> bool {
-        self.is_next_expanded_token_in_set_of_primitives(&["def"])
+        self.is_next_expanded_token_in_set_of_primitives(&[
+            Primitive::Def,
+            Primitive::EDef,
+            Primitive::GDef,
+            Primitive::XDef,
+        ])
     }
 
     fn is_let_assignment_head(&mut self) -> bool {
-        self.is_next_expanded_token_in_set_of_primitives(&["let"])
+        self.is_next_expanded_token_in_set_of_primitives(&[
+            Primitive::Let,
+            Primitive::FutureLet,
+        ])
+    }
+
+    fn is_read_assignment_head(&mut self) -> bool {
+        self.is_next_expanded_token_in_set_of_primitives(&[Primitive::Read])
     }
 
     fn is_arithmetic_head(&mut self) -> bool {
         self.is_next_expanded_token_in_set_of_primitives(&[
-            "advance", "multiply", "divide",
+            Primitive::Advance,
+            Primitive::Multiply,
+            Primitive::Divide,
         ])
     }
 
     fn is_shorthand_definition_head(&mut self) -> bool {
-        self.is_next_expanded_token_in_set_of_primitives(&["mathchardef"])
+        self.is_next_expanded_token_in_set_of_primitives(&[
+            Primitive::MathCharDef,
+            Primitive::UMathCharDef,
+            Primitive::CharDef,
+            Primitive::CountDef,
+            Primitive::DimenDef,
+            Primitive::SkipDef,
+        ])
     }
 
     fn is_code_assignment_head(&mut self) -> bool {
-        self.is_next_expanded_token_in_set_of_primitives(&["mathcode"])
+        self.is_next_expanded_token_in_set_of_primitives(&[
+            Primitive::CatCode,
+            Primitive::MathCode,
+            Primitive::LcCode,
+            Primitive::UcCode,
+        ])
     }
 
     fn is_font_assignment_head(&mut self) -> bool {
-        self.is_next_expanded_token_in_set_of_primitives(&["font"])
+        self.is_next_expanded_token_in_set_of_primitives(&[Primitive::Font])
     }
 
     fn is_fontdef_assignment_head(&mut self) -> bool {
@@ -58,8 +122,22 @@ impl<'a> Parser<'a> {
         }
     }
 
+    fn is_family_assignment_head(&mut self) -> bool {
+        self.is_next_expanded_token_in_set_of_primitives(&[
+            Primitive::TextFont,
+            Primitive::ScriptFont,
+            Primitive::ScriptScriptFont,
+        ])
+    }
+
+    fn is_shape_assignment_head(&mut self) -> bool {
+        self.is_next_expanded_token_in_set_of_primitives(&[Primitive::ParShape])
+    }
+
     fn is_intimate_assignment_head(&mut self) -> bool {
-        self.is_next_expanded_token_in_set_of_primitives(&["prevdepth"])
+        self.is_next_expanded_token_in_set_of_primitives(&[
+            Primitive::PrevDepth,
+        ])
     }
 
     fn is_global_assignment_head(&mut self) -> bool {
@@ -70,20 +148,39 @@ impl<'a> Parser<'a> {
         self.is_let_assignment_head()
             || self.is_variable_assignment_head()
             || self.is_arithmetic_head()
+            || self.is_read_assignment_head()
             || self.is_box_assignment_head()
             || self.is_shorthand_definition_head()
             || self.is_code_assignment_head()
             || self.is_font_assignment_head()
             || self.is_fontdef_assignment_head()
+            || self.is_family_assignment_head()
+            || self.is_shape_assignment_head()
             || self.is_global_assignment_head()
     }
 
     fn is_assignment_prefix(&mut self) -> bool {
-        self.is_next_expanded_token_in_set_of_primitives(&["global"])
+        self.is_next_expanded_token_in_set_of_primitives(&[Primitive::Global])
+    }
+
+    fn is_after_assignment_head(&mut self) -> bool {
+        self.is_next_expanded_token_in_set_of_primitives(&[
+            Primitive::AfterAssignment,
+        ])
+    }
+
+    fn is_after_group_head(&mut self) -> bool {
+        self.is_next_expanded_token_in_set_of_primitives(&[
+            Primitive::AfterGroup,
+        ])
     }
 
+    // \afterassignment and \aftergroup aren't assignments themselves, but they
+    // can go anywhere that an assignment can, so we parse them here too.
     pub fn is_assignment_head(&mut self) -> bool {
-        self.is_assignment_prefix()
+        self.is_after_assignment_head()
+            || self.is_after_group_head()
+            || self.is_assignment_prefix()
             || self.is_macro_assignment_head()
             || self.is_simple_assignment_head()
     }
@@ -104,6 +201,17 @@ impl<'a> Parser<'a> {
             self.parse_equals_expanded();
             let value = self.parse_glue();
             variable.set(self.state, global, value);
+        } else if self.is_token_list_variable_head() {
+            let variable = self.parse_token_list_variable();
+            self.parse_equals_expanded();
+            // Token lists can be set from other token list variables, like
+            // \toks0=\toks1, as well as from explicit text.
+            let tokens = if self.is_token_list_variable_head() {
+                self.parse_token_list_variable().get(self.state)
+            } else {
+                self.parse_general_text()
+            };
+            variable.set(self.state, global, tokens);
         } else {
             panic!("unimplemented");
         }
@@ -128,13 +236,25 @@ impl<'a> Parser<'a> {
     fn parse_let_assignment(&mut self, global: bool) {
         let tok = self.lex_expanded_token().unwrap();
 
-        if self.state.is_token_equal_to_prim(&tok, "let") {
+        if self.state.is_token_equal_to_prim(&tok, Primitive::Let) {
             let let_name = self.parse_unexpanded_control_sequence();
             self.parse_equals_unexpanded();
             self.parse_optional_space_unexpanded();
             let let_value = self.lex_unexpanded_token().unwrap();
 
             self.state.set_let(global, &let_name, &let_value);
+        } else if self
+            .state
+            .is_token_equal_to_prim(&tok, Primitive::FutureLet)
+        {
+            // \futurelet\cs<token1><token2> lets \cs be <token2>, and then
+            // puts both tokens back so they are read again afterwards.
+            let let_name = self.parse_unexpanded_control_sequence();
+            let first_token = self.lex_unexpanded_token().unwrap();
+            let let_value = self.lex_unexpanded_token().unwrap();
+
+            self.state.set_let(global, &let_name, &let_value);
+            self.add_upcoming_tokens(vec![first_token, let_value]);
         } else {
             panic!("unimplemented");
         }
@@ -142,19 +262,70 @@ impl<'a> Parser<'a> {
 
     fn parse_arithmetic(&mut self, global: bool) {
         let tok = self.lex_expanded_token().unwrap();
+
+        if self.is_dimen_variable_head() {
+            self.parse_dimen_arithmetic(&tok, global);
+            return;
+        } else if self.is_glue_variable_head() {
+            self.parse_glue_arithmetic(&tok, global);
+            return;
+        }
+
         let variable = self.parse_integer_variable();
         self.parse_optional_keyword_expanded("by");
         self.parse_optional_spaces_expanded();
 
-        if self.state.is_token_equal_to_prim(&tok, "advance") {
+        if self.state.is_token_equal_to_prim(&tok, Primitive::Advance) {
             let number = self.parse_number();
             // TODO(xymostech): ensure this doesn't overflow
             variable.set(self.state, global, variable.get(self.state) + number);
-        } else if self.state.is_token_equal_to_prim(&tok, "multiply") {
+        } else if self.state.is_token_equal_to_prim(&tok, Primitive::Multiply) {
             let number = self.parse_number();
             // TODO(xymostech): ensure this doesn't overflow
             variable.set(self.state, global, variable.get(self.state) * number);
-        } else if self.state.is_token_equal_to_prim(&tok, "divide") {
+        } else if self.state.is_token_equal_to_prim(&tok, Primitive::Divide) {
+            let number = self.parse_number();
+            variable.set(self.state, global, variable.get(self.state) / number);
+        } else {
+            panic!("Invalid arithmetic head: {:?}", tok);
+        }
+    }
+
+    // Dimens are advanced by another dimen, but multiplied and divided by
+    // integers.
+    fn parse_dimen_arithmetic(&mut self, tok: &Token, global: bool) {
+        let variable = self.parse_dimen_variable();
+        self.parse_optional_keyword_expanded("by");
+        self.parse_optional_spaces_expanded();
+
+        if self.state.is_token_equal_to_prim(tok, Primitive::Advance) {
+            let dimen = self.parse_dimen();
+            variable.set(self.state, global, variable.get(self.state) + dimen);
+        } else if self.state.is_token_equal_to_prim(tok, Primitive::Multiply) {
+            let number = self.parse_number();
+            variable.set(self.state, global, variable.get(self.state) * number);
+        } else if self.state.is_token_equal_to_prim(tok, Primitive::Divide) {
+            let number = self.parse_number();
+            variable.set(self.state, global, variable.get(self.state) / number);
+        } else {
+            panic!("Invalid arithmetic head: {:?}", tok);
+        }
+    }
+
+    // Glue is advanced componentwise by another glue, so the stretch and
+    // shrink of the two glues are added separately.
+    fn parse_glue_arithmetic(&mut self, tok: &Token, global: bool) {
+        let variable = self.parse_glue_variable();
+        self.parse_optional_keyword_expanded("by");
+        self.parse_optional_spaces_expanded();
+
+        if self.state.is_token_equal_to_prim(tok, Primitive::Advance) {
+            let glue = self.parse_glue();
+            variable.set(self.state, global, variable.get(self.state) + glue);
+        } else if self.state.is_token_equal_to_prim(tok, Primitive::Multiply) {
+            let number = self.parse_number();
+            variable.set(self.state, global, variable.get(self.state) * number);
+        } else if self.state.is_token_equal_to_prim(tok, Primitive::Divide) {
             let number = self.parse_number();
             variable.set(self.state, global, variable.get(self.state) / number);
         } else {
@@ -165,41 +336,90 @@ impl<'a> Parser<'a> {
     fn parse_macro_assignment(&mut self, global: bool) {
         let tok = self.lex_expanded_token().unwrap();
 
-        if self.state.is_token_equal_to_prim(&tok, "def") {
-            let control_sequence = self.parse_unexpanded_control_sequence();
-            let makro = self.parse_macro_definition();
+        // \gdef and \xdef are the same as \global\def and \global\edef.
+        let (expand, global) =
+            if self.state.is_token_equal_to_prim(&tok, Primitive::Def) {
+                (false, global)
+            } else if self.state.is_token_equal_to_prim(&tok, Primitive::EDef) {
+                (true, global)
+            } else if self.state.is_token_equal_to_prim(&tok, Primitive::GDef) {
+                (false, true)
+            } else if self.state.is_token_equal_to_prim(&tok, Primitive::XDef) {
+                (true, true)
+            } else {
+                panic!("unimplemented");
+            };
 
-            self.state
-                .set_macro(global, &control_sequence, &Rc::new(makro));
-        } else {
-            panic!("unimplemented");
+        let control_sequence = self.parse_unexpanded_control_sequence();
+        let makro = self.parse_macro_definition(expand);
+
+        self.state
+            .set_macro(global, &control_sequence, &Rc::new(makro));
+    }
+
+    fn parse_read_assignment(&mut self, global: bool) {
+        let tok = self.lex_expanded_token().unwrap();
+
+        if !self.state.is_token_equal_to_prim(&tok, Primitive::Read) {
+            panic!("Invalid read assignment head: {:?}", tok);
+        }
+
+        let stream = self.parse_number();
+        if !self.parse_optional_keyword_expanded("to") {
+            panic!("Missing `to' inserted");
         }
+        let control_sequence = self.parse_unexpanded_control_sequence();
+
+        let tokens = self.read_tokens_from_stream(stream);
+        let makro = Macro::new(
+            Vec::new(),
+            tokens.into_iter().map(MacroListElem::Token).collect(),
+        );
+
+        self.state
+            .set_macro(global, &control_sequence, &Rc::new(makro));
     }
 
     fn is_box_assignment_head(&mut self) -> bool {
-        self.is_next_expanded_token_in_set_of_primitives(&["setbox"])
+        self.is_next_expanded_token_in_set_of_primitives(&[Primitive::SetBox])
     }
 
     fn parse_box_assignment(&mut self, global: bool) {
         let tok = self.lex_expanded_token().unwrap();
 
-        if !self.state.is_token_equal_to_prim(&tok, "setbox") {
+        if !self.state.is_token_equal_to_prim(&tok, Primitive::SetBox) {
             panic!("Invalid box assignment head: {:?}", tok);
         }
 
         let box_index = self.parse_8bit_number();
         self.parse_equals_expanded();
-        let maybe_tex_box = self.parse_box();
+        // Like TeX's scan_box, any spaces and \relax before the box are
+        // skipped.
+        self.parse_filler_expanded();
+
+        if !self.is_box_head() {
+            // This is a recoverable error. The register is left alone, and
+            // the tokens that were there instead are read normally.
+            self.state.with_diagnostics(|diagnostics| {
+                diagnostics.report(DiagnosticKind::MissingBox)
+            });
+            return;
+        }
 
-        if let Some(tex_box) = maybe_tex_box {
-            self.state.set_box(global, box_index, tex_box);
+        match self.parse_box() {
+            Some(tex_box) => self.state.set_box(global, box_index, tex_box),
+            // Void boxes like \box1 make the register void too.
+            None => self.state.clear_box(global, box_index),
         }
     }
 
     fn parse_shorthand_definition(&mut self, global: bool) {
         let tok = self.lex_expanded_token().unwrap();
 
-        if self.state.is_token_equal_to_prim(&tok, "mathchardef") {
+        if self
+            .state
+            .is_token_equal_to_prim(&tok, Primitive::MathCharDef)
+        {
             let control_sequence = self.parse_unexpanded_control_sequence();
             self.parse_equals_expanded();
             let code_value = self.parse_15bit_number();
@@ -209,15 +429,68 @@ impl<'a> Parser<'a> {
                 &control_sequence,
                 &MathCode::from_number(code_value as u32),
             );
+        } else if self
+            .state
+            .is_token_equal_to_prim(&tok, Primitive::UMathCharDef)
+        {
+            let control_sequence = self.parse_unexpanded_control_sequence();
+            self.parse_equals_expanded();
+            let math_code = self.parse_umath_code();
+
+            self.state
+                .set_math_chardef(global, &control_sequence, &math_code);
+        } else if self.state.is_token_equal_to_prim(&tok, Primitive::CharDef) {
+            let control_sequence = self.parse_unexpanded_control_sequence();
+            self.parse_equals_expanded();
+            let char_code = self.parse_8bit_number();
+
+            self.state.set_chardef(global, &control_sequence, char_code);
+        } else if let Some(kind) = self.get_register_def_kind(&tok) {
+            let control_sequence = self.parse_unexpanded_control_sequence();
+            self.parse_equals_expanded();
+            let index = self.parse_8bit_number();
+
+            self.state
+                .set_register_def(global, &control_sequence, kind, index);
         } else {
             panic!("unimplemented!");
         }
     }
 
+    fn get_register_def_kind(&self, tok: &Token) -> Option<RegisterKind> {
+        if self.state.is_token_equal_to_prim(tok, Primitive::CountDef) {
+            Some(RegisterKind::Count)
+        } else if self.state.is_token_equal_to_prim(tok, Primitive::DimenDef) {
+            Some(RegisterKind::Dimen)
+        } else if self.state.is_token_equal_to_prim(tok, Primitive::SkipDef) {
+            Some(RegisterKind::Skip)
+        } else {
+            None
+        }
+    }
+
     fn parse_code_assignment(&mut self, global: bool) {
         let tok = self.lex_expanded_token().unwrap();
 
-        if self.state.is_token_equal_to_prim(&tok, "mathcode") {
+        if self.state.is_token_equal_to_prim(&tok, Primitive::CatCode) {
+            let num = self.parse_8bit_number();
+            self.parse_equals_expanded();
+            let code_value = self.parse_number();
+
+            if !(0..16).contains(&code_value) {
+                panic!("Invalid code ({}), should be at most 15", code_value);
+            }
+
+            // The new category only affects characters that the lexer reads
+            // after this point. Like in TeX, anything that was already read
+            // ahead (for instance, the token that ended the number above)
+            // keeps the category that it was lexed with.
+            self.state.set_category(
+                global,
+                num as char,
+                Category::from_number(code_value as u8),
+            );
+        } else if self.state.is_token_equal_to_prim(&tok, Primitive::MathCode) {
             let num = self.parse_8bit_number();
             self.parse_equals_expanded();
             let code_value = self.parse_number();
@@ -227,6 +500,24 @@ impl<'a> Parser<'a> {
                 num as char,
                 &MathCode::from_number(code_value as u32),
             );
+        } else if self.state.is_token_equal_to_prim(&tok, Primitive::LcCode)
+            || self.state.is_token_equal_to_prim(&tok, Primitive::UcCode)
+        {
+            let num = self.parse_8bit_number();
+            self.parse_equals_expanded();
+            let code_value = self.parse_number();
+
+            if !(0..256).contains(&code_value) {
+                panic!("Invalid code ({}), should be at most 255", code_value);
+            }
+
+            if self.state.is_token_equal_to_prim(&tok, Primitive::LcCode) {
+                self.state
+                    .set_lc_code(global, num as char, code_value as u8);
+            } else {
+                self.state
+                    .set_uc_code(global, num as char, code_value as u8);
+            }
         } else {
             panic!("unimplemented");
         }
@@ -251,7 +542,7 @@ impl<'a> Parser<'a> {
     fn parse_font_assignment(&mut self, global: bool) {
         let tok = self.lex_expanded_token().unwrap();
 
-        if !self.state.is_token_equal_to_prim(&tok, "font") {
+        if !self.state.is_token_equal_to_prim(&tok, Primitive::Font) {
             panic!("Invalid font assignment head");
         }
 
@@ -277,13 +568,35 @@ impl<'a> Parser<'a> {
         let font_name = self.parse_file_name();
         let at = self.parse_at_clause();
 
-        let font_metrics = FontMetrics::from_font(&Font {
+        let font_metrics = match FontMetrics::from_font(&Font {
             font_name: font_name.clone(),
             // Since we're only accessing the design size, the scale for
             // the font doesn't matter here.
             scale: Dimen::zero(),
-        })
-        .unwrap_or_else(|| panic!("Invalid font name: {}", font_name));
+        }) {
+            Some(font_metrics) => font_metrics,
+            None => {
+                // Like TeX, we report the error and keep going with the
+                // control sequence meaning \nullfont, so anything typeset
+                // in it is dropped instead of reaching the DVI file.
+                let control_sequence = match &fontdef_name {
+                    Token::ControlSequence(name) => Some(name.clone()),
+                    _ => None,
+                };
+                self.state.with_diagnostics(|diagnostics| {
+                    diagnostics.report(DiagnosticKind::MissingFont {
+                        control_sequence,
+                        font_name,
+                    })
+                });
+                self.state.set_fontdef(
+                    global,
+                    &fontdef_name,
+                    &Font::null_font(),
+                );
+                return;
+            }
+        };
 
         let design_size = 65536.0 * font_metrics.get_design_size();
 
@@ -308,6 +621,57 @@ impl<'a> Parser<'a> {
         };
 
         self.state.set_fontdef(global, &fontdef_name, &font);
+        self.state.set_font_identifier(&font, &fontdef_name);
+    }
+
+    // Parses a \textfont, \scriptfont, or \scriptscriptfont assignment, which
+    // sets the font used for a math family in the given size.
+    fn parse_family_assignment(&mut self, global: bool) {
+        let tok = self.lex_expanded_token().unwrap();
+        let style =
+            if self.state.is_token_equal_to_prim(&tok, Primitive::TextFont) {
+                MathStyle::TextStyle
+            } else if self
+                .state
+                .is_token_equal_to_prim(&tok, Primitive::ScriptFont)
+            {
+                MathStyle::ScriptStyle
+            } else if self
+                .state
+                .is_token_equal_to_prim(&tok, Primitive::ScriptScriptFont)
+            {
+                MathStyle::ScriptScriptStyle
+            } else {
+                panic!("Invalid family assignment head: {:?}", tok);
+            };
+
+        let family = self.parse_4bit_number();
+        self.parse_equals_expanded();
+        let font = self.parse_font();
+
+        self.state.set_math_font(global, &style, family, &font);
+    }
+
+    // Parses a \parshape, which is a number of lines n followed by the
+    // indentation and width of each of those n lines.
+    fn parse_shape_assignment(&mut self, global: bool) {
+        let tok = self.lex_expanded_token().unwrap();
+        if !self.state.is_token_equal_to_prim(&tok, Primitive::ParShape) {
+            panic!("Invalid shape assignment head: {:?}", tok);
+        }
+
+        self.parse_equals_expanded();
+        let num_lines = self.parse_number();
+
+        let par_shape: Vec<(Dimen, Dimen)> = (0..num_lines)
+            .map(|_| {
+                let indent = self.parse_dimen();
+                let width = self.parse_dimen();
+                (indent, width)
+            })
+            .collect();
+
+        self.state.set_par_shape(global, &par_shape);
     }
 
     fn parse_fontdef_assignment(&mut self, global: bool) {
@@ -316,52 +680,64 @@ impl<'a> Parser<'a> {
         self.state.set_current_font(global, &font);
     }
 
-    fn parse_intimate_assignment(
-        &mut self,
-        maybe_special_vars: Option<SpecialVariables>,
-    ) {
+    pub fn is_font_head(&mut self) -> bool {
+        match self.peek_expanded_token() {
+            Some(tok) => {
+                self.state.is_token_equal_to_prim(&tok, Primitive::Font)
+                    || self.state.get_fontdef(&tok).is_some()
+            }
+            None => false,
+        }
+    }
+
+    // Parses a <font>, which is either a control sequence defined with \font
+    // or \font itself, which refers to the current font. Like TeX, we skip
+    // any spaces before it.
+    pub fn parse_font(&mut self) -> Font {
+        self.parse_optional_spaces_expanded();
+        let tok = self.lex_expanded_token().unwrap();
+
+        if self.state.is_token_equal_to_prim(&tok, Primitive::Font) {
+            self.state.get_current_font()
+        } else if let Some(font) = self.state.get_fontdef(&tok) {
+            font
+        } else {
+            panic!("Missing font identifier: {:?}", tok);
+        }
+    }
+
+    fn parse_intimate_assignment(&mut self) {
         let tok = self.lex_expanded_token().unwrap();
 
-        if self.state.is_token_equal_to_prim(&tok, "prevdepth") {
+        if self
+            .state
+            .is_token_equal_to_prim(&tok, Primitive::PrevDepth)
+        {
             self.parse_equals_expanded();
             let dimen = self.parse_dimen();
-
-            if let Some(special_vars) = maybe_special_vars {
-                if let Some(prev_depth) = special_vars.prev_depth {
-                    *prev_depth = dimen;
-                } else {
-                    panic!("Invalid prevdepth assignment");
-                }
-            } else {
-                panic!("Invalid prevdepth assignment");
-            }
+            self.set_prev_depth(dimen);
         } else {
             panic!("unimplemented");
         }
     }
 
-    fn parse_global_assignment(
-        &mut self,
-        special_vars: Option<SpecialVariables>,
-    ) {
+    fn parse_global_assignment(&mut self) {
         if self.is_intimate_assignment_head() {
-            self.parse_intimate_assignment(special_vars)
+            self.parse_intimate_assignment()
         } else {
             panic!("unimplemented");
         }
     }
 
-    fn parse_simple_assignment(
-        &mut self,
-        global: bool,
-        special_vars: Option<SpecialVariables>,
-    ) {
+    fn parse_simple_assignment(&mut self, global: bool) {
         if self.is_variable_assignment_head() {
             self.parse_variable_assignment(global)
         } else if self.is_let_assignment_head() {
             self.parse_let_assignment(global)
         } else if self.is_arithmetic_head() {
             self.parse_arithmetic(global)
+        } else if self.is_read_assignment_head() {
+            self.parse_read_assignment(global)
         } else if self.is_box_assignment_head() {
             self.parse_box_assignment(global)
         } else if self.is_shorthand_definition_head() {
@@ -372,27 +748,27 @@ impl<'a> Parser<'a> {
             self.parse_font_assignment(global)
         } else if self.is_fontdef_assignment_head() {
             self.parse_fontdef_assignment(global)
+        } else if self.is_family_assignment_head() {
+            self.parse_family_assignment(global)
+        } else if self.is_shape_assignment_head() {
+            self.parse_shape_assignment(global)
         } else if self.is_global_assignment_head() {
-            self.parse_global_assignment(special_vars)
+            self.parse_global_assignment()
         } else {
             panic!("unimplemented");
         }
     }
 
-    fn parse_assignment_global(
-        &mut self,
-        global: bool,
-        special_vars: Option<SpecialVariables>,
-    ) {
+    fn parse_assignment_global(&mut self, global: bool) {
         if self.is_macro_assignment_head() {
             self.parse_macro_assignment(global)
         } else if self.is_simple_assignment_head() {
-            self.parse_simple_assignment(global, special_vars)
+            self.parse_simple_assignment(global)
         } else {
             let tok = self.lex_expanded_token().unwrap();
-            if self.state.is_token_equal_to_prim(&tok, "global") {
+            if self.state.is_token_equal_to_prim(&tok, Primitive::Global) {
                 if self.is_assignment_head() {
-                    self.parse_assignment_global(true, special_vars);
+                    self.parse_assignment_global(true);
                 } else {
                     panic!("Non-assignment head found after \\global");
                 }
@@ -402,8 +778,24 @@ impl<'a> Parser<'a> {
         }
     }
 
-    pub fn parse_assignment(&mut self, special_vars: Option<SpecialVariables>) {
-        self.parse_assignment_global(false, special_vars);
+    pub fn parse_assignment(&mut self) {
+        if self.is_after_assignment_head() {
+            self.lex_expanded_token();
+            // Only the most recent \afterassignment token is kept.
+            self.after_assignment_token = self.lex_unexpanded_token();
+            return;
+        } else if self.is_after_group_head() {
+            self.lex_expanded_token();
+            let token = self.lex_unexpanded_token().unwrap();
+            self.state.add_after_group_token(token);
+            return;
+        }
+
+        self.parse_assignment_global(false);
+
+        if let Some(token) = self.after_assignment_token.take() {
+            self.add_upcoming_token(token);
+        }
     }
 }
 
@@ -411,17 +803,106 @@ impl<'a> Parser<'a> {
 mod tests {
     use super::*;
 
+    use std::cell::RefCell;
+
+    use crate::boxes::TeXBox;
     use crate::category::Category;
     use crate::dimension::{Dimen, FilDimen, FilKind, SpringDimen, Unit};
     use crate::glue::Glue;
-    use crate::makro::{Macro, MacroListElem};
-    use crate::state::GlueParameter;
-    use crate::testing::with_parser;
+    use crate::list::HorizontalListElem;
+    use crate::parser::mode::Mode;
+    use crate::state::{GlueParameter, TeXState, TokenListParameter};
+    use crate::testing::{with_parser, SharedWriter};
+
+    #[test]
+    fn it_inserts_the_after_assignment_token_after_the_next_assignment() {
+        with_parser(
+            &[r"\def\x{a}%", r"\afterassignment\x\count0=1 b%"],
+            |parser| {
+                parser.parse_assignment();
+
+                assert!(parser.is_assignment_head());
+                parser.parse_assignment();
+                assert!(parser.is_assignment_head());
+                parser.parse_assignment();
+                assert_eq!(parser.state.get_count(0), 1);
+
+                assert_eq!(
+                    parser.lex_expanded_token(),
+                    Some(Token::Char('a', Category::Letter))
+                );
+                assert_eq!(
+                    parser.lex_expanded_token(),
+                    Some(Token::Char('b', Category::Letter))
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_saves_after_group_tokens_in_order_for_each_group() {
+        with_parser(
+            &[r"\aftergroup\a\aftergroup\b\aftergroup\c\aftergroup\d%"],
+            |parser| {
+                // \aftergroup is ignored outside of groups
+                parser.parse_assignment();
+
+                parser.state.push_state();
+                parser.parse_assignment();
+                parser.state.push_state();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.end_group();
+
+                assert_eq!(
+                    parser.lex_unexpanded_token(),
+                    Some(Token::ControlSequence("c".to_string()))
+                );
+                assert_eq!(
+                    parser.lex_unexpanded_token(),
+                    Some(Token::ControlSequence("d".to_string()))
+                );
+
+                parser.end_group();
+                assert_eq!(
+                    parser.lex_unexpanded_token(),
+                    Some(Token::ControlSequence("b".to_string()))
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_only_keeps_the_last_after_assignment_token() {
+        with_parser(
+            &[
+                r"\def\x{a}\def\y{b}%",
+                r"\afterassignment\x\afterassignment\y\count0=1 %",
+                r"\count0=2 %",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                assert_eq!(
+                    parser.lex_expanded_token(),
+                    Some(Token::Char('b', Category::Letter))
+                );
+
+                // The token is only inserted once.
+                parser.parse_assignment();
+                assert_eq!(parser.state.get_count(0), 2);
+            },
+        );
+    }
 
     #[test]
     fn it_assigns_macros() {
         with_parser(&["\\def\\a #1x{#1y#1}%"], |parser| {
-            parser.parse_assignment(None);
+            parser.parse_assignment();
 
             assert_eq!(
                 *parser
@@ -449,12 +930,128 @@ mod tests {
         });
     }
 
+    #[test]
+    fn it_expands_edef_replacement_lists_at_definition_time() {
+        with_parser(
+            &[r"\def\a{x}%", r"\edef\b#1{\a#1\noexpand\a}%", r"\def\a{y}%"],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                assert_eq!(
+                    *parser
+                        .state
+                        .get_macro(&Token::ControlSequence("b".to_string()))
+                        .unwrap(),
+                    Macro::new(
+                        vec![MacroListElem::Parameter(1)],
+                        vec![
+                            MacroListElem::Token(Token::Char(
+                                'x',
+                                Category::Letter
+                            )),
+                            MacroListElem::Parameter(1),
+                            MacroListElem::Token(Token::ControlSequence(
+                                "a".to_string()
+                            )),
+                        ]
+                    )
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_does_not_expand_the_results_of_the_in_edefs() {
+        with_parser(
+            &[
+                r"\def\a{x}%",
+                r"\everypar={\a}%",
+                r"\edef\b{\the\everypar\a}%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                assert_eq!(
+                    *parser
+                        .state
+                        .get_macro(&Token::ControlSequence("b".to_string()))
+                        .unwrap(),
+                    Macro::new(
+                        vec![],
+                        vec![
+                            MacroListElem::Token(Token::ControlSequence(
+                                "a".to_string()
+                            )),
+                            MacroListElem::Token(Token::Char(
+                                'x',
+                                Category::Letter
+                            )),
+                        ]
+                    )
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_sets_global_defs_with_gdef_and_xdef() {
+        with_parser(
+            &[r"\def\a{x}%", r"{\gdef\b{\a}\xdef\c{\a}}%", r"\def\a{y}%"],
+            |parser| {
+                parser.parse_assignment();
+                assert_eq!(
+                    parser.lex_expanded_token(),
+                    Some(Token::Char('{', Category::BeginGroup))
+                );
+                parser.state.push_state();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                assert_eq!(
+                    parser.lex_expanded_token(),
+                    Some(Token::Char('}', Category::EndGroup))
+                );
+                parser.state.pop_state();
+                parser.parse_assignment();
+
+                assert_eq!(
+                    *parser
+                        .state
+                        .get_macro(&Token::ControlSequence("b".to_string()))
+                        .unwrap(),
+                    Macro::new(
+                        vec![],
+                        vec![MacroListElem::Token(Token::ControlSequence(
+                            "a".to_string()
+                        ))]
+                    )
+                );
+                assert_eq!(
+                    *parser
+                        .state
+                        .get_macro(&Token::ControlSequence("c".to_string()))
+                        .unwrap(),
+                    Macro::new(
+                        vec![],
+                        vec![MacroListElem::Token(Token::Char(
+                            'x',
+                            Category::Letter
+                        ))]
+                    )
+                );
+            },
+        );
+    }
+
     #[test]
     fn it_sets_global_defs() {
         with_parser(&["\\global\\def\\a{x}%"], |parser| {
             parser.state.push_state();
             assert!(parser.is_assignment_head());
-            parser.parse_assignment(None);
+            parser.parse_assignment();
             assert_eq!(parser.lex_unexpanded_token(), None);
             parser.state.pop_state();
 
@@ -477,7 +1074,7 @@ mod tests {
     #[test]
     fn it_assigns_lets_for_characters() {
         with_parser(&["\\let\\a=b%"], |parser| {
-            parser.parse_assignment(None);
+            parser.parse_assignment();
 
             assert_eq!(
                 parser.state.get_renamed_token(&Token::ControlSequence(
@@ -491,8 +1088,8 @@ mod tests {
     #[test]
     fn it_assigns_lets_for_previously_defined_macros() {
         with_parser(&["\\def\\a{x}%", "\\let\\b=\\a%"], |parser| {
-            parser.parse_assignment(None);
-            parser.parse_assignment(None);
+            parser.parse_assignment();
+            parser.parse_assignment();
 
             assert_eq!(
                 *parser
@@ -514,7 +1111,7 @@ mod tests {
     fn it_doesnt_assign_lets_for_active_tokens() {
         with_parser(&["\\let\\a=@%"], |parser| {
             parser.state.set_category(false, '@', Category::Active);
-            parser.parse_assignment(None);
+            parser.parse_assignment();
 
             assert_eq!(
                 parser.state.get_renamed_token(&Token::ControlSequence(
@@ -526,22 +1123,94 @@ mod tests {
     }
 
     #[test]
-    fn it_assigns_lets_for_primitives() {
-        with_parser(&["\\let\\a=\\def%"], |parser| {
-            parser.parse_assignment(None);
-
-            assert!(parser.state.is_token_equal_to_prim(
-                &Token::ControlSequence("a".to_string()),
-                "def"
-            ));
-        });
-    }
+    fn it_assigns_lets_for_begin_group_characters() {
+        with_parser(&[r"\let\bgroup={%", r"\let\egroup}%"], |parser| {
+            parser.parse_assignment();
+            parser.parse_assignment();
 
-    #[test]
+            assert_eq!(
+                parser.state.get_renamed_token(&Token::ControlSequence(
+                    "bgroup".to_string()
+                )),
+                Some(Token::Char('{', Category::BeginGroup))
+            );
+            assert_eq!(
+                parser.state.get_renamed_token(&Token::ControlSequence(
+                    "egroup".to_string()
+                )),
+                Some(Token::Char('}', Category::EndGroup))
+            );
+        });
+    }
+
+    #[test]
+    fn it_assigns_lets_for_spaces_after_one_optional_space() {
+        // The space after \\ is kept, so \sp is let to the second of the two
+        // spaces after the =.
+        with_parser(&[r"\def\\{\let\sp= }\\ %"], |parser| {
+            parser.parse_assignment();
+            parser.parse_assignment();
+
+            assert_eq!(
+                parser.state.get_renamed_token(&Token::ControlSequence(
+                    "sp".to_string()
+                )),
+                Some(Token::Char(' ', Category::Space))
+            );
+        });
+    }
+
+    #[test]
+    fn it_assigns_lets_for_undefined_control_sequences() {
+        with_parser(&[r"\def\a{x}%", r"\let\a=\undefined%"], |parser| {
+            parser.parse_assignment();
+            parser.parse_assignment();
+
+            assert!(!parser
+                .state
+                .is_token_defined(&Token::ControlSequence("a".to_string())));
+        });
+    }
+
+    #[test]
+    fn it_assigns_futurelets() {
+        with_parser(&[r"\futurelet\a\b c%"], |parser| {
+            parser.parse_assignment();
+
+            assert_eq!(
+                parser.state.get_renamed_token(&Token::ControlSequence(
+                    "a".to_string()
+                )),
+                Some(Token::Char('c', Category::Letter))
+            );
+            assert_eq!(
+                parser.lex_unexpanded_token(),
+                Some(Token::ControlSequence("b".to_string()))
+            );
+            assert_eq!(
+                parser.lex_unexpanded_token(),
+                Some(Token::Char('c', Category::Letter))
+            );
+        });
+    }
+
+    #[test]
+    fn it_assigns_lets_for_primitives() {
+        with_parser(&["\\let\\a=\\def%"], |parser| {
+            parser.parse_assignment();
+
+            assert!(parser.state.is_token_equal_to_prim(
+                &Token::ControlSequence("a".to_string()),
+                Primitive::Def
+            ));
+        });
+    }
+
+    #[test]
     fn it_lets_let_be_let() {
         with_parser(&["\\let\\a=\\let%", "\\a\\x=y%"], |parser| {
-            parser.parse_assignment(None);
-            parser.parse_assignment(None);
+            parser.parse_assignment();
+            parser.parse_assignment();
 
             assert_eq!(
                 parser.state.get_renamed_token(&Token::ControlSequence(
@@ -555,8 +1224,8 @@ mod tests {
     #[test]
     fn it_lets_def_be_let() {
         with_parser(&["\\let\\a=\\def%", "\\a\\x #1{#1}%"], |parser| {
-            parser.parse_assignment(None);
-            parser.parse_assignment(None);
+            parser.parse_assignment();
+            parser.parse_assignment();
 
             assert_eq!(
                 *parser
@@ -575,7 +1244,7 @@ mod tests {
     fn it_sets_global_lets() {
         with_parser(&["\\global\\let\\a=b%"], |parser| {
             parser.state.push_state();
-            parser.parse_assignment(None);
+            parser.parse_assignment();
             parser.state.pop_state();
 
             assert_eq!(
@@ -587,14 +1256,137 @@ mod tests {
         });
     }
 
+    // Gives \foo a local definition inside of a group, and then globally lets
+    // \bar be \foo. After the group ends, \bar should still have the meaning
+    // that \foo had inside of the group, which is the same meaning that
+    // \expected is given outside of the group.
+    fn assert_global_let_survives_group(definition: &str, expected: &str) {
+        with_parser(
+            &[expected, definition, r"\global\let\bar=\foo%"],
+            |parser| {
+                parser.parse_assignment();
+
+                parser.state.push_state();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.state.pop_state();
+
+                let foo = Token::ControlSequence("foo".to_string());
+                let bar = Token::ControlSequence("bar".to_string());
+                let expected = Token::ControlSequence("expected".to_string());
+                assert!(!parser.state.is_token_defined(&foo));
+                assert!(parser.state.is_meaning_equal(&bar, &expected));
+            },
+        );
+    }
+
+    #[test]
+    fn it_keeps_global_lets_of_local_definitions_after_groups_end() {
+        assert_global_let_survives_group(
+            r"\let\foo=\hskip%",
+            r"\let\expected=\hskip%",
+        );
+        assert_global_let_survives_group(
+            r"\def\foo#1{a#1}%",
+            r"\def\expected#1{a#1}%",
+        );
+        assert_global_let_survives_group(
+            r"\font\foo=cmr10 %",
+            r"\font\expected=cmr10 %",
+        );
+        assert_global_let_survives_group(
+            r"\chardef\foo=65 %",
+            r"\chardef\expected=65 %",
+        );
+        assert_global_let_survives_group(
+            r"\countdef\foo=5 %",
+            r"\countdef\expected=5 %",
+        );
+        assert_global_let_survives_group(r"\let\foo=a%", r"\let\expected=a%");
+    }
+
+    // Gives \foo a global definition inside of a group, and then locally lets
+    // \bar be \foo. After the group ends, \bar should go back to being \relax
+    // while \foo keeps its definition, which is the same as \expected.
+    fn assert_local_let_is_restored(definition: &str, expected: &str) {
+        with_parser(
+            &[r"\let\bar=\relax%", expected, definition, r"\let\bar=\foo%"],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                parser.state.push_state();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.state.pop_state();
+
+                let foo = Token::ControlSequence("foo".to_string());
+                let bar = Token::ControlSequence("bar".to_string());
+                let expected = Token::ControlSequence("expected".to_string());
+                assert!(parser.state.is_meaning_equal(&foo, &expected));
+                assert!(parser
+                    .state
+                    .is_token_equal_to_prim(&bar, Primitive::Relax));
+            },
+        );
+    }
+
+    #[test]
+    fn it_restores_local_lets_of_global_definitions_after_groups_end() {
+        assert_local_let_is_restored(
+            r"\global\let\foo=\hskip%",
+            r"\let\expected=\hskip%",
+        );
+        assert_local_let_is_restored(
+            r"\gdef\foo#1{a#1}%",
+            r"\def\expected#1{a#1}%",
+        );
+        assert_local_let_is_restored(
+            r"\global\font\foo=cmr10 %",
+            r"\font\expected=cmr10 %",
+        );
+        assert_local_let_is_restored(
+            r"\global\chardef\foo=65 %",
+            r"\chardef\expected=65 %",
+        );
+        assert_local_let_is_restored(
+            r"\global\countdef\foo=5 %",
+            r"\countdef\expected=5 %",
+        );
+    }
+
+    #[test]
+    fn it_uses_global_lets_from_inside_of_groups_after_aftergroup() {
+        with_parser(
+            &[
+                r"\hbox{{\let\foo=\hskip",
+                r"\global\let\bar=\foo",
+                r"\aftergroup\bar}3pt}%",
+            ],
+            |parser| {
+                let hbox = match parser.parse_box() {
+                    Some(TeXBox::HorizontalBox(hbox)) => hbox,
+                    other => panic!("Expected an hbox, got {:?}", other),
+                };
+
+                assert_eq!(
+                    hbox.list,
+                    vec![HorizontalListElem::HSkip(Glue::from_dimen(
+                        Dimen::from_unit(3.0, Unit::Point)
+                    ))]
+                );
+            },
+        );
+    }
+
     #[test]
     fn it_sets_count_variables() {
         with_parser(
             &["\\count0=2%", "\\count100 -12345%", "\\count10=\\count100%"],
             |parser| {
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
 
                 assert_eq!(parser.state.get_count(0), 2);
                 assert_eq!(parser.state.get_count(100), -12345);
@@ -607,7 +1399,7 @@ mod tests {
     fn it_sets_count_variables_globally() {
         with_parser(&["\\global\\count0=2%"], |parser| {
             parser.state.push_state();
-            parser.parse_assignment(None);
+            parser.parse_assignment();
             parser.state.pop_state();
 
             assert_eq!(parser.state.get_count(0), 2);
@@ -625,50 +1417,299 @@ mod tests {
                 "\\divide\\count0 by\\count1%",
             ],
             |parser| {
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
+                parser.parse_assignment();
+                parser.parse_assignment();
 
                 assert_eq!(parser.state.get_count(0), 150);
                 assert_eq!(parser.state.get_count(1), 5);
 
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
                 assert_eq!(parser.state.get_count(0), 157);
 
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
                 assert_eq!(parser.state.get_count(1), 10);
 
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
                 assert_eq!(parser.state.get_count(0), 15);
             },
         );
     }
 
+    #[test]
+    fn it_sets_dimen_variables() {
+        with_parser(
+            &[
+                r"\dimen0=2pt%",
+                r"\dimen100 -1.5in%",
+                r"\dimen10=\dimen100%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                assert_eq!(
+                    parser.state.get_dimen(0),
+                    Dimen::from_unit(2.0, Unit::Point)
+                );
+                assert_eq!(
+                    parser.state.get_dimen(100),
+                    Dimen::from_unit(-1.5, Unit::Inch)
+                );
+                assert_eq!(
+                    parser.state.get_dimen(10),
+                    Dimen::from_unit(-1.5, Unit::Inch)
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_sets_dimen_variables_locally_and_globally() {
+        with_parser(&[r"\dimen0=2pt%", r"\global\dimen1=3pt%"], |parser| {
+            parser.state.push_state();
+            parser.parse_assignment();
+            parser.parse_assignment();
+            assert_eq!(
+                parser.state.get_dimen(0),
+                Dimen::from_unit(2.0, Unit::Point)
+            );
+            parser.state.pop_state();
+
+            assert_eq!(parser.state.get_dimen(0), Dimen::zero());
+            assert_eq!(
+                parser.state.get_dimen(1),
+                Dimen::from_unit(3.0, Unit::Point)
+            );
+        });
+    }
+
+    #[test]
+    fn it_parses_dimen_arithmetic() {
+        with_parser(
+            &[
+                r"\dimen0=10pt%",
+                r"\advance\dimen0 by 2.5pt%",
+                r"\multiply\dimen0 by3%",
+                r"\divide\dimen0 by\count1%",
+                r"\advance\dimen0-\dimen0%",
+            ],
+            |parser| {
+                parser.state.set_count(false, 1, 5);
+
+                parser.parse_assignment();
+                assert!(parser.is_assignment_head());
+                parser.parse_assignment();
+                assert_eq!(
+                    parser.state.get_dimen(0),
+                    Dimen::from_unit(12.5, Unit::Point)
+                );
+
+                assert!(parser.is_assignment_head());
+                parser.parse_assignment();
+                assert_eq!(
+                    parser.state.get_dimen(0),
+                    Dimen::from_unit(37.5, Unit::Point)
+                );
+
+                assert!(parser.is_assignment_head());
+                parser.parse_assignment();
+                assert_eq!(
+                    parser.state.get_dimen(0),
+                    Dimen::from_unit(7.5, Unit::Point)
+                );
+
+                assert!(parser.is_assignment_head());
+                parser.parse_assignment();
+                assert_eq!(parser.state.get_dimen(0), Dimen::zero());
+            },
+        );
+    }
+
+    #[test]
+    fn it_sets_skip_variables_locally_and_globally() {
+        with_parser(
+            &[
+                r"\skip0=1pt plus2pt minus3pt%",
+                r"\skip0=5pt%",
+                r"\global\skip1=\skip0%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                let original = Glue {
+                    space: Dimen::from_unit(1.0, Unit::Point),
+                    stretch: SpringDimen::Dimen(Dimen::from_unit(
+                        2.0,
+                        Unit::Point,
+                    )),
+                    shrink: SpringDimen::Dimen(Dimen::from_unit(
+                        3.0,
+                        Unit::Point,
+                    )),
+                };
+                assert_eq!(parser.state.get_skip(0), original);
+
+                parser.state.push_state();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.state.pop_state();
+
+                assert_eq!(parser.state.get_skip(0), original);
+                assert_eq!(
+                    parser.state.get_skip(1),
+                    Glue::from_dimen(Dimen::from_unit(5.0, Unit::Point))
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_parses_skip_arithmetic() {
+        with_parser(
+            &[
+                r"\skip0=10pt plus1pt minus2pt%",
+                r"\advance\skip0 by 3pt plus1fil%",
+                r"\multiply\skip0 by2%",
+                r"\divide\skip0 by4%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+
+                assert!(parser.is_assignment_head());
+                parser.parse_assignment();
+                assert_eq!(
+                    parser.state.get_skip(0),
+                    Glue {
+                        space: Dimen::from_unit(13.0, Unit::Point),
+                        stretch: SpringDimen::FilDimen(FilDimen::new(
+                            FilKind::Fil,
+                            1.0
+                        )),
+                        shrink: SpringDimen::Dimen(Dimen::from_unit(
+                            2.0,
+                            Unit::Point
+                        )),
+                    }
+                );
+
+                assert!(parser.is_assignment_head());
+                parser.parse_assignment();
+                assert!(parser.is_assignment_head());
+                parser.parse_assignment();
+                assert_eq!(
+                    parser.state.get_skip(0),
+                    Glue {
+                        space: Dimen::from_unit(6.5, Unit::Point),
+                        stretch: SpringDimen::FilDimen(FilDimen::new(
+                            FilKind::Fil,
+                            0.5
+                        )),
+                        shrink: SpringDimen::Dimen(Dimen::from_unit(
+                            1.0,
+                            Unit::Point
+                        )),
+                    }
+                );
+            },
+        );
+    }
+
     #[test]
     fn it_sets_boxes() {
         with_parser(&["\\setbox123=\\hbox{a}%"], |parser| {
             assert!(parser.is_assignment_head());
-            parser.parse_assignment(None);
+            parser.parse_assignment();
 
             assert!(parser.state.get_box(123).is_some());
         });
     }
 
+    #[test]
+    fn it_sets_boxes_from_every_kind_of_box() {
+        with_parser(
+            &[
+                r"\setbox1=\hbox{a}%",
+                r"\setbox2=\vbox{\hrule height5pt\vskip1pt\hrule height5pt}%",
+                r"\setbox3=\copy1 %",
+                r"\setbox4=\box1 %",
+                r"\setbox5=\vsplit2 to5pt%",
+                r"\setbox6= \hbox{}%",
+                r"\setbox7 \relax\vbox{}%",
+            ],
+            |parser| {
+                for _ in 0..7 {
+                    assert!(parser.is_assignment_head());
+                    parser.parse_assignment();
+                }
+
+                assert!(parser.state.get_box(1).is_none());
+                assert_eq!(parser.state.get_box(3), parser.state.get_box(4));
+                assert_eq!(
+                    parser.state.with_box(5, |tex_box| *tex_box.height()),
+                    Some(Dimen::from_unit(5.0, Unit::Point))
+                );
+                assert!(matches!(
+                    parser.state.get_box(6),
+                    Some(TeXBox::HorizontalBox(_))
+                ));
+                assert!(matches!(
+                    parser.state.get_box(7),
+                    Some(TeXBox::VerticalBox(_))
+                ));
+            },
+        );
+    }
+
+    #[test]
+    fn it_voids_boxes_that_are_set_to_void_boxes() {
+        with_parser(&[r"\setbox0=\hbox{}%", r"\setbox0=\box1 %"], |parser| {
+            parser.parse_assignment();
+            parser.parse_assignment();
+
+            assert!(parser.state.get_box(0).is_none());
+        });
+    }
+
+    #[test]
+    fn it_reports_missing_boxes_when_setting_boxes() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let state = TeXState::new();
+        state.with_diagnostics(|diagnostics| {
+            diagnostics.set_json_writer(Box::new(SharedWriter(output.clone())))
+        });
+
+        let mut parser = Parser::new(&[r"\setbox0=a%"], &state);
+        parser.parse_assignment();
+
+        // The assignment is skipped and the rest of the input is read
+        // normally.
+        assert!(parser.state.get_box(0).is_none());
+        assert_eq!(
+            parser.lex_expanded_token(),
+            Some(Token::Char('a', Category::Letter))
+        );
+
+        let output = String::from_utf8(output.borrow().clone()).unwrap();
+        assert!(output.contains(r#""kind":"missing_box""#));
+        assert!(output.contains("A <box> was supposed to be here"));
+    }
+
     #[test]
     fn it_sets_box_dimens() {
         with_parser(
             &[r"\setbox0=\hbox{a}%", r"\wd0=2pt%", r"\ht0=3pt%"],
             |parser| {
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
 
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
 
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
 
                 assert_eq!(
                     parser.state.with_box(0, |tex_box| *tex_box.width()),
@@ -682,6 +1723,168 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_ignores_grouping_when_setting_box_dimens() {
+        with_parser(
+            &[
+                r"\global\setbox0=\hbox{a}%",
+                r"\setbox1=\hbox{a}%",
+                r"\wd0=10pt%",
+                r"\ht1=10pt%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                // Like in TeX, the dimensions of a box are changed directly
+                // instead of being saved and restored at the end of the group.
+                parser.state.push_state();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.state.pop_state();
+
+                assert_eq!(
+                    parser.state.with_box(0, |tex_box| *tex_box.width()),
+                    Some(Dimen::from_unit(10.0, Unit::Point))
+                );
+                assert_eq!(
+                    parser.state.with_box(1, |tex_box| *tex_box.height()),
+                    Some(Dimen::from_unit(10.0, Unit::Point))
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_keeps_the_glue_set_when_setting_box_dimens() {
+        with_parser(
+            &[
+                r"\setbox0=\hbox to20pt{a\hskip0pt plus1fil}%",
+                r"\wd0=50pt%",
+                r"\setbox1=\hbox{\box0}%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                let original_box = parser.state.get_box_copy(0).unwrap();
+
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                let outer_box = match parser.state.get_box(1).unwrap() {
+                    TeXBox::HorizontalBox(hbox) => hbox,
+                    _ => panic!("Expected an hbox"),
+                };
+                // The outer box is measured with the new width of the inner
+                // box.
+                assert_eq!(
+                    outer_box.width,
+                    Dimen::from_unit(50.0, Unit::Point)
+                );
+
+                match (&outer_box.list[0], original_box) {
+                    (
+                        HorizontalListElem::Box {
+                            tex_box: TeXBox::HorizontalBox(inner_box),
+                            ..
+                        },
+                        TeXBox::HorizontalBox(original_box),
+                    ) => {
+                        assert_eq!(
+                            inner_box.width,
+                            Dimen::from_unit(50.0, Unit::Point)
+                        );
+                        assert_eq!(
+                            inner_box.glue_set_ratio,
+                            original_box.glue_set_ratio
+                        );
+                        assert!(inner_box.glue_set_ratio.is_some());
+                    }
+                    _ => panic!("Expected an hbox inside of an hbox"),
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn it_sets_chardefs() {
+        with_parser(
+            &[
+                r"\chardef\x=65 %",
+                r#"\chardef\y"7F%"#,
+                r"\def\a{=}\chardef\z\a`\a%",
+                r"\x\y\z%",
+            ],
+            |parser| {
+                assert!(parser.is_assignment_head());
+                parser.parse_assignment();
+                assert!(parser.is_assignment_head());
+                parser.parse_assignment();
+                assert!(parser.is_assignment_head());
+                parser.parse_assignment();
+                assert!(parser.is_assignment_head());
+                parser.parse_assignment();
+
+                let x = parser.parse_unexpanded_control_sequence();
+                let y = parser.parse_unexpanded_control_sequence();
+                let z = parser.parse_unexpanded_control_sequence();
+
+                assert_eq!(parser.state.get_chardef(&x), Some(65));
+                assert_eq!(parser.state.get_chardef(&y), Some(127));
+                assert_eq!(parser.state.get_chardef(&z), Some(97));
+                assert_eq!(parser.state.get_math_chardef(&x), None);
+            },
+        );
+    }
+
+    #[test]
+    fn it_assigns_registers_through_register_defs() {
+        with_parser(
+            &[
+                r"\countdef\c=5 \c=10 %",
+                r"\the\count5 %",
+                r"\dimendef\d=1 \d=2pt\advance\d by\d%",
+                r"\skipdef\s=2 %",
+                r"\global\s=1pt plus1fil%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+                assert_eq!(parser.state.get_count(5), 10);
+                assert_eq!(
+                    parser.expand_print(),
+                    vec![
+                        Token::Char('1', Category::Other),
+                        Token::Char('0', Category::Other),
+                    ]
+                );
+
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                assert_eq!(
+                    parser.state.get_dimen(1),
+                    Dimen::from_unit(4.0, Unit::Point)
+                );
+
+                parser.parse_assignment();
+                parser.state.push_state();
+                parser.parse_assignment();
+                parser.state.pop_state();
+                assert_eq!(
+                    parser.state.get_skip(2),
+                    Glue {
+                        space: Dimen::from_unit(1.0, Unit::Point),
+                        stretch: SpringDimen::FilDimen(FilDimen::new(
+                            FilKind::Fil,
+                            1.0
+                        )),
+                        shrink: SpringDimen::Dimen(Dimen::zero()),
+                    }
+                );
+            },
+        );
+    }
+
     #[test]
     fn it_sets_mathchardefs() {
         with_parser(
@@ -693,15 +1896,15 @@ mod tests {
             ],
             |parser| {
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
 
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
 
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
 
                 let x = parser.parse_unexpanded_control_sequence();
                 let y = parser.parse_unexpanded_control_sequence();
@@ -723,16 +1926,56 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_sets_umathchardefs() {
+        with_parser(
+            &[
+                r#"\Umathchardef\x="7 "1F "1D44E %"#,
+                r#"\Umathchardef\y=1 3 "50 %"#,
+                r"\x\y%",
+            ],
+            |parser| {
+                assert!(parser.is_assignment_head());
+                parser.parse_assignment();
+                assert!(parser.is_assignment_head());
+                parser.parse_assignment();
+
+                let x = parser.parse_unexpanded_control_sequence();
+                let y = parser.parse_unexpanded_control_sequence();
+
+                assert_eq!(
+                    parser.state.get_math_chardef(&x),
+                    Some(MathCode::from_umath_parts(7, 0x1F, 0x1D44E))
+                );
+                assert_eq!(
+                    parser.state.get_math_chardef(&y),
+                    Some(MathCode::from_number(0x1350))
+                );
+
+                // Math chars that don't fit in a \mathchar are shown as
+                // \Umathchars instead.
+                assert_eq!(
+                    parser.state.get_meaning_string(&x),
+                    r#"\Umathchar"7"1F"01D44E"#
+                );
+                assert_eq!(
+                    parser.state.get_meaning_string(&y),
+                    r#"\mathchar"1350"#
+                );
+            },
+        );
+    }
+
     #[test]
     fn it_sets_mathcodes() {
         with_parser(
             &[r#"\mathcode`*="2203%"#, r#"\mathcode`<="313C%"#],
             |parser| {
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
 
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
 
                 assert_eq!(
                     parser.state.get_math_code('*'),
@@ -746,6 +1989,69 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_sets_case_codes() {
+        with_parser(&[r"\lccode`A=`b%", r"\uccode`a=0%"], |parser| {
+            assert_eq!(parser.state.get_lc_code('A'), b'a');
+            assert_eq!(parser.state.get_uc_code('a'), b'A');
+
+            assert!(parser.is_assignment_head());
+            parser.parse_assignment();
+
+            assert!(parser.is_assignment_head());
+            parser.parse_assignment();
+
+            assert_eq!(parser.state.get_lc_code('A'), b'b');
+            assert_eq!(parser.state.get_uc_code('a'), 0);
+        });
+    }
+
+    #[test]
+    fn it_sets_category_codes() {
+        with_parser(
+            &[
+                r"\catcode`\@=11 %",
+                r"\def\m@ke{x}%",
+                r"\m@ke\number\catcode`\@%",
+            ],
+            |parser| {
+                assert!(parser.is_assignment_head());
+                parser.parse_assignment();
+                assert_eq!(parser.state.get_category('@'), Category::Letter);
+
+                parser.parse_assignment();
+                assert_eq!(
+                    parser.lex_expanded_token(),
+                    Some(Token::Char('x', Category::Letter))
+                );
+                assert_eq!(
+                    parser.lex_expanded_token(),
+                    Some(Token::Char('1', Category::Other))
+                );
+                assert_eq!(
+                    parser.lex_expanded_token(),
+                    Some(Token::Char('1', Category::Other))
+                );
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid code (16)")]
+    fn it_fails_to_set_invalid_category_codes() {
+        with_parser(&[r"\catcode`\@=16%"], |parser| {
+            parser.parse_assignment();
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid code (256)")]
+    fn it_fails_to_set_invalid_case_codes() {
+        with_parser(&[r"\lccode`A=256%"], |parser| {
+            parser.parse_assignment();
+        });
+    }
+
     #[test]
     fn it_assigns_fonts() {
         with_parser(
@@ -756,13 +2062,13 @@ mod tests {
             ],
             |parser| {
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
 
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
 
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
 
                 assert_eq!(
                     parser.state.get_fontdef(&Token::ControlSequence(
@@ -797,11 +2103,69 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_assigns_math_family_fonts() {
+        with_parser(
+            &[
+                r"\font\tenrm=cmr10 %",
+                r"{\textfont2=\tenrm \global\scriptfont15=\tenrm}%",
+                r"\scriptscriptfont 3 = \font%",
+            ],
+            |parser| {
+                let cmr10 = Font {
+                    font_name: "cmr10".to_string(),
+                    scale: Dimen::from_unit(10.0, Unit::Point),
+                };
+                let cmsy10 = Font {
+                    font_name: "cmsy10".to_string(),
+                    scale: Dimen::from_unit(10.0, Unit::Point),
+                };
+
+                parser.parse_assignment();
+                assert_eq!(
+                    parser.lex_expanded_token(),
+                    Some(Token::Char('{', Category::BeginGroup))
+                );
+                parser.state.push_state();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                assert_eq!(
+                    parser.state.get_math_font(&MathStyle::TextStyle, 2),
+                    cmr10
+                );
+                parser.state.pop_state();
+                parser.lex_expanded_token();
+
+                assert_eq!(
+                    parser.state.get_math_font(&MathStyle::TextStyle, 2),
+                    cmsy10
+                );
+                assert_eq!(
+                    parser.state.get_math_font(&MathStyle::ScriptStyle, 15),
+                    cmr10
+                );
+
+                parser.parse_assignment();
+                assert_eq!(
+                    parser
+                        .state
+                        .get_math_font(&MathStyle::ScriptScriptStyle, 3),
+                    parser.state.get_current_font()
+                );
+                // Families that haven't been set use \nullfont
+                assert!(parser
+                    .state
+                    .get_math_font(&MathStyle::TextStyle, 4)
+                    .is_null_font());
+            },
+        );
+    }
+
     #[test]
     fn it_expands_macros_in_font_assignment() {
         with_parser(&[r"\def\y{10}%", r"\font\z=cmr\y%"], |parser| {
-            parser.parse_assignment(None);
-            parser.parse_assignment(None);
+            parser.parse_assignment();
+            parser.parse_assignment();
 
             assert_eq!(
                 parser
@@ -816,14 +2180,39 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "Invalid font name: cmr")]
     fn it_does_not_expand_the_assigned_font_name_in_font_assignment() {
         with_parser(&[r"\def\x{10}%", r"\font\x=cmr\x%"], |parser| {
-            parser.parse_assignment(None);
-            parser.parse_assignment(None);
+            parser.parse_assignment();
+            parser.parse_assignment();
+
+            // There's no cmr.tfm, so \x ends up as \nullfont instead of
+            // cmr10.
+            assert_eq!(
+                parser
+                    .state
+                    .get_fontdef(&Token::ControlSequence("x".to_string())),
+                Some(Font::null_font())
+            );
+
+            parser.parse_assignment();
+            assert_eq!(parser.state.get_current_font(), Font::null_font());
         });
     }
 
+    #[test]
+    fn it_drops_characters_in_fonts_that_could_not_be_loaded() {
+        with_parser(
+            &[r"\font\bad=nonexistentfont %", r"\bad ab\nullfont c%"],
+            |parser| {
+                parser.parse_assignment();
+
+                let list = parser.parse_horizontal_list(false, false);
+                assert_eq!(list, vec![]);
+                assert_eq!(parser.state.get_current_font(), Font::null_font());
+            },
+        );
+    }
+
     #[test]
     fn it_sets_current_fonts() {
         with_parser(
@@ -836,9 +2225,9 @@ mod tests {
             ],
             |parser| {
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
 
                 assert_eq!(
                     parser.state.get_current_font(),
@@ -849,9 +2238,9 @@ mod tests {
                 );
 
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
 
                 assert_eq!(
                     parser.state.get_current_font(),
@@ -862,9 +2251,9 @@ mod tests {
                 );
 
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
 
                 assert_eq!(
                     parser.state.get_current_font(),
@@ -875,9 +2264,9 @@ mod tests {
                 );
 
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
 
                 assert_eq!(
                     parser.state.get_current_font(),
@@ -893,25 +2282,36 @@ mod tests {
     #[test]
     fn it_assigns_prevdepth_values() {
         with_parser(&[r"\prevdepth=2pt%"], |parser| {
-            let mut prev_depth = Dimen::zero();
-
-            let special_variables = SpecialVariables {
-                prev_depth: Some(&mut prev_depth),
-            };
+            parser.push_mode(Mode::InternalVertical);
+            parser.prev_depth_stack.push(Dimen::zero());
 
             assert!(parser.is_assignment_head());
-            parser.parse_assignment(Some(special_variables));
+            parser.parse_assignment();
+
+            assert_eq!(
+                parser.get_prev_depth(),
+                Dimen::from_unit(2.0, Unit::Point)
+            );
+        });
+    }
 
-            assert_eq!(prev_depth, Dimen::from_unit(2.0, Unit::Point));
+    #[test]
+    #[should_panic(expected = "Improper \\prevdepth")]
+    fn it_fails_to_assign_prevdepth_values_outside_of_vertical_lists() {
+        with_parser(&[r"\prevdepth=2pt%"], |parser| {
+            parser.parse_assignment();
         });
     }
 
     #[test]
-    #[should_panic(expected = "Invalid prevdepth assignment")]
-    fn it_fails_to_assign_prevdepth_values_with_unassigned_special_variable() {
+    #[should_panic(expected = "Improper \\prevdepth")]
+    fn it_fails_to_assign_prevdepth_values_in_horizontal_mode() {
         with_parser(&[r"\prevdepth=2pt%"], |parser| {
-            parser
-                .parse_assignment(Some(SpecialVariables { prev_depth: None }));
+            parser.push_mode(Mode::InternalVertical);
+            parser.prev_depth_stack.push(Dimen::zero());
+            parser.push_mode(Mode::RestrictedHorizontal);
+
+            parser.parse_assignment();
         });
     }
 
@@ -921,7 +2321,7 @@ mod tests {
             &[r"\parskip=1pt plus2pt minus1fil%", r"\spaceskip5pt%"],
             |parser| {
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
 
                 assert_eq!(
                     parser.state.get_glue_parameter(&GlueParameter::ParSkip),
@@ -939,7 +2339,7 @@ mod tests {
                 );
 
                 assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                parser.parse_assignment();
 
                 assert_eq!(
                     parser.state.get_glue_parameter(&GlueParameter::SpaceSkip),
@@ -948,4 +2348,99 @@ mod tests {
             },
         );
     }
+
+    #[test]
+    fn it_assigns_token_list_variables() {
+        with_parser(
+            &[r"\everypar={a{\b}}%", r"\everypar\relax{c}%"],
+            |parser| {
+                assert!(parser.is_assignment_head());
+                parser.parse_assignment();
+
+                assert_eq!(
+                    parser.state.get_token_list_parameter(
+                        &TokenListParameter::EveryPar
+                    ),
+                    vec![
+                        Token::Char('a', Category::Letter),
+                        Token::Char('{', Category::BeginGroup),
+                        Token::ControlSequence("b".to_string()),
+                        Token::Char('}', Category::EndGroup),
+                    ]
+                );
+
+                assert!(parser.is_assignment_head());
+                parser.parse_assignment();
+
+                assert_eq!(
+                    parser.state.get_token_list_parameter(
+                        &TokenListParameter::EveryPar
+                    ),
+                    vec![Token::Char('c', Category::Letter)]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_sets_toks_registers_locally_and_globally() {
+        with_parser(
+            &[
+                r"\toks0={a}%",
+                r"\toks0={b}%",
+                r"\global\toks1=\toks0%",
+                r"\everypar=\toks1%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                assert_eq!(
+                    parser.state.get_toks(0),
+                    vec![Token::Char('a', Category::Letter)]
+                );
+
+                parser.state.push_state();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.state.pop_state();
+
+                assert_eq!(
+                    parser.state.get_toks(0),
+                    vec![Token::Char('a', Category::Letter)]
+                );
+                assert_eq!(
+                    parser.state.get_toks(1),
+                    vec![Token::Char('b', Category::Letter)]
+                );
+                assert_eq!(parser.state.get_toks(2), vec![]);
+
+                parser.parse_assignment();
+                assert_eq!(
+                    parser.state.get_token_list_parameter(
+                        &TokenListParameter::EveryPar
+                    ),
+                    vec![Token::Char('b', Category::Letter)]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_keeps_nested_braces_in_toks_registers() {
+        with_parser(&[r"\toks3={a{b{}}\x}%"], |parser| {
+            parser.parse_assignment();
+
+            assert_eq!(
+                parser.state.get_toks(3),
+                vec![
+                    Token::Char('a', Category::Letter),
+                    Token::Char('{', Category::BeginGroup),
+                    Token::Char('b', Category::Letter),
+                    Token::Char('{', Category::BeginGroup),
+                    Token::Char('}', Category::EndGroup),
+                    Token::Char('}', Category::EndGroup),
+                    Token::ControlSequence("x".to_string()),
+                ]
+            );
+        });
+    }
 }
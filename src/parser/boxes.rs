@@ -1,13 +1,30 @@
 use crate::boxes::{
-    get_set_dimen_and_ratio, BoxLayout, HorizontalBox, TeXBox, VerticalBox,
+    get_badness_for_layout, get_set_dimen_and_ratio, BoxLayout, HorizontalBox,
+    TeXBox, VerticalBox,
 };
 use crate::category::Category;
 use crate::dimension::Dimen;
 use crate::glue::Glue;
-use crate::list::HorizontalListElem;
+use crate::limits::ResourceLimit;
+use crate::list::{HorizontalListElem, VerticalListElem};
+use crate::page_breaking::find_vertical_list_split;
+use crate::parser::mode::Mode;
 use crate::parser::Parser;
+use crate::primitive::Primitive;
+use crate::state::{DimenParameter, TokenListParameter};
 use crate::token::Token;
 
+/// The primitives that make boxes or take them out of registers.
+#[cfg(test)]
+pub const HANDLED_PRIMITIVES: &[Primitive] = &[
+    Primitive::HBox,
+    Primitive::VBox,
+    Primitive::Box,
+    Primitive::Copy,
+    Primitive::VSplit,
+    Primitive::LastBox,
+];
+
 impl<'a> Parser<'a> {
     pub fn add_to_natural_layout_horizontal_box(
         &mut self,
@@ -52,7 +69,14 @@ impl<'a> Parser<'a> {
     ) -> VerticalBox {
         // Parse the actual list of elements
         let list = self.parse_vertical_list(internal);
+        self.create_vertical_box_with_layout(list, layout)
+    }
 
+    pub fn create_vertical_box_with_layout(
+        &mut self,
+        list: Vec<VerticalListElem>,
+        layout: &BoxLayout,
+    ) -> VerticalBox {
         // Keep track of the total height of the elements
         let mut height = Glue::zero();
         // Keep track of the depth of the most recently seen element. This will
@@ -78,6 +102,10 @@ impl<'a> Parser<'a> {
             }
         }
 
+        self.state
+            .set_last_badness(get_badness_for_layout(&height, layout));
+        self.state.with_stats(|stats| stats.boxes_built += 1);
+
         // Figure out the true height and set ratio
         let (set_height, glue_set) = get_set_dimen_and_ratio(height, layout);
 
@@ -93,42 +121,58 @@ impl<'a> Parser<'a> {
 
     fn parse_box_specification(&mut self) -> BoxLayout {
         if self.parse_optional_keyword_expanded("to") {
-            let dimen = self.parse_dimen();
-            self.parse_filler_expanded();
-            BoxLayout::Fixed(dimen)
+            BoxLayout::Fixed(self.parse_dimen())
         } else if self.parse_optional_keyword_expanded("spread") {
-            let dimen = self.parse_dimen();
-            self.parse_filler_expanded();
-            BoxLayout::Spread(dimen)
+            BoxLayout::Spread(self.parse_dimen())
         } else {
-            self.parse_filler_expanded();
             BoxLayout::Natural
         }
     }
 
     pub fn is_box_head(&mut self) -> bool {
         self.is_next_expanded_token_in_set_of_primitives(&[
-            "hbox", "vbox", "box", "copy",
+            Primitive::HBox,
+            Primitive::VBox,
+            Primitive::Box,
+            Primitive::Copy,
+            Primitive::VSplit,
+            Primitive::LastBox,
         ])
     }
 
+    // Keeps track of how deeply boxes are nested, so that documents with
+    // deeply nested boxes are stopped by the box nesting limit instead of
+    // overflowing the stack.
+    fn enter_box_group(&mut self) {
+        self.box_nesting_depth += 1;
+        self.state.check_resource_level(
+            ResourceLimit::BoxNesting,
+            self.box_nesting_depth,
+        );
+    }
+
     pub fn parse_box(&mut self) -> Option<TeXBox> {
         let head = self.lex_expanded_token().unwrap();
 
-        if self.state.is_token_equal_to_prim(&head, "hbox") {
+        if self.state.is_token_equal_to_prim(&head, Primitive::HBox) {
             let layout = self.parse_box_specification();
 
             // We expect a { after the box specification
-            match self.lex_expanded_token() {
-                Some(Token::Char(_, Category::BeginGroup)) => (),
-                _ => panic!("{}", "Expected { when parsing box"),
+            if !self.parse_left_brace_expanded() {
+                panic!("{}", "Expected { when parsing box");
             }
 
             self.state.push_state();
+            self.enter_box_group();
 
-            let hbox = self.parse_horizontal_box(&layout, true, false);
+            // The \everyhbox tokens are read inside of the group for the box,
+            // so any assignments in them only apply to this box.
+            let every_hbox = self
+                .state
+                .get_token_list_parameter(&TokenListParameter::EveryHBox);
+            self.add_upcoming_tokens(every_hbox);
 
-            self.state.pop_state();
+            let hbox = self.parse_horizontal_box(&layout, true, false);
 
             // And there should always be a } after the horizontal list
             match self.lex_expanded_token() {
@@ -136,44 +180,159 @@ impl<'a> Parser<'a> {
                 _ => panic!("{}", "Expected } when parsing box"),
             }
 
+            self.end_group();
+            self.box_nesting_depth -= 1;
+
             Some(TeXBox::HorizontalBox(hbox))
-        } else if self.state.is_token_equal_to_prim(&head, "vbox") {
-            let layout = self.parse_box_specification();
+        } else if self.state.is_token_equal_to_prim(&head, Primitive::VBox) {
+            Some(TeXBox::VerticalBox(self.parse_vertical_box_group()))
+        } else if self.state.is_token_equal_to_prim(&head, Primitive::Box) {
+            let box_index = self.parse_8bit_number();
+            self.state.get_box(box_index)
+        } else if self.state.is_token_equal_to_prim(&head, Primitive::Copy) {
+            let box_index = self.parse_8bit_number();
+            self.state.get_box_copy(box_index)
+        } else if self.state.is_token_equal_to_prim(&head, Primitive::VSplit) {
+            let box_index = self.parse_8bit_number();
+            if !self.parse_optional_keyword_expanded("to") {
+                panic!("Missing `to' inserted");
+            }
+            let height = self.parse_dimen();
+
+            match self.state.get_box(box_index) {
+                None => None,
+                Some(TeXBox::HorizontalBox(_)) => {
+                    panic!(r"\vsplit needs a \vbox")
+                }
+                Some(TeXBox::VerticalBox(vbox)) => {
+                    let (split_off, remainder) =
+                        self.split_vertical_box(vbox, height);
+                    self.state.replace_box_contents(
+                        box_index,
+                        remainder.map(TeXBox::VerticalBox),
+                    );
+                    Some(TeXBox::VerticalBox(split_off))
+                }
+            }
+        } else if self.state.is_token_equal_to_prim(&head, Primitive::LastBox) {
+            self.remove_last_box()
+        } else {
+            panic!("unimplemented");
+        }
+    }
 
-            // We expect a { after the box specification
-            match self.lex_expanded_token() {
-                Some(Token::Char(_, Category::BeginGroup)) => (),
-                _ => panic!("{}", "Expected { when parsing box"),
+    // Removes the box at the end of the list that is currently being built,
+    // for \lastbox. If the list doesn't end with a box, nothing is removed
+    // and the result is void. Like in TeX, math lists never have a last box.
+    fn remove_last_box(&mut self) -> Option<TeXBox> {
+        match self.get_mode() {
+            Mode::Vertical | Mode::InternalVertical => {
+                let list = self.vertical_list_stack.last_mut()?;
+                match list.pop() {
+                    Some(VerticalListElem::Box { tex_box, .. }) => {
+                        Some(tex_box)
+                    }
+                    other => {
+                        list.extend(other);
+                        None
+                    }
+                }
             }
+            Mode::Horizontal | Mode::RestrictedHorizontal => {
+                let list = self.horizontal_list_stack.last_mut()?;
+                match list.pop() {
+                    Some(HorizontalListElem::Box { tex_box, .. }) => {
+                        Some(tex_box)
+                    }
+                    other => {
+                        list.extend(other);
+                        None
+                    }
+                }
+            }
+            Mode::Math => None,
+        }
+    }
 
-            self.state.push_state();
+    /// Parses the box specification and the braced vertical list after a
+    /// \vbox or \vcenter, and packages the list into a vbox.
+    pub fn parse_vertical_box_group(&mut self) -> VerticalBox {
+        let layout = self.parse_box_specification();
 
-            let vbox = self.parse_vertical_box(&layout, true);
+        // We expect a { after the box specification
+        if !self.parse_left_brace_expanded() {
+            panic!("{}", "Expected { when parsing box");
+        }
 
-            self.state.pop_state();
+        self.state.push_state();
+        self.enter_box_group();
 
-            // And there should always be a } after the horizontal list
-            match self.lex_expanded_token() {
-                Some(Token::Char(_, Category::EndGroup)) => (),
-                _ => panic!("{}", "Expected } when parsing box"),
-            }
+        let every_vbox = self
+            .state
+            .get_token_list_parameter(&TokenListParameter::EveryVBox);
+        self.add_upcoming_tokens(every_vbox);
 
-            Some(TeXBox::VerticalBox(vbox))
-        } else if self.state.is_token_equal_to_prim(&head, "box") {
-            let box_index = self.parse_8bit_number();
-            self.state.get_box(box_index)
-        } else if self.state.is_token_equal_to_prim(&head, "copy") {
-            let box_index = self.parse_8bit_number();
-            self.state.get_box_copy(box_index)
-        } else {
-            panic!("unimplemented");
+        let vbox = self.parse_vertical_box(&layout, true);
+
+        // And there should always be a } after the vertical list
+        match self.lex_expanded_token() {
+            Some(Token::Char(_, Category::EndGroup)) => (),
+            _ => panic!("{}", "Expected } when parsing box"),
         }
+
+        self.end_group();
+        self.box_nesting_depth -= 1;
+
+        vbox
     }
 
-    // Used for early testing, when we want to output test the output of
-    // parsing an entire box.
+    /// Splits the material that best fits in the given height off of the top
+    /// of a vertical box, like \vsplit. Returns a box of exactly that height
+    /// with the split off material, and a box with the rest of the material
+    /// (or None if there isn't anything left). Like at the top of a page, the
+    /// glue, kerns, and penalties at the top of the rest are discarded.
+    pub fn split_vertical_box(
+        &mut self,
+        vbox: VerticalBox,
+        height: Dimen,
+    ) -> (VerticalBox, Option<VerticalBox>) {
+        let max_depth = self
+            .state
+            .get_dimen_parameter(&DimenParameter::SplitMaxDepth);
+
+        let mut list = vbox.list;
+        let split_index = find_vertical_list_split(&list, height, max_depth);
+        let rest: Vec<VerticalListElem> = list
+            .split_off(split_index)
+            .into_iter()
+            .skip_while(|elem| elem.is_discardable())
+            .collect();
+
+        let split_off = self
+            .create_vertical_box_with_layout(list, &BoxLayout::Fixed(height));
+        let remainder = if rest.is_empty() {
+            None
+        } else {
+            Some(
+                self.create_vertical_box_with_layout(rest, &BoxLayout::Natural),
+            )
+        };
+
+        (split_off, remainder)
+    }
+
+    // Parses the whole document as a single page. Like the pages that TeX's
+    // page builder makes, the page is set to be \vsize tall, so glue like
+    // \vfil can be used to position things on it. The page builder has
+    // replaced this outside of tests, which use it to check single pages.
+    #[cfg(test)]
     pub fn parse_outer_vertical_box(&mut self) -> VerticalBox {
-        self.parse_vertical_box(&BoxLayout::Natural, false)
+        let list = self.parse_vertical_list(false);
+
+        // \vsize is checked after the page has been parsed, since it could be
+        // set anywhere on the page.
+        let vsize = self.state.get_dimen_parameter(&DimenParameter::VSize);
+        self.create_vertical_box_with_layout(list, &BoxLayout::Fixed(vsize))
     }
 }
 
@@ -186,8 +345,9 @@ mod tests {
     use crate::boxes::{GlueSetRatio, GlueSetRatioKind};
     use crate::dimension::{Dimen, FilDimen, FilKind, SpringDimen, Unit};
     use crate::font::Font;
-    use crate::state::DimenParameter;
-    use crate::testing::with_parser;
+    use crate::limits::{ResourceLimitExceeded, ResourceLimits};
+    use crate::testing::{get_resource_limit_exceeded, with_parser};
+    use crate::token::tokenize_other;
 
     static CMR10: Lazy<Font> = Lazy::new(|| Font {
         font_name: "cmr10".to_string(),
@@ -244,6 +404,35 @@ mod tests {
         });
     }
 
+    #[test]
+    fn it_adds_kerns_to_box_widths() {
+        with_parser(&[r"\hbox{a\kern2pt b}\hbox{ab}%"], |parser| {
+            let kerned_box = parser.parse_box().unwrap();
+            let unkerned_box = parser.parse_box().unwrap();
+
+            assert_eq!(
+                *kerned_box.width(),
+                *unkerned_box.width() + Dimen::from_unit(2.0, Unit::Point)
+            );
+        });
+    }
+
+    #[test]
+    fn it_adds_kerns_to_vertical_box_heights() {
+        with_parser(&[r"\vbox{\kern3pt\hbox{a}\kern-1pt}%"], |parser| {
+            let metrics = parser.state.get_metrics_for_font(&CMR10).unwrap();
+            let vbox = parser.parse_box().unwrap();
+
+            assert_eq!(
+                *vbox.height(),
+                metrics.get_height('a')
+                    + metrics.get_depth('a')
+                    + Dimen::from_unit(2.0, Unit::Point)
+            );
+            assert_eq!(*vbox.depth(), Dimen::zero());
+        });
+    }
+
     #[test]
     fn it_stretches_boxes_with_finite_glue_to_a_fixed_width() {
         with_parser(&["a\\hskip 0pt plus1pt b%"], |parser| {
@@ -468,14 +657,16 @@ mod tests {
     fn it_parses_horizontal_boxes_with_natural_width() {
         with_parser(&["\\hbox{abc}%"], |parser| {
             let metrics = parser.state.get_metrics_for_font(&CMR10).unwrap();
+            // There is a kern between b and c in cmr10
             let expected_width = metrics.get_width('a')
                 + metrics.get_width('b')
+                + metrics.get_kern('b', 'c').unwrap()
                 + metrics.get_width('c');
 
             assert!(parser.is_box_head());
             let hbox = parser.parse_box().unwrap();
             if let TeXBox::HorizontalBox(hbox) = hbox {
-                assert_eq!(hbox.list.len(), 3);
+                assert_eq!(hbox.list.len(), 4);
                 assert_eq!(hbox.glue_set_ratio, None);
                 assert_eq!(hbox.width, expected_width);
             } else {
@@ -484,6 +675,48 @@ mod tests {
         });
     }
 
+    #[test]
+    fn it_enumerates_glyphs_in_boxes() {
+        with_parser(
+            &[
+                r"\font\big=cmr10 at 20pt%",
+                r"\hbox{ab}%",
+                r"\vbox{\hbox{a\big b}}%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                let big_cmr10 = Font {
+                    font_name: "cmr10".to_string(),
+                    scale: Dimen::from_unit(20.0, Unit::Point),
+                };
+
+                let hbox = parser.parse_box().unwrap();
+                assert_eq!(
+                    hbox.glyphs().collect::<Vec<_>>(),
+                    vec![('a', &*CMR10), ('b', &*CMR10)]
+                );
+
+                let vbox = parser.parse_box().unwrap();
+                assert_eq!(
+                    vbox.glyphs().collect::<Vec<_>>(),
+                    vec![('a', &*CMR10), ('b', &big_cmr10)]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_includes_kerns_in_natural_box_widths() {
+        with_parser(&["\\hbox{AV}%"], |parser| {
+            let metrics = parser.state.get_metrics_for_font(&CMR10).unwrap();
+
+            let hbox = parser.parse_box().unwrap();
+            assert!(
+                *hbox.width() < metrics.get_width('A') + metrics.get_width('V')
+            );
+        });
+    }
+
     #[test]
     fn it_parses_horizontal_boxes_with_fixed_width() {
         with_parser(&["\\hbox to20pt{a\\hskip 0pt plus1filc}%"], |parser| {
@@ -517,10 +750,37 @@ mod tests {
         });
     }
 
+    #[test]
+    fn it_parses_box_specifications_with_internal_dimens() {
+        with_parser(
+            &[
+                r"\dimen0=50pt\setbox0=\hbox to\dimen0{a}%",
+                r"\hbox spread\wd0{a}%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+                assert_eq!(
+                    parser.state.get_box_copy(0).unwrap().width(),
+                    &Dimen::from_unit(50.0, Unit::Point)
+                );
+
+                let metrics =
+                    parser.state.get_metrics_for_font(&CMR10).unwrap();
+                let expected_width = metrics.get_width('a')
+                    + Dimen::from_unit(50.0, Unit::Point);
+
+                assert!(parser.is_box_head());
+                let hbox = parser.parse_box().unwrap();
+                assert_eq!(hbox.width(), &expected_width);
+            },
+        );
+    }
+
     #[test]
     fn it_parses_boxes_from_box_registers() {
         with_parser(&[r"\setbox0=\hbox{a}%", r"\box0", r"\box0"], |parser| {
-            parser.parse_assignment(None);
+            parser.parse_assignment();
 
             let metrics = parser.state.get_metrics_for_font(&CMR10).unwrap();
 
@@ -536,7 +796,7 @@ mod tests {
     #[test]
     fn it_parses_copied_boxes_from_box_registers() {
         with_parser(&[r"\setbox0=\hbox{a}%", r"\copy0", r"\box0"], |parser| {
-            parser.parse_assignment(None);
+            parser.parse_assignment();
 
             assert!(parser.is_box_head());
             let copied_box = parser.parse_box().unwrap();
@@ -637,7 +897,7 @@ mod tests {
                 let metrics =
                     parser.state.get_metrics_for_font(&CMR10).unwrap();
 
-                parser.parse_assignment(None);
+                parser.parse_assignment();
                 let vbox = parser.parse_box().unwrap();
 
                 assert_eq!(*vbox.height(), Dimen::from_unit(20.0, Unit::Point));
@@ -646,6 +906,54 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_starts_implicit_paragraphs_in_vboxes() {
+        with_parser(
+            &[r"\vbox{ab}%", r"\vbox{\char97 b\par\noindent c}%"],
+            |parser| {
+                let vbox = parser.parse_box().unwrap();
+                assert_eq!(
+                    vbox.to_chars().into_iter().collect::<String>(),
+                    " ab \n"
+                );
+
+                let vbox = parser.parse_box().unwrap();
+                assert_eq!(
+                    vbox.to_chars().into_iter().collect::<String>(),
+                    " ab \nc \n"
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_includes_rules_in_box_sizes() {
+        with_parser(
+            &[
+                r"\vbox{\hrule width10pt}%",
+                r"\hbox{\vrule height3pt depth1pt width2pt}%",
+                r"\hbox{\vrule}%",
+            ],
+            |parser| {
+                let vbox = parser.parse_box().unwrap();
+                assert_eq!(*vbox.height(), Dimen::from_unit(0.4, Unit::Point));
+                assert_eq!(*vbox.depth(), Dimen::zero());
+                assert_eq!(*vbox.width(), Dimen::from_unit(10.0, Unit::Point));
+
+                let hbox = parser.parse_box().unwrap();
+                assert_eq!(*hbox.height(), Dimen::from_unit(3.0, Unit::Point));
+                assert_eq!(*hbox.depth(), Dimen::from_unit(1.0, Unit::Point));
+                assert_eq!(*hbox.width(), Dimen::from_unit(2.0, Unit::Point));
+
+                // Running dimensions don't make the box any bigger
+                let hbox = parser.parse_box().unwrap();
+                assert_eq!(*hbox.height(), Dimen::zero());
+                assert_eq!(*hbox.depth(), Dimen::zero());
+                assert_eq!(*hbox.width(), Dimen::from_unit(0.4, Unit::Point));
+            },
+        );
+    }
+
     #[test]
     fn it_parses_state_group_around_box_definitions() {
         with_parser(
@@ -661,8 +969,8 @@ mod tests {
                 r"\setbox1=\hbox{1\hbox{12}1\vbox{12}1}%",
             ],
             |parser| {
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
+                parser.parse_assignment();
+                parser.parse_assignment();
 
                 assert_eq!(parser.state.get_box(0), parser.state.get_box(1),);
             },
@@ -679,10 +987,10 @@ mod tests {
                 r"\setbox3=\hbox to1500sp{\hskip 0pt plus 1000fil}%",
             ],
             |parser| {
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
 
                 assert_eq!(
                     parser.state.get_box(0),
@@ -774,4 +1082,332 @@ mod tests {
             },
         );
     }
+
+    #[test]
+    fn it_sets_badness_after_making_boxes() {
+        with_parser(
+            &[
+                r"\setbox0=\hbox to1pt{AA}\count1=\badness%",
+                r"\setbox0=\hbox{AA}\count2=\badness%",
+                r"\setbox0=\vbox to10pt{}\count3=\badness%",
+                r"\setbox0=\hbox spread10pt{A\hskip0pt plus10pt}%",
+                r"\count4=\badness%",
+            ],
+            |parser| {
+                for _ in 0..8 {
+                    parser.parse_assignment();
+                }
+
+                assert_eq!(parser.state.get_count(1), 1000000);
+                assert_eq!(parser.state.get_count(2), 0);
+                assert_eq!(parser.state.get_count(3), 10000);
+                assert_eq!(parser.state.get_count(4), 100);
+            },
+        );
+    }
+    #[test]
+    fn it_adds_everyhbox_tokens_to_each_hbox() {
+        with_parser(
+            &[
+                r"\everyhbox={\hskip1pt}%",
+                r"\setbox0=\hbox{}%",
+                r"\setbox1=\hbox{\hbox{}}%",
+            ],
+            |parser| {
+                for _ in 0..3 {
+                    parser.parse_assignment();
+                }
+
+                assert_eq!(
+                    parser.state.get_box_copy(0).unwrap().width(),
+                    &Dimen::from_unit(1.0, Unit::Point)
+                );
+                assert_eq!(
+                    parser.state.get_box_copy(1).unwrap().width(),
+                    &Dimen::from_unit(2.0, Unit::Point)
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_scopes_assignments_in_everyhbox_to_the_box() {
+        with_parser(
+            &[r"\everyhbox={\def\a{x}}%", r"\setbox0=\hbox{\a}%"],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                let hbox = match parser.state.get_box_copy(0).unwrap() {
+                    TeXBox::HorizontalBox(hbox) => hbox,
+                    _ => panic!("Expected an hbox"),
+                };
+                assert_eq!(hbox.to_chars(), vec!['x']);
+                assert!(parser
+                    .state
+                    .get_macro(&Token::ControlSequence("a".to_string()))
+                    .is_none());
+            },
+        );
+    }
+
+    #[test]
+    fn it_adds_everyvbox_tokens_to_vboxes_inside_of_everyhbox() {
+        with_parser(
+            &[
+                r"\everyhbox={\vbox{}}%",
+                r"\everyvbox={\vskip1pt}%",
+                r"\setbox0=\hbox{}%",
+            ],
+            |parser| {
+                for _ in 0..3 {
+                    parser.parse_assignment();
+                }
+
+                let hbox = match parser.state.get_box_copy(0).unwrap() {
+                    TeXBox::HorizontalBox(hbox) => hbox,
+                    _ => panic!("Expected an hbox"),
+                };
+                assert_eq!(hbox.list.len(), 1);
+                assert_eq!(hbox.height, Dimen::from_unit(1.0, Unit::Point));
+            },
+        );
+    }
+
+    #[test]
+    fn it_skips_filler_before_box_contents() {
+        with_parser(
+            &[
+                r"\let\bgroup={%",
+                r"\hbox \relax {a}%",
+                r"\vbox to 2pt \relax\relax {}%",
+                r"\hbox spread 1pt\relax \bgroup a}%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+
+                let natural = parser.parse_box().unwrap();
+                assert_ne!(*natural.width(), Dimen::zero());
+
+                let fixed = parser.parse_box().unwrap();
+                assert_eq!(*fixed.height(), Dimen::from_unit(2.0, Unit::Point));
+
+                let spread = parser.parse_box().unwrap();
+                assert_eq!(
+                    *spread.width(),
+                    *natural.width() + Dimen::from_unit(1.0, Unit::Point)
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_stops_deeply_nested_boxes_at_the_nesting_limit() {
+        let err = get_resource_limit_exceeded(|| {
+            with_parser(
+                &[r"\let\bgroup={%", r"\def\a{\hbox\bgroup\a}%", r"\a"],
+                |parser| {
+                    parser.state.set_resource_limits(
+                        ResourceLimits::unlimited()
+                            .with_max(ResourceLimit::BoxNesting, 20),
+                    );
+                    parser.parse_assignment();
+                    parser.parse_assignment();
+                    parser.parse_box();
+                },
+            )
+        });
+        assert_eq!(
+            err,
+            ResourceLimitExceeded {
+                limit: ResourceLimit::BoxNesting,
+                max: 20,
+            }
+        );
+    }
+
+    #[test]
+    fn it_splits_vertical_boxes() {
+        with_parser(
+            &[
+                r"\setbox1=\vbox{%",
+                r"  \hrule height 10pt\vskip 5pt%",
+                r"  \hrule height 10pt\vskip 5pt%",
+                r"  \hrule height 10pt%",
+                r"}%",
+                r"\setbox2=\vsplit1 to 25pt%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                let rule = VerticalListElem::Rule {
+                    height: Some(Dimen::from_unit(10.0, Unit::Point)),
+                    depth: Some(Dimen::zero()),
+                    width: None,
+                };
+                let skip = VerticalListElem::VSkip(Glue::from_dimen(
+                    Dimen::from_unit(5.0, Unit::Point),
+                ));
+
+                // The best split is after the second rule, where the split
+                // off material exactly fills the box. The glue after it is
+                // discarded.
+                let split_off = parser.state.get_box(2).unwrap();
+                assert_eq!(
+                    *split_off.height(),
+                    Dimen::from_unit(25.0, Unit::Point)
+                );
+                match split_off {
+                    TeXBox::VerticalBox(vbox) => assert_eq!(
+                        vbox.list,
+                        vec![rule.clone(), skip, rule.clone()]
+                    ),
+                    _ => panic!("Expected a vbox"),
+                }
+
+                let remainder = parser.state.get_box(1).unwrap();
+                assert_eq!(
+                    *remainder.height(),
+                    Dimen::from_unit(10.0, Unit::Point)
+                );
+                match remainder {
+                    TeXBox::VerticalBox(vbox) => {
+                        assert_eq!(vbox.list, vec![rule])
+                    }
+                    _ => panic!("Expected a vbox"),
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn it_leaves_void_boxes_after_splitting_everything() {
+        with_parser(
+            &[
+                r"\setbox1=\vbox{\hrule height 10pt}%",
+                r"\setbox2=\vsplit1 to 25pt%",
+                r"\setbox3=\vsplit4 to 25pt%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                assert_eq!(
+                    parser.state.get_box(2).map(|b| *b.height()),
+                    Some(Dimen::from_unit(25.0, Unit::Point))
+                );
+                assert!(parser.state.get_box(1).is_none());
+                // Splitting a void box gives a void box.
+                assert!(parser.state.get_box(3).is_none());
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r"\vsplit needs a \vbox")]
+    fn it_fails_to_split_horizontal_boxes() {
+        with_parser(
+            &[r"\setbox1=\hbox{}%", r"\setbox2=\vsplit1 to 25pt%"],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+            },
+        );
+    }
+
+    #[test]
+    fn it_reads_the_dimensions_of_boxes_removed_with_lastbox() {
+        with_parser(
+            &[
+                r"\setbox1=\hbox{\hbox{ab}\global\setbox0=\lastbox}%",
+                r"\the\wd0 %",
+            ],
+            |parser| {
+                parser.parse_assignment();
+
+                let metrics =
+                    parser.state.get_metrics_for_font(&CMR10).unwrap();
+                let width = metrics.get_width('a') + metrics.get_width('b');
+                assert_eq!(
+                    parser.expand_print(),
+                    tokenize_other(&width.to_pt_string())
+                );
+
+                assert_eq!(
+                    parser.state.get_box(0).unwrap().to_chars(),
+                    vec!['a', 'b']
+                );
+                assert_eq!(
+                    *parser.state.get_box(1).unwrap().width(),
+                    Dimen::zero()
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_removes_boxes_from_the_ends_of_lists_with_lastbox() {
+        with_parser(
+            &[
+                r"\setbox1=\vbox{\hbox{a}\hbox{b}\global\setbox0=\lastbox}%",
+                r"\setbox3=\hbox{\hbox{a}\hbox{b}\setbox0=\lastbox",
+                r"  \global\setbox2=\lastbox}%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                assert_eq!(
+                    parser.state.get_box(0).unwrap().to_chars(),
+                    vec!['b']
+                );
+                // The interline glue before the removed box is left behind.
+                match parser.state.get_box(1).unwrap() {
+                    TeXBox::VerticalBox(vbox) => {
+                        assert_eq!(vbox.list.len(), 2);
+                        assert!(matches!(
+                            vbox.list[1],
+                            VerticalListElem::VSkip(_)
+                        ));
+                    }
+                    _ => panic!("Expected a vbox"),
+                }
+
+                // Consecutive boxes can be removed one at a time.
+                assert_eq!(
+                    parser.state.get_box(2).unwrap().to_chars(),
+                    vec!['a']
+                );
+                assert_eq!(parser.state.get_box(3).unwrap().to_chars(), vec![]);
+            },
+        );
+    }
+
+    #[test]
+    fn it_makes_void_boxes_with_lastbox_when_lists_dont_end_in_boxes() {
+        with_parser(
+            &[
+                r"\setbox0=\hbox{}%",
+                r"\setbox1=\hbox{\hbox{a}\kern1pt\global\setbox0=\lastbox}%",
+                r"\setbox2=\lastbox%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                assert!(parser.state.get_box(0).is_none());
+                match parser.state.get_box(1).unwrap() {
+                    TeXBox::HorizontalBox(hbox) => {
+                        assert_eq!(hbox.list.len(), 2)
+                    }
+                    _ => panic!("Expected an hbox"),
+                }
+                // There isn't a list being built at all here.
+                assert!(parser.state.get_box(2).is_none());
+            },
+        );
+    }
 }
@@ -0,0 +1,193 @@
+use crate::parser::Parser;
+use crate::primitive::Primitive;
+use crate::token::Token;
+
+/// The primitives that change the case of token lists.
+#[cfg(test)]
+pub const HANDLED_PRIMITIVES: &[Primitive] =
+    &[Primitive::Lowercase, Primitive::Uppercase];
+
+impl<'a> Parser<'a> {
+    pub fn is_case_conversion_head(&mut self) -> bool {
+        match self.peek_unexpanded_token() {
+            Some(token) => {
+                self.state
+                    .is_token_equal_to_prim(&token, Primitive::Lowercase)
+                    || self
+                        .state
+                        .is_token_equal_to_prim(&token, Primitive::Uppercase)
+            }
+            _ => false,
+        }
+    }
+
+    /// Parses a \lowercase or \uppercase and the following group, and returns
+    /// the tokens in the group with their characters replaced according to
+    /// the \lccode or \uccode tables. Only the character codes are changed,
+    /// not the categories, and control sequences are left alone.
+    pub fn expand_case_conversion(&mut self) -> Vec<Token> {
+        let head = self.lex_unexpanded_token().unwrap();
+
+        let is_lowercase = if self
+            .state
+            .is_token_equal_to_prim(&head, Primitive::Lowercase)
+        {
+            true
+        } else if self
+            .state
+            .is_token_equal_to_prim(&head, Primitive::Uppercase)
+        {
+            false
+        } else {
+            panic!("unimplemented");
+        };
+
+        self.parse_general_text()
+            .into_iter()
+            .map(|token| match token {
+                Token::Char(ch, cat) => {
+                    let code = if is_lowercase {
+                        self.state.get_lc_code(ch)
+                    } else {
+                        self.state.get_uc_code(ch)
+                    };
+
+                    // Characters with a code of 0 are left unchanged.
+                    if code == 0 {
+                        Token::Char(ch, cat)
+                    } else {
+                        Token::Char(code as char, cat)
+                    }
+                }
+                Token::ControlSequence(_) => token,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::category::Category;
+    use crate::testing::with_parser;
+
+    #[test]
+    fn it_converts_characters_to_uppercase() {
+        with_parser(&[r"\uppercase{aB1}%"], |parser| {
+            assert!(parser.is_case_conversion_head());
+            assert_eq!(
+                parser.expand_case_conversion(),
+                vec![
+                    Token::Char('A', Category::Letter),
+                    Token::Char('B', Category::Letter),
+                    Token::Char('1', Category::Other),
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn it_skips_filler_before_the_general_text() {
+        with_parser(&[r"\uppercase \relax {a}%"], |parser| {
+            assert_eq!(
+                parser.expand_case_conversion(),
+                vec![Token::Char('A', Category::Letter)]
+            );
+        });
+    }
+
+    #[test]
+    fn it_converts_characters_to_lowercase() {
+        with_parser(&[r"\lowercase{aB1}%"], |parser| {
+            assert!(parser.is_case_conversion_head());
+            assert_eq!(
+                parser.expand_case_conversion(),
+                vec![
+                    Token::Char('a', Category::Letter),
+                    Token::Char('b', Category::Letter),
+                    Token::Char('1', Category::Other),
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn it_leaves_control_sequences_and_categories_alone() {
+        with_parser(&[r"\uppercase{\a{b}}%"], |parser| {
+            parser.state.set_uc_code(false, '{', b'x');
+            parser.state.set_uc_code(false, '}', b'y');
+
+            assert_eq!(
+                parser.expand_case_conversion(),
+                vec![
+                    Token::ControlSequence("a".to_string()),
+                    Token::Char('x', Category::BeginGroup),
+                    Token::Char('B', Category::Letter),
+                    Token::Char('y', Category::EndGroup),
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn it_uses_the_current_case_codes() {
+        with_parser(&[r"\lccode`A=`z \lowercase{AB}%"], |parser| {
+            parser.parse_assignment();
+
+            assert_eq!(
+                parser.expand_case_conversion(),
+                vec![
+                    Token::Char('z', Category::Letter),
+                    Token::Char('b', Category::Letter),
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn it_restores_case_codes_at_the_end_of_groups() {
+        with_parser(
+            &[r"\uccode`a=`z \uppercase{abc}%", r"\uppercase{abc}%"],
+            |parser| {
+                parser.state.push_state();
+                parser.parse_assignment();
+                assert_eq!(
+                    parser.expand_case_conversion(),
+                    vec![
+                        Token::Char('z', Category::Letter),
+                        Token::Char('B', Category::Letter),
+                        Token::Char('C', Category::Letter),
+                    ]
+                );
+                parser.state.pop_state();
+
+                assert_eq!(
+                    parser.expand_case_conversion(),
+                    vec![
+                        Token::Char('A', Category::Letter),
+                        Token::Char('B', Category::Letter),
+                        Token::Char('C', Category::Letter),
+                    ]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_expands_the_converted_tokens_afterwards() {
+        with_parser(&[r"\def\a{b}%", r"\uppercase{\a a}%"], |parser| {
+            parser.parse_assignment();
+
+            assert_eq!(
+                parser.lex_expanded_token(),
+                Some(Token::Char('b', Category::Letter))
+            );
+            assert_eq!(
+                parser.lex_expanded_token(),
+                Some(Token::Char('A', Category::Letter))
+            );
+            assert_eq!(parser.lex_expanded_token(), None);
+        });
+    }
+}
@@ -1,14 +1,44 @@
 use crate::category::Category;
+use crate::parser::mode::Mode;
 use crate::parser::Parser;
+use crate::primitive::Primitive;
 use crate::token::Token;
 
+/// The primitives that start, separate, and end conditionals.
+#[cfg(test)]
+pub const HANDLED_PRIMITIVES: &[Primitive] = &[
+    Primitive::IfTrue,
+    Primitive::IfFalse,
+    Primitive::IfNum,
+    Primitive::IfDim,
+    Primitive::IfX,
+    Primitive::IfOdd,
+    Primitive::IfCase,
+    Primitive::IfCat,
+    Primitive::IfInner,
+    Primitive::IfVMode,
+    Primitive::IfHMode,
+    Primitive::IfMMode,
+    Primitive::IfEOF,
+    Primitive::Else,
+    Primitive::Or,
+    Primitive::Fi,
+];
+
 enum Relation {
     GreaterThan,
     EqualTo,
     LessThan,
 }
 
-fn check_relation(rel: Relation, left: i32, right: i32) -> bool {
+// The token that ended a skipped branch of a conditional.
+enum BranchEnd {
+    Fi,
+    Else,
+    Or,
+}
+
+fn check_relation<T: PartialOrd>(rel: Relation, left: T, right: T) -> bool {
     match rel {
         Relation::GreaterThan => left > right,
         Relation::EqualTo => left == right,
@@ -18,41 +48,55 @@ fn check_relation(rel: Relation, left: i32, right: i32) -> bool {
 
 impl<'a> Parser<'a> {
     fn is_conditional_start(&mut self, token: &Token) -> bool {
-        self.state.is_token_equal_to_prim(token, "iftrue")
-            || self.state.is_token_equal_to_prim(token, "iffalse")
-            || self.state.is_token_equal_to_prim(token, "ifnum")
+        self.state.is_token_equal_to_prim(token, Primitive::IfTrue)
+            || self.state.is_token_equal_to_prim(token, Primitive::IfFalse)
+            || self.state.is_token_equal_to_prim(token, Primitive::IfNum)
+            || self.state.is_token_equal_to_prim(token, Primitive::IfDim)
+            || self.state.is_token_equal_to_prim(token, Primitive::IfX)
+            || self.state.is_token_equal_to_prim(token, Primitive::IfOdd)
+            || self.state.is_token_equal_to_prim(token, Primitive::IfCase)
+            || self.state.is_token_equal_to_prim(token, Primitive::IfCat)
+            || self.state.is_token_equal_to_prim(token, Primitive::IfInner)
+            || self.state.is_token_equal_to_prim(token, Primitive::IfVMode)
+            || self.state.is_token_equal_to_prim(token, Primitive::IfHMode)
+            || self.state.is_token_equal_to_prim(token, Primitive::IfMMode)
+            || self.state.is_token_equal_to_prim(token, Primitive::IfEOF)
     }
 
     pub fn is_conditional_head(&mut self) -> bool {
         match self.peek_unexpanded_token() {
             Some(token) => {
                 self.is_conditional_start(&token)
-                    || self.state.is_token_equal_to_prim(&token, "else")
-                    || self.state.is_token_equal_to_prim(&token, "fi")
+                    || self
+                        .state
+                        .is_token_equal_to_prim(&token, Primitive::Else)
+                    || self.state.is_token_equal_to_prim(&token, Primitive::Or)
+                    || self.state.is_token_equal_to_prim(&token, Primitive::Fi)
             }
             _ => false,
         }
     }
 
-    // Skips tokens until a \fi or \else is parsed. Returns true if the token
-    // we found is \else, false if it is \fi.
-    fn skip_to_fi_or_else(&mut self) -> bool {
-        let mut ends_with_else = false;
+    // Skips tokens until a \fi, \else, or \or is parsed at the current
+    // level of conditional nesting, and returns which one we found.
+    fn skip_to_end_of_branch(&mut self) -> BranchEnd {
         loop {
             let token = self.lex_unexpanded_token().unwrap();
             if self.is_conditional_start(&token) {
                 // If we see a conditional start while we're skipping, we need
                 // to just skip to the end of that inner conditional before we
-                // continue looking for the outer \fi.
+                // continue looking for the outer \fi. This also skips any
+                // \or tokens which belong to inner \ifcases.
                 self.skip_to_fi();
-            } else if self.state.is_token_equal_to_prim(&token, "fi") {
-                break;
-            } else if self.state.is_token_equal_to_prim(&token, "else") {
-                ends_with_else = true;
-                break;
+            } else if self.state.is_token_equal_to_prim(&token, Primitive::Fi) {
+                return BranchEnd::Fi;
+            } else if self.state.is_token_equal_to_prim(&token, Primitive::Else)
+            {
+                return BranchEnd::Else;
+            } else if self.state.is_token_equal_to_prim(&token, Primitive::Or) {
+                return BranchEnd::Or;
             }
         }
-        ends_with_else
     }
 
     // Skips tokens until a \fi is found.
@@ -64,7 +108,7 @@ impl<'a> Parser<'a> {
                 // to just skip to the end of that inner conditional before we
                 // continue looking for the outer \fi.
                 self.skip_to_fi();
-            } else if self.state.is_token_equal_to_prim(&token, "fi") {
+            } else if self.state.is_token_equal_to_prim(&token, Primitive::Fi) {
                 break;
             }
         }
@@ -75,11 +119,52 @@ impl<'a> Parser<'a> {
     }
 
     fn handle_false(&mut self) {
-        if self.skip_to_fi_or_else() {
-            // If we skipped all the way to a \fi, we don't add to our depth of
-            // conditionals because we already exited this one. If we only
+        match self.skip_to_end_of_branch() {
+            // If we skipped all the way to a \fi, we don't add to our depth
+            // of conditionals because we already exited this one. If we only
             // skipped to a \else, we are now inside a conditional.
-            self.conditional_depth += 1;
+            BranchEnd::Fi => {}
+            BranchEnd::Else => self.conditional_depth += 1,
+            BranchEnd::Or => panic!("Extra \\or"),
+        }
+    }
+
+    // Handles the body of an \ifcase, which is made up of branches separated
+    // by \or, with an optional \else branch at the end which is used when
+    // none of the other branches are chosen.
+    fn handle_case(&mut self, case_number: i32) {
+        let mut remaining = case_number;
+        loop {
+            if remaining == 0 {
+                self.conditional_depth += 1;
+                break;
+            }
+
+            match self.skip_to_end_of_branch() {
+                BranchEnd::Fi => break,
+                BranchEnd::Else => {
+                    self.conditional_depth += 1;
+                    break;
+                }
+                // Negative case numbers never match a branch, so we just keep
+                // skipping until we find the \else or \fi.
+                BranchEnd::Or => {
+                    if remaining > 0 {
+                        remaining -= 1;
+                    }
+                }
+            }
+        }
+    }
+
+    // Returns the category of a token for \ifcat. Control sequences which
+    // have been \let to characters use the category of the character, and
+    // all other control sequences have no category (which TeX represents as
+    // category 16).
+    fn get_token_category(&mut self, token: Token) -> Option<Category> {
+        match self.replace_renamed_token(Some(token)) {
+            Some(Token::Char(_, cat)) => Some(cat),
+            _ => None,
         }
     }
 
@@ -97,26 +182,36 @@ impl<'a> Parser<'a> {
     pub fn expand_conditional(&mut self) {
         let token = self.lex_unexpanded_token().unwrap();
 
-        if self.state.is_token_equal_to_prim(&token, "fi") {
+        if self.state.is_token_equal_to_prim(&token, Primitive::Fi) {
             if self.conditional_depth == 0 {
                 panic!("Extra \\fi");
             }
             self.conditional_depth -= 1;
-        } else if self.state.is_token_equal_to_prim(&token, "else") {
+        } else if self.state.is_token_equal_to_prim(&token, Primitive::Else)
+            || self.state.is_token_equal_to_prim(&token, Primitive::Or)
+        {
             if self.conditional_depth == 0 {
-                panic!("Extra \\else");
+                if self.state.is_token_equal_to_prim(&token, Primitive::Else) {
+                    panic!("Extra \\else");
+                } else {
+                    panic!("Extra \\or");
+                }
             }
             self.conditional_depth -= 1;
-            // When we encounter an \else, we know that we're in a 'true'
-            // conditional because in a 'false' conditional, we always already
-            // parse the \else token in skip_to_fi_or_else(). Thus, we just
-            // need to skip tokens until we see a \fi.
+            // When we encounter an \else or \or, we know that we're in a
+            // 'true' conditional (or a chosen \ifcase branch) because in a
+            // 'false' conditional, we always already parse the \else token in
+            // skip_to_end_of_branch(). Thus, we just need to skip tokens until
+            // we see a \fi.
             self.skip_to_fi();
-        } else if self.state.is_token_equal_to_prim(&token, "iftrue") {
+        } else if self.state.is_token_equal_to_prim(&token, Primitive::IfTrue) {
             self.handle_true();
-        } else if self.state.is_token_equal_to_prim(&token, "iffalse") {
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::IfFalse)
+        {
             self.handle_false();
-        } else if self.state.is_token_equal_to_prim(&token, "ifnum") {
+        } else if self.state.is_token_equal_to_prim(&token, Primitive::IfNum) {
             let num1 = self.parse_number();
             let relation = self.parse_relation();
             let num2 = self.parse_number();
@@ -126,6 +221,89 @@ impl<'a> Parser<'a> {
             } else {
                 self.handle_false();
             }
+        } else if self.state.is_token_equal_to_prim(&token, Primitive::IfDim) {
+            let dimen1 = self.parse_dimen();
+            let relation = self.parse_relation();
+            let dimen2 = self.parse_dimen();
+
+            if check_relation(relation, dimen1, dimen2) {
+                self.handle_true();
+            } else {
+                self.handle_false();
+            }
+        } else if self.state.is_token_equal_to_prim(&token, Primitive::IfOdd) {
+            let num = self.parse_number();
+
+            if num % 2 != 0 {
+                self.handle_true();
+            } else {
+                self.handle_false();
+            }
+        } else if self.state.is_token_equal_to_prim(&token, Primitive::IfCase) {
+            let case_number = self.parse_number();
+            self.handle_case(case_number);
+        } else if self.state.is_token_equal_to_prim(&token, Primitive::IfCat) {
+            let left = self.lex_expanded_token().unwrap();
+            let right = self.lex_expanded_token().unwrap();
+
+            if self.get_token_category(left) == self.get_token_category(right) {
+                self.handle_true();
+            } else {
+                self.handle_false();
+            }
+        } else if self.state.is_token_equal_to_prim(&token, Primitive::IfX) {
+            let left = self.lex_unexpanded_token().unwrap();
+            let right = self.lex_unexpanded_token().unwrap();
+
+            if self.state.is_meaning_equal(&left, &right) {
+                self.handle_true();
+            } else {
+                self.handle_false();
+            }
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::IfInner)
+        {
+            if self.get_mode().is_inner() {
+                self.handle_true();
+            } else {
+                self.handle_false();
+            }
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::IfVMode)
+        {
+            if self.get_mode().is_vertical() {
+                self.handle_true();
+            } else {
+                self.handle_false();
+            }
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::IfHMode)
+        {
+            if self.get_mode().is_horizontal() {
+                self.handle_true();
+            } else {
+                self.handle_false();
+            }
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::IfMMode)
+        {
+            if self.get_mode() == Mode::Math {
+                self.handle_true();
+            } else {
+                self.handle_false();
+            }
+        } else if self.state.is_token_equal_to_prim(&token, Primitive::IfEOF) {
+            let stream = self.parse_4bit_number();
+
+            if self.is_input_stream_at_end(stream) {
+                self.handle_true();
+            } else {
+                self.handle_false();
+            }
         } else {
             panic!("unimplemented");
         }
@@ -429,4 +607,331 @@ mod tests {
             },
         );
     }
+
+    fn assert_conditional_result(lines: &[&str], expected: bool) {
+        with_parser(lines, |parser| {
+            while parser.is_assignment_head() {
+                parser.parse_assignment();
+            }
+
+            let expected_char = if expected { 'T' } else { 'F' };
+            assert_eq!(
+                parser.lex_expanded_token(),
+                Some(Token::Char(expected_char, Category::Letter))
+            );
+            assert_eq!(parser.lex_expanded_token(), None);
+        });
+    }
+
+    #[test]
+    fn it_parses_ifdim() {
+        assert_conditional_result(&[r"\ifdim 1pt<2pt T\else F\fi%"], true);
+        assert_conditional_result(&[r"\ifdim 2pt<1pt T\else F\fi%"], false);
+        assert_conditional_result(&[r"\ifdim 2pt>1pt T\else F\fi%"], true);
+        assert_conditional_result(&[r"\ifdim 1pt>2pt T\else F\fi%"], false);
+        assert_conditional_result(&[r"\ifdim 1pt=1pt T\else F\fi%"], true);
+        assert_conditional_result(&[r"\ifdim 1pt=2pt T\else F\fi%"], false);
+        assert_conditional_result(
+            &[r"\ifdim 1in  =  72.27pt  T\else F\fi%"],
+            true,
+        );
+    }
+
+    #[test]
+    fn it_compares_dimens_exactly_in_ifdim() {
+        assert_conditional_result(&[r"\ifdim 65536sp=1pt T\else F\fi%"], true);
+        assert_conditional_result(&[r"\ifdim 65537sp=1pt T\else F\fi%"], false);
+        assert_conditional_result(&[r"\ifdim 65537sp>1pt T\else F\fi%"], true);
+        assert_conditional_result(&[r"\ifdim 65535sp<1pt T\else F\fi%"], true);
+        assert_conditional_result(&[r"\ifdim 65536sp<1pt T\else F\fi%"], false);
+        assert_conditional_result(&[r"\ifdim -1sp<0pt T\else F\fi%"], true);
+    }
+
+    #[test]
+    fn it_parses_dimen_variables_in_ifdim() {
+        // The spaces after the assignments stop the dimens from expanding the
+        // \ifdim while looking for an optional space before they're assigned.
+        assert_conditional_result(
+            &[
+                r"\setbox0=\hbox{}\wd0=3pt \hsize=4pt %",
+                r"\ifdim\wd0<\hsize T\else F\fi%",
+            ],
+            true,
+        );
+        assert_conditional_result(
+            &[
+                r"\setbox0=\hbox{}\wd0=3pt %",
+                r"\ifdim\wd0=3pt T\else F\fi%",
+            ],
+            true,
+        );
+    }
+
+    #[test]
+    fn it_compares_primitives_with_ifx() {
+        assert_conditional_result(&[r"\ifx\relax\relax T\else F\fi%"], true);
+        assert_conditional_result(&[r"\ifx\relax\par T\else F\fi%"], false);
+        assert_conditional_result(
+            &[r"\let\a=\relax \let\b=\relax%", r"\ifx\a\b T\else F\fi%"],
+            true,
+        );
+        assert_conditional_result(
+            &[r"\let\a=\relax%", r"\ifx\a\relax T\else F\fi%"],
+            true,
+        );
+        assert_conditional_result(
+            &[r"\let\a=\relax \let\b=\par%", r"\ifx\a\b T\else F\fi%"],
+            false,
+        );
+    }
+
+    #[test]
+    fn it_compares_undefined_tokens_with_ifx() {
+        assert_conditional_result(
+            &[r"\ifx\undefined\alsoundefined T\else F\fi%"],
+            true,
+        );
+        assert_conditional_result(
+            &[r"\ifx\relax\undefined T\else F\fi%"],
+            false,
+        );
+        assert_conditional_result(
+            &[r"\ifx\undefined\relax T\else F\fi%"],
+            false,
+        );
+    }
+
+    #[test]
+    fn it_compares_macros_with_ifx() {
+        assert_conditional_result(
+            &[r"\def\a{x}\def\b{x}%", r"\ifx\a\b T\else F\fi%"],
+            true,
+        );
+        assert_conditional_result(
+            &[r"\def\a#1{x#1}\def\b#1{x#1}%", r"\ifx\a\b T\else F\fi%"],
+            true,
+        );
+        assert_conditional_result(
+            &[r"\def\a{x}\def\b{y}%", r"\ifx\a\b T\else F\fi%"],
+            false,
+        );
+        assert_conditional_result(
+            &[r"\def\a#1{x}\def\b{x}%", r"\ifx\a\b T\else F\fi%"],
+            false,
+        );
+        assert_conditional_result(
+            &[r"\def\a{x}\let\b=\a%", r"\ifx\a\b T\else F\fi%"],
+            true,
+        );
+    }
+
+    #[test]
+    fn it_does_not_consider_macros_equal_to_their_expansion_with_ifx() {
+        assert_conditional_result(
+            &[r"\def\a{\relax}%", r"\ifx\a\relax T\else F\fi%"],
+            false,
+        );
+        assert_conditional_result(
+            &[r"\def\a{x}%", r"\ifx\a x T\else F\fi%"],
+            false,
+        );
+    }
+
+    #[test]
+    fn it_compares_characters_with_ifx() {
+        assert_conditional_result(&[r"\ifx aaT\else F\fi%"], true);
+        assert_conditional_result(&[r"\ifx abT\else F\fi%"], false);
+        assert_conditional_result(&[r"\ifx a1T\else F\fi%"], false);
+        assert_conditional_result(
+            &[r"\let\a=a%", r"\ifx\a aT\else F\fi%"],
+            true,
+        );
+        assert_conditional_result(
+            &[r"\let\a=a%", r"\ifx\a bT\else F\fi%"],
+            false,
+        );
+    }
+
+    #[test]
+    fn it_compares_active_characters_by_their_definitions_with_ifx() {
+        with_parser(
+            &[
+                r"\ifx @\undefined T\else F\fi%",
+                r"\def @{x}\def\a{x}%",
+                r"\ifx @\a T\else F\fi%",
+                r"\ifx @x T\else F\fi%",
+            ],
+            |parser| {
+                parser.state.set_category(false, '@', Category::Active);
+
+                assert_eq!(
+                    parser.lex_expanded_token(),
+                    Some(Token::Char('T', Category::Letter))
+                );
+
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                assert_eq!(
+                    parser.lex_expanded_token(),
+                    Some(Token::Char('T', Category::Letter))
+                );
+                assert_eq!(
+                    parser.lex_expanded_token(),
+                    Some(Token::Char('F', Category::Letter))
+                );
+                assert_eq!(parser.lex_expanded_token(), None);
+            },
+        );
+    }
+
+    #[test]
+    fn it_compares_characters_by_category_with_ifx() {
+        with_parser(&[r"\ifx a%", r"aT\else F\fi%"], |parser| {
+            // Change the category of `a` after the first one has been lexed.
+            parser.lex_unexpanded_token();
+            let first_a = parser.lex_unexpanded_token();
+            parser.state.set_category(false, 'a', Category::Other);
+            parser.add_upcoming_token(first_a.unwrap());
+            parser
+                .add_upcoming_token(Token::ControlSequence("ifx".to_string()));
+
+            assert_eq!(
+                parser.lex_expanded_token(),
+                Some(Token::Char('F', Category::Letter))
+            );
+            assert_eq!(parser.lex_expanded_token(), None);
+        });
+    }
+
+    #[test]
+    fn it_compares_mathchardefs_with_ifx() {
+        assert_conditional_result(
+            &[
+                r#"\mathchardef\a="1350 \mathchardef\b="1350"#,
+                r"\ifx\a\b T\else F\fi%",
+            ],
+            true,
+        );
+        assert_conditional_result(
+            &[
+                r#"\mathchardef\a="1350 \mathchardef\b="1351"#,
+                r"\ifx\a\b T\else F\fi%",
+            ],
+            false,
+        );
+    }
+
+    #[test]
+    fn it_compares_fonts_with_ifx() {
+        // The \relax stops the font assignments from expanding the \ifx
+        // while looking for an `at` clause.
+        with_parser(
+            &[
+                r"\font\a=cmr10 \font\b=cmr10 \relax%",
+                r"\ifx\a\b T\else F\fi%",
+                r"\ifx\a\relax T\else F\fi%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.lex_expanded_token();
+
+                assert_eq!(
+                    parser.lex_expanded_token(),
+                    Some(Token::Char('T', Category::Letter))
+                );
+                assert_eq!(
+                    parser.lex_expanded_token(),
+                    Some(Token::Char('F', Category::Letter))
+                );
+                assert_eq!(parser.lex_expanded_token(), None);
+            },
+        );
+    }
+
+    #[test]
+    fn it_skips_nested_ifx_in_false_branches() {
+        assert_conditional_result(&[r"\iffalse \ifx aa\fi F\else T\fi%"], true);
+    }
+
+    #[test]
+    fn it_parses_ifodd() {
+        assert_conditional_result(&[r"\ifodd 3 T\else F\fi%"], true);
+        assert_conditional_result(&[r"\ifodd 4 T\else F\fi%"], false);
+        assert_conditional_result(&[r"\ifodd -1 T\else F\fi%"], true);
+        assert_conditional_result(&[r"\ifodd 0 T\else F\fi%"], false);
+        assert_conditional_result(
+            &[r"\count1=7 %", r"\ifodd\count1 T\else F\fi%"],
+            true,
+        );
+    }
+
+    fn assert_case_result(lines: &[&str], expected: char) {
+        with_parser(lines, |parser| {
+            assert_eq!(
+                parser.lex_expanded_token(),
+                Some(Token::Char(expected, Category::Letter))
+            );
+            assert_eq!(parser.lex_expanded_token(), None);
+        });
+    }
+
+    #[test]
+    fn it_parses_ifcase() {
+        assert_case_result(&[r"\ifcase 0 a\or b\or c\else d\fi%"], 'a');
+        assert_case_result(&[r"\ifcase 1 a\or b\or c\else d\fi%"], 'b');
+        assert_case_result(&[r"\ifcase 2 a\or b\or c\else d\fi%"], 'c');
+        assert_case_result(&[r"\ifcase2 a\or b\or c\else d\fi%"], 'c');
+        assert_case_result(&[r"\ifcase 3 a\or b\or c\else d\fi%"], 'd');
+        assert_case_result(&[r"\ifcase -1 a\or b\or c\else d\fi%"], 'd');
+    }
+
+    #[test]
+    fn it_falls_through_out_of_range_ifcase_without_else() {
+        with_parser(&[r"\ifcase 5 a\or b\fi%"], |parser| {
+            assert_eq!(parser.lex_expanded_token(), None);
+        });
+    }
+
+    #[test]
+    fn it_skips_ors_in_nested_conditionals_in_ifcase() {
+        assert_case_result(
+            &[r"\ifcase 1 \ifcase 0 a\or b\fi\or c\or d\fi%"],
+            'c',
+        );
+        assert_case_result(
+            &[r"\ifcase 0 \ifcase 1 a\or b\fi\or c\or d\fi%"],
+            'b',
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Extra \\or")]
+    fn it_fails_on_or_outside_of_ifcase() {
+        with_parser(&[r"\iffalse a\or b\fi%"], |parser| {
+            parser.lex_expanded_token();
+        });
+    }
+
+    #[test]
+    fn it_compares_categories_with_ifcat() {
+        assert_conditional_result(&[r"\ifcat abT\else F\fi%"], true);
+        assert_conditional_result(&[r"\ifcat a1T\else F\fi%"], false);
+        assert_conditional_result(&[r"\ifcat 12T\else F\fi%"], true);
+        assert_conditional_result(&[r"\ifcat \relax\par T\else F\fi%"], true);
+        assert_conditional_result(&[r"\ifcat \relax aT\else F\fi%"], false);
+    }
+
+    #[test]
+    fn it_uses_categories_of_let_characters_and_expansions_with_ifcat() {
+        assert_conditional_result(
+            &[r"\let\x=a%", r"\ifcat \x bT\else F\fi%"],
+            true,
+        );
+        assert_conditional_result(
+            &[r"\def\y{1}%", r"\ifcat \y 2T\else F\fi%"],
+            true,
+        );
+    }
 }
@@ -3,8 +3,14 @@ use crate::dimension::{Dimen, FilDimen, FilKind, SpringDimen, Unit};
 use crate::parser::number::{is_token_digit, token_digit_value};
 use crate::parser::primitives::token_equals_keyword_char;
 use crate::parser::Parser;
+use crate::primitive::Primitive;
 use crate::token::Token;
 
+/// The primitives for internal dimensions that aren't variables.
+#[cfg(test)]
+pub const HANDLED_PRIMITIVES: &[Primitive] =
+    &[Primitive::FontDimen, Primitive::PrevDepth];
+
 fn is_decimal_point_token(token: &Token) -> bool {
     match token {
         Token::Char(ch, Category::Other) => *ch == ',' || *ch == '.',
@@ -59,10 +65,20 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_unsigned_dimen(&mut self, allow_fil: bool) -> SpringDimen {
-        self.parse_normal_dimen(allow_fil)
+        // Internal glue can be used as a dimen, in which case only its
+        // natural space is used. This is what TeX calls a <coerced dimen>.
+        if self.is_internal_glue_head() {
+            SpringDimen::Dimen(self.parse_internal_glue().space)
+        } else {
+            self.parse_normal_dimen(allow_fil)
+        }
     }
 
     fn parse_normal_dimen(&mut self, allow_fil: bool) -> SpringDimen {
+        if self.is_internal_dimen_head() {
+            return SpringDimen::Dimen(self.parse_internal_dimen());
+        }
+
         let factor = self.parse_factor();
         let (unit_factor, unit_or_fil) = self.parse_unit_of_measure(allow_fil);
 
@@ -194,6 +210,18 @@ impl<'a> Parser<'a> {
         if self.is_internal_integer_head() {
             let value = self.parse_internal_integer();
             (value as f64, UnitOrFil::Unit(Unit::ScaledPoint))
+        } else if self.is_internal_dimen_head() {
+            let value = self.parse_internal_dimen();
+            (
+                value.as_scaled_points() as f64,
+                UnitOrFil::Unit(Unit::ScaledPoint),
+            )
+        } else if self.is_internal_glue_head() {
+            let value = self.parse_internal_glue().space;
+            (
+                value.as_scaled_points() as f64,
+                UnitOrFil::Unit(Unit::ScaledPoint),
+            )
         } else {
             match self.parse_unit(allow_fil) {
                 ParsedUnit::PhysicalUnit(is_true, unit) => {
@@ -322,12 +350,62 @@ impl<'a> Parser<'a> {
 
     pub fn is_internal_dimen_head(&mut self) -> bool {
         self.is_dimen_variable_head()
+            || self.is_next_expanded_token_in_set_of_primitives(&[
+                Primitive::FontDimen,
+                Primitive::PrevDepth,
+            ])
+    }
+
+    fn parse_font_dimension(&mut self) -> Dimen {
+        let tok = self.lex_expanded_token().unwrap();
+        if !self
+            .state
+            .is_token_equal_to_prim(&tok, Primitive::FontDimen)
+        {
+            panic!("Invalid font dimension head: {:?}", tok);
+        }
+
+        let number = self.parse_number();
+        let font = self.parse_font();
+
+        // \nullfont has no metrics, but TeX gives it the usual seven
+        // parameters, all zero.
+        if font.is_null_font() {
+            if !(1..=7).contains(&number) {
+                panic!("Font nullfont has only 7 fontdimen parameters");
+            }
+            return Dimen::zero();
+        }
+
+        self.state
+            .with_metrics_for_font(&font, |metrics| {
+                if number <= 0
+                    || number as usize > metrics.get_num_font_dimensions()
+                {
+                    panic!(
+                        "Font {} has only {} fontdimen parameters",
+                        font.font_name,
+                        metrics.get_num_font_dimensions()
+                    );
+                }
+                metrics.get_font_dimension(number as usize)
+            })
+            .unwrap()
     }
 
     pub fn parse_internal_dimen(&mut self) -> Dimen {
         if self.is_dimen_variable_head() {
             let variable = self.parse_dimen_variable();
             variable.get(self.state)
+        } else if self.is_next_expanded_token_in_set_of_primitives(&[
+            Primitive::FontDimen,
+        ]) {
+            self.parse_font_dimension()
+        } else if self.is_next_expanded_token_in_set_of_primitives(&[
+            Primitive::PrevDepth,
+        ]) {
+            self.lex_expanded_token();
+            self.get_prev_depth()
         } else {
             panic!("unimplemented");
         }
@@ -500,7 +578,7 @@ mod tests {
     #[test]
     fn it_parses_internal_dimens() {
         with_parser(&[r"\setbox0=\hbox{a}%", r"\wd0%", r"\ht0"], |parser| {
-            parser.parse_assignment(None);
+            parser.parse_assignment();
 
             let metrics = parser
                 .state
@@ -517,4 +595,65 @@ mod tests {
             assert_eq!(parser.parse_internal_dimen(), metrics.get_height('a'));
         });
     }
+
+    #[test]
+    fn it_parses_internal_dimens_in_dimens() {
+        with_parser(
+            &[
+                r"\hsize=10pt%",
+                r"\hsize%",
+                r"-\hsize%",
+                r"2\hsize%",
+                r".5\hsize%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+
+                assert_eq!(
+                    parser.parse_dimen(),
+                    Dimen::from_unit(10.0, Unit::Point)
+                );
+                assert_eq!(
+                    parser.parse_dimen(),
+                    Dimen::from_unit(-10.0, Unit::Point)
+                );
+                assert_eq!(
+                    parser.parse_dimen(),
+                    Dimen::from_unit(20.0, Unit::Point)
+                );
+                assert_eq!(
+                    parser.parse_dimen(),
+                    Dimen::from_unit(5.0, Unit::Point)
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_coerces_internal_glue_to_dimens() {
+        with_parser(
+            &[
+                r"\skip1=3pt plus 2fil minus 1pt%",
+                r"\skip1 %",
+                r"-\skip1 %",
+                r"2\skip1 %",
+            ],
+            |parser| {
+                parser.parse_assignment();
+
+                assert_eq!(
+                    parser.parse_dimen(),
+                    Dimen::from_unit(3.0, Unit::Point)
+                );
+                assert_eq!(
+                    parser.parse_dimen(),
+                    Dimen::from_unit(-3.0, Unit::Point)
+                );
+                assert_eq!(
+                    parser.parse_dimen(),
+                    Dimen::from_unit(6.0, Unit::Point)
+                );
+            },
+        );
+    }
 }
@@ -0,0 +1,162 @@
+use crate::category::Category;
+use crate::list::HorizontalListElem;
+use crate::parser::Parser;
+use crate::primitive::Primitive;
+use crate::token::Token;
+
+/// The primitives that make discretionaries.
+#[cfg(test)]
+pub const HANDLED_PRIMITIVES: &[Primitive] =
+    &[Primitive::Discretionary, Primitive::DiscretionaryHyphen];
+
+impl<'a> Parser<'a> {
+    pub fn is_discretionary_head(&mut self) -> bool {
+        self.is_next_expanded_token_in_set_of_primitives(&[
+            Primitive::Discretionary,
+            Primitive::DiscretionaryHyphen,
+        ])
+    }
+
+    // Parses one of the three groups after a \discretionary as a restricted
+    // horizontal list.
+    fn parse_discretionary_list(&mut self) -> Vec<HorizontalListElem> {
+        if !self.parse_left_brace_expanded() {
+            panic!("{}", "Expected { when parsing discretionary");
+        }
+
+        self.state.push_state();
+        let list = self.parse_horizontal_list(true, false);
+
+        match self.lex_expanded_token() {
+            Some(Token::Char(_, Category::EndGroup)) => (),
+            _ => panic!("{}", "Expected } when parsing discretionary"),
+        }
+
+        self.end_group();
+
+        list
+    }
+
+    /// Parses a \discretionary{pre}{post}{replace}, or a \-, which is a
+    /// discretionary hyphen.
+    pub fn parse_discretionary(&mut self) -> HorizontalListElem {
+        let tok = self.lex_expanded_token().unwrap();
+
+        if self
+            .state
+            .is_token_equal_to_prim(&tok, Primitive::Discretionary)
+        {
+            let pre = self.parse_discretionary_list();
+            let post = self.parse_discretionary_list();
+            let replace = self.parse_discretionary_list();
+
+            HorizontalListElem::Discretionary { pre, post, replace }
+        } else if self
+            .state
+            .is_token_equal_to_prim(&tok, Primitive::DiscretionaryHyphen)
+        {
+            // TODO: This should use the \hyphenchar of the current font once
+            // that exists. For now, we use the `-` that plain TeX sets as the
+            // \defaulthyphenchar.
+            HorizontalListElem::Discretionary {
+                pre: vec![HorizontalListElem::Char {
+                    chr: '-',
+                    font: self.state.get_current_font(),
+                }],
+                post: vec![],
+                replace: vec![],
+            }
+        } else {
+            panic!("Invalid discretionary head: {:?}", tok);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dimension::{Dimen, Unit};
+    use crate::font::Font;
+    use crate::testing::with_parser;
+
+    fn cmr10_char(chr: char) -> HorizontalListElem {
+        HorizontalListElem::Char {
+            chr,
+            font: Font {
+                font_name: "cmr10".to_string(),
+                scale: Dimen::from_unit(10.0, Unit::Point),
+            },
+        }
+    }
+
+    #[test]
+    fn it_parses_discretionaries() {
+        with_parser(&[r"\discretionary{a}{b}{\char100}%"], |parser| {
+            assert!(parser.is_discretionary_head());
+            assert_eq!(
+                parser.parse_discretionary(),
+                HorizontalListElem::Discretionary {
+                    pre: vec![cmr10_char('a')],
+                    post: vec![cmr10_char('b')],
+                    replace: vec![cmr10_char('d')],
+                }
+            );
+        });
+    }
+
+    #[test]
+    fn it_skips_filler_before_discretionary_lists() {
+        with_parser(
+            &[r"\discretionary \relax{a} {b}\relax \relax{}%"],
+            |parser| {
+                assert_eq!(
+                    parser.parse_discretionary(),
+                    HorizontalListElem::Discretionary {
+                        pre: vec![cmr10_char('a')],
+                        post: vec![cmr10_char('b')],
+                        replace: vec![],
+                    }
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_parses_discretionary_hyphens() {
+        with_parser(&[r"\-%"], |parser| {
+            assert!(parser.is_discretionary_head());
+            assert_eq!(
+                parser.parse_discretionary(),
+                HorizontalListElem::Discretionary {
+                    pre: vec![cmr10_char('-')],
+                    post: vec![],
+                    replace: vec![],
+                }
+            );
+        });
+    }
+
+    #[test]
+    fn it_uses_replacement_text_in_unbroken_discretionaries() {
+        with_parser(&[r"\hbox{a\discretionary{-}{}{x}b}%"], |parser| {
+            let hbox = parser.parse_box().unwrap();
+            let metrics = parser
+                .state
+                .get_metrics_for_font(&Font {
+                    font_name: "cmr10".to_string(),
+                    scale: Dimen::from_unit(10.0, Unit::Point),
+                })
+                .unwrap();
+
+            assert_eq!(
+                *hbox.width(),
+                metrics.get_width('a')
+                    + metrics.get_width('x')
+                    + metrics.get_width('b')
+            );
+            assert_eq!(*hbox.height(), metrics.get_height('b'));
+            assert_eq!(hbox.to_chars(), vec!['a', 'x', 'b']);
+        });
+    }
+}
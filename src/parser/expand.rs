@@ -1,49 +1,286 @@
+use std::fs;
+use std::path::Path;
+
+use crate::limits::ResourceLimit;
 use crate::parser::Parser;
-use crate::token::Token;
+use crate::paths::get_path_to_input_file;
+use crate::primitive::Primitive;
+use crate::state::IntegerParameter;
+use crate::token::{escape_name, stringify_characters, tokenize_other, Token};
+
+/// The expandable primitives that aren't conditionals or macros.
+#[cfg(test)]
+pub const HANDLED_PRIMITIVES: &[Primitive] = &[
+    Primitive::NoExpand,
+    Primitive::ExpandAfter,
+    Primitive::Input,
+    Primitive::EndInput,
+    Primitive::CsName,
+    Primitive::EndCsName,
+    Primitive::String,
+    Primitive::Meaning,
+];
 
 impl<'a> Parser<'a> {
     pub fn lex_expanded_token(&mut self) -> Option<Token> {
+        if self.is_noexpand_head() {
+            // The token after a \noexpand is returned as-is, even if it is
+            // expandable.
+            self.lex_unexpanded_token();
+            self.lex_unexpanded_token()
+        } else if self.expand_next_token() {
+            self.lex_expanded_token()
+        } else {
+            self.lex_unexpanded_token()
+        }
+    }
+
+    pub fn is_noexpand_head(&mut self) -> bool {
+        match self.peek_unexpanded_token() {
+            Some(token) => self
+                .state
+                .is_token_equal_to_prim(&token, Primitive::NoExpand),
+            _ => false,
+        }
+    }
+
+    // Expands the next token one level, if it is expandable, by replacing it
+    // (and its arguments) in the input with its expansion. Returns whether an
+    // expansion happened.
+    pub fn expand_next_token(&mut self) -> bool {
         if self.is_conditional_head() {
             // Handle conditionals, like \ifnum
             self.expand_conditional();
-            return self.lex_expanded_token();
         } else if self.is_print_head() {
             // Handle printing, like \number\count1
             let replacement = self.expand_print();
             self.add_upcoming_tokens(replacement);
-            return self.lex_expanded_token();
+        } else if self.is_case_conversion_head() {
+            // Handle case conversion, like \uppercase{abc}
+            let replacement = self.expand_case_conversion();
+            self.add_upcoming_tokens(replacement);
+        } else if self.is_csname_head() {
+            // Handle building control sequences, like \csname abc\endcsname
+            let token = self.expand_csname();
+            self.add_upcoming_token(token);
+        } else if self.is_string_or_meaning_head() {
+            // Handle converting tokens to strings, like \string\foo
+            let replacement = self.expand_string_or_meaning();
+            self.add_upcoming_tokens(replacement);
+        } else if self.is_expandafter_head() {
+            // Handle reordering expansion, like \expandafter\a\b
+            self.expand_expandafter();
+        } else if self.is_input_head() {
+            // Handle reading from other files, like \input macros
+            self.expand_input();
+        } else {
+            let token = match self.peek_unexpanded_token() {
+                Some(token) => token,
+                None => return false,
+            };
+
+            // Handle macro expansion
+            if let Some(makro) = self.state.get_macro(&token) {
+                self.state.use_resource(ResourceLimit::ExpansionSteps, 1);
+                self.lex_unexpanded_token();
+                let replacement_map = self.parse_replacement_map(&makro);
+                let replacement = makro.get_replacement(&replacement_map);
+                self.add_upcoming_tokens(replacement);
+            } else {
+                return false;
+            }
         }
 
-        match self.lex_unexpanded_token() {
-            None => None,
+        true
+    }
+
+    fn is_expandafter_head(&mut self) -> bool {
+        match self.peek_unexpanded_token() {
+            Some(token) => self
+                .state
+                .is_token_equal_to_prim(&token, Primitive::ExpandAfter),
+            _ => false,
+        }
+    }
+
+    /// Parses an \expandafter, and then expands the token after the next
+    /// token once, leaving the next token in front of that expansion. If the
+    /// token after the next one isn't expandable, it is left alone.
+    fn expand_expandafter(&mut self) {
+        let head = self.lex_unexpanded_token().unwrap();
+        if !self
+            .state
+            .is_token_equal_to_prim(&head, Primitive::ExpandAfter)
+        {
+            panic!("Invalid expandafter head: {:?}", head);
+        }
+
+        let token = match self.lex_unexpanded_token() {
+            Some(token) => token,
+            None => panic!(r"EOF found while parsing \expandafter"),
+        };
+
+        self.expand_next_token();
+        self.add_upcoming_token(token);
+    }
+
+    fn is_input_head(&mut self) -> bool {
+        match self.peek_unexpanded_token() {
             Some(token) => {
-                // Handle macro expansion
-                if let Some(makro) = self.state.get_macro(&token) {
-                    let replacement_map = self.parse_replacement_map(&makro);
-                    let replacement = makro.get_replacement(&replacement_map);
-                    self.add_upcoming_tokens(replacement);
-                    self.lex_expanded_token()
-                } else {
-                    // Passthrough anything else
-                    Some(token)
-                }
+                self.state.is_token_equal_to_prim(&token, Primitive::Input)
+                    || self
+                        .state
+                        .is_token_equal_to_prim(&token, Primitive::EndInput)
             }
+            _ => false,
         }
     }
 
-    pub fn peek_expanded_token(&mut self) -> Option<Token> {
-        match self.lex_expanded_token() {
+    /// Parses an \input and the file name after it, and switches to reading
+    /// from that file. If the file name doesn't have an extension, ".tex" is
+    /// added, and the file is looked for in the current directory and then
+    /// where TeX's input files are installed. Once the file runs out, we go
+    /// back to reading from where we were. An \endinput instead stops reading
+    /// from the current file at the end of the current line.
+    fn expand_input(&mut self) {
+        let head = self.lex_unexpanded_token().unwrap();
+        if self
+            .state
+            .is_token_equal_to_prim(&head, Primitive::EndInput)
+        {
+            self.lexer.end_source_after_line();
+            return;
+        }
+
+        let mut file_name = self.parse_file_name();
+        // A space ending the file name is part of the \input.
+        self.parse_optional_space_expanded();
+
+        if Path::new(&file_name).extension().is_none() {
+            file_name.push_str(".tex");
+        }
+
+        self.check_can_open_another_file();
+        let contents = get_path_to_input_file(&file_name)
+            .and_then(|path| fs::read_to_string(path).ok())
+            .unwrap_or_else(|| panic!("I can't find file `{}'", file_name));
+        let lines: Vec<&str> = contents.lines().collect();
+
+        // Any tokens that we've already read ahead come after the file.
+        let upcoming_tokens = std::mem::take(&mut self.upcoming_tokens);
+        self.lexer.push_source(&file_name, &lines, upcoming_tokens);
+    }
+
+    fn is_csname_head(&mut self) -> bool {
+        match self.peek_unexpanded_token() {
             Some(token) => {
-                self.add_upcoming_token(token.clone());
+                self.state.is_token_equal_to_prim(&token, Primitive::CsName)
+            }
+            _ => false,
+        }
+    }
+
+    /// Parses a \csname, and expands the tokens up until the matching
+    /// \endcsname. The characters in the expansion are used as the name of
+    /// the resulting control sequence.
+    fn expand_csname(&mut self) -> Token {
+        let head = self.lex_unexpanded_token().unwrap();
+        if !self.state.is_token_equal_to_prim(&head, Primitive::CsName) {
+            panic!("Invalid csname head: {:?}", head);
+        }
+
+        let mut chars = Vec::new();
+        loop {
+            match self.lex_expanded_token() {
+                Some(token @ Token::Char(_, _)) => chars.push(token),
                 Some(token)
+                    if self.state.is_token_equal_to_prim(
+                        &token,
+                        Primitive::EndCsName,
+                    ) =>
+                {
+                    break
+                }
+                Some(_) => panic!(r"Missing \endcsname inserted"),
+                None => panic!(r"EOF found while parsing \csname"),
             }
-            None => None,
+        }
+
+        let token = Token::ControlSequence(stringify_characters(&chars));
+        self.state.set_relax_if_undefined(false, &token);
+        token
+    }
+
+    fn is_string_or_meaning_head(&mut self) -> bool {
+        match self.peek_unexpanded_token() {
+            Some(token) => {
+                self.state.is_token_equal_to_prim(&token, Primitive::String)
+                    || self
+                        .state
+                        .is_token_equal_to_prim(&token, Primitive::Meaning)
+            }
+            _ => false,
+        }
+    }
+
+    /// Parses a \string or \meaning and the unexpanded token after it, and
+    /// returns the characters of the token's name or of a description of its
+    /// meaning.
+    fn expand_string_or_meaning(&mut self) -> Vec<Token> {
+        let head = self.lex_unexpanded_token().unwrap();
+        let token = match self.lex_unexpanded_token() {
+            Some(token) => token,
+            None => panic!("EOF found while parsing {:?}", head),
+        };
+
+        if self.state.is_token_equal_to_prim(&head, Primitive::String) {
+            // Unlike when printing token lists, no space is added after the
+            // names of control words.
+            let escapechar = self
+                .state
+                .get_integer_parameter(&IntegerParameter::EscapeChar);
+            match token {
+                Token::ControlSequence(name) if name.is_empty() => {
+                    tokenize_other(&format!(
+                        "{}{}",
+                        escape_name("csname", escapechar),
+                        escape_name("endcsname", escapechar)
+                    ))
+                }
+                Token::ControlSequence(name) => {
+                    tokenize_other(&escape_name(&name, escapechar))
+                }
+                Token::Char(ch, _) => tokenize_other(&ch.to_string()),
+            }
+        } else if self.state.is_token_equal_to_prim(&head, Primitive::Meaning) {
+            tokenize_other(&self.state.get_meaning_string(&token))
+        } else {
+            panic!("Invalid string head: {:?}", head);
+        }
+    }
+
+    pub fn peek_expanded_token(&mut self) -> Option<Token> {
+        if self.is_noexpand_head() {
+            // We leave the \noexpand in the input, so that the token after it
+            // still isn't expanded when it is lexed later.
+            let noexpand = self.lex_unexpanded_token().unwrap();
+            let token = self.peek_unexpanded_token();
+            self.add_upcoming_token(noexpand);
+            token
+        } else if self.expand_next_token() {
+            self.peek_expanded_token()
+        } else {
+            self.peek_unexpanded_token()
         }
     }
 
     pub fn lex_unexpanded_token(&mut self) -> Option<Token> {
         if self.upcoming_tokens.is_empty() {
-            self.lexer.lex_token()
+            let token = self.lexer.lex_token();
+            if token.is_some() {
+                self.state.with_stats(|stats| stats.tokens_processed += 1);
+            }
+            token
         } else {
             self.upcoming_tokens.pop()
         }
@@ -91,11 +328,16 @@ impl<'a> Parser<'a> {
 mod tests {
     use super::*;
 
+    use std::cell::RefCell;
     use std::rc::Rc;
 
     use crate::category::Category;
+    use crate::limits::{ResourceLimitExceeded, ResourceLimits};
+    use crate::list::HorizontalListElem;
     use crate::makro::{Macro, MacroListElem};
-    use crate::testing::with_parser;
+    use crate::testing::{
+        get_resource_limit_exceeded, with_parser, SharedWriter,
+    };
 
     #[test]
     fn it_lexes_tokens() {
@@ -211,7 +453,7 @@ mod tests {
     #[test]
     fn it_prints_numbers() {
         with_parser(&["\\count1=-100 %", "\\number\\count1%"], |parser| {
-            parser.parse_assignment(None);
+            parser.parse_assignment();
             assert_eq!(
                 parser.lex_expanded_token(),
                 Some(Token::Char('-', Category::Other))
@@ -230,4 +472,415 @@ mod tests {
             );
         });
     }
+
+    #[test]
+    fn it_expands_csnames_built_from_macros() {
+        with_parser(
+            &[
+                r"\def\name{fo}%",
+                r"\def\foo{bar}%",
+                r"\csname\name o\endcsname%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                assert_eq!(
+                    parser.lex_expanded_token(),
+                    Some(Token::Char('b', Category::Letter))
+                );
+                assert_eq!(
+                    parser.lex_expanded_token(),
+                    Some(Token::Char('a', Category::Letter))
+                );
+                assert_eq!(
+                    parser.lex_expanded_token(),
+                    Some(Token::Char('r', Category::Letter))
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_defines_undefined_csnames_as_relax() {
+        with_parser(&[r"\csname un defined\endcsname%"], |parser| {
+            let undefined = Token::ControlSequence("un defined".to_string());
+            assert!(!parser
+                .state
+                .is_token_equal_to_prim(&undefined, Primitive::Relax));
+
+            assert_eq!(parser.lex_expanded_token(), Some(undefined.clone()));
+            assert!(parser
+                .state
+                .is_token_equal_to_prim(&undefined, Primitive::Relax));
+        });
+    }
+
+    #[test]
+    fn it_defines_and_lets_csnames_after_expandafter() {
+        with_parser(
+            &[
+                r"\expandafter\def\csname my cs\endcsname{hi}%",
+                r"\expandafter\let\expandafter\alias\csname my cs\endcsname%",
+                r"\csname my cs\endcsname\alias%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                let chars: String = parser
+                    .parse_horizontal_list(false, false)
+                    .into_iter()
+                    .map(|elem| match elem {
+                        HorizontalListElem::Char { chr, .. } => chr,
+                        _ => panic!("Expected a character: {:?}", elem),
+                    })
+                    .collect();
+                assert_eq!(chars, "hihi");
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r"Missing \endcsname inserted")]
+    fn it_fails_on_non_characters_in_csnames() {
+        with_parser(&[r"\csname a\relax\endcsname%"], |parser| {
+            parser.lex_expanded_token();
+        });
+    }
+
+    fn lex_all_expanded_tokens(parser: &mut Parser) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        while let Some(token) = parser.lex_expanded_token() {
+            tokens.push(token);
+        }
+        tokens
+    }
+
+    #[test]
+    fn it_expands_string_to_other_characters() {
+        with_parser(&[r"\string\foo\string a\string\ \string{%"], |parser| {
+            let mut expected = tokenize_other(r"\foo");
+            expected.push(Token::Char('a', Category::Other));
+            expected.push(Token::Char('\\', Category::Other));
+            expected.push(Token::Char(' ', Category::Space));
+            expected.push(Token::Char('{', Category::Other));
+
+            assert_eq!(lex_all_expanded_tokens(parser), expected);
+        });
+    }
+
+    #[test]
+    fn it_uses_escapechar_in_string_and_meaning() {
+        with_parser(&[r"\meaning\def%"], |parser| {
+            assert_eq!(
+                lex_all_expanded_tokens(parser),
+                tokenize_other(r"\def")
+            );
+        });
+
+        with_parser(
+            &[r"\escapechar=`/ \string\foo\meaning\count%"],
+            |parser| {
+                parser.parse_assignment();
+                assert_eq!(
+                    lex_all_expanded_tokens(parser),
+                    tokenize_other("/foo/count")
+                );
+            },
+        );
+
+        with_parser(
+            &[r"\escapechar=-1 \string\foo\meaning\relax%"],
+            |parser| {
+                parser.parse_assignment();
+                assert_eq!(
+                    lex_all_expanded_tokens(parser),
+                    tokenize_other("foorelax")
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_expands_the_meaning_of_macros() {
+        with_parser(
+            &[
+                r"\def\a#1{#1#1}%",
+                r"\def\b x#1y{\a{#1}##}%",
+                r"\meaning\a\meaning\b%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                let mut expected = tokenize_other("macro:#1->#1#1");
+                expected.extend(tokenize_other(r"macro:x#1y->\a {#1}##"));
+                assert_eq!(lex_all_expanded_tokens(parser), expected);
+            },
+        );
+    }
+
+    #[test]
+    fn it_expands_the_meaning_of_other_tokens() {
+        with_parser(
+            &[
+                r"\let\x=\relax%",
+                r"\meaning\x\meaning a\meaning\undefined\meaning{%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+
+                let mut expected = tokenize_other(r"\relax");
+                expected.extend(tokenize_other("the letter a"));
+                expected.extend(tokenize_other("undefined"));
+                expected.extend(tokenize_other("begin-group character {"));
+                assert_eq!(lex_all_expanded_tokens(parser), expected);
+            },
+        );
+    }
+
+    #[test]
+    fn it_expands_the_meaning_of_every_primitive_and_its_aliases() {
+        for prim in Primitive::ALL {
+            let line = format!(
+                r"\let\x=\{}\meaning\{}\meaning\x%",
+                prim.name(),
+                prim.name()
+            );
+            with_parser(&[&line], |parser| {
+                parser.parse_assignment();
+
+                let x = Token::ControlSequence("x".to_string());
+                assert!(parser.state.is_token_equal_to_prim(&x, *prim));
+
+                let mut expected =
+                    tokenize_other(&format!(r"\{}", prim.name()));
+                expected.extend(expected.clone());
+                assert_eq!(lex_all_expanded_tokens(parser), expected);
+            });
+        }
+    }
+
+    #[test]
+    fn it_expands_the_token_after_the_next_one_with_expandafter() {
+        with_parser(
+            &[
+                r"\def\a#1{\string#1}%",
+                r"\def\b{x}%",
+                r"\def\c{\b}%",
+                r"\a\c\expandafter\a\c%",
+                r"\expandafter\expandafter\expandafter\a\c%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                // \c is only expanded once before \a sees it, unless the
+                // \expandafters are chained to expand it twice.
+                assert_eq!(
+                    lex_all_expanded_tokens(parser),
+                    tokenize_other(r"\c\bx")
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_expands_past_begin_groups_with_expandafter() {
+        with_parser(
+            &[
+                r"\def\a#1{\string#1}%",
+                r"\def\c{\b}%",
+                r"\a{\c}\expandafter\a\expandafter{\c}%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                assert_eq!(
+                    lex_all_expanded_tokens(parser),
+                    tokenize_other(r"\c\b")
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_leaves_unexpandable_tokens_alone_with_expandafter() {
+        with_parser(
+            &[r"\expandafter a\relax\expandafter\relax b%"],
+            |parser| {
+                assert_eq!(
+                    lex_all_expanded_tokens(parser),
+                    vec![
+                        Token::Char('a', Category::Letter),
+                        Token::ControlSequence("relax".to_string()),
+                        Token::ControlSequence("relax".to_string()),
+                        Token::Char('b', Category::Letter),
+                    ],
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_does_not_expand_tokens_after_noexpand() {
+        with_parser(
+            &[r"\def\a{x}%", r"\def\b{\noexpand\a}%", r"\noexpand\a\a\b%"],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                let a = Token::ControlSequence("a".to_string());
+                let x = Token::Char('x', Category::Letter);
+                assert_eq!(parser.peek_expanded_token(), Some(a.clone()));
+                assert_eq!(
+                    lex_all_expanded_tokens(parser),
+                    vec![a.clone(), x, a]
+                );
+            },
+        );
+    }
+
+    // Writes a file for a test to \input, and returns its path without the
+    // .tex extension.
+    fn make_input_file(name: &str, contents: &str) -> String {
+        let directory = std::env::temp_dir()
+            .join(format!("xymostex-input-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join(format!("{}.tex", name)), contents).unwrap();
+        directory.join(name).to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn it_reads_input_files() {
+        let path = make_input_file("macros", "\\def\\fromfile{ok}%\nb%\n");
+
+        with_parser(&[&format!(r"\input {} a%", path)], |parser| {
+            parser.parse_assignment();
+            assert_eq!(
+                parser.lex_expanded_token(),
+                Some(Token::Char('b', Category::Letter))
+            );
+            assert_eq!(
+                parser.lex_expanded_token(),
+                Some(Token::Char('a', Category::Letter))
+            );
+            assert_eq!(
+                parser
+                    .state
+                    .get_macro(&Token::ControlSequence("fromfile".to_string())),
+                Some(Rc::new(Macro::new(
+                    vec![],
+                    vec![
+                        MacroListElem::Token(Token::Char(
+                            'o',
+                            Category::Letter
+                        )),
+                        MacroListElem::Token(Token::Char(
+                            'k',
+                            Category::Letter
+                        )),
+                    ]
+                )))
+            );
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "I can't find file `xymostex-missing-file.tex'")]
+    fn it_fails_to_input_files_that_cannot_be_found() {
+        with_parser(&[r"\input xymostex-missing-file %"], |parser| {
+            parser.lex_expanded_token();
+        });
+    }
+
+    #[test]
+    fn it_reads_tokens_after_input_file_names_after_the_file() {
+        let path = make_input_file("lookahead", "\\def\\x{ok}%\n");
+
+        with_parser(&[&format!(r"\input {}\x%", path)], |parser| {
+            // \x is read while looking for the end of the file name, but it
+            // still comes after the definition in the file
+            parser.parse_assignment();
+            assert_eq!(
+                parser.lex_expanded_token(),
+                Some(Token::Char('o', Category::Letter))
+            );
+            assert_eq!(
+                parser.lex_expanded_token(),
+                Some(Token::Char('k', Category::Letter))
+            );
+        });
+    }
+
+    #[test]
+    fn it_reports_diagnostics_with_the_input_file_name() {
+        let path =
+            make_input_file("diagnostics", "\\setbox0=\\hbox{\\char200}%\n");
+        let output = Rc::new(RefCell::new(Vec::new()));
+
+        with_parser(
+            &[&format!(r"\input {} %", path), r"\setbox0=\hbox{\char200}%"],
+            |parser| {
+                parser.state.with_diagnostics(|diagnostics| {
+                    diagnostics
+                        .set_json_writer(Box::new(SharedWriter(output.clone())))
+                });
+                parser.parse_assignment();
+                parser.parse_assignment();
+            },
+        );
+
+        let output = String::from_utf8(output.borrow().clone()).unwrap();
+        let diagnostics: Vec<&str> = output.lines().collect();
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics[0]
+            .contains(&format!(r#""file":"{}.tex","line":1"#, path)));
+        assert!(diagnostics[1].contains(r#""file":null,"line":2"#));
+    }
+
+    #[test]
+    fn it_stops_reading_input_files_at_endinput() {
+        let path = make_input_file("end", "a\\endinput b%\nc%\n");
+
+        with_parser(&[&format!(r"\input {} d%", path)], |parser| {
+            assert_eq!(
+                parser.lex_expanded_token(),
+                Some(Token::Char('a', Category::Letter))
+            );
+            // The rest of the line with the \endinput is still read
+            assert_eq!(
+                parser.lex_expanded_token(),
+                Some(Token::Char('b', Category::Letter))
+            );
+            assert_eq!(
+                parser.lex_expanded_token(),
+                Some(Token::Char('d', Category::Letter))
+            );
+        });
+    }
+
+    #[test]
+    fn it_stops_infinite_expansion_at_the_expansion_limit() {
+        let err = get_resource_limit_exceeded(|| {
+            with_parser(&[r"\def\a{\a}%", r"\a"], |parser| {
+                parser.state.set_resource_limits(
+                    ResourceLimits::unlimited()
+                        .with_max(ResourceLimit::ExpansionSteps, 1000),
+                );
+                parser.parse_assignment();
+                parser.lex_expanded_token();
+            })
+        });
+        assert_eq!(
+            err,
+            ResourceLimitExceeded {
+                limit: ResourceLimit::ExpansionSteps,
+                max: 1000,
+            }
+        );
+    }
 }
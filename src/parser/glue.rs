@@ -4,11 +4,11 @@ use crate::dimension::{Dimen, SpringDimen};
 use crate::glue::Glue;
 
 impl<'a> Parser<'a> {
-    fn is_internal_glue_head(&mut self) -> bool {
+    pub fn is_internal_glue_head(&mut self) -> bool {
         self.is_glue_variable_head()
     }
 
-    fn parse_internal_glue(&mut self) -> Glue {
+    pub fn parse_internal_glue(&mut self) -> Glue {
         if self.is_glue_variable_head() {
             let variable = self.parse_glue_variable();
             variable.get(self.state)
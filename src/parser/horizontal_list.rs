@@ -1,12 +1,37 @@
 use crate::boxes::{HorizontalBox, TeXBox};
 use crate::category::Category;
+use crate::diagnostics::DiagnosticKind;
 use crate::dimension::{Dimen, SpringDimen, Unit};
 use crate::glue::Glue;
+use crate::limits::ResourceLimit;
 use crate::list::HorizontalListElem;
 use crate::math_list::MathStyle;
+use crate::parser::mode::Mode;
 use crate::parser::Parser;
+use crate::primitive::Primitive;
+use crate::state::{DimenParameter, TokenListParameter};
 use crate::token::Token;
 
+/// The primitives for horizontal material, and for the vertical material
+/// that ends a paragraph.
+#[cfg(test)]
+pub const HANDLED_PRIMITIVES: &[Primitive] = &[
+    Primitive::VSkip,
+    Primitive::VFil,
+    Primitive::VFill,
+    Primitive::End,
+    Primitive::HRule,
+    Primitive::HAlign,
+    Primitive::Char,
+    Primitive::Par,
+    Primitive::HSkip,
+    Primitive::Kern,
+    Primitive::Raise,
+    Primitive::Lower,
+    Primitive::MoveLeft,
+    Primitive::MoveRight,
+];
+
 fn get_space_glue() -> Glue {
     Glue {
         space: Dimen::from_unit(3.33333, Unit::Point),
@@ -24,8 +49,100 @@ enum ElemResult {
 impl<'a> Parser<'a> {
     /// Returns if the next token is the start of something that only makes
     /// sense in vertical mode.
-    fn is_vertical_material_head(&mut self) -> bool {
-        self.is_next_expanded_token_in_set_of_primitives(&["vskip", "end"])
+    pub fn is_vertical_material_head(&mut self) -> bool {
+        self.is_next_expanded_token_in_set_of_primitives(&[
+            Primitive::VSkip,
+            Primitive::VFil,
+            Primitive::VFill,
+            Primitive::End,
+            Primitive::HRule,
+            Primitive::HAlign,
+        ])
+    }
+
+    /// Returns if the next token will produce a character in the list. Only
+    /// characters that come directly after each other can form ligatures, so
+    /// anything else in between (like a group or \relax) prevents them.
+    fn is_horizontal_char_head(&mut self) -> bool {
+        let expanded_token = self.peek_expanded_token();
+        match self.replace_renamed_token(expanded_token) {
+            Some(Token::Char(_, Category::Letter)) => true,
+            Some(Token::Char(_, Category::Other)) => true,
+            Some(ref tok) => {
+                self.state.is_token_equal_to_prim(tok, Primitive::Char)
+                    || self.state.get_chardef(tok).is_some()
+            }
+            None => false,
+        }
+    }
+
+    /// Adds a character to the end of a horizontal list. If the previous
+    /// element is a character in the same font, the font's lig/kern program
+    /// for the pair is applied: a ligature replaces the two characters with
+    /// the ligature character, and a kern is inserted between them.
+    fn add_char_with_ligatures_and_kerns(
+        &mut self,
+        list: &mut Vec<HorizontalListElem>,
+        elem: HorizontalListElem,
+    ) {
+        if let (
+            Some(HorizontalListElem::Char {
+                chr: left,
+                font: left_font,
+            }),
+            HorizontalListElem::Char { chr: right, font },
+        ) = (list.last(), &elem)
+        {
+            if left_font == font {
+                let (maybe_ligature, maybe_kern) = self
+                    .state
+                    .with_metrics_for_font(font, |metrics| {
+                        (
+                            metrics.get_ligature(*left, *right),
+                            metrics.get_kern(*left, *right),
+                        )
+                    })
+                    .unwrap_or((None, None));
+
+                if let Some(ligature) = maybe_ligature {
+                    let font = font.clone();
+                    list.pop();
+                    list.push(HorizontalListElem::Char {
+                        chr: ligature,
+                        font,
+                    });
+                    return;
+                }
+
+                if let Some(kern) = maybe_kern {
+                    list.push(HorizontalListElem::Kern(kern));
+                }
+            }
+        }
+
+        list.push(elem);
+    }
+
+    // Makes the list element for a character in the current font. Like in
+    // TeX, characters that the font doesn't have are dropped with a warning.
+    fn get_char_elem(&mut self, chr: char) -> ElemResult {
+        let font = self.state.get_current_font();
+        let has_char = self
+            .state
+            .with_metrics_for_font(&font, |metrics| metrics.has_char(chr))
+            .unwrap_or(false);
+
+        if has_char {
+            ElemResult::Elem(HorizontalListElem::Char { chr, font })
+        } else {
+            self.state.with_diagnostics(|diagnostics| {
+                diagnostics.report(DiagnosticKind::MissingCharacter {
+                    chr,
+                    font_name: font.font_name,
+                })
+            });
+            ElemResult::Elems(vec![])
+        }
     }
 
     fn parse_horizontal_list_elem(
@@ -40,17 +157,11 @@ impl<'a> Parser<'a> {
             Some(Token::Char(ch, cat)) => match cat {
                 Category::Letter => {
                     self.lex_expanded_token();
-                    ElemResult::Elem(HorizontalListElem::Char {
-                        chr: ch,
-                        font: self.state.get_current_font(),
-                    })
+                    self.get_char_elem(ch)
                 }
                 Category::Other => {
                     self.lex_expanded_token();
-                    ElemResult::Elem(HorizontalListElem::Char {
-                        chr: ch,
-                        font: self.state.get_current_font(),
-                    })
+                    self.get_char_elem(ch)
                 }
                 Category::Space => {
                     self.lex_expanded_token();
@@ -70,7 +181,7 @@ impl<'a> Parser<'a> {
                     } else {
                         self.lex_expanded_token();
                         *group_level -= 1;
-                        self.state.pop_state();
+                        self.end_group();
                         self.parse_horizontal_list_elem(group_level, restricted)
                     }
                 }
@@ -90,6 +201,13 @@ impl<'a> Parser<'a> {
                     } else {
                         self.state.push_state();
 
+                        // Like \everyhbox, the \everymath tokens are read
+                        // inside of the group for the math formula.
+                        let every_math = self.state.get_token_list_parameter(
+                            &TokenListParameter::EveryMath,
+                        );
+                        self.add_upcoming_tokens(every_math);
+
                         let math_list = self.parse_math_list();
                         let horizontal_list = self
                             .convert_math_list_to_horizontal_list(
@@ -104,14 +222,16 @@ impl<'a> Parser<'a> {
                             }
                         }
 
-                        self.state.pop_state();
+                        self.end_group();
 
                         ElemResult::Elems(horizontal_list)
                     }
                 }
                 _ => panic!("unimplemented"),
             },
-            Some(ref tok) if self.state.is_token_equal_to_prim(tok, "par") => {
+            Some(ref tok)
+                if self.state.is_token_equal_to_prim(tok, Primitive::Par) =>
+            {
                 self.lex_expanded_token();
 
                 if restricted {
@@ -125,14 +245,21 @@ impl<'a> Parser<'a> {
                 }
             }
             Some(ref tok)
-                if self.state.is_token_equal_to_prim(tok, "hskip") =>
+                if self.state.is_token_equal_to_prim(tok, Primitive::HSkip) =>
             {
                 self.lex_expanded_token();
                 let glue = self.parse_glue();
                 ElemResult::Elem(HorizontalListElem::HSkip(glue))
             }
             Some(ref tok)
-                if self.state.is_token_equal_to_prim(tok, "raise") =>
+                if self.state.is_token_equal_to_prim(tok, Primitive::Kern) =>
+            {
+                self.lex_expanded_token();
+                let dimen = self.parse_dimen();
+                ElemResult::Elem(HorizontalListElem::Kern(dimen))
+            }
+            Some(ref tok)
+                if self.state.is_token_equal_to_prim(tok, Primitive::Raise) =>
             {
                 self.lex_expanded_token();
                 let shift = self.parse_dimen();
@@ -143,7 +270,7 @@ impl<'a> Parser<'a> {
                 }
             }
             Some(ref tok)
-                if self.state.is_token_equal_to_prim(tok, "lower") =>
+                if self.state.is_token_equal_to_prim(tok, Primitive::Lower) =>
             {
                 self.lex_expanded_token();
                 let shift = self.parse_dimen();
@@ -156,19 +283,49 @@ impl<'a> Parser<'a> {
                     self.parse_horizontal_list_elem(group_level, restricted)
                 }
             }
-            Some(ref tok) if self.state.is_token_equal_to_prim(tok, "char") => {
+            Some(ref tok)
+                if self.state.is_token_equal_to_prim(tok, Primitive::Char) =>
+            {
                 self.lex_expanded_token();
                 let char_number = self.parse_8bit_number();
-
-                ElemResult::Elem(HorizontalListElem::Char {
-                    chr: char_number as char,
-                    font: self.state.get_current_font(),
-                })
+                self.get_char_elem(char_number as char)
+            }
+            Some(ref tok) if self.state.get_chardef(tok).is_some() => {
+                let char_number = self.parse_chardef();
+                self.get_char_elem(char_number as char)
             }
             _ => {
                 if self.is_assignment_head() {
-                    self.parse_assignment(None);
+                    self.parse_assignment();
+                    self.parse_horizontal_list_elem(group_level, restricted)
+                } else if self.is_output_file_command_head() {
+                    match self.parse_output_file_command() {
+                        Some(operation) => ElemResult::Elem(
+                            HorizontalListElem::Whatsit(operation),
+                        ),
+                        None => self.parse_horizontal_list_elem(
+                            group_level,
+                            restricted,
+                        ),
+                    }
+                } else if self.is_input_file_command_head() {
+                    self.parse_input_file_command();
+                    self.parse_horizontal_list_elem(group_level, restricted)
+                } else if self.is_message_head() {
+                    self.parse_message();
                     self.parse_horizontal_list_elem(group_level, restricted)
+                } else if self.is_shipout_head() {
+                    self.parse_shipout();
+                    self.parse_horizontal_list_elem(group_level, restricted)
+                } else if self.is_semi_simple_group_head() {
+                    self.parse_semi_simple_group_head();
+                    self.parse_horizontal_list_elem(group_level, restricted)
+                } else if self.is_vertical_rule_head() {
+                    ElemResult::Elem(self.parse_vertical_rule())
+                } else if self.is_discretionary_head() {
+                    ElemResult::Elem(self.parse_discretionary())
+                } else if self.is_vertical_alignment_head() {
+                    ElemResult::Elems(self.parse_vertical_alignment())
                 } else if self.is_box_head() {
                     let maybe_tex_box = self.parse_box();
                     if let Some(tex_box) = maybe_tex_box {
@@ -179,6 +336,31 @@ impl<'a> Parser<'a> {
                     } else {
                         self.parse_horizontal_list_elem(group_level, restricted)
                     }
+                } else if self.is_next_expanded_token_in_set_of_primitives(&[
+                    Primitive::MoveLeft,
+                    Primitive::MoveRight,
+                ]) {
+                    self.report_illegal_case(&[
+                        Primitive::MoveLeft,
+                        Primitive::MoveRight,
+                    ]);
+                } else if restricted
+                    && self.is_next_expanded_token_in_set_of_primitives(&[
+                        Primitive::HRule,
+                    ])
+                {
+                    // TODO(xymostech): Allow this once \leaders exists.
+                    panic!(r"You can't use \hrule here except with leaders");
+                } else if restricted && self.is_vertical_material_head() {
+                    // There's no paragraph to end in restricted horizontal
+                    // mode, so vertical material isn't allowed at all.
+                    self.report_illegal_case(&[
+                        Primitive::VSkip,
+                        Primitive::VFil,
+                        Primitive::VFill,
+                        Primitive::End,
+                        Primitive::HAlign,
+                    ]);
                 } else if self.is_vertical_material_head() {
                     // If we see vertical mode material, we add a \par token to
                     // the input stream, continue and let that be parsed, after
@@ -188,7 +370,7 @@ impl<'a> Parser<'a> {
                     ));
                     self.parse_horizontal_list_elem(group_level, restricted)
                 } else {
-                    panic!("unimplemented!");
+                    self.fail_on_unexpected_token();
                 }
             }
         }
@@ -199,6 +381,12 @@ impl<'a> Parser<'a> {
         restricted: bool,
         indent: bool,
     ) -> Vec<HorizontalListElem> {
+        self.push_mode(if restricted {
+            Mode::RestrictedHorizontal
+        } else {
+            Mode::Horizontal
+        });
+
         let mut result = Vec::new();
 
         // Optionally add in indentation
@@ -208,8 +396,8 @@ impl<'a> Parser<'a> {
         // control this.
         if indent {
             let mut hbox = HorizontalBox::empty();
-            // TODO(xymostech): This should be \parindent, not a fixed 20pt.
-            hbox.width = Dimen::from_unit(20.0, Unit::Point);
+            hbox.width =
+                self.state.get_dimen_parameter(&DimenParameter::ParIndent);
             let tex_box = TeXBox::HorizontalBox(hbox);
             result.push(HorizontalListElem::Box {
                 tex_box,
@@ -218,16 +406,45 @@ impl<'a> Parser<'a> {
         }
 
         let mut group_level = 0;
+        // Whether the last element in the list came directly from a character
+        // token, so the next character might form a ligature with it.
+        let mut prev_elem_is_char = false;
 
         loop {
-            match self.parse_horizontal_list_elem(&mut group_level, restricted)
-            {
+            let elem_is_char = self.is_horizontal_char_head();
+
+            // \lastbox can take the last box off of the list while the next
+            // element is being parsed.
+            self.horizontal_list_stack.push(result);
+            let elem_result =
+                self.parse_horizontal_list_elem(&mut group_level, restricted);
+            result = self.horizontal_list_stack.pop().unwrap();
+
+            match elem_result {
                 ElemResult::Nothing => break,
-                ElemResult::Elem(elem) => result.push(elem),
-                ElemResult::Elems(mut elems) => result.append(&mut elems),
+                ElemResult::Elem(elem) => {
+                    self.state.use_resource(ResourceLimit::ListElements, 1);
+                    if elem_is_char && prev_elem_is_char {
+                        self.add_char_with_ligatures_and_kerns(
+                            &mut result,
+                            elem,
+                        );
+                    } else {
+                        result.push(elem);
+                    }
+                }
+                ElemResult::Elems(mut elems) => {
+                    self.state
+                        .use_resource(ResourceLimit::ListElements, elems.len());
+                    result.append(&mut elems)
+                }
             }
+
+            prev_elem_is_char = elem_is_char;
         }
 
+        self.pop_mode();
+
         result
     }
 }
@@ -238,8 +455,10 @@ mod tests {
 
     use once_cell::sync::Lazy;
 
+    use crate::boxes::BoxLayout;
     use crate::dimension::{FilDimen, FilKind};
     use crate::font::Font;
+    use crate::font_metrics::FontMetrics;
     use crate::math_code::MathCode;
     use crate::testing::with_parser;
 
@@ -270,6 +489,13 @@ mod tests {
         assert_parses_to_with_restricted(lines, expected_toks, true);
     }
 
+    fn get_cmr10_kern(left: char, right: char) -> Dimen {
+        FontMetrics::from_font(&CMR10)
+            .unwrap()
+            .get_kern(left, right)
+            .unwrap()
+    }
+
     #[test]
     fn it_parses_letters() {
         assert_parses_to(
@@ -321,6 +547,7 @@ mod tests {
                     chr: 'b',
                     font: CMR10.clone(),
                 },
+                HorizontalListElem::Kern(get_cmr10_kern('b', 'c')),
                 HorizontalListElem::Char {
                     chr: 'c',
                     font: CMR10.clone(),
@@ -329,6 +556,92 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_adds_everymath_tokens_inside_of_math_mode() {
+        with_parser(
+            &[
+                r"\everymath={\def\x{5}}%",
+                r"$\global\count1=\x $\def\x{6}%",
+                r"$\global\count2=\x $%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                assert_eq!(parser.parse_horizontal_list(true, false), vec![]);
+
+                // The tokens are added again at the start of each formula,
+                // so they override the \x from outside of math mode.
+                assert_eq!(parser.state.get_count(1), 5);
+                assert_eq!(parser.state.get_count(2), 5);
+            },
+        );
+    }
+
+    #[test]
+    fn it_parses_semi_simple_groups() {
+        let expected: Vec<_> = "bba"
+            .chars()
+            .map(|chr| HorizontalListElem::Char {
+                chr,
+                font: CMR10.clone(),
+            })
+            .collect();
+
+        assert_parses_to(
+            &[
+                r"\def\x{a}\def\y{b}%",
+                r"\begingroup\def\x{b}\aftergroup\y\x\endgroup\x%",
+            ],
+            &expected,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r"Extra }, or forgotten \endgroup")]
+    fn it_should_fail_when_boxes_end_inside_semi_simple_groups() {
+        with_parser(&[r"\hbox{\begingroup}%"], |parser| {
+            parser.parse_horizontal_list(false, false);
+        });
+    }
+
+    #[test]
+    fn it_inserts_after_group_tokens_at_the_end_of_groups() {
+        let expected: Vec<_> = "345126"
+            .chars()
+            .map(|chr| HorizontalListElem::Char {
+                chr,
+                font: CMR10.clone(),
+            })
+            .collect();
+
+        assert_parses_to(
+            &[r"{\aftergroup1\aftergroup23{\aftergroup4}5}6%"],
+            &expected,
+        );
+        assert_parses_to(
+            &[r"\def\x{\aftergroup1}%", r"\hbox{\x}\aftergroup23%"],
+            &[
+                HorizontalListElem::Box {
+                    tex_box: TeXBox::HorizontalBox(HorizontalBox {
+                        width: Dimen::zero(),
+                        height: Dimen::zero(),
+                        depth: Dimen::zero(),
+                        list: vec![],
+                        glue_set_ratio: None,
+                    }),
+                    shift: Dimen::zero(),
+                },
+                HorizontalListElem::Char {
+                    chr: '1',
+                    font: CMR10.clone(),
+                },
+                HorizontalListElem::Char {
+                    chr: '3',
+                    font: CMR10.clone(),
+                },
+            ],
+        );
+    }
+
     #[test]
     fn it_handles_let_assigned_tokens() {
         assert_parses_to(
@@ -408,6 +721,234 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_parses_hskips_from_dimen_registers() {
+        with_parser(&[r"\dimen0=3pt%", r"\hskip\dimen0%"], |parser| {
+            parser.parse_assignment();
+
+            assert_eq!(
+                parser.parse_horizontal_list(true, false),
+                vec![HorizontalListElem::HSkip(Glue::from_dimen(
+                    Dimen::from_unit(3.0, Unit::Point)
+                ))]
+            );
+        });
+    }
+
+    #[test]
+    fn it_parses_hskips_from_skip_registers() {
+        with_parser(
+            &[r"\skip0=3pt plus1fil minus2pt%", r"\hskip\skip0%"],
+            |parser| {
+                parser.parse_assignment();
+
+                assert_eq!(
+                    parser.parse_horizontal_list(true, false),
+                    vec![HorizontalListElem::HSkip(Glue {
+                        space: Dimen::from_unit(3.0, Unit::Point),
+                        stretch: SpringDimen::FilDimen(FilDimen::new(
+                            FilKind::Fil,
+                            1.0
+                        )),
+                        shrink: SpringDimen::Dimen(Dimen::from_unit(
+                            2.0,
+                            Unit::Point
+                        )),
+                    })]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_parses_kern_tokens() {
+        assert_parses_to(
+            &["a\\kern 2pt b\\kern-1.5pt%"],
+            &[
+                HorizontalListElem::Char {
+                    chr: 'a',
+                    font: CMR10.clone(),
+                },
+                HorizontalListElem::Kern(Dimen::from_unit(2.0, Unit::Point)),
+                HorizontalListElem::Char {
+                    chr: 'b',
+                    font: CMR10.clone(),
+                },
+                HorizontalListElem::Kern(Dimen::from_unit(-1.5, Unit::Point)),
+            ],
+        );
+    }
+
+    #[test]
+    fn it_replaces_characters_with_ligatures() {
+        with_parser(&["ff%"], |parser| {
+            let list = parser.parse_horizontal_list(true, false);
+
+            assert_eq!(
+                list,
+                &[HorizontalListElem::Char {
+                    chr: 11 as char,
+                    font: CMR10.clone(),
+                }]
+            );
+
+            let metrics = parser.state.get_metrics_for_font(&CMR10).unwrap();
+            let hbox = HorizontalBox::create_from_horizontal_list_with_layout(
+                list,
+                &BoxLayout::Natural,
+                parser.state,
+            );
+            assert_eq!(hbox.width, metrics.get_width(11 as char));
+        });
+    }
+
+    #[test]
+    fn it_forms_ligatures_from_ligatures() {
+        assert_parses_to(
+            &["ffi---%"],
+            &[
+                HorizontalListElem::Char {
+                    chr: 14 as char,
+                    font: CMR10.clone(),
+                },
+                HorizontalListElem::Char {
+                    chr: 124 as char,
+                    font: CMR10.clone(),
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn it_inserts_kerns_between_characters() {
+        with_parser(&["AVA{}V%"], |parser| {
+            let metrics = parser.state.get_metrics_for_font(&CMR10).unwrap();
+
+            assert_eq!(
+                parser.parse_horizontal_list(true, false),
+                &[
+                    HorizontalListElem::Char {
+                        chr: 'A',
+                        font: CMR10.clone(),
+                    },
+                    HorizontalListElem::Kern(
+                        metrics.get_kern('A', 'V').unwrap()
+                    ),
+                    HorizontalListElem::Char {
+                        chr: 'V',
+                        font: CMR10.clone(),
+                    },
+                    HorizontalListElem::Kern(
+                        metrics.get_kern('V', 'A').unwrap()
+                    ),
+                    HorizontalListElem::Char {
+                        chr: 'A',
+                        font: CMR10.clone(),
+                    },
+                    HorizontalListElem::Char {
+                        chr: 'V',
+                        font: CMR10.clone(),
+                    },
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn it_doesnt_insert_kerns_between_characters_and_boxes() {
+        with_parser(&[r"A\hbox{}V%"], |parser| {
+            let list = parser.parse_horizontal_list(true, false);
+
+            assert_eq!(list.len(), 3);
+            assert_eq!(
+                list[0],
+                HorizontalListElem::Char {
+                    chr: 'A',
+                    font: CMR10.clone(),
+                }
+            );
+            match &list[1] {
+                HorizontalListElem::Box { .. } => {}
+                elem => panic!("Expected a box, found {:?}", elem),
+            }
+            assert_eq!(
+                list[2],
+                HorizontalListElem::Char {
+                    chr: 'V',
+                    font: CMR10.clone(),
+                }
+            );
+        });
+    }
+
+    #[test]
+    fn it_applies_ligatures_and_kerns_throughout_long_words() {
+        // "office" becomes "o", the "ffi" ligature, "c", and "e". "AV" gets a
+        // kern between the letters, and so does the "V" at the end of each
+        // repetition and the "o" at the start of the next one.
+        let word = "officeAV".repeat(2000);
+        let line = format!("{}%", word);
+
+        with_parser(&[&line], |parser| {
+            let list = parser.parse_horizontal_list(true, false);
+
+            let chars: Vec<char> = list
+                .iter()
+                .filter_map(|elem| match elem {
+                    HorizontalListElem::Char { chr, .. } => Some(*chr),
+                    _ => None,
+                })
+                .collect();
+            let num_kerns = list
+                .iter()
+                .filter(|elem| matches!(elem, HorizontalListElem::Kern(_)))
+                .count();
+
+            assert_eq!(
+                chars,
+                "o\x0EceAV".repeat(2000).chars().collect::<Vec<_>>()
+            );
+            assert_eq!(num_kerns, 2000 + 1999);
+        });
+    }
+
+    #[test]
+    fn it_typesets_case_converted_characters() {
+        with_parser(&[r"\uppercase{abc}\lowercase{DEF}%"], |parser| {
+            let chars: Vec<char> = parser
+                .parse_horizontal_list(true, false)
+                .into_iter()
+                .filter_map(|elem| match elem {
+                    HorizontalListElem::Char { chr, .. } => Some(chr),
+                    _ => None,
+                })
+                .collect();
+
+            assert_eq!(chars, vec!['A', 'B', 'C', 'd', 'e', 'f']);
+        });
+    }
+
+    #[test]
+    fn it_does_not_form_ligatures_across_other_tokens() {
+        assert_parses_to(
+            &["f{}f{f}%"],
+            &[
+                HorizontalListElem::Char {
+                    chr: 'f',
+                    font: CMR10.clone(),
+                },
+                HorizontalListElem::Char {
+                    chr: 'f',
+                    font: CMR10.clone(),
+                },
+                HorizontalListElem::Char {
+                    chr: 'f',
+                    font: CMR10.clone(),
+                },
+            ],
+        );
+    }
+
     #[test]
     fn it_parses_explicit_box_elems() {
         with_parser(&[r"a\hbox{a\hskip 2pt plus1filg}b%"], |parser| {
@@ -511,6 +1052,7 @@ mod tests {
                     chr: 'b',
                     font: CMR10.clone(),
                 },
+                HorizontalListElem::Kern(get_cmr10_kern('b', 'c')),
                 HorizontalListElem::Char {
                     chr: 'c',
                     font: CMR10.clone(),
@@ -560,24 +1102,33 @@ mod tests {
 
     #[test]
     fn it_adds_indentation() {
-        with_parser(&[r"\setbox0=\hbox{}%", r"\wd0=20pt%", "a%"], |parser| {
-            parser.parse_assignment(None);
-            parser.parse_assignment(None);
+        with_parser(
+            &[
+                r"\setbox0=\hbox{}%",
+                r"\wd0=15pt%",
+                r"\parindent=15pt%",
+                "a%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
 
-            assert_eq!(
-                parser.parse_horizontal_list(false, true),
-                &[
-                    HorizontalListElem::Box {
-                        tex_box: parser.state.get_box(0).unwrap(),
-                        shift: Dimen::zero()
-                    },
-                    HorizontalListElem::Char {
-                        chr: 'a',
-                        font: CMR10.clone(),
-                    },
-                ]
-            );
-        });
+                assert_eq!(
+                    parser.parse_horizontal_list(false, true),
+                    &[
+                        HorizontalListElem::Box {
+                            tex_box: parser.state.get_box(0).unwrap(),
+                            shift: Dimen::zero()
+                        },
+                        HorizontalListElem::Char {
+                            chr: 'a',
+                            font: CMR10.clone(),
+                        },
+                    ]
+                );
+            },
+        );
     }
 
     #[test]
@@ -768,6 +1319,35 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_raises_and_lowers_by_the_space_of_glue() {
+        with_parser(
+            &[
+                r"\skip1=2pt plus 1fil minus 3pt%",
+                r"\hbox{a}%",
+                r"\raise\skip1\hbox{a}\lower\skip1\hbox{a}%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                let abox = parser.parse_box().unwrap();
+
+                assert_eq!(
+                    parser.parse_horizontal_list(false, false),
+                    &[
+                        HorizontalListElem::Box {
+                            tex_box: abox.clone(),
+                            shift: Dimen::from_unit(2.0, Unit::Point),
+                        },
+                        HorizontalListElem::Box {
+                            tex_box: abox,
+                            shift: Dimen::from_unit(-2.0, Unit::Point),
+                        },
+                    ]
+                );
+            },
+        );
+    }
+
     #[test]
     fn it_ignores_empty_boxes_in_raise_and_lower() {
         with_parser(
@@ -826,4 +1406,82 @@ mod tests {
             );
         });
     }
+
+    #[test]
+    fn it_parses_chardefs_as_characters() {
+        with_parser(
+            &[r"\chardef\a=65 \let\b=\a%", r"\a\b\char\a%"],
+            |parser| {
+                assert_eq!(
+                    parser.parse_horizontal_list(false, false),
+                    &[
+                        HorizontalListElem::Char {
+                            chr: 'A',
+                            font: CMR10.clone(),
+                        },
+                        HorizontalListElem::Char {
+                            chr: 'A',
+                            font: CMR10.clone(),
+                        },
+                        HorizontalListElem::Char {
+                            chr: 'A',
+                            font: CMR10.clone(),
+                        },
+                    ]
+                );
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r"You can't use \moveleft in horizontal mode")]
+    fn it_fails_on_moveleft_in_horizontal_mode() {
+        with_parser(&[r"a\moveleft 2pt\hbox{b}%"], |parser| {
+            parser.parse_horizontal_list(false, false);
+        });
+    }
+
+    #[test]
+    #[should_panic(
+        expected = r"You can't use \vskip in restricted horizontal mode"
+    )]
+    fn it_fails_on_vertical_material_in_restricted_horizontal_mode() {
+        with_parser(&[r"a\vskip 2pt%"], |parser| {
+            parser.parse_horizontal_list(true, false);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = r"You can't use \hrule here except with leaders")]
+    fn it_fails_on_hrules_in_restricted_horizontal_mode() {
+        with_parser(&[r"a\hrule%"], |parser| {
+            parser.parse_horizontal_list(true, false);
+        });
+    }
+
+    #[test]
+    fn it_expands_ifinner_in_restricted_horizontal_mode() {
+        with_parser(
+            &[r"\def\x{\ifinner a\else b\fi}%", r"\x\hbox{\x}%"],
+            |parser| {
+                parser.parse_assignment();
+
+                let list = parser.parse_horizontal_list(false, false);
+                assert_eq!(list.len(), 2);
+                assert_eq!(
+                    list[0],
+                    HorizontalListElem::Char {
+                        chr: 'b',
+                        font: CMR10.clone(),
+                    }
+                );
+                match &list[1] {
+                    HorizontalListElem::Box { tex_box, .. } => {
+                        assert_eq!(tex_box.to_chars(), vec!['a'])
+                    }
+                    elem => panic!("Expected box, got {:?}", elem),
+                }
+            },
+        );
+    }
 }
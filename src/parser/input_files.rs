@@ -0,0 +1,266 @@
+use std::path::Path;
+
+use crate::category::Category;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::paths::get_path_to_input_file;
+use crate::primitive::Primitive;
+use crate::token::Token;
+
+/// The primitives for files read with \read.
+#[cfg(test)]
+pub const HANDLED_PRIMITIVES: &[Primitive] =
+    &[Primitive::OpenIn, Primitive::CloseIn];
+
+impl<'a> Parser<'a> {
+    pub fn is_input_file_command_head(&mut self) -> bool {
+        self.is_next_expanded_token_in_set_of_primitives(&[
+            Primitive::OpenIn,
+            Primitive::CloseIn,
+        ])
+    }
+
+    pub fn parse_input_file_command(&mut self) {
+        let tok = self.lex_expanded_token().unwrap();
+
+        if self.state.is_token_equal_to_prim(&tok, Primitive::OpenIn) {
+            let stream = self.parse_4bit_number();
+            self.parse_equals_expanded();
+            let mut file_name = self.parse_file_name();
+            // Like with \input, a space ending the file name is part of the
+            // \openin.
+            self.parse_optional_space_expanded();
+
+            if Path::new(&file_name).extension().is_none() {
+                file_name.push_str(".tex");
+            }
+
+            self.check_can_open_another_file();
+            match get_path_to_input_file(&file_name) {
+                Some(path) => self.state.with_input_files(|files| {
+                    files.open(stream, Path::new(&path))
+                }),
+                // Like TeX, a file that can't be found just leaves the stream
+                // closed, so that documents can check for it with \ifeof.
+                None => {
+                    self.state.with_input_files(|files| files.close(stream))
+                }
+            }
+        } else if self.state.is_token_equal_to_prim(&tok, Primitive::CloseIn) {
+            let stream = self.parse_4bit_number();
+            self.state.with_input_files(|files| files.close(stream));
+        } else {
+            panic!("unimplemented");
+        }
+    }
+
+    /// Returns whether the given stream has run out of lines, which is also
+    /// true if it isn't open. This is used for \ifeof.
+    pub fn is_input_stream_at_end(&self, stream: u8) -> bool {
+        !self.state.with_input_files(|files| files.is_open(stream))
+    }
+
+    /// Reads the tokens for a \read from the given stream. This is usually a
+    /// single line, but like in TeX we keep reading lines until the braces in
+    /// them are balanced.
+    pub fn read_tokens_from_stream(&mut self, stream: i32) -> Vec<Token> {
+        let is_open = (0..16).contains(&stream)
+            && self
+                .state
+                .with_input_files(|files| files.is_open(stream as u8));
+        if !is_open {
+            // We read the whole document up front and don't have a terminal
+            // to read from, which is the same situation that TeX is in when
+            // it isn't running interactively.
+            panic!(r"*** (cannot \read from terminal in nonstop modes)");
+        }
+
+        let mut tokens = Vec::new();
+        let mut brace_depth = 0;
+        loop {
+            let maybe_line = self
+                .state
+                .with_input_files(|files| files.read_line(stream as u8));
+
+            // When the file runs out, we act like it ended with an empty
+            // line.
+            let line_tokens = match maybe_line {
+                Some(line) => Lexer::lex_line(&line, self.state),
+                None if brace_depth > 0 => {
+                    panic!(r"File ended within \read");
+                }
+                None => Lexer::lex_line("", self.state),
+            };
+
+            for token in line_tokens {
+                match token {
+                    Token::Char(_, Category::BeginGroup) => brace_depth += 1,
+                    Token::Char(_, Category::EndGroup) => {
+                        if brace_depth == 0 {
+                            panic!("{}", r"Argument of \read has an extra }");
+                        }
+                        brace_depth -= 1;
+                    }
+                    _ => (),
+                }
+                tokens.push(token);
+            }
+
+            if brace_depth == 0 {
+                return tokens;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::category::Category;
+    use crate::testing::with_parser;
+    use crate::token::Token;
+
+    // Writes a file for a test to read from, and returns its path.
+    fn make_input_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "xymostex-{}-{}.tex",
+            name,
+            std::process::id()
+        ));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn it_reads_lines_from_input_files_into_macros() {
+        let path = make_input_file("read", "first line\n{second\nline}\n");
+        let open_line = format!(r"\openin3={}%", path.display());
+
+        with_parser(
+            &[
+                open_line.as_str(),
+                r"\read3 to\x%",
+                r"\read3 to\y%",
+                r"\def\a{first line }%",
+                r"\def\b{{second line} }%",
+                r"\closein3 %",
+            ],
+            |parser| {
+                parser.parse_input_file_command();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_input_file_command();
+
+                assert!(parser.state.is_meaning_equal(
+                    &Token::ControlSequence("x".to_string()),
+                    &Token::ControlSequence("a".to_string())
+                ));
+                assert!(parser.state.is_meaning_equal(
+                    &Token::ControlSequence("y".to_string()),
+                    &Token::ControlSequence("b".to_string())
+                ));
+                assert!(parser.is_input_stream_at_end(3));
+            },
+        );
+    }
+
+    #[test]
+    fn it_uses_the_current_category_codes_when_reading() {
+        let path = make_input_file("catcodes", "\\a@b\n");
+        let open_line = format!(r"\openin0={}%", path.display());
+
+        with_parser(
+            &[
+                open_line.as_str(),
+                r"\catcode`@=11 %",
+                r"\endlinechar=-1 %",
+                r"\read0 to\x%",
+                r"\def\y{\a@b}%",
+            ],
+            |parser| {
+                parser.parse_input_file_command();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                assert!(parser.state.is_meaning_equal(
+                    &Token::ControlSequence("x".to_string()),
+                    &Token::ControlSequence("y".to_string())
+                ));
+            },
+        );
+    }
+
+    #[test]
+    fn it_reaches_the_end_of_input_files_after_an_empty_line() {
+        let path = make_input_file("eof", "only\n");
+        let open_line = format!(r"\openin1={}%", path.display());
+
+        with_parser(
+            &[open_line.as_str(), r"\read1 to\x%", r"\read1 to\y%"],
+            |parser| {
+                parser.parse_input_file_command();
+
+                parser.parse_assignment();
+                assert!(!parser.is_input_stream_at_end(1));
+
+                parser.parse_assignment();
+                assert!(parser.is_input_stream_at_end(1));
+            },
+        );
+    }
+
+    #[test]
+    fn it_checks_for_the_end_of_input_files_with_ifeof() {
+        let path = make_input_file("ifeof", "");
+        let open_line = format!(r"\openin4={} %", path.display());
+
+        with_parser(
+            &[
+                r"\ifeof4 T\else F\fi%",
+                open_line.as_str(),
+                r"\ifeof4 T\else F\fi%",
+                r"\read4 to\x%",
+                r"\ifeof4 T\else F\fi%",
+            ],
+            |parser| {
+                assert_eq!(
+                    parser.lex_expanded_token(),
+                    Some(Token::Char('T', Category::Letter))
+                );
+                parser.parse_input_file_command();
+                assert_eq!(
+                    parser.lex_expanded_token(),
+                    Some(Token::Char('F', Category::Letter))
+                );
+                parser.parse_assignment();
+                assert_eq!(
+                    parser.lex_expanded_token(),
+                    Some(Token::Char('T', Category::Letter))
+                );
+                assert_eq!(parser.lex_expanded_token(), None);
+            },
+        );
+    }
+
+    #[test]
+    fn it_leaves_streams_closed_for_missing_files() {
+        with_parser(&[r"\openin2=doesnotexist%"], |parser| {
+            parser.parse_input_file_command();
+            assert!(parser.is_input_stream_at_end(2));
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = r"cannot \read from terminal")]
+    fn it_fails_to_read_from_closed_streams() {
+        with_parser(&[r"\read5 to\x%"], |parser| {
+            parser.parse_assignment();
+        });
+    }
+}
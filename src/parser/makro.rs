@@ -3,8 +3,13 @@ use std::collections::HashMap;
 use crate::category::Category;
 use crate::makro::{Macro, MacroListElem};
 use crate::parser::Parser;
+use crate::primitive::Primitive;
 use crate::token::Token;
 
+/// The primitives that are expanded inside of \edef and \write text.
+#[cfg(test)]
+pub const HANDLED_PRIMITIVES: &[Primitive] = &[Primitive::The];
+
 fn parse_parameter_number(ch: char) -> usize {
     if ch >= '1' && ch <= '9' {
         ((ch as u8) - (b'0')) as usize
@@ -34,8 +39,10 @@ enum SingleTokenGroup {
 }
 
 impl<'a> Parser<'a> {
-    // Parses a parameter list and replacement list into a macro object
-    pub fn parse_macro_definition(&mut self) -> Macro {
+    // Parses a parameter list and replacement list into a macro object. If
+    // `expand` is true, like for \edef, the replacement list is expanded as it
+    // is read.
+    pub fn parse_macro_definition(&mut self, expand: bool) -> Macro {
         let mut parameter_list: Vec<MacroListElem> = Vec::new();
 
         // When the last character of the parameter list (right before the {)
@@ -92,7 +99,21 @@ impl<'a> Parser<'a> {
         let mut group_level = 0;
 
         loop {
-            if let Some(token) = self.lex_unexpanded_token() {
+            if expand {
+                if let Some(tokens) = self.expand_next_token_fully() {
+                    replacement_list
+                        .extend(tokens.into_iter().map(MacroListElem::Token));
+                    continue;
+                }
+            }
+
+            let maybe_token = if expand {
+                self.lex_expanded_token()
+            } else {
+                self.lex_unexpanded_token()
+            };
+
+            if let Some(token) = maybe_token {
                 match token {
                     Token::Char(_, Category::EndGroup) => {
                         // If we see a group close and we're at the same group
@@ -142,6 +163,32 @@ impl<'a> Parser<'a> {
         Macro::new(parameter_list, replacement_list)
     }
 
+    // While reading the replacement list of an \edef (or other text that is
+    // fully expanded), this expands the next token if it is expandable.
+    // Returns None if it wasn't expandable. Like in TeX, the tokens that come
+    // from a \the aren't expanded any further, so they are returned to be
+    // added directly to the result.
+    fn expand_next_token_fully(&mut self) -> Option<Vec<Token>> {
+        if self.is_noexpand_head() {
+            return None;
+        }
+
+        let is_the = match self.peek_unexpanded_token() {
+            Some(token) => {
+                self.state.is_token_equal_to_prim(&token, Primitive::The)
+            }
+            None => false,
+        };
+
+        if is_the {
+            Some(self.expand_print())
+        } else if self.expand_next_token() {
+            Some(Vec::new())
+        } else {
+            None
+        }
+    }
+
     // This parses a list of tokens that is delimited group tokens and has a
     // balanced number of begin and end tokens. It returns the list of tokens
     // and the final ending group token.
@@ -173,6 +220,53 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Parses a <general text>, which is a balanced list of tokens surrounded
+    /// by braces, like the right hand side of `\everypar={...}`. The opening
+    /// brace can come from expansion, but the contents are left unexpanded.
+    /// Returns the tokens inside of the braces.
+    pub fn parse_general_text(&mut self) -> Vec<Token> {
+        if !self.parse_left_brace_expanded() {
+            panic!("{}", "Expected { when parsing general text");
+        }
+
+        let (tokens, _) = self.parse_balanced_text();
+        tokens
+    }
+
+    /// Parses a <general text> and fully expands its contents, like the
+    /// replacement list of an \edef. This is used for \message and \write.
+    /// Returns the expanded tokens inside of the braces.
+    pub fn parse_expanded_general_text(&mut self) -> Vec<Token> {
+        if !self.parse_left_brace_expanded() {
+            panic!("{}", "Expected { when parsing general text");
+        }
+
+        let mut result = Vec::new();
+        let mut group_level = 0;
+        loop {
+            if let Some(tokens) = self.expand_next_token_fully() {
+                result.extend(tokens);
+                continue;
+            }
+
+            let token = match self.lex_expanded_token() {
+                Some(token) => token,
+                None => panic!("EOF found parsing general text"),
+            };
+            match token {
+                Token::Char(_, Category::BeginGroup) => group_level += 1,
+                Token::Char(_, Category::EndGroup) => {
+                    if group_level == 0 {
+                        return result;
+                    }
+                    group_level -= 1;
+                }
+                _ => {}
+            }
+            result.push(token);
+        }
+    }
+
     // While we're parsing tokens for macro parameters, we often want to get
     // either a single token or, if the first token is a {, parse an entire
     // balanced group. This function handles that and returns all the
@@ -365,7 +459,10 @@ mod tests {
                     Some(Token::ControlSequence("a".to_string())),
                     parser.lex_unexpanded_token()
                 );
-                assert_eq!(expected_macro, parser.parse_macro_definition());
+                assert_eq!(
+                    expected_macro,
+                    parser.parse_macro_definition(false)
+                );
             });
         }
 
@@ -379,7 +476,7 @@ mod tests {
                     Some(Token::ControlSequence("a".to_string())),
                     parser.lex_unexpanded_token()
                 );
-                parser.parse_macro_definition();
+                parser.parse_macro_definition(false);
             });
         }
 
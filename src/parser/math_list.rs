@@ -8,14 +8,42 @@ use crate::dimension::{Dimen, FilDimen, FilKind, MuDimen, SpringDimen, Unit};
 use crate::font::Font;
 use crate::glue::{Glue, MuGlue};
 use crate::list::{HorizontalListElem, VerticalListElem};
-use crate::math_code::MathCode;
+use crate::math_code::{MathClass, MathCode};
 use crate::math_list::{
     AtomKind, GeneralizedFraction, MathAtom, MathDelimiter, MathField,
-    MathList, MathListElem, MathStyle, MathSymbol,
+    MathList, MathListElem, MathStyle, MathSymbol, OpLimits,
 };
+use crate::output_files::FileOperation;
+use crate::parser::mode::Mode;
 use crate::parser::Parser;
+use crate::primitive::Primitive;
+use crate::tfm::ExtensibleRecipe;
 use crate::token::Token;
 
+/// The primitives for math material.
+#[cfg(test)]
+pub const HANDLED_PRIMITIVES: &[Primitive] = &[
+    Primitive::Char,
+    Primitive::UMathChar,
+    Primitive::DisplayStyle,
+    Primitive::TextStyle,
+    Primitive::ScriptStyle,
+    Primitive::ScriptScriptStyle,
+    Primitive::DisplayLimits,
+    Primitive::Limits,
+    Primitive::NoLimits,
+    Primitive::VCenter,
+    Primitive::Over,
+    Primitive::Atop,
+    Primitive::Above,
+    Primitive::OverWithDelims,
+    Primitive::AtopWithDelims,
+    Primitive::AboveWithDelims,
+    Primitive::MoveLeft,
+    Primitive::MoveRight,
+    Primitive::Par,
+];
+
 #[derive(Clone)]
 enum InterAtomSpacing {
     None,
@@ -194,6 +222,15 @@ static INTER_ATOM_SPACING: Lazy<
     .collect()
 });
 
+// Returns how far a box needs to be raised so that its center is on the
+// axis.
+fn get_shift_to_center_on_axis(
+    tex_box: &TeXBox,
+    params: &MathFontParams,
+) -> Dimen {
+    params.axis_height - (*tex_box.height() - *tex_box.depth()) / 2
+}
+
 fn get_font_style_for_math_style(style: &MathStyle) -> MathStyle {
     match style {
         MathStyle::DisplayStyle => MathStyle::TextStyle,
@@ -207,98 +244,53 @@ fn get_font_style_for_math_style(style: &MathStyle) -> MathStyle {
     }
 }
 
-// TODO: pull these from \textfont, \scriptfont, and \scriptscriptfont
-static MATH_FONTS: Lazy<HashMap<(MathStyle, u8), Font>> = Lazy::new(|| {
-    [
-        (
-            (MathStyle::TextStyle, 0),
-            Font {
-                font_name: "cmr10".to_string(),
-                scale: Dimen::from_unit(10.0, Unit::Point),
-            },
-        ),
-        (
-            (MathStyle::ScriptStyle, 0),
-            Font {
-                font_name: "cmr7".to_string(),
-                scale: Dimen::from_unit(7.0, Unit::Point),
-            },
-        ),
-        (
-            (MathStyle::ScriptScriptStyle, 0),
-            Font {
-                font_name: "cmr5".to_string(),
-                scale: Dimen::from_unit(5.0, Unit::Point),
-            },
-        ),
-        (
-            (MathStyle::TextStyle, 1),
-            Font {
-                font_name: "cmmi10".to_string(),
-                scale: Dimen::from_unit(10.0, Unit::Point),
-            },
-        ),
-        (
-            (MathStyle::ScriptStyle, 1),
-            Font {
-                font_name: "cmmi7".to_string(),
-                scale: Dimen::from_unit(7.0, Unit::Point),
-            },
-        ),
-        (
-            (MathStyle::ScriptScriptStyle, 1),
-            Font {
-                font_name: "cmmi5".to_string(),
-                scale: Dimen::from_unit(5.0, Unit::Point),
-            },
-        ),
-        (
-            (MathStyle::TextStyle, 2),
-            Font {
-                font_name: "cmsy10".to_string(),
-                scale: Dimen::from_unit(10.0, Unit::Point),
-            },
-        ),
-        (
-            (MathStyle::ScriptStyle, 2),
-            Font {
-                font_name: "cmsy7".to_string(),
-                scale: Dimen::from_unit(7.0, Unit::Point),
-            },
-        ),
-        (
-            (MathStyle::ScriptScriptStyle, 2),
-            Font {
-                font_name: "cmsy5".to_string(),
-                scale: Dimen::from_unit(5.0, Unit::Point),
-            },
-        ),
-        (
-            (MathStyle::TextStyle, 3),
-            Font {
-                font_name: "cmex10".to_string(),
-                scale: Dimen::from_unit(10.0, Unit::Point),
-            },
-        ),
-        (
-            (MathStyle::ScriptStyle, 3),
-            Font {
-                font_name: "cmex10".to_string(),
-                scale: Dimen::from_unit(10.0, Unit::Point),
-            },
-        ),
-        (
-            (MathStyle::ScriptScriptStyle, 3),
-            Font {
-                font_name: "cmex10".to_string(),
-                scale: Dimen::from_unit(10.0, Unit::Point),
-            },
-        ),
-    ]
-    .iter()
-    .cloned()
-    .collect()
-});
+// The font dimensions from the symbol (family 2) and extension (family 3)
+// fonts that are needed to lay out a math list in a given style. Since these
+// only change when the style does, they're looked up once for each style and
+// then passed around, instead of being fetched from the font metrics for each
+// atom. The names match the parameters in Appendix G of the TeXbook.
+struct MathFontParams {
+    x_height: Dimen,
+    quad: Dimen,
+    num1: Dimen,
+    num2: Dimen,
+    num3: Dimen,
+    denom1: Dimen,
+    denom2: Dimen,
+    sup1: Dimen,
+    sup2: Dimen,
+    sup3: Dimen,
+    sub1: Dimen,
+    sub2: Dimen,
+    // These come from the fonts of the superscript and subscript styles,
+    // rather than the font of the current style.
+    sup_drop: Dimen,
+    sub_drop: Dimen,
+    delim1: Dimen,
+    delim2: Dimen,
+    axis_height: Dimen,
+    default_rule_thickness: Dimen,
+    big_op_spacing1: Dimen,
+    big_op_spacing2: Dimen,
+    big_op_spacing3: Dimen,
+    big_op_spacing4: Dimen,
+    big_op_spacing5: Dimen,
+}
+
+impl MathFontParams {
+    // The minimum amount that superscripts are raised in the given style,
+    // which is larger in display style and smaller in cramped styles.
+    fn sup_shift_for_style(&self, style: &MathStyle) -> Dimen {
+        match style {
+            MathStyle::DisplayStyle => self.sup1,
+            MathStyle::DisplayStylePrime
+            | MathStyle::TextStylePrime
+            | MathStyle::ScriptStylePrime
+            | MathStyle::ScriptScriptStylePrime => self.sup3,
+            _ => self.sup2,
+        }
+    }
+}
 
 struct TranslatedNucleus {
     translation: Vec<HorizontalListElem>,
@@ -321,6 +313,7 @@ struct TranslatedMathAtom {
 enum TranslatedMathListElem {
     Atom(TranslatedMathAtom),
     StyleChange(MathStyle),
+    Whatsit(FileOperation),
 }
 
 impl<'a> Parser<'a> {
@@ -328,9 +321,24 @@ impl<'a> Parser<'a> {
         let expanded_token = self.peek_expanded_token();
 
         match self.replace_renamed_token(expanded_token) {
+            // Characters with a math code of "8000 act like active characters
+            // in math mode, so we replace them with the active character and
+            // look at what that expands to instead.
+            // TODO(xymostech): This should also happen for \char.
+            Some(Token::Char(ch, Category::Letter))
+            | Some(Token::Char(ch, Category::Other))
+                if self.state.get_math_code(ch).class == MathClass::Active =>
+            {
+                self.lex_expanded_token();
+                self.add_upcoming_token(Token::Char(ch, Category::Active));
+                self.is_character_head()
+            }
             Some(Token::Char(_, Category::Letter)) => true,
             Some(Token::Char(_, Category::Other)) => true,
-            Some(tok) => self.state.is_token_equal_to_prim(&tok, "char"),
+            Some(tok) => {
+                self.state.is_token_equal_to_prim(&tok, Primitive::Char)
+                    || self.state.get_chardef(&tok).is_some()
+            }
             _ => false,
         }
     }
@@ -342,9 +350,11 @@ impl<'a> Parser<'a> {
         let ch: char = match expanded_renamed_token {
             Some(Token::Char(ch, _)) => ch,
             Some(tok) => {
-                if self.state.is_token_equal_to_prim(&tok, "char") {
+                if self.state.is_token_equal_to_prim(&tok, Primitive::Char) {
                     let char_number = self.parse_8bit_number();
                     char_number as char
+                } else if let Some(char_number) = self.state.get_chardef(&tok) {
+                    char_number as char
                 } else {
                     panic!("invalid char token head");
                 }
@@ -382,8 +392,28 @@ impl<'a> Parser<'a> {
         }
     }
 
+    fn is_umathchar_head(&mut self) -> bool {
+        self.is_next_expanded_token_in_set_of_primitives(&[
+            Primitive::UMathChar,
+        ])
+    }
+
+    fn parse_umathchar(&mut self) -> MathCode {
+        let tok = self.lex_expanded_token().unwrap();
+        if !self
+            .state
+            .is_token_equal_to_prim(&tok, Primitive::UMathChar)
+        {
+            panic!("Invalid Umathchar head: {:?}", tok);
+        }
+
+        self.parse_umath_code()
+    }
+
     fn is_math_symbol_head(&mut self) -> bool {
-        self.is_character_head() || self.is_math_character_head()
+        self.is_character_head()
+            || self.is_math_character_head()
+            || self.is_umathchar_head()
     }
 
     fn parse_math_symbol(&mut self) -> MathCode {
@@ -391,30 +421,34 @@ impl<'a> Parser<'a> {
             self.parse_character_to_math_code()
         } else if self.is_math_character_head() {
             self.parse_math_character_to_math_code()
+        } else if self.is_umathchar_head() {
+            self.parse_umathchar()
         } else {
             panic!("Unimplemented");
         }
     }
 
     fn parse_math_group(&mut self) -> MathList {
-        let begin_group = self.lex_expanded_token();
-        match begin_group {
-            Some(Token::Char(_, Category::BeginGroup)) => (),
-            tok => panic!("Invalid start of math group: {:?}", tok),
+        // Math groups can also be delimited by tokens that have been \let to
+        // braces, like \bgroup and \egroup.
+        if !self.parse_left_brace_expanded() {
+            panic!("{}", "Invalid start of math group");
         }
 
         self.state.push_state();
 
         let math_list = self.parse_math_list();
 
-        self.state.pop_state();
-
+        // The end of the group is read before the group's assignments are
+        // undone, since it might be defined inside of the group.
         let end_group = self.lex_expanded_token();
-        match end_group {
+        match self.replace_renamed_token(end_group) {
             Some(Token::Char(_, Category::EndGroup)) => (),
             tok => panic!("Math group didn't end with an EndGroup: {:?}", tok),
         }
 
+        self.end_group();
+
         math_list
     }
 
@@ -470,37 +504,94 @@ impl<'a> Parser<'a> {
 
     fn is_style_change_head(&mut self) -> bool {
         self.is_next_expanded_token_in_set_of_primitives(&[
-            "displaystyle",
-            "textstyle",
-            "scriptstyle",
-            "scriptscriptstyle",
+            Primitive::DisplayStyle,
+            Primitive::TextStyle,
+            Primitive::ScriptStyle,
+            Primitive::ScriptScriptStyle,
         ])
     }
 
     fn parse_style_change(&mut self) -> MathStyle {
         let tok = self.lex_expanded_token().unwrap();
 
-        if self.state.is_token_equal_to_prim(&tok, "displaystyle") {
+        if self
+            .state
+            .is_token_equal_to_prim(&tok, Primitive::DisplayStyle)
+        {
             MathStyle::DisplayStyle
-        } else if self.state.is_token_equal_to_prim(&tok, "textstyle") {
+        } else if self
+            .state
+            .is_token_equal_to_prim(&tok, Primitive::TextStyle)
+        {
             MathStyle::TextStyle
-        } else if self.state.is_token_equal_to_prim(&tok, "scriptstyle") {
+        } else if self
+            .state
+            .is_token_equal_to_prim(&tok, Primitive::ScriptStyle)
+        {
             MathStyle::ScriptStyle
-        } else if self.state.is_token_equal_to_prim(&tok, "scriptscriptstyle") {
+        } else if self
+            .state
+            .is_token_equal_to_prim(&tok, Primitive::ScriptScriptStyle)
+        {
             MathStyle::ScriptScriptStyle
         } else {
             panic!("Invalid style change");
         }
     }
 
+    fn is_limits_head(&mut self) -> bool {
+        self.is_next_expanded_token_in_set_of_primitives(&[
+            Primitive::DisplayLimits,
+            Primitive::Limits,
+            Primitive::NoLimits,
+        ])
+    }
+
+    fn parse_limits(&mut self) -> OpLimits {
+        let tok = self.lex_expanded_token().unwrap();
+
+        if self
+            .state
+            .is_token_equal_to_prim(&tok, Primitive::DisplayLimits)
+        {
+            OpLimits::Normal
+        } else if self.state.is_token_equal_to_prim(&tok, Primitive::Limits) {
+            OpLimits::Limits
+        } else if self.state.is_token_equal_to_prim(&tok, Primitive::NoLimits) {
+            OpLimits::NoLimits
+        } else {
+            panic!("Invalid limits");
+        }
+    }
+
+    fn is_vcenter_head(&mut self) -> bool {
+        self.is_next_expanded_token_in_set_of_primitives(&[Primitive::VCenter])
+    }
+
+    // Parses a \vcenter, which makes a Vcent atom with the vbox as its
+    // nucleus. The box is centered on the axis when the atom is translated,
+    // since that's when we know which style's axis height to use.
+    fn parse_vcenter(&mut self) -> MathAtom {
+        let tok = self.lex_expanded_token().unwrap();
+        if !self.state.is_token_equal_to_prim(&tok, Primitive::VCenter) {
+            panic!("Invalid vcenter head: {:?}", tok);
+        }
+
+        let vbox = self.parse_vertical_box_group();
+        MathAtom {
+            kind: AtomKind::Vcent,
+            ..MathAtom::from_box(TeXBox::VerticalBox(vbox))
+        }
+    }
+
     fn is_generalized_fraction_head(&mut self) -> bool {
         self.is_next_expanded_token_in_set_of_primitives(&[
-            "over",
-            "atop",
-            "above",
-            "overwithdelims",
-            "atopwithdelims",
-            "abovewithdelims",
+            Primitive::Over,
+            Primitive::Atop,
+            Primitive::Above,
+            Primitive::OverWithDelims,
+            Primitive::AtopWithDelims,
+            Primitive::AboveWithDelims,
         ])
     }
 
@@ -509,7 +600,7 @@ impl<'a> Parser<'a> {
     ) -> (Option<MathDelimiter>, Option<MathDelimiter>, Dimen) {
         let tok = self.lex_expanded_token().unwrap();
 
-        if self.state.is_token_equal_to_prim(&tok, "atop") {
+        if self.state.is_token_equal_to_prim(&tok, Primitive::Atop) {
             (None, None, Dimen::zero())
         } else {
             panic!("unimplemented");
@@ -517,6 +608,8 @@ impl<'a> Parser<'a> {
     }
 
     pub fn parse_math_list(&mut self) -> MathList {
+        self.push_mode(Mode::Math);
+
         let mut current_list = Vec::new();
 
         // Keep track of whether there's been a generalized fraction operation
@@ -551,15 +644,56 @@ impl<'a> Parser<'a> {
                     self.parse_math_subscript(last_atom)
                 }));
             } else if self.is_assignment_head() {
-                self.parse_assignment(None);
+                self.parse_assignment();
+            } else if self.is_output_file_command_head() {
+                if let Some(operation) = self.parse_output_file_command() {
+                    current_list.push(MathListElem::Whatsit(operation));
+                }
+            } else if self.is_input_file_command_head() {
+                self.parse_input_file_command();
+            } else if self.is_message_head() {
+                self.parse_message();
+            } else if self.is_shipout_head() {
+                self.parse_shipout();
+            } else if self.is_semi_simple_group_head() {
+                self.parse_semi_simple_group_head();
             } else if self.is_style_change_head() {
                 let style_change = self.parse_style_change();
                 current_list.push(MathListElem::StyleChange(style_change));
+            } else if self.is_limits_head() {
+                let limits = self.parse_limits();
+
+                match current_list.last_mut() {
+                    Some(MathListElem::Atom(atom))
+                        if atom.kind == AtomKind::Op =>
+                    {
+                        atom.limits = limits;
+                    }
+                    _ => panic!("Limit controls must follow a math operator"),
+                }
+            } else if self.is_vcenter_head() {
+                current_list.push(MathListElem::Atom(self.parse_vcenter()));
             } else if self.is_box_head() {
                 if let Some(tex_box) = self.parse_box() {
                     current_list
                         .push(MathListElem::Atom(MathAtom::from_box(tex_box)));
                 }
+            } else if self.is_next_expanded_token_in_set_of_primitives(&[
+                Primitive::MoveLeft,
+                Primitive::MoveRight,
+            ]) {
+                self.report_illegal_case(&[
+                    Primitive::MoveLeft,
+                    Primitive::MoveRight,
+                ]);
+            } else if self.is_vertical_material_head()
+                || self.is_next_expanded_token_in_set_of_primitives(&[
+                    Primitive::Par,
+                ])
+            {
+                // TeX would insert a $ here to get out of math mode and then
+                // continue, but we can't recover from this.
+                panic!("Missing $ inserted");
             } else if self.is_generalized_fraction_head() {
                 if list_fraction.is_some() {
                     panic!("Ambiguous generalized fraction");
@@ -580,7 +714,8 @@ impl<'a> Parser<'a> {
                 });
                 current_list = vec![];
             } else {
-                match self.peek_expanded_token() {
+                let expanded_token = self.peek_expanded_token();
+                match self.replace_renamed_token(expanded_token) {
                     Some(Token::Char(_, Category::BeginGroup)) => {
                         let inner_list = self.parse_math_group();
                         current_list.push(MathListElem::Atom(
@@ -593,11 +728,13 @@ impl<'a> Parser<'a> {
                     Some(Token::Char(_, Category::EndGroup)) => break,
                     Some(Token::Char(_, Category::MathShift)) => break,
                     None => break,
-                    _ => panic!("unimplemented"),
+                    _ => self.fail_on_unexpected_token(),
                 }
             }
         }
 
+        self.pop_mode();
+
         match list_fraction {
             None => current_list,
             Some(mut fraction) => {
@@ -694,16 +831,11 @@ impl<'a> Parser<'a> {
     ) -> TeXBox {
         match field {
             MathField::Symbol(symbol) => {
-                let font = MATH_FONTS
-                    .get(&(
-                        get_font_style_for_math_style(style),
-                        symbol.family_number,
-                    ))
-                    .unwrap();
+                let font = self.get_math_font(style, symbol.family_number);
 
                 let char_elem = HorizontalListElem::Char {
-                    chr: symbol.position_number as char,
-                    font: font.clone(),
+                    chr: symbol.get_char(),
+                    font,
                 };
 
                 let hbox = self.add_to_natural_layout_horizontal_box(
@@ -714,6 +846,11 @@ impl<'a> Parser<'a> {
                 TeXBox::HorizontalBox(hbox)
             }
             MathField::TeXBox(tex_box) => tex_box,
+            // Font changes inside of a math group (like {\tt\hbox{x}x}) were
+            // already applied while the list was parsed, so they only affect
+            // boxes built in text mode. The symbols in the list still use the
+            // family fonts, which are looked up here when the list is
+            // converted.
             MathField::MathList(list) => {
                 let hlist = self
                     .convert_math_list_to_horizontal_list(list, style.clone());
@@ -729,57 +866,235 @@ impl<'a> Parser<'a> {
         }
     }
 
+    fn get_math_font(&self, style: &MathStyle, family: u8) -> Font {
+        self.state
+            .get_math_font(&get_font_style_for_math_style(style), family)
+    }
+
+    // Looks up a parameter of the family 2 or 3 font for the given style. If
+    // the font doesn't have enough parameters, an error is reported and we
+    // use zero instead so that we can keep going.
+    fn get_math_font_dimension(
+        &self,
+        style: &MathStyle,
+        family: u8,
+        dimen_number: usize,
+    ) -> Dimen {
+        let font = self.get_math_font(style, family);
+        if !self.state.is_valid_math_font(&font, family) {
+            return Dimen::zero();
+        }
+
+        self.state
+            .with_metrics_for_font(&font, |metrics| {
+                metrics.get_font_dimension(dimen_number)
+            })
+            .unwrap()
+    }
+
+    fn get_math_font_params(&self, style: &MathStyle) -> MathFontParams {
+        let sym =
+            |dimen_number| self.get_math_font_dimension(style, 2, dimen_number);
+        let ex =
+            |dimen_number| self.get_math_font_dimension(style, 3, dimen_number);
+
+        MathFontParams {
+            x_height: sym(5),
+            quad: sym(6),
+            num1: sym(8),
+            num2: sym(9),
+            num3: sym(10),
+            denom1: sym(11),
+            denom2: sym(12),
+            sup1: sym(13),
+            sup2: sym(14),
+            sup3: sym(15),
+            sub1: sym(16),
+            sub2: sym(17),
+            sup_drop: self.get_math_font_dimension(&style.up_arrow(), 2, 18),
+            sub_drop: self.get_math_font_dimension(&style.down_arrow(), 2, 19),
+            delim1: sym(20),
+            delim2: sym(21),
+            axis_height: sym(22),
+            default_rule_thickness: ex(8),
+            big_op_spacing1: ex(9),
+            big_op_spacing2: ex(10),
+            big_op_spacing3: ex(11),
+            big_op_spacing4: ex(12),
+            big_op_spacing5: ex(13),
+        }
+    }
+
+    // Builds a box containing the glyph for the given character that is at
+    // least min_size tall (counting its depth), by walking through the
+    // character's successors in the font. If we reach a character with an
+    // extensible recipe before finding one that is big enough, the glyph is
+    // assembled from the recipe instead. If no glyph is big enough and there's
+    // no recipe, we use the last (and biggest) successor.
+    fn build_extensible_glyph(
+        &mut self,
+        font: &Font,
+        chr: char,
+        min_size: Dimen,
+    ) -> TeXBox {
+        let mut chr = chr;
+
+        loop {
+            let (size, successor, maybe_recipe) = self
+                .state
+                .with_metrics_for_font(font, |metrics| {
+                    (
+                        metrics.get_height(chr) + metrics.get_depth(chr),
+                        metrics.get_successor(chr),
+                        metrics.get_extensible_recipe(chr),
+                    )
+                })
+                .unwrap();
+
+            if size >= min_size {
+                break;
+            }
+
+            if let Some(recipe) = maybe_recipe {
+                return TeXBox::VerticalBox(
+                    self.stack_extensible_recipe(font, &recipe, min_size),
+                );
+            }
+
+            if successor == chr {
+                break;
+            }
+            chr = successor;
+        }
+
+        TeXBox::HorizontalBox(self.add_to_natural_layout_horizontal_box(
+            HorizontalBox::empty(),
+            HorizontalListElem::Char {
+                chr,
+                font: font.clone(),
+            },
+        ))
+    }
+
+    // Stacks the pieces of an extensible recipe into a vertical box, repeating
+    // the extender until the whole thing is at least min_size tall. Like in
+    // TeX, the extenders are split evenly above and below the middle piece,
+    // and the height of the box is the height of its topmost piece.
+    fn stack_extensible_recipe(
+        &mut self,
+        font: &Font,
+        recipe: &ExtensibleRecipe,
+        min_size: Dimen,
+    ) -> VerticalBox {
+        let (rep_size, mut total_size) = self
+            .state
+            .with_metrics_for_font(font, |metrics| {
+                let piece_size = |chr: char| {
+                    metrics.get_height(chr) + metrics.get_depth(chr)
+                };
+
+                let fixed_size = [recipe.top, recipe.mid, recipe.bot]
+                    .iter()
+                    .flatten()
+                    .fold(Dimen::zero(), |size, &chr| size + piece_size(chr));
+
+                (piece_size(recipe.rep), fixed_size)
+            })
+            .unwrap();
+
+        let mut num_reps = 0;
+        if rep_size > Dimen::zero() {
+            while total_size < min_size {
+                total_size = total_size + rep_size;
+                if recipe.mid.is_some() {
+                    total_size = total_size + rep_size;
+                }
+                num_reps += 1;
+            }
+        }
+
+        let reps = vec![recipe.rep; num_reps];
+        let mut pieces = Vec::new();
+        pieces.extend(recipe.top);
+        pieces.extend(&reps);
+        if let Some(mid) = recipe.mid {
+            pieces.push(mid);
+            pieces.extend(&reps);
+        }
+        pieces.extend(recipe.bot);
+
+        let mut list = Vec::new();
+        for chr in pieces {
+            let piece_box = self.add_to_natural_layout_horizontal_box(
+                HorizontalBox::empty(),
+                HorizontalListElem::Char {
+                    chr,
+                    font: font.clone(),
+                },
+            );
+            list.push(VerticalListElem::Box {
+                tex_box: TeXBox::HorizontalBox(piece_box),
+                shift: Dimen::zero(),
+            });
+        }
+
+        let height = match list.first() {
+            Some(VerticalListElem::Box { tex_box, .. }) => *tex_box.height(),
+            _ => Dimen::zero(),
+        };
+        let width = self
+            .state
+            .with_metrics_for_font(font, |metrics| {
+                metrics.get_width(recipe.rep)
+            })
+            .unwrap();
+
+        VerticalBox {
+            height,
+            depth: total_size - height,
+            width,
+
+            list,
+            glue_set_ratio: None,
+        }
+    }
+
     fn translate_op_atom_nucleus(
         &mut self,
         nucleus: Option<MathField>,
         current_style: &MathStyle,
+        params: &MathFontParams,
     ) -> TranslatedNucleus {
         match nucleus {
             Some(MathField::Symbol(symbol)) => {
-                let font = &MATH_FONTS[&(
-                    get_font_style_for_math_style(current_style),
-                    symbol.family_number,
-                )];
-
-                let position_number = match current_style {
-                    // In DisplayStyle, we fetch the successor
-                    // for symbol op atoms
+                let font =
+                    self.get_math_font(current_style, symbol.family_number);
+
+                let chr = symbol.get_char();
+
+                let glyph_box = match current_style {
+                    // In DisplayStyle, we use the successor for symbol op
+                    // atoms
                     MathStyle::DisplayStyle | MathStyle::DisplayStylePrime => {
-                        self.state
-                            .with_metrics_for_font(font, |metrics| {
-                                metrics.get_successor(
-                                    symbol.position_number as char,
-                                )
+                        let successor = self
+                            .state
+                            .with_metrics_for_font(&font, |metrics| {
+                                metrics.get_successor(chr)
                             })
-                            .unwrap() as u8
+                            .unwrap();
+                        self.build_extensible_glyph(
+                            &font,
+                            successor,
+                            Dimen::zero(),
+                        )
                     }
-                    _ => symbol.position_number,
-                };
-
-                let elem = HorizontalListElem::Char {
-                    chr: position_number as char,
-                    font: font.clone(),
+                    _ => self.build_extensible_glyph(&font, chr, Dimen::zero()),
                 };
 
-                let boxed_elem = self.add_to_natural_layout_horizontal_box(
-                    HorizontalBox::empty(),
-                    elem,
-                );
-
-                let sym_font = &MATH_FONTS
-                    [&(get_font_style_for_math_style(&current_style), 2)];
-                let axis_height = self
-                    .state
-                    .with_metrics_for_font(sym_font, |metrics| {
-                        metrics.get_font_dimension(22)
-                    })
-                    .unwrap();
-
-                let shift =
-                    axis_height - (boxed_elem.height - boxed_elem.depth) / 2;
+                let shift = get_shift_to_center_on_axis(&glyph_box, params);
 
                 let char_elem = HorizontalListElem::Box {
-                    tex_box: TeXBox::HorizontalBox(boxed_elem),
+                    tex_box: glyph_box,
                     shift,
                 };
 
@@ -816,6 +1131,34 @@ impl<'a> Parser<'a> {
         }
     }
 
+    // Vcent atoms are centered on the axis like symbol Op atoms are, but
+    // they keep their height and depth so that the atoms around them are
+    // placed correctly.
+    fn translate_vcenter_atom_nucleus(
+        &mut self,
+        nucleus: Option<MathField>,
+        params: &MathFontParams,
+    ) -> TranslatedNucleus {
+        let vbox = match nucleus {
+            Some(MathField::TeXBox(tex_box)) => tex_box,
+            other => panic!("Invalid vcenter nucleus: {:?}", other),
+        };
+
+        let shift = get_shift_to_center_on_axis(&vbox, params);
+        let effective_height = *vbox.height() + shift;
+        let effective_depth = *vbox.depth() - shift;
+
+        TranslatedNucleus {
+            translation: vec![HorizontalListElem::Box {
+                tex_box: vbox,
+                shift,
+            }],
+            nucleus_is_symbol: false,
+            effective_height,
+            effective_depth,
+        }
+    }
+
     fn translate_atom_nucleus(
         &mut self,
         nucleus: Option<MathField>,
@@ -823,14 +1166,12 @@ impl<'a> Parser<'a> {
     ) -> TranslatedNucleus {
         match nucleus {
             Some(MathField::Symbol(symbol)) => {
-                let font = &MATH_FONTS[&(
-                    get_font_style_for_math_style(current_style),
-                    symbol.family_number,
-                )];
+                let font =
+                    self.get_math_font(current_style, symbol.family_number);
 
                 let char_elem = HorizontalListElem::Char {
-                    chr: symbol.position_number as char,
-                    font: font.clone(),
+                    chr: symbol.get_char(),
+                    font,
                 };
 
                 TranslatedNucleus {
@@ -872,42 +1213,20 @@ impl<'a> Parser<'a> {
         subscript: Option<MathField>,
         translated_nucleus: TranslatedNucleus,
         current_style: &MathStyle,
+        params: &MathFontParams,
     ) -> Vec<HorizontalListElem> {
-        let sup_sym_font = &MATH_FONTS
-            [&(get_font_style_for_math_style(&current_style.up_arrow()), 2)];
-        let sub_sym_font = &MATH_FONTS[&(
-            get_font_style_for_math_style(&current_style.down_arrow()),
-            2,
-        )];
-
-        let sup_drop = self
-            .state
-            .with_metrics_for_font(sup_sym_font, |metrics| {
-                metrics.get_font_dimension(18)
-            })
-            .unwrap();
-        let sub_drop = self
-            .state
-            .with_metrics_for_font(sub_sym_font, |metrics| {
-                metrics.get_font_dimension(19)
-            })
-            .unwrap();
-
-        let sym_font =
-            &MATH_FONTS[&(get_font_style_for_math_style(&current_style), 2)];
-
         // The amount that the superscript and subscript will be
         // shifted with respect to the nucleus. Called u and v in
         // the TeXbook.
         let mut sup_shift = if translated_nucleus.nucleus_is_symbol {
             Dimen::zero()
         } else {
-            translated_nucleus.effective_height - sup_drop
+            translated_nucleus.effective_height - params.sup_drop
         };
         let mut sub_shift = if translated_nucleus.nucleus_is_symbol {
             Dimen::zero()
         } else {
-            translated_nucleus.effective_depth + sub_drop
+            translated_nucleus.effective_depth + params.sub_drop
         };
 
         // TODO(xymostech): Pull this from \scriptspace
@@ -921,35 +1240,9 @@ impl<'a> Parser<'a> {
                 );
                 *sup_box.mut_width() = *sup_box.width() + scriptspace;
 
-                let (sup_shift_for_style, x_height) = self
-                    .state
-                    .with_metrics_for_font(sym_font, |metrics| {
-                        let sup_shift_for_style = match current_style {
-                            MathStyle::DisplayStyle => {
-                                metrics.get_font_dimension(13)
-                            }
-                            MathStyle::DisplayStylePrime => {
-                                metrics.get_font_dimension(15)
-                            }
-                            MathStyle::TextStylePrime => {
-                                metrics.get_font_dimension(15)
-                            }
-                            MathStyle::ScriptStylePrime => {
-                                metrics.get_font_dimension(15)
-                            }
-                            MathStyle::ScriptScriptStylePrime => {
-                                metrics.get_font_dimension(15)
-                            }
-                            _ => metrics.get_font_dimension(14),
-                        };
-
-                        (sup_shift_for_style, metrics.get_font_dimension(5))
-                    })
-                    .unwrap();
-
                 sup_shift = max(
-                    max(sup_shift, sup_shift_for_style),
-                    *sup_box.depth() + x_height.abs() / 4,
+                    max(sup_shift, params.sup_shift_for_style(current_style)),
+                    *sup_box.depth() + params.x_height.abs() / 4,
                 );
 
                 Some(HorizontalListElem::Box {
@@ -964,19 +1257,9 @@ impl<'a> Parser<'a> {
                 );
                 *sub_box.mut_width() = *sub_box.width() + scriptspace;
 
-                let (sub1, x_height) = self
-                    .state
-                    .with_metrics_for_font(sym_font, |metrics| {
-                        (
-                            metrics.get_font_dimension(16),
-                            metrics.get_font_dimension(5),
-                        )
-                    })
-                    .unwrap();
-
                 sub_shift = max(
-                    max(sub_shift, sub1),
-                    *sub_box.height() - x_height.abs() * 4 / 5,
+                    max(sub_shift, params.sub1),
+                    *sub_box.height() - params.x_height.abs() * 4 / 5,
                 );
 
                 Some(HorizontalListElem::Box {
@@ -991,39 +1274,9 @@ impl<'a> Parser<'a> {
                 );
                 *sup_box.mut_width() = *sup_box.width() + scriptspace;
 
-                let (sup_shift_for_style, sub_2, x_height) = self
-                    .state
-                    .with_metrics_for_font(sym_font, |metrics| {
-                        let sup_shift_for_style = match current_style {
-                            MathStyle::DisplayStyle => {
-                                metrics.get_font_dimension(13)
-                            }
-                            MathStyle::DisplayStylePrime => {
-                                metrics.get_font_dimension(15)
-                            }
-                            MathStyle::TextStylePrime => {
-                                metrics.get_font_dimension(15)
-                            }
-                            MathStyle::ScriptStylePrime => {
-                                metrics.get_font_dimension(15)
-                            }
-                            MathStyle::ScriptScriptStylePrime => {
-                                metrics.get_font_dimension(15)
-                            }
-                            _ => metrics.get_font_dimension(14),
-                        };
-
-                        (
-                            sup_shift_for_style,
-                            metrics.get_font_dimension(17),
-                            metrics.get_font_dimension(5),
-                        )
-                    })
-                    .unwrap();
-
                 sup_shift = max(
-                    max(sup_shift, sup_shift_for_style),
-                    *sup_box.depth() + x_height.abs() / 4,
+                    max(sup_shift, params.sup_shift_for_style(current_style)),
+                    *sup_box.depth() + params.x_height.abs() / 4,
                 );
 
                 let mut sub_box = self.convert_math_field_to_box(
@@ -1037,16 +1290,9 @@ impl<'a> Parser<'a> {
                 let sub_height = *sub_box.height();
                 let sub_depth = *sub_box.depth();
 
-                sub_shift = max(sub_shift, sub_2);
+                sub_shift = max(sub_shift, params.sub2);
 
-                let ext_font = &MATH_FONTS
-                    [&(get_font_style_for_math_style(&current_style), 3)];
-                let default_rule_thickness = self
-                    .state
-                    .with_metrics_for_font(ext_font, |metrics| {
-                        metrics.get_font_dimension(8)
-                    })
-                    .unwrap();
+                let default_rule_thickness = params.default_rule_thickness;
 
                 if (sup_shift - sup_depth) - (sub_height - sub_shift)
                     < default_rule_thickness * 4
@@ -1059,7 +1305,7 @@ impl<'a> Parser<'a> {
                     );
 
                     let final_shift =
-                        x_height.abs() * 4 / 5 - (sup_shift - sup_depth);
+                        params.x_height.abs() * 4 / 5 - (sup_shift - sup_depth);
 
                     if final_shift > Dimen::zero() {
                         sup_shift = sup_shift + final_shift;
@@ -1119,6 +1365,119 @@ impl<'a> Parser<'a> {
         translation
     }
 
+    // Stacks the superscript and subscript of an Op atom above and below its
+    // nucleus, following rule 13a of Appendix G in the TeXbook.
+    fn add_limits_to_op_atom_with_translated_nucleus(
+        &mut self,
+        superscript: Option<MathField>,
+        subscript: Option<MathField>,
+        translated_nucleus: TranslatedNucleus,
+        current_style: &MathStyle,
+        params: &MathFontParams,
+    ) -> Vec<HorizontalListElem> {
+        // Packing the nucleus into a box means that the shift onto the axis
+        // from `translate_op_atom_nucleus()` is included in its height and
+        // depth.
+        let nucleus_box = TeXBox::HorizontalBox(
+            HorizontalBox::create_from_horizontal_list_with_layout(
+                translated_nucleus.translation,
+                &BoxLayout::Natural,
+                self.state,
+            ),
+        );
+        let maybe_sup_box = superscript.map(|superscript| {
+            self.convert_math_field_to_box(
+                superscript,
+                &current_style.up_arrow(),
+            )
+        });
+        let maybe_sub_box = subscript.map(|subscript| {
+            self.convert_math_field_to_box(
+                subscript,
+                &current_style.down_arrow(),
+            )
+        });
+
+        // Everything is centered in a box as wide as the widest of the three.
+        let mut width = *nucleus_box.width();
+        if let Some(sup_box) = &maybe_sup_box {
+            width = max(width, *sup_box.width());
+        }
+        if let Some(sub_box) = &maybe_sub_box {
+            width = max(width, *sub_box.width());
+        }
+
+        // TODO(xymostech): Shift the scripts by half of the italic correction
+        // of the nucleus once we can read that from the font metrics.
+        let nucleus_box = self.rebox_box_to_width(nucleus_box, width);
+
+        // The baseline of the resulting stack is the baseline of the nucleus,
+        // so everything above it goes into the height and everything below it
+        // goes into the depth.
+        let mut height = *nucleus_box.height();
+        let mut depth = *nucleus_box.depth();
+        let mut list = Vec::new();
+
+        if let Some(sup_box) = maybe_sup_box {
+            let sup_box = self.rebox_box_to_width(sup_box, width);
+            let sup_kern = max(
+                params.big_op_spacing1,
+                params.big_op_spacing3 - *sup_box.depth(),
+            );
+
+            height = height
+                + params.big_op_spacing5
+                + *sup_box.height()
+                + *sup_box.depth()
+                + sup_kern;
+
+            list.push(VerticalListElem::Kern(params.big_op_spacing5));
+            list.push(VerticalListElem::Box {
+                tex_box: sup_box,
+                shift: Dimen::zero(),
+            });
+            list.push(VerticalListElem::Kern(sup_kern));
+        }
+
+        list.push(VerticalListElem::Box {
+            tex_box: nucleus_box,
+            shift: Dimen::zero(),
+        });
+
+        if let Some(sub_box) = maybe_sub_box {
+            let sub_box = self.rebox_box_to_width(sub_box, width);
+            let sub_kern = max(
+                params.big_op_spacing2,
+                params.big_op_spacing4 - *sub_box.height(),
+            );
+
+            depth = depth
+                + sub_kern
+                + *sub_box.height()
+                + *sub_box.depth()
+                + params.big_op_spacing5;
+
+            list.push(VerticalListElem::Kern(sub_kern));
+            list.push(VerticalListElem::Box {
+                tex_box: sub_box,
+                shift: Dimen::zero(),
+            });
+            list.push(VerticalListElem::Kern(params.big_op_spacing5));
+        }
+
+        vec![HorizontalListElem::Box {
+            tex_box: TeXBox::VerticalBox(VerticalBox {
+                height,
+                depth,
+                width,
+
+                list,
+                glue_set_ratio: None,
+            }),
+            shift: Dimen::zero(),
+        }]
+    }
+
     fn generate_delimiter_box(
         &mut self,
         maybe_delim: Option<MathDelimiter>,
@@ -1145,6 +1504,9 @@ impl<'a> Parser<'a> {
         let mut elems_after_first_pass: Vec<TranslatedMathListElem> =
             Vec::new();
         let mut current_style = start_style.clone();
+        // The font params for the current style, which are looked up the first
+        // time they're needed after each style change.
+        let mut maybe_params: Option<MathFontParams> = None;
         let mut prev_atom_kind = None;
 
         for elem in list {
@@ -1155,6 +1517,7 @@ impl<'a> Parser<'a> {
                         | AtomKind::Open
                         | AtomKind::Inner
                         | AtomKind::Op => atom.kind,
+                        AtomKind::Vcent => AtomKind::Ord,
                         AtomKind::Bin => match prev_atom_kind {
                             None => AtomKind::Ord,
                             Some(AtomKind::Bin) => AtomKind::Ord,
@@ -1191,10 +1554,30 @@ impl<'a> Parser<'a> {
 
                     prev_atom_kind = Some(atom_kind);
 
+                    let uses_limits = atom.kind == AtomKind::Op
+                        && match atom.limits {
+                            OpLimits::Normal => {
+                                current_style >= MathStyle::DisplayStylePrime
+                            }
+                            OpLimits::Limits => true,
+                            OpLimits::NoLimits => false,
+                        };
+
+                    let params: &MathFontParams = maybe_params
+                        .get_or_insert_with(|| {
+                            self.get_math_font_params(&current_style)
+                        });
+
                     let translated_nucleus = if atom.kind == AtomKind::Op {
                         self.translate_op_atom_nucleus(
                             atom.nucleus,
                             &current_style,
+                            params,
+                        )
+                    } else if atom.kind == AtomKind::Vcent {
+                        self.translate_vcenter_atom_nucleus(
+                            atom.nucleus,
+                            params,
                         )
                     } else {
                         self.translate_atom_nucleus(
@@ -1203,7 +1586,17 @@ impl<'a> Parser<'a> {
                         )
                     };
 
-                    let atom_translation = self.add_superscripts_and_subscripts_to_atom_with_translated_nucleus(atom.superscript, atom.subscript, translated_nucleus, &current_style);
+                    let atom_translation = if uses_limits {
+                        self.add_limits_to_op_atom_with_translated_nucleus(
+                            atom.superscript,
+                            atom.subscript,
+                            translated_nucleus,
+                            &current_style,
+                            params,
+                        )
+                    } else {
+                        self.add_superscripts_and_subscripts_to_atom_with_translated_nucleus(atom.superscript, atom.subscript, translated_nucleus, &current_style, params)
+                    };
 
                     let translated_atom = TranslatedMathAtom {
                         kind: atom_kind,
@@ -1278,41 +1671,23 @@ impl<'a> Parser<'a> {
                         _ => {}
                     }
 
-                    let sym_font = &MATH_FONTS
-                        [&(get_font_style_for_math_style(&current_style), 2)];
-
-                    let (mut numerator_shift, mut denominator_shift) = self
-                        .state
-                        .with_metrics_for_font(sym_font, |metrics| {
-                            if current_style > MathStyle::TextStyle {
-                                (
-                                    metrics.get_font_dimension(8),
-                                    metrics.get_font_dimension(11),
-                                )
-                            } else if bar_height == Dimen::zero() {
-                                (
-                                    metrics.get_font_dimension(10),
-                                    metrics.get_font_dimension(12),
-                                )
-                            } else {
-                                (
-                                    metrics.get_font_dimension(9),
-                                    metrics.get_font_dimension(12),
-                                )
-                            }
-                        })
-                        .unwrap();
-
-                    let ex_font = &MATH_FONTS
-                        [&(get_font_style_for_math_style(&current_style), 3)];
+                    let params: &MathFontParams = maybe_params
+                        .get_or_insert_with(|| {
+                            self.get_math_font_params(&current_style)
+                        });
+
+                    let (mut numerator_shift, mut denominator_shift) =
+                        if current_style > MathStyle::TextStyle {
+                            (params.num1, params.denom1)
+                        } else if bar_height == Dimen::zero() {
+                            (params.num3, params.denom2)
+                        } else {
+                            (params.num2, params.denom2)
+                        };
 
                     let stack = if bar_height == Dimen::zero() {
-                        let default_rule_thickness = self
-                            .state
-                            .with_metrics_for_font(ex_font, |metrics| {
-                                metrics.get_font_dimension(8)
-                            })
-                            .unwrap();
+                        let default_rule_thickness =
+                            params.default_rule_thickness;
 
                         let minimum_clearance =
                             if current_style > MathStyle::TextStyle {
@@ -1377,28 +1752,19 @@ impl<'a> Parser<'a> {
                         panic!("unimplemented");
                     };
 
-                    let min_delim_size = self
-                        .state
-                        .with_metrics_for_font(sym_font, |metrics| {
-                            if current_style > MathStyle::TextStyle {
-                                metrics.get_font_dimension(20)
-                            } else {
-                                metrics.get_font_dimension(21)
-                            }
-                        })
-                        .unwrap();
+                    let min_delim_size = if current_style > MathStyle::TextStyle
+                    {
+                        params.delim1
+                    } else {
+                        params.delim2
+                    };
 
                     let left_delim_box =
                         self.generate_delimiter_box(left_delim, min_delim_size);
                     let right_delim_box = self
                         .generate_delimiter_box(right_delim, min_delim_size);
 
-                    let axis_height = self
-                        .state
-                        .with_metrics_for_font(sym_font, |metrics| {
-                            metrics.get_font_dimension(22)
-                        })
-                        .unwrap();
+                    let axis_height = params.axis_height;
 
                     let left_shift = axis_height
                         - (*left_delim_box.height() - *left_delim_box.depth())
@@ -1428,9 +1794,14 @@ impl<'a> Parser<'a> {
                 }
                 MathListElem::StyleChange(new_style) => {
                     current_style = new_style.clone();
+                    maybe_params = None;
                     elems_after_first_pass
                         .push(TranslatedMathListElem::StyleChange(new_style));
                 }
+                MathListElem::Whatsit(operation) => {
+                    elems_after_first_pass
+                        .push(TranslatedMathListElem::Whatsit(operation));
+                }
                 _ => {
                     panic!("unimplemented math list elem: {:?}", elem);
                 }
@@ -1440,6 +1811,7 @@ impl<'a> Parser<'a> {
         let mut resulting_horizontal_list: Vec<HorizontalListElem> = Vec::new();
         let mut maybe_last_atom_kind: Option<AtomKind> = None;
         let mut current_style = start_style;
+        let mut maybe_params: Option<MathFontParams> = None;
 
         for elem in elems_after_first_pass {
             match elem {
@@ -1450,19 +1822,11 @@ impl<'a> Parser<'a> {
                             &atom.kind,
                             &current_style,
                         ) {
-                            let sym_font = &MATH_FONTS[&(
-                                get_font_style_for_math_style(&current_style),
-                                2,
-                            )];
-
-                            let quad = self
-                                .state
-                                .with_metrics_for_font(sym_font, |metrics| {
-                                    metrics.get_font_dimension(6)
-                                })
-                                .unwrap();
-
-                            let skip = muskip.to_glue(quad);
+                            let params =
+                                maybe_params.get_or_insert_with(|| {
+                                    self.get_math_font_params(&current_style)
+                                });
+                            let skip = muskip.to_glue(params.quad);
 
                             resulting_horizontal_list
                                 .push(HorizontalListElem::HSkip(skip));
@@ -1475,6 +1839,11 @@ impl<'a> Parser<'a> {
                 }
                 TranslatedMathListElem::StyleChange(new_style) => {
                     current_style = new_style;
+                    maybe_params = None;
+                }
+                TranslatedMathListElem::Whatsit(operation) => {
+                    resulting_horizontal_list
+                        .push(HorizontalListElem::Whatsit(operation));
                 }
             }
         }
@@ -1486,8 +1855,32 @@ impl<'a> Parser<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
     use crate::boxes::{GlueSetRatio, GlueSetRatioKind};
-    use crate::testing::with_parser;
+    use crate::state::TeXState;
+    use crate::testing::{
+        debug_format_horizontal_list, with_parser, SharedWriter,
+    };
+
+    fn assert_math_list_converts_to_dump(
+        math_list_lines: &[&str],
+        expected_dump: &[&str],
+    ) {
+        with_parser(math_list_lines, |parser| {
+            let math_list = parser.parse_math_list();
+            let horizontal_list = parser.convert_math_list_to_horizontal_list(
+                math_list,
+                MathStyle::TextStyle,
+            );
+
+            assert_eq!(
+                debug_format_horizontal_list(&horizontal_list, parser.state),
+                expected_dump.join("\n") + "\n"
+            );
+        });
+    }
 
     fn assert_math_list_converts_to_horizontal_list(
         math_list_lines: &[&str],
@@ -1531,7 +1924,7 @@ mod tests {
     #[test]
     fn it_parses_math_symbols_from_chardefs() {
         with_parser(&[r"\let\x=z%", r"\x%"], |parser| {
-            parser.parse_assignment(None);
+            parser.parse_assignment();
 
             assert_eq!(
                 parser.parse_math_symbol(),
@@ -1540,6 +1933,95 @@ mod tests {
         });
     }
 
+    #[test]
+    fn it_renders_umathchars_from_their_family_font() {
+        with_parser(
+            &[
+                r"\font\tenrm=cmr10 \textfont5=\tenrm%",
+                r#"\Umathchardef\x=0 5 "42 %"#,
+                r#"\x\Umathchar 0 5 "43 %"#,
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                let math_list = parser.parse_math_list();
+                assert_eq!(
+                    math_list,
+                    vec![
+                        MathListElem::Atom(MathAtom::from_math_code(
+                            &MathCode::from_umath_parts(0, 5, 0x42)
+                        )),
+                        MathListElem::Atom(MathAtom::from_math_code(
+                            &MathCode::from_umath_parts(0, 5, 0x43)
+                        )),
+                    ]
+                );
+
+                let cmr10 = Font {
+                    font_name: "cmr10".to_string(),
+                    scale: Dimen::from_unit(10.0, Unit::Point),
+                };
+                let chars: Vec<(char, Font)> = parser
+                    .convert_math_list_to_horizontal_list(
+                        math_list,
+                        MathStyle::TextStyle,
+                    )
+                    .into_iter()
+                    .map(|elem| match elem {
+                        HorizontalListElem::Char { chr, font } => (chr, font),
+                        other => panic!("Expected a char, got {:?}", other),
+                    })
+                    .collect();
+                assert_eq!(chars, vec![('B', cmr10.clone()), ('C', cmr10)]);
+            },
+        );
+    }
+
+    #[test]
+    fn it_uses_the_current_font_only_for_boxes_in_math_lists() {
+        with_parser(
+            &[r"\font\tt=cmtt10 %", r"\hbox{\tt x}x{\tt\hbox{x}x}%"],
+            |parser| {
+                parser.parse_assignment();
+
+                let math_list = parser.parse_math_list();
+                let hlist = parser.convert_math_list_to_horizontal_list(
+                    math_list,
+                    MathStyle::TextStyle,
+                );
+
+                fn get_chars(
+                    list: &[HorizontalListElem],
+                ) -> Vec<(char, String)> {
+                    list.iter()
+                        .flat_map(|elem| match elem {
+                            HorizontalListElem::Char { chr, font } => {
+                                vec![(*chr, font.font_name.clone())]
+                            }
+                            HorizontalListElem::Box {
+                                tex_box: TeXBox::HorizontalBox(hbox),
+                                ..
+                            } => get_chars(&hbox.list),
+                            _ => vec![],
+                        })
+                        .collect()
+                }
+
+                assert_eq!(
+                    get_chars(&hlist),
+                    vec![
+                        ('x', "cmtt10".to_string()),
+                        ('x', "cmmi10".to_string()),
+                        ('x', "cmtt10".to_string()),
+                        ('x', "cmmi10".to_string()),
+                    ]
+                );
+            },
+        );
+    }
+
     #[test]
     fn it_parses_basic_atoms_in_math_lists() {
         with_parser(&[r"a*%"], |parser| {
@@ -1630,9 +2112,68 @@ mod tests {
         });
     }
 
+    #[test]
+    fn it_expands_active_characters_in_math_lists() {
+        with_parser(&[r"\def~{*}%", r"a~%"], |parser| {
+            parser.state.set_category(false, '~', Category::Active);
+            parser.parse_assignment();
+
+            assert_eq!(
+                parser.parse_math_list(),
+                vec![
+                    MathListElem::Atom(MathAtom::from_math_code(
+                        &MathCode::from_number(0x7161)
+                    )),
+                    MathListElem::Atom(MathAtom::from_math_code(
+                        &MathCode::from_number(0x002a)
+                    )),
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn it_treats_characters_with_active_math_codes_as_active() {
+        with_parser(
+            &[r"\def'{^*}%", r"\mathcode`'=32768 %", r"a'%"],
+            |parser| {
+                parser.state.set_category(false, '\'', Category::Active);
+                parser.parse_assignment();
+                parser.state.set_category(false, '\'', Category::Other);
+                parser.parse_assignment();
+
+                assert_eq!(
+                    parser.parse_math_list(),
+                    vec![MathListElem::Atom(
+                        MathAtom::from_math_code(&MathCode::from_number(
+                            0x7161
+                        ))
+                        .with_superscript(
+                            MathField::Symbol(MathSymbol::from_math_code(
+                                &MathCode::from_number(0x002a)
+                            ))
+                        )
+                    )]
+                );
+            },
+        );
+    }
+
     #[test]
     fn it_ignores_filler_before_math_fields() {
-        with_parser(&[r"  a   {a}%"], |parser| {
+        with_parser(&[r"  a   {a}%", r"\relax a\relax \relax{a}%"], |parser| {
+            assert_eq!(
+                parser.parse_math_field(),
+                MathField::Symbol(MathSymbol::from_math_code(
+                    &MathCode::from_number(0x7161)
+                ))
+            );
+            assert_eq!(
+                parser.parse_math_field(),
+                MathField::MathList(vec![MathListElem::Atom(
+                    MathAtom::from_math_code(&MathCode::from_number(0x7161))
+                ),],)
+            );
             assert_eq!(
                 parser.parse_math_field(),
                 MathField::Symbol(MathSymbol::from_math_code(
@@ -1681,6 +2222,69 @@ mod tests {
         });
     }
 
+    #[test]
+    fn it_parses_primes_with_active_quotes() {
+        let a_code = MathCode::from_number(0x7161);
+        let b_code = MathCode::from_number(0x7162);
+        let prime_code = MathCode::from_number(0x0230);
+
+        // These are the definitions from plain.tex, which use \futurelet to
+        // collect any following primes and superscript into the same
+        // superscript.
+        with_parser(
+            &[
+                r"\def'{^\bgroup\primes}%",
+                r"\let\bgroup={\let\egroup}%",
+                r#"\mathchardef\prime="0230%"#,
+                r"\def\primes{\prime\futurelet\next\primesnext}%",
+                r"\def\primesnext{\ifx'\next\let\nxt\primesprime\else%",
+                r"\ifx^\next\let\nxt\primessup\else\let\nxt\egroup\fi\fi\nxt}%",
+                r"\def\primesprime#1{\primes}%",
+                r"\def\primessup#1#2{#2\egroup}%",
+                r"\mathcode`'=32768 %",
+                r"a'^b a'' %",
+            ],
+            |parser| {
+                parser.state.set_category(false, '\'', Category::Active);
+                parser.parse_assignment();
+                parser.state.set_category(false, '\'', Category::Other);
+                for _ in 0..8 {
+                    parser.parse_assignment();
+                }
+
+                assert_eq!(
+                    parser.parse_math_list(),
+                    vec![
+                        MathListElem::Atom(
+                            MathAtom::from_math_code(&a_code).with_superscript(
+                                MathField::MathList(vec![
+                                    MathListElem::Atom(
+                                        MathAtom::from_math_code(&prime_code)
+                                    ),
+                                    MathListElem::Atom(
+                                        MathAtom::from_math_code(&b_code)
+                                    ),
+                                ])
+                            )
+                        ),
+                        MathListElem::Atom(
+                            MathAtom::from_math_code(&a_code).with_superscript(
+                                MathField::MathList(vec![
+                                    MathListElem::Atom(
+                                        MathAtom::from_math_code(&prime_code)
+                                    ),
+                                    MathListElem::Atom(
+                                        MathAtom::from_math_code(&prime_code)
+                                    ),
+                                ])
+                            )
+                        ),
+                    ]
+                );
+            },
+        );
+    }
+
     #[test]
     fn it_parses_superscripts_at_beginning_of_lists() {
         let a_code = MathCode::from_number(0x7161);
@@ -1697,6 +2301,22 @@ mod tests {
         });
     }
 
+    #[test]
+    #[should_panic(expected = r"You can't use \moveright in math mode")]
+    fn it_fails_on_moveright_in_math_mode() {
+        with_parser(&[r"a\moveright 2pt\hbox{b}%"], |parser| {
+            parser.parse_math_list();
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "Missing $ inserted")]
+    fn it_fails_on_par_in_math_mode() {
+        with_parser(&[r"a\par%"], |parser| {
+            parser.parse_math_list();
+        });
+    }
+
     #[test]
     #[should_panic(expected = "Double superscript")]
     fn it_fails_on_multiple_superscripts() {
@@ -1797,6 +2417,22 @@ mod tests {
         });
     }
 
+    #[test]
+    fn it_parses_chardefs_as_characters() {
+        let a_code = MathCode::from_number(0x7161);
+        let b_code = MathCode::from_number(0x7162);
+
+        with_parser(&[r"\chardef\hello=98 %", r"a\hello%"], |parser| {
+            assert_eq!(
+                parser.parse_math_list(),
+                vec![
+                    MathListElem::Atom(MathAtom::from_math_code(&a_code)),
+                    MathListElem::Atom(MathAtom::from_math_code(&b_code)),
+                ],
+            );
+        });
+    }
+
     #[test]
     fn it_parses_assignments_in_math_mode() {
         let a_code = MathCode::from_number(0x7161);
@@ -1901,6 +2537,22 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_dumps_converted_math_lists() {
+        assert_math_list_converts_to_dump(
+            &[r"a+(1)%"],
+            &[
+                r"\cmmi10 a",
+                r"\glue 2.22217 plus 1.11108 minus 2.22217",
+                r"\cmr10 +",
+                r"\glue 2.22217 plus 1.11108 minus 2.22217",
+                r"\cmr10 (",
+                r"\cmr10 1",
+                r"\cmr10 )",
+            ],
+        );
+    }
+
     #[test]
     fn it_adds_space_between_atoms_of_different_types_in_math_lists() {
         // o = ord
@@ -2373,6 +3025,154 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_centers_vcenter_boxes_on_the_axis() {
+        assert_math_list_converts_to_horizontal_list(
+            &[r"\vcenter{\hbox{x}}%"],
+            &[
+                r"\setbox0=\vbox{\hbox{x}}%",
+                // cmsy10's axis height is 2.5pt
+                r"\dimen0=\dp0 %",
+                r"\advance\dimen0 by -\ht0 %",
+                r"\divide\dimen0 by 2 %",
+                r"\advance\dimen0 by 2.5pt %",
+                r"\raise\dimen0 \box0%",
+            ],
+        );
+    }
+
+    #[test]
+    fn it_assembles_glyphs_too_tall_for_any_single_glyph_from_extensible_recipes(
+    ) {
+        with_parser(&[], |parser| {
+            let cmex10 = parser.state.get_math_font(&MathStyle::TextStyle, 3);
+            let min_size = Dimen::from_unit(100.0, Unit::Point);
+
+            let get_char = |tex_box: &TeXBox| match tex_box {
+                TeXBox::HorizontalBox(hbox) => match &hbox.list[..] {
+                    [HorizontalListElem::Char { chr, font: _ }] => *chr,
+                    other => panic!("Expected a single char, got {:?}", other),
+                },
+                other => panic!("Expected a horizontal box, got {:?}", other),
+            };
+
+            // Without a recipe, we end up with the biggest \sum
+            let sum =
+                parser.build_extensible_glyph(&cmex10, 0x50 as char, min_size);
+            assert_eq!(get_char(&sum), 0x58 as char);
+
+            // The biggest left parenthesis glyph has a recipe with a top,
+            // bottom, and extender piece
+            let paren = match parser.build_extensible_glyph(
+                &cmex10,
+                0x00 as char,
+                min_size,
+            ) {
+                TeXBox::VerticalBox(vbox) => vbox,
+                other => panic!("Expected a vertical box, got {:?}", other),
+            };
+            assert!(paren.height + paren.depth >= min_size);
+
+            let pieces: Vec<char> = paren
+                .list
+                .iter()
+                .map(|elem| match elem {
+                    VerticalListElem::Box { tex_box, shift: _ } => {
+                        get_char(tex_box)
+                    }
+                    other => panic!("Expected a box, got {:?}", other),
+                })
+                .collect();
+            assert!(pieces.len() > 2);
+            assert_eq!(pieces[0], 0x30 as char);
+            assert_eq!(pieces[pieces.len() - 1], 0x40 as char);
+            for piece in &pieces[1..pieces.len() - 1] {
+                assert_eq!(*piece, 0x42 as char);
+            }
+        });
+    }
+
+    #[test]
+    fn it_centers_limits_over_and_under_display_operators() {
+        with_parser(
+            &[
+                r#"\mathchardef\sumop="1350%"#,
+                r"\displaystyle\sumop\limits_a^b%",
+            ],
+            |parser| {
+                let math_list = parser.parse_math_list();
+                let hlist = parser.convert_math_list_to_horizontal_list(
+                    math_list,
+                    MathStyle::TextStyle,
+                );
+
+                let big_sum_width = parser
+                    .state
+                    .with_metrics_for_font(
+                        &parser.state.get_math_font(&MathStyle::TextStyle, 3),
+                        |metrics| metrics.get_width(88 as char),
+                    )
+                    .unwrap();
+
+                assert_eq!(hlist.len(), 1);
+                let stack = match &hlist[0] {
+                    HorizontalListElem::Box {
+                        tex_box: TeXBox::VerticalBox(vbox),
+                        shift: _,
+                    } => vbox,
+                    other => panic!("Expected a vertical box, got {:?}", other),
+                };
+
+                // The summation glyph is wider than either script, so it
+                // determines the width of the whole stack.
+                assert_eq!(stack.width, big_sum_width);
+
+                let boxes: Vec<&TeXBox> = stack
+                    .list
+                    .iter()
+                    .filter_map(|elem| match elem {
+                        VerticalListElem::Box { tex_box, shift: _ } => {
+                            Some(tex_box)
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                assert_eq!(boxes.len(), 3);
+
+                for tex_box in &boxes {
+                    assert_eq!(*tex_box.width(), big_sum_width);
+                }
+
+                // The superscript and subscript are centered by equal glue on
+                // either side.
+                for script_box in &[boxes[0], boxes[2]] {
+                    match script_box {
+                        TeXBox::HorizontalBox(hbox) => {
+                            assert_eq!(hbox.list.first(), hbox.list.last());
+                            match hbox.list.first() {
+                                Some(HorizontalListElem::HSkip(_)) => {}
+                                other => {
+                                    panic!("Expected glue, got {:?}", other)
+                                }
+                            }
+                        }
+                        other => {
+                            panic!("Expected a horizontal box, got {:?}", other)
+                        }
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Limit controls must follow a math operator")]
+    fn it_fails_on_limits_after_non_operators() {
+        with_parser(&[r"a\limits%"], |parser| {
+            parser.parse_math_list();
+        });
+    }
+
     #[test]
     fn it_reboxes_boxes_to_widths() {
         with_parser(
@@ -2393,15 +3193,15 @@ mod tests {
                 r"\hbox to 10pt{\hfil\copy1\hfil}%",
             ],
             |parser| {
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
                 let five_pt_box = parser.parse_box().unwrap();
                 let ten_pt_box_with_hfil = parser.parse_box().unwrap();
                 let box_with_hfil = parser.parse_box().unwrap();
                 let box_with_hfill = parser.parse_box().unwrap();
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
+                parser.parse_assignment();
+                parser.parse_assignment();
                 let vbox = parser.state.get_box_copy(1).unwrap();
 
                 let ten_pt = Dimen::from_unit(10.0, Unit::Point);
@@ -2570,6 +3370,60 @@ mod tests {
         });
     }
 
+    // Typesets some fractions after the given assignments, and returns the
+    // diagnostics that were reported as JSON lines.
+    fn get_diagnostics_for_fractions(assignments: &[&str]) -> Vec<String> {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let state = TeXState::new();
+        state.with_diagnostics(|diagnostics| {
+            diagnostics.set_json_writer(Box::new(SharedWriter(output.clone())))
+        });
+
+        let mut lines = assignments.to_vec();
+        lines.push(r"{a\atop b}+{a\atop b}%");
+        let mut parser = Parser::new(&lines, &state);
+        while parser.is_assignment_head() {
+            parser.parse_assignment();
+        }
+
+        let math_list = parser.parse_math_list();
+        let hlist = parser.convert_math_list_to_horizontal_list(
+            math_list,
+            MathStyle::TextStyle,
+        );
+        assert_eq!(hlist.len(), 3);
+
+        let output = String::from_utf8(output.borrow().clone()).unwrap();
+        output.lines().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn it_reports_math_fonts_without_enough_parameters() {
+        // cmr10 only has 7 parameters, so it can't be used for families 2 or 3.
+        // We report that once for each family, and use zero for the missing
+        // parameters instead.
+        let diagnostics = get_diagnostics_for_fractions(&[
+            r"\font\tenrm=cmr10 %",
+            r"\textfont1=\tenrm \scriptfont1=\tenrm \scriptscriptfont1=\tenrm %",
+            r"\textfont2=\tenrm \scriptfont2=\tenrm \scriptscriptfont2=\tenrm %",
+            r"\textfont3=\tenrm \scriptfont3=\tenrm \scriptscriptfont3=\tenrm %",
+        ]);
+
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics[0].contains(
+            r#""kind":"insufficient_math_font","message":"Font cmr10 is not a symbol font""#
+        ));
+        assert!(diagnostics[0].contains(r#""font":"cmr10","family":2"#));
+        assert!(diagnostics[1]
+            .contains(r#""message":"Font cmr10 is not an extension font""#));
+        assert!(diagnostics[1].contains(r#""font":"cmr10","family":3"#));
+    }
+
+    #[test]
+    fn it_uses_symbol_and_extension_fonts_without_errors() {
+        assert!(get_diagnostics_for_fractions(&[]).is_empty());
+    }
+
     #[test]
     #[should_panic(expected = "Ambiguous generalized fraction")]
     fn it_fails_on_ambiguous_generalized_fractions() {
@@ -2733,4 +3587,34 @@ mod tests {
             ],
         );
     }
+
+    #[test]
+    fn it_looks_up_math_font_params_once_per_style() {
+        let count_lookups_for_formula = |formula: &str| -> usize {
+            let mut lookups = 0;
+            with_parser(&[&format!("{}%", formula)], |parser| {
+                let math_list = parser.parse_math_list();
+
+                let before =
+                    parser.state.with_stats(|stats| stats.font_metric_lookups);
+                parser.convert_math_list_to_horizontal_list(
+                    math_list,
+                    MathStyle::TextStyle,
+                );
+                lookups =
+                    parser.state.with_stats(|stats| stats.font_metric_lookups)
+                        - before;
+            });
+            lookups
+        };
+
+        // Characters in a math list don't need their metrics until they're
+        // put in a box, so the only lookups are for the math font params,
+        // which shouldn't depend on how many atoms (and spaces between them)
+        // there are.
+        let short_lookups = count_lookups_for_formula("a+b=c");
+        let long_lookups = count_lookups_for_formula(&"a+b=c".repeat(200));
+
+        assert_eq!(short_lookups, long_lookups);
+    }
 }
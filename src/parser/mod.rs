@@ -1,4 +1,10 @@
+use crate::boxes::TeXBox;
+use crate::dimension::Dimen;
 use crate::lexer::Lexer;
+use crate::list::{HorizontalListElem, VerticalListElem};
+use crate::parser::mode::Mode;
+#[cfg(test)]
+use crate::primitive::Primitive;
 use crate::state::TeXState;
 use crate::token::Token;
 
@@ -12,6 +18,32 @@ pub struct Parser<'a> {
     // Used in conditional module to keep track of the level of nesting of
     // conditionals
     conditional_depth: usize,
+
+    // Used in mode module to keep track of the modes of the lists that are
+    // currently being parsed
+    mode_stack: Vec<Mode>,
+
+    // Used in vertical_list module to keep track of \prevdepth for each of
+    // the vertical lists that are currently being parsed
+    prev_depth_stack: Vec<Dimen>,
+
+    // Used in horizontal_list and vertical_list modules to keep track of the
+    // elements of the lists that are currently being parsed, so that \lastbox
+    // in the boxes module can remove the last box from them
+    horizontal_list_stack: Vec<Vec<HorizontalListElem>>,
+    vertical_list_stack: Vec<Vec<VerticalListElem>>,
+
+    // Used in assignment module to keep track of the token saved by
+    // \afterassignment
+    after_assignment_token: Option<Token>,
+
+    // Used in page_builder module to keep track of the boxes that have been
+    // sent out with \shipout
+    shipped_boxes: Vec<TeXBox>,
+
+    // Used in boxes module to keep track of how many \hbox and \vbox groups
+    // are currently being parsed inside of each other
+    box_nesting_depth: usize,
 }
 
 impl<'a> Parser<'a> {
@@ -26,21 +58,63 @@ impl<'a> Parser<'a> {
             state,
             upcoming_tokens: Vec::new(),
             conditional_depth: 0,
+            mode_stack: Vec::new(),
+            prev_depth_stack: Vec::new(),
+            horizontal_list_stack: Vec::new(),
+            vertical_list_stack: Vec::new(),
+            after_assignment_token: None,
+            shipped_boxes: Vec::new(),
+            box_nesting_depth: 0,
         }
     }
 }
 
+mod alignment;
 mod assignment;
 mod boxes;
+mod case_conversion;
 mod conditional;
 mod dimen;
+mod discretionary;
 mod expand;
 mod glue;
 mod horizontal_list;
+mod input_files;
 mod makro;
 mod math_list;
+mod mode;
 mod number;
+mod output_files;
+mod page_builder;
 mod primitives;
 mod printing;
+mod rule;
 mod variable;
 mod vertical_list;
+
+/// The primitives that each module of the parser checks for and handles.
+/// Every primitive should be handled somewhere, or else it can be defined but
+/// does nothing when used.
+#[cfg(test)]
+pub const HANDLED_PRIMITIVES: &[&[Primitive]] = &[
+    alignment::HANDLED_PRIMITIVES,
+    assignment::HANDLED_PRIMITIVES,
+    boxes::HANDLED_PRIMITIVES,
+    case_conversion::HANDLED_PRIMITIVES,
+    conditional::HANDLED_PRIMITIVES,
+    dimen::HANDLED_PRIMITIVES,
+    discretionary::HANDLED_PRIMITIVES,
+    expand::HANDLED_PRIMITIVES,
+    horizontal_list::HANDLED_PRIMITIVES,
+    input_files::HANDLED_PRIMITIVES,
+    makro::HANDLED_PRIMITIVES,
+    math_list::HANDLED_PRIMITIVES,
+    number::HANDLED_PRIMITIVES,
+    output_files::HANDLED_PRIMITIVES,
+    page_builder::HANDLED_PRIMITIVES,
+    primitives::HANDLED_PRIMITIVES,
+    printing::HANDLED_PRIMITIVES,
+    rule::HANDLED_PRIMITIVES,
+    variable::HANDLED_PRIMITIVES,
+    vertical_list::HANDLED_PRIMITIVES,
+];
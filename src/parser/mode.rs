@@ -0,0 +1,108 @@
+use crate::parser::Parser;
+use crate::primitive::Primitive;
+
+/// The modes that TeX can be in while building lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    // Building the main vertical list.
+    Vertical,
+    // Building a vertical list inside of something else, like a \vbox.
+    InternalVertical,
+    // Building a paragraph.
+    Horizontal,
+    // Building a horizontal list inside of something else, like an \hbox.
+    RestrictedHorizontal,
+    Math,
+}
+
+impl Mode {
+    /// Whether this mode is one of the "inner" modes that \ifinner checks
+    /// for.
+    pub fn is_inner(&self) -> bool {
+        match self {
+            Mode::Vertical | Mode::Horizontal => false,
+            Mode::InternalVertical
+            | Mode::RestrictedHorizontal
+            | Mode::Math => true,
+        }
+    }
+
+    /// Whether this is one of the modes that \ifvmode checks for.
+    pub fn is_vertical(&self) -> bool {
+        matches!(self, Mode::Vertical | Mode::InternalVertical)
+    }
+
+    /// Whether this is one of the modes that \ifhmode checks for.
+    pub fn is_horizontal(&self) -> bool {
+        matches!(self, Mode::Horizontal | Mode::RestrictedHorizontal)
+    }
+
+    fn get_name(&self) -> &'static str {
+        match self {
+            Mode::Vertical => "vertical mode",
+            Mode::InternalVertical => "internal vertical mode",
+            Mode::Horizontal => "horizontal mode",
+            Mode::RestrictedHorizontal => "restricted horizontal mode",
+            Mode::Math => "math mode",
+        }
+    }
+}
+
+impl<'a> Parser<'a> {
+    // The mode is pushed when we start parsing a list and popped when we're
+    // done with it, so the top of the stack is the mode of the innermost list
+    // being built.
+    pub fn push_mode(&mut self, mode: Mode) {
+        self.mode_stack.push(mode);
+    }
+
+    pub fn pop_mode(&mut self) {
+        self.mode_stack.pop();
+    }
+
+    /// Returns the current mode. Before any lists are being parsed, TeX is in
+    /// vertical mode.
+    pub fn get_mode(&self) -> Mode {
+        *self.mode_stack.last().unwrap_or(&Mode::Vertical)
+    }
+
+    /// Lexes the next token, which should be one of the given primitives, and
+    /// fails because that primitive isn't allowed in the current mode.
+    pub fn report_illegal_case(&mut self, primitives: &[Primitive]) -> ! {
+        let tok = self.lex_expanded_token().unwrap();
+        let prim = primitives
+            .iter()
+            .find(|prim| self.state.is_token_equal_to_prim(&tok, **prim))
+            .unwrap_or_else(|| panic!("Invalid illegal case: {:?}", tok));
+
+        panic!(
+            "You can't use \\{} in {}",
+            prim.name(),
+            self.get_mode().get_name()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::with_parser;
+
+    #[test]
+    fn it_keeps_track_of_the_current_mode() {
+        with_parser(&[], |parser| {
+            assert_eq!(parser.get_mode(), Mode::Vertical);
+
+            parser.push_mode(Mode::RestrictedHorizontal);
+            parser.push_mode(Mode::Math);
+            assert_eq!(parser.get_mode(), Mode::Math);
+
+            parser.pop_mode();
+            assert_eq!(parser.get_mode(), Mode::RestrictedHorizontal);
+
+            parser.pop_mode();
+            assert_eq!(parser.get_mode(), Mode::Vertical);
+        });
+    }
+}
@@ -1,7 +1,20 @@
 use crate::category::Category;
+use crate::math_code::MathCode;
 use crate::parser::Parser;
+use crate::primitive::Primitive;
 use crate::token::Token;
 
+/// The primitives for internal integers that aren't variables.
+#[cfg(test)]
+pub const HANDLED_PRIMITIVES: &[Primitive] = &[
+    Primitive::ParShape,
+    Primitive::Badness,
+    Primitive::CatCode,
+    Primitive::MathCode,
+    Primitive::LcCode,
+    Primitive::UcCode,
+];
+
 pub fn is_token_digit(token: &Token) -> bool {
     match token {
         Token::Char(ch, Category::Other) => *ch >= '0' && *ch <= '9',
@@ -139,14 +152,79 @@ impl<'a> Parser<'a> {
         char_value as u32
     }
 
+    /// Returns if the next token was defined with \chardef. These produce
+    /// characters in horizontal and math mode, but can also be used as
+    /// numbers.
+    pub fn is_chardef_head(&mut self) -> bool {
+        let expanded_token = self.peek_expanded_token();
+        match self.replace_renamed_token(expanded_token) {
+            Some(tok) => self.state.get_chardef(&tok).is_some(),
+            None => false,
+        }
+    }
+
+    pub fn parse_chardef(&mut self) -> u8 {
+        let expanded_token = self.lex_expanded_token();
+        let expanded_renamed_token =
+            self.replace_renamed_token(expanded_token).unwrap();
+
+        match self.state.get_chardef(&expanded_renamed_token) {
+            Some(char_code) => char_code,
+            None => {
+                panic!("Invalid chardef token: {:?}", expanded_renamed_token)
+            }
+        }
+    }
+
     pub fn is_internal_integer_head(&mut self) -> bool {
         self.is_integer_variable_head()
+            || self.is_chardef_head()
+            || self.is_next_expanded_token_in_set_of_primitives(&[
+                Primitive::ParShape,
+                Primitive::Badness,
+                Primitive::CatCode,
+                Primitive::MathCode,
+                Primitive::LcCode,
+                Primitive::UcCode,
+            ])
     }
 
     pub fn parse_internal_integer(&mut self) -> i32 {
         if self.is_integer_variable_head() {
             let variable = self.parse_integer_variable();
             variable.get(self.state)
+        } else if self.is_chardef_head() {
+            self.parse_chardef() as i32
+        } else if self
+            .is_next_expanded_token_in_set_of_primitives(&[Primitive::ParShape])
+        {
+            // \parshape as a number is the number of lines in the shape.
+            self.lex_expanded_token();
+            self.state.get_par_shape().len() as i32
+        } else if self
+            .is_next_expanded_token_in_set_of_primitives(&[Primitive::Badness])
+        {
+            self.lex_expanded_token();
+            self.state.get_last_badness() as i32
+        } else {
+            self.parse_code_name()
+        }
+    }
+
+    // Parses a <codename> followed by the character whose code is being
+    // looked up, and returns the value of that code.
+    fn parse_code_name(&mut self) -> i32 {
+        let tok = self.lex_expanded_token().unwrap();
+        let chr = self.parse_8bit_number() as char;
+
+        if self.state.is_token_equal_to_prim(&tok, Primitive::CatCode) {
+            self.state.get_category(chr).to_number() as i32
+        } else if self.state.is_token_equal_to_prim(&tok, Primitive::MathCode) {
+            self.state.get_math_code(chr).to_number() as i32
+        } else if self.state.is_token_equal_to_prim(&tok, Primitive::LcCode) {
+            self.state.get_lc_code(chr) as i32
+        } else if self.state.is_token_equal_to_prim(&tok, Primitive::UcCode) {
+            self.state.get_uc_code(chr) as i32
         } else {
             panic!("unimplemented");
         }
@@ -218,6 +296,14 @@ impl<'a> Parser<'a> {
         sign
     }
 
+    pub fn parse_4bit_number(&mut self) -> u8 {
+        let number = self.parse_number();
+        if !(0..=15).contains(&number) {
+            panic!("Invalid 4-bit number: {}", number);
+        }
+        number as u8
+    }
+
     pub fn parse_8bit_number(&mut self) -> u8 {
         let number = self.parse_number();
         if number < 0 || number > 255 {
@@ -234,6 +320,22 @@ impl<'a> Parser<'a> {
         number as u16
     }
 
+    /// Parses the class, family, and position of a \Umathchar, which are
+    /// given as three separate numbers.
+    pub fn parse_umath_code(&mut self) -> MathCode {
+        let class = self.parse_number();
+        if !(0..=7).contains(&class) {
+            panic!("Invalid math class: {}", class);
+        }
+        let family = self.parse_8bit_number();
+        let position = self.parse_number();
+        if position < 0 {
+            panic!("Invalid math char position: {}", position);
+        }
+
+        MathCode::from_umath_parts(class as u8, family, position as u32)
+    }
+
     pub fn parse_number(&mut self) -> i32 {
         let sign = self.parse_optional_signs();
         let value = self.parse_unsigned_number();
@@ -257,6 +359,26 @@ mod tests {
         });
     }
 
+    #[test]
+    fn it_parses_code_names_as_numbers() {
+        with_parser(
+            &[
+                r"\catcode`\a %",
+                r"\catcode`\{ %",
+                r"\mathcode`\a %",
+                r"\lccode`\A %",
+                r"\uccode`\a %",
+            ],
+            |parser| {
+                assert_eq!(parser.parse_number(), 11);
+                assert_eq!(parser.parse_number(), 1);
+                assert_eq!(parser.parse_number(), 0x7161);
+                assert_eq!(parser.parse_number(), 97);
+                assert_eq!(parser.parse_number(), 65);
+            },
+        );
+    }
+
     #[test]
     #[should_panic(expected = "Invalid 8-bit number: -1234")]
     fn it_fails_parsing_8bit_numbers() {
@@ -273,6 +395,15 @@ mod tests {
         });
     }
 
+    #[test]
+    fn it_parses_numbers_from_chardefs() {
+        with_parser(&[r"\chardef\a=65 %", r"\a-\a%"], |parser| {
+            parser.parse_assignment();
+            assert_eq!(parser.parse_number(), 65);
+            assert_eq!(parser.parse_number(), -65);
+        });
+    }
+
     #[test]
     fn it_parses_negative_integer_variables() {
         with_parser(&["-\\count10%"], |parser| {
@@ -317,7 +448,7 @@ mod tests {
     #[test]
     fn it_parses_coerced_dimens() {
         with_parser(&[r"\setbox0=\hbox{g}%", r"\wd0%", r"-\ht0%"], |parser| {
-            parser.parse_assignment(None);
+            parser.parse_assignment();
 
             let metrics = parser
                 .state
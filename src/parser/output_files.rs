@@ -0,0 +1,551 @@
+use std::path::Path;
+
+use crate::boxes::TeXBox;
+use crate::category::Category;
+use crate::limits::ResourceLimit;
+use crate::output_files::FileOperation;
+#[cfg(test)]
+use crate::output_files::PagePosition;
+use crate::parser::Parser;
+use crate::primitive::Primitive;
+use crate::state::IntegerParameter;
+use crate::token::{stringify, Token};
+
+/// The primitives for output files and messages.
+#[cfg(test)]
+pub const HANDLED_PRIMITIVES: &[Primitive] = &[
+    Primitive::OpenOut,
+    Primitive::Write,
+    Primitive::CloseOut,
+    Primitive::Immediate,
+    Primitive::Message,
+];
+
+impl<'a> Parser<'a> {
+    /// Checks that opening another file won't go over the limit on the
+    /// number of open files, counting the files that are being read with
+    /// \input or \read and the files that are open for writing.
+    pub fn check_can_open_another_file(&self) {
+        let num_open = self.lexer.get_input_depth()
+            + self.state.with_input_files(|files| files.get_num_open())
+            + self.state.with_output_files(|files| files.get_num_open());
+        self.state
+            .check_resource_level(ResourceLimit::OpenFiles, num_open + 1);
+    }
+
+    pub fn is_output_file_command_head(&mut self) -> bool {
+        self.is_next_expanded_token_in_set_of_primitives(&[
+            Primitive::OpenOut,
+            Primitive::Write,
+            Primitive::CloseOut,
+            Primitive::Immediate,
+        ])
+    }
+
+    fn parse_file_operation(&mut self) -> FileOperation {
+        let tok = self.lex_expanded_token().unwrap();
+
+        if self.state.is_token_equal_to_prim(&tok, Primitive::OpenOut) {
+            let stream = self.parse_4bit_number();
+            self.parse_equals_expanded();
+            let mut file_name = self.parse_file_name();
+
+            // Like TeX, we add a .tex extension to file names that don't
+            // have one.
+            if Path::new(&file_name).extension().is_none() {
+                file_name.push_str(".tex");
+            }
+
+            FileOperation::Open { stream, file_name }
+        } else if self.state.is_token_equal_to_prim(&tok, Primitive::Write) {
+            let stream = self.parse_number();
+            let tokens = self.parse_general_text();
+            FileOperation::Write { stream, tokens }
+        } else if self.state.is_token_equal_to_prim(&tok, Primitive::CloseOut) {
+            let stream = self.parse_4bit_number();
+            FileOperation::Close { stream }
+        } else {
+            panic!("unimplemented");
+        }
+    }
+
+    fn perform_file_operation(&mut self, operation: FileOperation) {
+        match operation {
+            FileOperation::Open { stream, file_name } => {
+                self.check_can_open_another_file();
+                self.state
+                    .with_output_files(|files| files.open(stream, &file_name));
+            }
+            FileOperation::Write { stream, tokens } => {
+                // The tokens are expanded now instead of when the \write was
+                // read, so macros in them use their current definitions.
+                let mut text = vec![Token::Char('{', Category::BeginGroup)];
+                text.extend(tokens);
+                text.push(Token::Char('}', Category::EndGroup));
+                self.add_upcoming_tokens(text);
+                let expanded = self.parse_expanded_general_text();
+
+                let escapechar = self
+                    .state
+                    .get_integer_parameter(&IntegerParameter::EscapeChar);
+                let line = stringify(&expanded, escapechar);
+                self.state
+                    .with_output_files(|files| files.write(stream, &line));
+            }
+            FileOperation::Close { stream } => {
+                self.state.with_output_files(|files| files.close(stream));
+            }
+        }
+    }
+
+    /// Parses an \openout, \write, or \closeout. Like in TeX, the operation
+    /// is returned so that it can be put into the current list as a whatsit,
+    /// and is carried out when the page that it ends up on is shipped out.
+    /// With \immediate, it is carried out right away instead.
+    pub fn parse_output_file_command(&mut self) -> Option<FileOperation> {
+        if self.is_next_expanded_token_in_set_of_primitives(&[
+            Primitive::Immediate,
+        ]) {
+            self.lex_expanded_token();
+            if self.is_next_expanded_token_in_set_of_primitives(&[
+                Primitive::OpenOut,
+                Primitive::Write,
+                Primitive::CloseOut,
+            ]) {
+                let operation = self.parse_file_operation();
+                self.perform_file_operation(operation);
+            }
+            return None;
+        }
+
+        Some(self.parse_file_operation())
+    }
+
+    /// Carries out the file operations in a box that is being shipped out as
+    /// the given page, in the order that they appear on the page.
+    #[cfg_attr(not(test), allow(unused_variables))]
+    pub fn ship_out_file_operations(
+        &mut self,
+        page: &TeXBox,
+        page_number: usize,
+    ) {
+        for (operation, h, v) in page.file_operations(self.state) {
+            #[cfg(test)]
+            self.state.with_output_files(|files| {
+                files.set_journal_position(Some(PagePosition {
+                    page: page_number,
+                    h,
+                    v,
+                }))
+            });
+            self.perform_file_operation(operation.clone());
+        }
+
+        #[cfg(test)]
+        self.state
+            .with_output_files(|files| files.set_journal_position(None));
+    }
+
+    pub fn is_message_head(&mut self) -> bool {
+        self.is_next_expanded_token_in_set_of_primitives(&[Primitive::Message])
+    }
+
+    /// Parses a \message, and prints its fully expanded text to the
+    /// terminal. TeX puts messages on the same line when they fit, but we
+    /// always put each one on its own line.
+    pub fn parse_message(&mut self) {
+        let tok = self.lex_expanded_token().unwrap();
+        if !self.state.is_token_equal_to_prim(&tok, Primitive::Message) {
+            panic!("Invalid message head: {:?}", tok);
+        }
+
+        let tokens = self.parse_expanded_general_text();
+        let escapechar = self
+            .state
+            .get_integer_parameter(&IntegerParameter::EscapeChar);
+        let text = stringify(&tokens, escapechar);
+        self.state.with_output_files(|files| files.print(&text));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::rc::Rc;
+
+    use crate::dimension::{Dimen, Unit};
+    use crate::output_files::{FileOperation, JournalEntry, PagePosition};
+    use crate::parser::Parser;
+    use crate::state::TeXState;
+    use crate::testing::{with_parser, SharedWriter};
+
+    // Makes an empty directory for a test to write files into.
+    fn make_output_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!(
+            "xymostex-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn it_opens_output_files_named_after_the_job() {
+        let directory = make_output_directory("jobname");
+
+        with_parser(&[r"\immediate\openout0=\jobname.aux%"], |parser| {
+            parser
+                .state
+                .with_output_files(|files| files.set_directory(&directory));
+
+            assert!(parser.is_output_file_command_head());
+            parser.parse_output_file_command();
+        });
+
+        assert!(directory.join("texput.aux").exists());
+    }
+
+    #[test]
+    fn it_adds_tex_extensions_to_output_files() {
+        let directory = make_output_directory("extension");
+
+        with_parser(
+            &[r"\immediate\openout1 notes\immediate\closeout1%"],
+            |parser| {
+                parser
+                    .state
+                    .with_output_files(|files| files.set_directory(&directory));
+
+                parser.parse_output_file_command();
+                parser.parse_output_file_command();
+            },
+        );
+
+        assert!(directory.join("notes.tex").exists());
+    }
+
+    #[test]
+    fn it_expands_counts_in_output_file_names() {
+        let directory = make_output_directory("counts");
+
+        with_parser(
+            &[
+                r"\count1=5 %",
+                r"\immediate\openout2=file\number\count1.aux%",
+            ],
+            |parser| {
+                parser
+                    .state
+                    .with_output_files(|files| files.set_directory(&directory));
+
+                parser.parse_assignment();
+                parser.parse_output_file_command();
+            },
+        );
+
+        assert!(directory.join("file5.aux").exists());
+    }
+
+    #[test]
+    fn it_defers_file_operations_that_arent_immediate() {
+        let directory = make_output_directory("deferred");
+
+        with_parser(
+            &[
+                r"\immediate\openout1=first%",
+                r"\openout2=second%",
+                r"\closeout1 %",
+                r"\immediate\closeout1 %",
+            ],
+            |parser| {
+                parser
+                    .state
+                    .with_output_files(|files| files.set_directory(&directory));
+
+                assert_eq!(parser.parse_output_file_command(), None);
+                assert_eq!(
+                    parser.parse_output_file_command(),
+                    Some(FileOperation::Open {
+                        stream: 2,
+                        file_name: "second.tex".to_string(),
+                    })
+                );
+                assert_eq!(
+                    parser.parse_output_file_command(),
+                    Some(FileOperation::Close { stream: 1 })
+                );
+                assert_eq!(parser.parse_output_file_command(), None);
+
+                let journal = parser
+                    .state
+                    .with_output_files(|files| files.take_journal());
+                assert_eq!(
+                    journal,
+                    vec![
+                        JournalEntry {
+                            kind: "openout",
+                            stream: 1,
+                            content: "first.tex".to_string(),
+                            position: None,
+                        },
+                        JournalEntry {
+                            kind: "closeout",
+                            stream: 1,
+                            content: String::new(),
+                            position: None,
+                        },
+                    ]
+                );
+            },
+        );
+
+        assert!(directory.join("first.tex").exists());
+        assert!(!directory.join("second.tex").exists());
+    }
+
+    // Parses a whole document and ships out its pages, and returns the
+    // journal of the file operations that were carried out along the way.
+    fn get_document_journal(
+        directory: &Path,
+        lines: &[&str],
+    ) -> Vec<JournalEntry> {
+        let state = TeXState::new();
+        state.with_output_files(|files| files.set_directory(directory));
+
+        let mut parser = Parser::new(lines, &state);
+        parser.parse_pages();
+
+        state.with_output_files(|files| files.take_journal())
+    }
+
+    fn write_entry(
+        content: &str,
+        position: Option<PagePosition>,
+    ) -> JournalEntry {
+        JournalEntry {
+            kind: "write",
+            stream: 1,
+            content: content.to_string(),
+            position,
+        }
+    }
+
+    fn page_position(page: usize, h: f64, v: f64) -> Option<PagePosition> {
+        Some(PagePosition {
+            page,
+            h: Dimen::from_unit(h, Unit::Point),
+            v: Dimen::from_unit(v, Unit::Point),
+        })
+    }
+
+    #[test]
+    fn it_carries_out_deferred_operations_in_shipout_order() {
+        let directory = make_output_directory("shipout-order");
+
+        let journal = get_document_journal(
+            &directory,
+            &[
+                r"\immediate\openout1=order%",
+                r"\vsize=100pt%",
+                r"\write1{a}%",
+                r"\hbox{\write1{b}\kern10pt\write1{c}}%",
+                r"\kern5pt%",
+                r"\write1{d}%",
+                r"\end%",
+            ],
+        );
+
+        assert_eq!(
+            journal[1..],
+            [
+                write_entry("a", page_position(1, 0.0, 0.0)),
+                write_entry("b", page_position(1, 0.0, 0.0)),
+                write_entry("c", page_position(1, 10.0, 0.0)),
+                write_entry("d", page_position(1, 0.0, 5.0)),
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(directory.join("order.tex")).unwrap(),
+            "a\nb\nc\nd\n"
+        );
+    }
+
+    #[test]
+    fn it_interleaves_immediate_operations_with_shipped_out_ones() {
+        let directory = make_output_directory("interleave");
+
+        let journal = get_document_journal(
+            &directory,
+            &[
+                r"\immediate\openout1=interleave%",
+                r"\def\x{early}%",
+                r"\write1{deferred \x}%",
+                r"\def\x{late}%",
+                r"\immediate\write1{immediate \x}%",
+                r"\shipout\hbox{\write1{shipped}}%",
+                r"\immediate\write1{after shipout}%",
+                r"\end%",
+            ],
+        );
+
+        // The \write on the main vertical list is on the page after the box
+        // from \shipout, and is expanded when that page is shipped out.
+        assert_eq!(
+            journal[1..],
+            [
+                write_entry("immediate late", None),
+                write_entry("shipped", page_position(1, 0.0, 0.0)),
+                write_entry("after shipout", None),
+                write_entry("deferred late", page_position(2, 0.0, 0.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_carries_out_writes_in_broken_paragraphs_once() {
+        let directory = make_output_directory("rebreak");
+
+        let journal = get_document_journal(
+            &directory,
+            &[
+                r"\immediate\openout1=rebreak%",
+                r"\hsize=20pt \parindent=0pt \tolerance=10000 %",
+                r"a a a a\write1{once}a a a a\par%",
+                r"\end%",
+            ],
+        );
+
+        let writes: Vec<&JournalEntry> = journal
+            .iter()
+            .filter(|entry| entry.kind == "write")
+            .collect();
+        assert_eq!(writes.len(), 1);
+        assert_eq!(writes[0].content, "once");
+        assert_eq!(writes[0].position.unwrap().page, 1);
+        assert_eq!(
+            fs::read_to_string(directory.join("rebreak.tex")).unwrap(),
+            "once\n"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "outside of the output directory")]
+    fn it_fails_to_open_output_files_outside_of_the_output_directory() {
+        let directory = make_output_directory("escape");
+
+        with_parser(&[r"\immediate\openout3=../escape%"], |parser| {
+            parser
+                .state
+                .with_output_files(|files| files.set_directory(&directory));
+
+            parser.parse_output_file_command();
+        });
+    }
+
+    // Captures what is printed to the terminal while parsing the given lines,
+    // which are all file commands or messages.
+    fn get_terminal_output(lines: &[&str]) -> String {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let state = TeXState::new();
+        state.with_output_files(|files| {
+            files.set_terminal(Box::new(SharedWriter(output.clone())))
+        });
+
+        let mut parser = Parser::new(lines, &state);
+        loop {
+            if parser.is_assignment_head() {
+                parser.parse_assignment();
+            } else if parser.is_message_head() {
+                parser.parse_message();
+            } else if parser.is_output_file_command_head() {
+                parser.parse_output_file_command();
+            } else {
+                break;
+            }
+        }
+        assert_eq!(parser.lex_unexpanded_token(), None);
+
+        let output = output.borrow().clone();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn it_prints_expanded_messages() {
+        assert_eq!(
+            get_terminal_output(&[
+                r"\def\x{a}%",
+                r"\message{\x\relax b \noexpand\x}%",
+            ]),
+            "a\\relax b \\x \n"
+        );
+    }
+
+    #[test]
+    fn it_expands_writes_when_they_are_carried_out() {
+        assert_eq!(
+            get_terminal_output(&[
+                r"\def\x{1}%",
+                r"\immediate\write16{\x}%",
+                r"\def\x{2}%",
+                r"\immediate\write16{\x y}%",
+            ]),
+            "1\n2y\n"
+        );
+    }
+
+    #[test]
+    fn it_writes_to_the_terminal_for_streams_that_arent_open() {
+        assert_eq!(
+            get_terminal_output(&[
+                r"\immediate\write5{a}%",
+                r"\immediate\write-1{b}%",
+            ]),
+            "a\nb\n"
+        );
+    }
+
+    #[test]
+    fn it_writes_lines_to_output_files() {
+        let directory = make_output_directory("write");
+
+        with_parser(
+            &[
+                r"\immediate\openout3=lines%",
+                r"\immediate\write3{first \jobname}%",
+                r"\immediate\write3{second}%",
+                r"\immediate\closeout3 %",
+            ],
+            |parser| {
+                parser
+                    .state
+                    .with_output_files(|files| files.set_directory(&directory));
+
+                for _ in 0..4 {
+                    parser.parse_output_file_command();
+                }
+
+                let journal = parser
+                    .state
+                    .with_output_files(|files| files.take_journal());
+                assert_eq!(
+                    journal[1],
+                    JournalEntry {
+                        kind: "write",
+                        stream: 3,
+                        content: "first texput".to_string(),
+                        position: None,
+                    }
+                );
+            },
+        );
+
+        assert_eq!(
+            fs::read_to_string(directory.join("lines.tex")).unwrap(),
+            "first texput\nsecond\n"
+        );
+    }
+}
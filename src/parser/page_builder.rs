@@ -0,0 +1,165 @@
+use std::io;
+
+use crate::boxes::{BoxLayout, TeXBox};
+use crate::limits::ResourceLimit;
+use crate::page_breaking::{
+    break_vertical_list_into_pages, PageBreakingParams,
+};
+use crate::parser::Parser;
+use crate::primitive::Primitive;
+use crate::state::{DimenParameter, IntegerParameter};
+
+/// The primitives for shipping out pages.
+#[cfg(test)]
+pub const HANDLED_PRIMITIVES: &[Primitive] = &[Primitive::ShipOut];
+
+impl<'a> Parser<'a> {
+    pub fn is_shipout_head(&mut self) -> bool {
+        self.is_next_expanded_token_in_set_of_primitives(&[Primitive::ShipOut])
+    }
+
+    pub fn parse_shipout(&mut self) {
+        let tok = self.lex_expanded_token().unwrap();
+        if !self.state.is_token_equal_to_prim(&tok, Primitive::ShipOut) {
+            panic!("Invalid shipout head: {:?}", tok);
+        }
+
+        // Shipping out a void box doesn't produce a page.
+        if let Some(tex_box) = self.parse_box() {
+            self.state.use_resource(ResourceLimit::Pages, 1);
+            let page_number = self.shipped_boxes.len() + 1;
+            self.ship_out_file_operations(&tex_box, page_number);
+            self.shipped_boxes.push(tex_box);
+        }
+    }
+
+    /// Parses the whole document and returns the boxes that should be
+    /// shipped out as pages. Boxes sent out with \shipout come first, in the
+    /// order they were seen. Since there's no \output routine yet, the main
+    /// vertical list is then broken into \vsize-tall pages which are shipped
+    /// out as they are. The file operations on each page are carried out as
+    /// it is shipped out.
+    pub fn parse_pages(&mut self) -> Vec<TeXBox> {
+        let list = self.parse_vertical_list(false);
+
+        // Like in parse_outer_vertical_box(), the page parameters are checked
+        // after the list has been parsed since they could be set anywhere.
+        let vsize = self.state.get_dimen_parameter(&DimenParameter::VSize);
+        let params = PageBreakingParams {
+            vsize,
+            max_depth: self
+                .state
+                .get_dimen_parameter(&DimenParameter::MaxDepth),
+            should_log: self
+                .state
+                .get_integer_parameter(&IntegerParameter::TracingPages)
+                > 0,
+        };
+
+        let pages =
+            break_vertical_list_into_pages(&list, &params, &mut io::stdout());
+
+        let mut boxes: Vec<TeXBox> = self.shipped_boxes.drain(..).collect();
+        for page in pages {
+            self.state.use_resource(ResourceLimit::Pages, 1);
+            let page =
+                TeXBox::VerticalBox(self.create_vertical_box_with_layout(
+                    page,
+                    &BoxLayout::Fixed(vsize),
+                ));
+            self.ship_out_file_operations(&page, boxes.len() + 1);
+            boxes.push(page);
+        }
+        boxes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dimension::{Dimen, Unit};
+    use crate::limits::{ResourceLimit, ResourceLimitExceeded, ResourceLimits};
+    use crate::testing::{get_resource_limit_exceeded, with_parser};
+
+    #[test]
+    fn it_breaks_the_main_vertical_list_into_pages() {
+        with_parser(
+            &[
+                r"\vsize=25pt%",
+                r"\hrule height10pt\vskip0pt%",
+                r"\hrule height10pt\vskip0pt%",
+                r"\hrule height10pt%",
+                r"\end%",
+            ],
+            |parser| {
+                let pages = parser.parse_pages();
+                assert_eq!(pages.len(), 2);
+                for page in pages {
+                    assert_eq!(
+                        *page.height(),
+                        Dimen::from_unit(25.0, Unit::Point)
+                    );
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn it_makes_one_page_when_everything_fits() {
+        with_parser(
+            &[
+                r"\vsize=100pt%",
+                r"\hrule height10pt\vskip0pt%",
+                r"\hrule height10pt\vskip0pt%",
+                r"\hrule height10pt%",
+                r"\end%",
+            ],
+            |parser| {
+                assert_eq!(parser.parse_pages().len(), 1);
+            },
+        );
+    }
+
+    #[test]
+    fn it_ships_out_boxes() {
+        with_parser(
+            &[
+                r"\setbox1=\hbox{b}%",
+                r"\shipout\hbox{a}%",
+                r"\shipout\box1%",
+                r"\shipout\box1%",
+                r"\end%",
+            ],
+            |parser| {
+                let pages = parser.parse_pages();
+                let page_chars: Vec<Vec<char>> =
+                    pages.iter().map(|page| page.to_chars()).collect();
+                // The second \shipout\box1 ships out a void box, so it doesn't
+                // make a page, and the main vertical list is empty.
+                assert_eq!(page_chars, vec![vec!['a'], vec!['b']]);
+            },
+        );
+    }
+
+    #[test]
+    fn it_stops_shipping_out_pages_at_the_page_limit() {
+        let err = get_resource_limit_exceeded(|| {
+            with_parser(
+                &[r"\def\loop{\shipout\hbox{a}\loop}%", r"\loop"],
+                |parser| {
+                    parser.state.set_resource_limits(
+                        ResourceLimits::unlimited()
+                            .with_max(ResourceLimit::Pages, 5),
+                    );
+                    parser.parse_pages();
+                },
+            )
+        });
+        assert_eq!(
+            err,
+            ResourceLimitExceeded {
+                limit: ResourceLimit::Pages,
+                max: 5,
+            }
+        );
+    }
+}
@@ -3,9 +3,16 @@
  * the TeX grammar.
  */
 use crate::category::Category;
+use crate::diagnostics::DiagnosticKind;
 use crate::parser::Parser;
+use crate::primitive::Primitive;
 use crate::token::Token;
 
+/// The primitives for grouping and doing nothing.
+#[cfg(test)]
+pub const HANDLED_PRIMITIVES: &[Primitive] =
+    &[Primitive::Relax, Primitive::BeginGroup, Primitive::EndGroup];
+
 /// Checks if a token matches a character from a keyword. In particular, this
 /// matches if it is a char token whose char component equals the the keyword
 /// character case-insensitively.
@@ -133,14 +140,19 @@ impl<'a> Parser<'a> {
         true
     }
 
-    /// Parses a <filler>, which is any amount of spaces and \relax
+    /// Parses a <filler>, which is any amount of spaces and \relax. This is
+    /// only allowed before the braces of boxes, math fields, general text, and
+    /// other groups. Numbers, dimens, and glue only skip optional spaces.
     pub fn parse_filler_expanded(&mut self) {
         self.parse_optional_spaces_expanded();
         loop {
             match self.peek_expanded_token() {
                 None => break,
                 Some(token) => {
-                    if self.state.is_token_equal_to_prim(&token, "relax") {
+                    if self
+                        .state
+                        .is_token_equal_to_prim(&token, Primitive::Relax)
+                    {
                         self.lex_expanded_token();
                     } else {
                         break;
@@ -151,14 +163,69 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Parses a <filler> followed by a <left brace>, like TeX's
+    /// scan_left_brace. Implicit braces like \bgroup are allowed. Returns
+    /// whether a brace was found.
+    pub fn parse_left_brace_expanded(&mut self) -> bool {
+        self.parse_filler_expanded();
+
+        let token = self.lex_expanded_token();
+        matches!(
+            self.replace_renamed_token(token),
+            Some(Token::Char(_, Category::BeginGroup))
+        )
+    }
+
+    /// Ends the current group, and then inserts the tokens that were saved with
+    /// \aftergroup so they're read next. This is for groups that aren't
+    /// started with \begingroup, which have to be ended with \endgroup.
+    pub fn end_group(&mut self) {
+        if self.state.is_in_semi_simple_group() {
+            panic!("{}", r"Extra }, or forgotten \endgroup");
+        }
+        self.pop_group();
+    }
+
+    fn pop_group(&mut self) {
+        let after_group_tokens = self.state.pop_state();
+        self.add_upcoming_tokens(after_group_tokens);
+    }
+
+    pub fn is_semi_simple_group_head(&mut self) -> bool {
+        self.is_next_expanded_token_in_set_of_primitives(&[
+            Primitive::BeginGroup,
+            Primitive::EndGroup,
+        ])
+    }
+
+    /// Groups started with \begingroup act like ones started with {, except
+    /// that they can only be ended with a matching \endgroup, and vice versa.
+    pub fn parse_semi_simple_group_head(&mut self) {
+        let tok = self.lex_expanded_token().unwrap();
+
+        if self
+            .state
+            .is_token_equal_to_prim(&tok, Primitive::BeginGroup)
+        {
+            self.state.push_semi_simple_state();
+        } else if self.state.is_token_equal_to_prim(&tok, Primitive::EndGroup) {
+            if !self.state.is_in_semi_simple_group() {
+                panic!("{}", r"Extra \endgroup");
+            }
+            self.pop_group();
+        } else {
+            panic!("Invalid semi-simple group head: {:?}", tok);
+        }
+    }
+
     pub fn is_next_expanded_token_in_set_of_primitives(
         &mut self,
-        primitives: &[&str],
+        primitives: &[Primitive],
     ) -> bool {
         match self.peek_expanded_token() {
             Some(token) => primitives
                 .iter()
-                .any(|prim| self.state.is_token_equal_to_prim(&token, prim)),
+                .any(|prim| self.state.is_token_equal_to_prim(&token, *prim)),
             _ => false,
         }
     }
@@ -180,6 +247,30 @@ impl<'a> Parser<'a> {
             .map(|token| self.state.get_renamed_token(&token).unwrap_or(token))
     }
 
+    /// Fails because the next token can't be used where it is. Undefined
+    /// control sequences are reported as errors, and anything else is a
+    /// command that we haven't implemented yet.
+    pub fn fail_on_unexpected_token(&mut self) -> ! {
+        let token = self.lex_expanded_token();
+        let undefined_name = match token {
+            Some(ref token) if self.state.is_token_defined(token) => None,
+            Some(Token::ControlSequence(name)) => Some(name),
+            Some(Token::Char(ch, Category::Active)) => Some(ch.to_string()),
+            _ => None,
+        };
+
+        match undefined_name {
+            Some(name) => {
+                let kind = DiagnosticKind::UndefinedControlSequence { name };
+                let message = kind.get_message();
+                self.state
+                    .with_diagnostics(|diagnostics| diagnostics.report(kind));
+                panic!("{}", message);
+            }
+            None => panic!("unimplemented"),
+        }
+    }
+
     fn parse_file_name_character(&mut self) -> Option<char> {
         let ch = match self.peek_expanded_token() {
             Some(Token::Char(ch, _)) => Some(ch),
@@ -191,6 +282,8 @@ impl<'a> Parser<'a> {
             || 'A' <= ch && ch <= 'Z'
             || ch == '-'
             || ch == '_'
+            || ch == '.'
+            || ch == '/'
         {
             self.lex_expanded_token();
             Some(ch)
@@ -200,7 +293,7 @@ impl<'a> Parser<'a> {
     }
 
     /// Parses a filename. A filename consists of a consecutive string of
-    /// alphanumeric characters and - and _ of any character token type.
+    /// alphanumeric characters and -, _, ., and / of any character token type.
     pub fn parse_file_name(&mut self) -> String {
         self.parse_optional_spaces_expanded();
 
@@ -240,7 +333,7 @@ mod tests {
 
         // Now with an expanded space
         with_parser(&["\\def\\x{ }%", "a a\\x aa%"], |parser| {
-            parser.parse_assignment(None);
+            parser.parse_assignment();
 
             assert_eq!(
                 parser.lex_expanded_token(),
@@ -288,7 +381,7 @@ mod tests {
 
         // Testing multiple optional expanded spaces
         with_parser(&["\\def\\x{ }%", "aa a \\x a\\x\\x a%"], |parser| {
-            parser.parse_assignment(None);
+            parser.parse_assignment();
 
             assert_eq!(
                 parser.lex_expanded_token(),
@@ -317,6 +410,55 @@ mod tests {
         });
     }
 
+    #[test]
+    fn it_parses_filler() {
+        with_parser(
+            &[r"\def\x{ \relax}%", r"a \relax\relax \x \x a\relax%"],
+            |parser| {
+                parser.parse_assignment();
+
+                assert_eq!(
+                    parser.lex_expanded_token(),
+                    Some(Token::Char('a', Category::Letter))
+                );
+                parser.parse_filler_expanded();
+                assert_eq!(
+                    parser.lex_expanded_token(),
+                    Some(Token::Char('a', Category::Letter))
+                );
+                parser.parse_filler_expanded();
+                assert_eq!(parser.lex_expanded_token(), None);
+            },
+        );
+    }
+
+    #[test]
+    fn it_parses_left_braces_after_filler() {
+        with_parser(
+            &[r"\let\bgroup={%", r"{ \relax { \relax\bgroup a%"],
+            |parser| {
+                parser.parse_assignment();
+
+                assert!(parser.parse_left_brace_expanded());
+                assert!(parser.parse_left_brace_expanded());
+                assert!(parser.parse_left_brace_expanded());
+                assert!(!parser.parse_left_brace_expanded());
+            },
+        );
+    }
+
+    #[test]
+    fn it_only_skips_spaces_around_numbers() {
+        // Unlike groups, numbers don't skip \relax
+        with_parser(&[r"  2 \relax%"], |parser| {
+            assert_eq!(parser.parse_number(), 2);
+            assert_eq!(
+                parser.lex_expanded_token(),
+                Some(Token::ControlSequence("relax".to_string()))
+            );
+        });
+    }
+
     #[test]
     fn it_parses_equals_signs() {
         with_parser(&["a=a  =a%"], |parser| {
@@ -372,7 +514,7 @@ mod tests {
     #[test]
     fn it_fetches_renamed_tokens() {
         with_parser(&[r"\let\bgroup={%", r"\bgroup"], |parser| {
-            parser.parse_assignment(None);
+            parser.parse_assignment();
 
             let unreplaced = parser.lex_unexpanded_token();
             assert_eq!(
@@ -403,6 +545,10 @@ mod tests {
             );
         });
 
+        with_parser(&[r"../sub/file.aux%"], |parser| {
+            assert_eq!(parser.parse_file_name(), "../sub/file.aux".to_string(),);
+        });
+
         with_parser(&[r"abc+%"], |parser| {
             assert_eq!(parser.parse_file_name(), "abc".to_string(),);
             assert!(parser.lex_unexpanded_token().is_some());
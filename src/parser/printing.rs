@@ -1,15 +1,46 @@
 use crate::category::Category;
 use crate::parser::Parser;
-use crate::token::Token;
+use crate::primitive::Primitive;
+use crate::state::IntegerParameter;
+use crate::token::{escape_name, stringify, tokenize_other, Token};
+
+/// The primitives that print or turn values into tokens.
+#[cfg(test)]
+pub const HANDLED_PRIMITIVES: &[Primitive] = &[
+    Primitive::Number,
+    Primitive::The,
+    Primitive::ShowThe,
+    Primitive::Show,
+    Primitive::ShowBox,
+    Primitive::JobName,
+];
 
 impl<'a> Parser<'a> {
     pub fn is_print_head(&mut self) -> bool {
         match self.peek_unexpanded_token() {
-            Some(token) => self.state.is_token_equal_to_prim(&token, "number"),
+            Some(token) => {
+                self.state.is_token_equal_to_prim(&token, Primitive::Number)
+                    || self.state.is_token_equal_to_prim(&token, Primitive::The)
+                    || self
+                        .state
+                        .is_token_equal_to_prim(&token, Primitive::ShowThe)
+                    || self
+                        .state
+                        .is_token_equal_to_prim(&token, Primitive::Show)
+                    || self
+                        .state
+                        .is_token_equal_to_prim(&token, Primitive::ShowBox)
+                    || self
+                        .state
+                        .is_token_equal_to_prim(&token, Primitive::JobName)
+            }
             _ => false,
         }
     }
 
+    // Formats an integer the way \number and \the do. All integers that get
+    // turned into tokens should go through here, like dimensions go through
+    // Dimen::to_pt_string.
     fn print_number(&mut self, value: i32) -> Vec<Token> {
         // Turn a number into Char tokens by taking advantage of rust's
         // built-in printing.
@@ -20,12 +51,110 @@ impl<'a> Parser<'a> {
             .collect()
     }
 
+    // Parses the internal quantity after a \the or \showthe and returns the
+    // tokens that it expands to.
+    fn parse_internal_quantity_tokens(&mut self) -> Vec<Token> {
+        if self.is_token_list_variable_head() {
+            // Token lists are the only quantities that don't get turned into
+            // Other tokens.
+            let variable = self.parse_token_list_variable();
+            variable.get(self.state)
+        } else if self.is_internal_dimen_head() {
+            tokenize_other(&self.parse_internal_dimen().to_pt_string())
+        } else if self.is_internal_integer_head() {
+            let value = self.parse_internal_integer();
+            self.print_number(value)
+        } else if self.is_glue_variable_head() {
+            let variable = self.parse_glue_variable();
+            tokenize_other(&variable.get(self.state).to_tex_string())
+        } else if self.is_font_head() {
+            // Fonts produce the control sequence that they were defined with.
+            // The default font wasn't defined with \font, so we call it
+            // \nullfont like TeX's default font.
+            let font = self.parse_font();
+            let identifier = self.state.get_font_identifier(&font);
+            vec![identifier.unwrap_or_else(|| {
+                Token::ControlSequence("nullfont".to_string())
+            })]
+        } else {
+            panic!("unimplemented");
+        }
+    }
+
+    // Prints the output of \show, \showthe, or \showbox to the terminal.
+    fn show(&mut self, text: &str) {
+        self.state
+            .with_output_files(|files| files.print(&format!("> {}", text)));
+    }
+
+    // Describes the meaning of a token the way that \show does. This is the
+    // same as \meaning, except that control sequences are shown with their
+    // name, and the body of macros starts on a new line.
+    fn get_show_string(&mut self, token: &Token) -> String {
+        let meaning = self.state.get_meaning_string(token);
+        let meaning = match meaning.strip_prefix("macro:") {
+            Some(body) => format!("macro:\n{}", body),
+            None => meaning,
+        };
+
+        match token {
+            Token::ControlSequence(name) => {
+                let escapechar = self
+                    .state
+                    .get_integer_parameter(&IntegerParameter::EscapeChar);
+                format!("{}={}.", escape_name(name, escapechar), meaning)
+            }
+            Token::Char(_, _) => format!("{}.", meaning),
+        }
+    }
+
     pub fn expand_print(&mut self) -> Vec<Token> {
         let head = self.lex_unexpanded_token().unwrap();
 
-        if self.state.is_token_equal_to_prim(&head, "number") {
+        if self.state.is_token_equal_to_prim(&head, Primitive::Number) {
             let value = self.parse_number();
             self.print_number(value)
+        } else if self.state.is_token_equal_to_prim(&head, Primitive::The) {
+            self.parse_internal_quantity_tokens()
+        } else if self.state.is_token_equal_to_prim(&head, Primitive::JobName) {
+            tokenize_other(&self.state.get_job_name())
+        } else if self.state.is_token_equal_to_prim(&head, Primitive::ShowThe) {
+            // In TeX, \showthe isn't expandable, but handling it here lets it
+            // work in every mode. It doesn't produce any tokens.
+            let tokens = self.parse_internal_quantity_tokens();
+            let escapechar = self
+                .state
+                .get_integer_parameter(&IntegerParameter::EscapeChar);
+            self.show(&format!("{}.", stringify(&tokens, escapechar)));
+            Vec::new()
+        } else if self.state.is_token_equal_to_prim(&head, Primitive::Show) {
+            // Like \showthe, \show and \showbox are handled here so that
+            // they work in every mode.
+            let token = match self.lex_unexpanded_token() {
+                Some(token) => token,
+                None => panic!(r"EOF found while parsing \show"),
+            };
+            let text = self.get_show_string(&token);
+            self.show(&text);
+            Vec::new()
+        } else if self.state.is_token_equal_to_prim(&head, Primitive::ShowBox) {
+            let box_index = self.parse_8bit_number();
+            let escapechar = self
+                .state
+                .get_integer_parameter(&IntegerParameter::EscapeChar);
+            let name =
+                format!("{}{}", escape_name("box", escapechar), box_index);
+
+            // TeX shows the contents of the box starting on the line after
+            // the name of the register.
+            let contents = match self.state.get_box_copy(box_index) {
+                Some(tex_box) => {
+                    format!("\n{}", tex_box.to_layout_tree(self.state))
+                }
+                None => "void".to_string(),
+            };
+            self.show(&format!("{}={}", name, contents.trim_end()));
+            Vec::new()
         } else {
             panic!("unimplemented");
         }
@@ -36,7 +165,384 @@ impl<'a> Parser<'a> {
 mod tests {
     use super::*;
 
-    use crate::testing::with_parser;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::state::TeXState;
+    use crate::testing::{with_parser, SharedWriter};
+
+    // Captures what is printed to the terminal while parsing the given lines,
+    // which are all assignments or things that get printed.
+    fn get_terminal_output(lines: &[&str]) -> String {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let state = TeXState::new();
+        state.with_output_files(|files| {
+            files.set_terminal(Box::new(SharedWriter(output.clone())))
+        });
+
+        let mut parser = Parser::new(lines, &state);
+        loop {
+            if parser.is_assignment_head() {
+                parser.parse_assignment();
+            } else if parser.is_print_head() {
+                assert_eq!(parser.expand_print(), vec![]);
+            } else {
+                break;
+            }
+        }
+        assert_eq!(parser.lex_unexpanded_token(), None);
+
+        let output = output.borrow().clone();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn it_expands_jobname() {
+        with_parser(&[r"\jobname%"], |parser| {
+            assert!(parser.is_print_head());
+            assert_eq!(parser.expand_print(), tokenize_other("texput"));
+        });
+    }
+
+    #[test]
+    fn it_expands_the_date_and_time() {
+        with_parser(
+            &[
+                r"\number\year\number\month\number\day\number\time%",
+                r"\year=2000 %",
+                r"\number\year%",
+            ],
+            |parser| {
+                // 2023-11-14 22:13:20 UTC
+                parser.state.set_date_and_time(1700000000);
+
+                assert_eq!(parser.expand_print(), tokenize_other("2023"));
+                assert_eq!(parser.expand_print(), tokenize_other("11"));
+                assert_eq!(parser.expand_print(), tokenize_other("14"));
+                assert_eq!(parser.expand_print(), tokenize_other("1333"));
+
+                parser.state.push_state();
+                parser.parse_assignment();
+                parser.state.pop_state();
+
+                assert_eq!(parser.expand_print(), tokenize_other("2023"));
+            },
+        );
+    }
+
+    #[test]
+    fn it_expands_the_for_dimens_and_integers() {
+        with_parser(
+            &[
+                r"\hsize=-1.5pt %",
+                r"\count1=12 %",
+                r"\the\hsize%",
+                r"\the\count1 %",
+                r"\the\fontdimen6\font%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                assert!(parser.is_print_head());
+                assert_eq!(parser.expand_print(), tokenize_other("-1.5pt"));
+
+                assert!(parser.is_print_head());
+                assert_eq!(parser.expand_print(), tokenize_other("12"));
+
+                // cmr10's quad is 1.000003 times its design size
+                assert!(parser.is_print_head());
+                assert_eq!(parser.expand_print(), tokenize_other("10.00002pt"));
+            },
+        );
+    }
+
+    #[test]
+    fn it_expands_the_for_registers_and_parameters() {
+        with_parser(
+            &[
+                r"\count0=3 %",
+                r"\hsize=3pt %",
+                r"\the\count0 %",
+                r"\the\hsize%",
+                r"\the\parfillskip%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                assert_eq!(parser.expand_print(), tokenize_other("3"));
+                assert_eq!(parser.expand_print(), tokenize_other("3.0pt"));
+                assert_eq!(
+                    parser.expand_print(),
+                    tokenize_other("0.0pt plus 1.0fil")
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_expands_the_for_glue_with_stretch_and_shrink() {
+        with_parser(
+            &[
+                r"\parskip=1pt plus 2fill minus 3pt %",
+                r"\spaceskip=-1.5pt minus 1fil %",
+                r"\the\parskip%",
+                r"\the\spaceskip%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                assert_eq!(
+                    parser.expand_print(),
+                    tokenize_other("1.0pt plus 2.0fill minus 3.0pt")
+                );
+                assert_eq!(
+                    parser.expand_print(),
+                    tokenize_other("-1.5pt minus 1.0fil")
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_expands_the_like_tex() {
+        with_parser(
+            &[
+                r"\hsize=6.5in %",
+                r"\dimen0=1sp %",
+                r"\dimen1=-65535sp %",
+                r"\parskip=0pt plus 1pt %",
+                r"\tolerance=200 %",
+                r"\the\hsize\the\dimen0 \the\dimen1 %",
+                r"\the\tolerance\the\parskip\the\catcode`\a%",
+            ],
+            |parser| {
+                for _ in 0..5 {
+                    parser.parse_assignment();
+                }
+
+                assert_eq!(
+                    parser.expand_print(),
+                    tokenize_other("469.75499pt")
+                );
+                assert_eq!(parser.expand_print(), tokenize_other("0.00002pt"));
+                assert_eq!(parser.expand_print(), tokenize_other("-0.99998pt"));
+                assert_eq!(parser.expand_print(), tokenize_other("200"));
+                assert_eq!(
+                    parser.expand_print(),
+                    tokenize_other("0.0pt plus 1.0pt")
+                );
+                assert_eq!(parser.expand_print(), tokenize_other("11"));
+            },
+        );
+    }
+
+    #[test]
+    fn it_prints_zero_negative_and_maximum_quantities_consistently() {
+        with_parser(
+            &[
+                r"\dimen0=16383.99999pt %",
+                r"\dimen1=-\dimen0 %",
+                r"\count1=-2147483647 %",
+                r"\the\dimen0 \the\dimen1 \the\dimen2 %",
+                r"\the\count1 \number\count1 \the\count2 \number0 %",
+                r"\number-\dimen0 %",
+            ],
+            |parser| {
+                for _ in 0..3 {
+                    parser.parse_assignment();
+                }
+
+                assert_eq!(
+                    parser.expand_print(),
+                    tokenize_other("16383.99998pt")
+                );
+                assert_eq!(
+                    parser.expand_print(),
+                    tokenize_other("-16383.99998pt")
+                );
+                assert_eq!(parser.expand_print(), tokenize_other("0.0pt"));
+
+                assert_eq!(
+                    parser.expand_print(),
+                    tokenize_other("-2147483647")
+                );
+                assert_eq!(
+                    parser.expand_print(),
+                    tokenize_other("-2147483647")
+                );
+                assert_eq!(parser.expand_print(), tokenize_other("0"));
+                assert_eq!(parser.expand_print(), tokenize_other("0"));
+
+                // Dimensions are coerced to integers in scaled points.
+                assert_eq!(
+                    parser.expand_print(),
+                    tokenize_other("-1073741823")
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_expands_the_for_fonts_to_their_identifiers() {
+        with_parser(
+            &[
+                r"\the\font%",
+                r"\font\tenrm=cmr10 %",
+                r"\font\bigrm=cmr10 at 20pt %",
+                r"\the\font\the\bigrm%",
+            ],
+            |parser| {
+                assert_eq!(
+                    parser.expand_print(),
+                    vec![Token::ControlSequence("nullfont".to_string())]
+                );
+
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                // The current font is the same as \tenrm, so it uses that name
+                assert_eq!(
+                    parser.expand_print(),
+                    vec![Token::ControlSequence("tenrm".to_string())]
+                );
+                assert_eq!(
+                    parser.expand_print(),
+                    vec![Token::ControlSequence("bigrm".to_string())]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_expands_the_for_token_lists_without_changing_categories() {
+        with_parser(&[r"\everypar={a\relax}%", r"\the\everypar%"], |parser| {
+            parser.parse_assignment();
+
+            assert_eq!(
+                parser.expand_print(),
+                vec![
+                    Token::Char('a', Category::Letter),
+                    Token::ControlSequence("relax".to_string()),
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn it_expands_the_for_toks_registers() {
+        with_parser(
+            &[r"\toks0={a$\relax}\def\x{\the\toks0}%", r"\x%"],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                assert_eq!(
+                    parser.lex_expanded_token(),
+                    Some(Token::Char('a', Category::Letter))
+                );
+                assert_eq!(
+                    parser.lex_expanded_token(),
+                    Some(Token::Char('$', Category::MathShift))
+                );
+                assert_eq!(
+                    parser.lex_unexpanded_token(),
+                    Some(Token::ControlSequence("relax".to_string()))
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_expands_the_for_font_dimensions_of_font_identifiers() {
+        with_parser(
+            &[r"\font\teni=cmmi10 %", r"\the\fontdimen6\teni%"],
+            |parser| {
+                parser.parse_assignment();
+
+                assert!(parser.is_print_head());
+                assert_eq!(parser.expand_print(), tokenize_other("10.00002pt"));
+            },
+        );
+    }
+
+    #[test]
+    fn it_shows_internal_quantities_without_expanding_to_anything() {
+        with_parser(&[r"\showthe\fontdimen2\font%"], |parser| {
+            assert!(parser.is_print_head());
+            assert_eq!(parser.expand_print(), vec![]);
+        });
+    }
+
+    #[test]
+    fn it_shows_the_meanings_of_tokens() {
+        assert_eq!(
+            get_terminal_output(&[
+                r"\def\a#1{x#1}%",
+                r"\let\b=c%",
+                r"\show\a",
+                r"\show\hbox",
+                r"\show a%",
+                r"\show\b",
+                r"\show\undefined",
+            ]),
+            [
+                r"> \a=macro:",
+                r"#1->x#1.",
+                r"> \hbox=\hbox.",
+                r"> the letter a.",
+                r"> \b=the letter c.",
+                r"> \undefined=undefined.",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn it_shows_what_the_would_produce() {
+        assert_eq!(
+            get_terminal_output(&[
+                r"\count1=-5 %",
+                r"\dimen2=1.5pt %",
+                r"\showthe\count1 %",
+                r"\showthe\dimen2 %",
+                r"\showthe\parfillskip",
+            ]),
+            "> -5.\n> 1.5pt.\n> 0.0pt plus 1.0fil.\n"
+        );
+    }
+
+    #[test]
+    fn it_shows_the_contents_of_box_registers() {
+        assert_eq!(
+            get_terminal_output(&[
+                r"\font\tenrm=cmr10 \tenrm%",
+                r"\setbox1=\hbox{a\hskip 1pt plus 2fil}%",
+                r"\showbox1 %",
+                r"\showbox2 %",
+            ]),
+            [
+                r"> \box1=",
+                r"\hbox(4.30554+0.0)x6.00002",
+                r".\tenrm a",
+                r".\glue 1.0 plus 2.0fil",
+                r"> \box2=void",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "has only 7 fontdimen parameters")]
+    fn it_fails_to_read_missing_font_dimensions() {
+        with_parser(&[r"\the\fontdimen8\font%"], |parser| {
+            parser.expand_print();
+        });
+    }
 
     #[test]
     fn it_expands_numbers() {
@@ -0,0 +1,169 @@
+use crate::dimension::{Dimen, Unit};
+use crate::list::{HorizontalListElem, VerticalListElem};
+use crate::parser::Parser;
+use crate::primitive::Primitive;
+
+/// The primitives that make rules.
+#[cfg(test)]
+pub const HANDLED_PRIMITIVES: &[Primitive] =
+    &[Primitive::HRule, Primitive::VRule];
+
+// The dimensions of a rule, where None means that a dimension is running.
+struct RuleSpecification {
+    height: Option<Dimen>,
+    depth: Option<Dimen>,
+    width: Option<Dimen>,
+}
+
+fn get_default_rule_thickness() -> Dimen {
+    Dimen::from_unit(0.4, Unit::Point)
+}
+
+impl<'a> Parser<'a> {
+    pub fn is_horizontal_rule_head(&mut self) -> bool {
+        self.is_next_expanded_token_in_set_of_primitives(&[Primitive::HRule])
+    }
+
+    pub fn is_vertical_rule_head(&mut self) -> bool {
+        self.is_next_expanded_token_in_set_of_primitives(&[Primitive::VRule])
+    }
+
+    // Parses the `width`, `height`, and `depth` keywords after a rule. These
+    // can come in any order and can be repeated, in which case the last one
+    // wins.
+    fn parse_rule_specification(
+        &mut self,
+        mut spec: RuleSpecification,
+    ) -> RuleSpecification {
+        loop {
+            if self.parse_optional_keyword_expanded("width") {
+                spec.width = Some(self.parse_dimen());
+            } else if self.parse_optional_keyword_expanded("height") {
+                spec.height = Some(self.parse_dimen());
+            } else if self.parse_optional_keyword_expanded("depth") {
+                spec.depth = Some(self.parse_dimen());
+            } else {
+                break;
+            }
+        }
+
+        spec
+    }
+
+    /// Parses an \hrule, which by default is 0.4pt tall and extends to the
+    /// width of the enclosing box.
+    pub fn parse_horizontal_rule(&mut self) -> VerticalListElem {
+        let tok = self.lex_expanded_token().unwrap();
+        if !self.state.is_token_equal_to_prim(&tok, Primitive::HRule) {
+            panic!("Invalid horizontal rule head: {:?}", tok);
+        }
+
+        let spec = self.parse_rule_specification(RuleSpecification {
+            height: Some(get_default_rule_thickness()),
+            depth: Some(Dimen::zero()),
+            width: None,
+        });
+
+        VerticalListElem::Rule {
+            height: spec.height,
+            depth: spec.depth,
+            width: spec.width,
+        }
+    }
+
+    /// Parses a \vrule, which by default is 0.4pt wide and extends to the
+    /// height and depth of the enclosing box.
+    pub fn parse_vertical_rule(&mut self) -> HorizontalListElem {
+        let tok = self.lex_expanded_token().unwrap();
+        if !self.state.is_token_equal_to_prim(&tok, Primitive::VRule) {
+            panic!("Invalid vertical rule head: {:?}", tok);
+        }
+
+        let spec = self.parse_rule_specification(RuleSpecification {
+            height: None,
+            depth: None,
+            width: Some(get_default_rule_thickness()),
+        });
+
+        HorizontalListElem::Rule {
+            height: spec.height,
+            depth: spec.depth,
+            width: spec.width,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::with_parser;
+
+    #[test]
+    fn it_parses_default_rules() {
+        with_parser(&[r"\hrule\vrule%"], |parser| {
+            assert!(parser.is_horizontal_rule_head());
+            assert_eq!(
+                parser.parse_horizontal_rule(),
+                VerticalListElem::Rule {
+                    height: Some(Dimen::from_unit(0.4, Unit::Point)),
+                    depth: Some(Dimen::zero()),
+                    width: None,
+                }
+            );
+
+            assert!(parser.is_vertical_rule_head());
+            assert_eq!(
+                parser.parse_vertical_rule(),
+                HorizontalListElem::Rule {
+                    height: None,
+                    depth: None,
+                    width: Some(Dimen::from_unit(0.4, Unit::Point)),
+                }
+            );
+        });
+    }
+
+    #[test]
+    fn it_parses_rule_specifications() {
+        with_parser(
+            &[
+                r"\hrule width 10pt height 2pt depth 1pt%",
+                r"\vrule depth1pt height2pt width3pt%",
+            ],
+            |parser| {
+                assert_eq!(
+                    parser.parse_horizontal_rule(),
+                    VerticalListElem::Rule {
+                        height: Some(Dimen::from_unit(2.0, Unit::Point)),
+                        depth: Some(Dimen::from_unit(1.0, Unit::Point)),
+                        width: Some(Dimen::from_unit(10.0, Unit::Point)),
+                    }
+                );
+
+                assert_eq!(
+                    parser.parse_vertical_rule(),
+                    HorizontalListElem::Rule {
+                        height: Some(Dimen::from_unit(2.0, Unit::Point)),
+                        depth: Some(Dimen::from_unit(1.0, Unit::Point)),
+                        width: Some(Dimen::from_unit(3.0, Unit::Point)),
+                    }
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_uses_the_last_repeated_rule_keyword() {
+        with_parser(&[r"\vrule width 1pt height 2pt width 3pt%"], |parser| {
+            assert_eq!(
+                parser.parse_vertical_rule(),
+                HorizontalListElem::Rule {
+                    height: Some(Dimen::from_unit(2.0, Unit::Point)),
+                    depth: None,
+                    width: Some(Dimen::from_unit(3.0, Unit::Point)),
+                }
+            );
+        });
+    }
+}
@@ -1,82 +1,339 @@
 use crate::parser::Parser;
-use crate::state::{DimenParameter, GlueParameter, IntegerParameter};
-use crate::variable::{DimenVariable, GlueVariable, IntegerVariable};
+use crate::primitive::Primitive;
+use crate::state::{
+    DimenParameter, GlueParameter, IntegerParameter, RegisterKind,
+    TokenListParameter,
+};
+use crate::variable::{
+    DimenVariable, GlueVariable, IntegerVariable, TokenListVariable,
+};
+
+/// The primitives for registers and parameters, which can be read or
+/// assigned to.
+#[cfg(test)]
+pub const HANDLED_PRIMITIVES: &[Primitive] = &[
+    Primitive::Count,
+    Primitive::Tolerance,
+    Primitive::Pretolerance,
+    Primitive::TracingParagraphs,
+    Primitive::TracingStats,
+    Primitive::Time,
+    Primitive::Day,
+    Primitive::Month,
+    Primitive::Year,
+    Primitive::AdjDemerits,
+    Primitive::HangAfter,
+    Primitive::Looseness,
+    Primitive::PrevGraf,
+    Primitive::EndLineChar,
+    Primitive::TracingPages,
+    Primitive::EscapeChar,
+    Primitive::Dimen,
+    Primitive::Wd,
+    Primitive::Ht,
+    Primitive::Dp,
+    Primitive::HSize,
+    Primitive::VSize,
+    Primitive::MaxDepth,
+    Primitive::SplitMaxDepth,
+    Primitive::HangIndent,
+    Primitive::ParIndent,
+    Primitive::HFuzz,
+    Primitive::OverfullRule,
+    Primitive::Skip,
+    Primitive::ParSkip,
+    Primitive::SpaceSkip,
+    Primitive::ParFillSkip,
+    Primitive::LeftSkip,
+    Primitive::RightSkip,
+    Primitive::TabSkip,
+    Primitive::Toks,
+    Primitive::EveryPar,
+    Primitive::EveryHBox,
+    Primitive::EveryVBox,
+    Primitive::EveryMath,
+];
 
 impl<'a> Parser<'a> {
+    // Control sequences defined with \countdef and friends can be used
+    // anywhere that the corresponding register can.
+    fn is_register_def_head(&mut self, kind: RegisterKind) -> bool {
+        match self.peek_expanded_token() {
+            Some(token) => match self.state.get_register_def(&token) {
+                Some((register_kind, _)) => register_kind == kind,
+                None => false,
+            },
+            None => false,
+        }
+    }
+
     pub fn is_integer_variable_head(&mut self) -> bool {
-        self.is_next_expanded_token_in_set_of_primitives(&[
-            "count",
-            "tolerance",
-            "pretolerance",
-            "tracingparagraphs",
-            "adjdemerits",
-        ])
+        self.is_register_def_head(RegisterKind::Count)
+            || self.is_next_expanded_token_in_set_of_primitives(&[
+                Primitive::Count,
+                Primitive::Tolerance,
+                Primitive::Pretolerance,
+                Primitive::TracingParagraphs,
+                Primitive::TracingStats,
+                Primitive::Time,
+                Primitive::Day,
+                Primitive::Month,
+                Primitive::Year,
+                Primitive::AdjDemerits,
+                Primitive::HangAfter,
+                Primitive::Looseness,
+                Primitive::PrevGraf,
+                Primitive::EndLineChar,
+                Primitive::TracingPages,
+                Primitive::EscapeChar,
+            ])
     }
 
     pub fn parse_integer_variable(&mut self) -> IntegerVariable {
         let token = self.lex_expanded_token().unwrap();
 
-        if self.state.is_token_equal_to_prim(&token, "count") {
+        if let Some((RegisterKind::Count, index)) =
+            self.state.get_register_def(&token)
+        {
+            IntegerVariable::CountRegister(index)
+        } else if self.state.is_token_equal_to_prim(&token, Primitive::Count) {
             let index = self.parse_8bit_number();
             IntegerVariable::CountRegister(index)
-        } else if self.state.is_token_equal_to_prim(&token, "tolerance") {
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::Tolerance)
+        {
             IntegerVariable::Parameter(IntegerParameter::Tolerance)
-        } else if self.state.is_token_equal_to_prim(&token, "pretolerance") {
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::Pretolerance)
+        {
             IntegerVariable::Parameter(IntegerParameter::Pretolerance)
         } else if self
             .state
-            .is_token_equal_to_prim(&token, "tracingparagraphs")
+            .is_token_equal_to_prim(&token, Primitive::TracingParagraphs)
         {
             IntegerVariable::Parameter(IntegerParameter::TracingParagraphs)
-        } else if self.state.is_token_equal_to_prim(&token, "adjdemerits") {
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::TracingStats)
+        {
+            IntegerVariable::Parameter(IntegerParameter::TracingStats)
+        } else if self.state.is_token_equal_to_prim(&token, Primitive::Time) {
+            IntegerVariable::Parameter(IntegerParameter::Time)
+        } else if self.state.is_token_equal_to_prim(&token, Primitive::Day) {
+            IntegerVariable::Parameter(IntegerParameter::Day)
+        } else if self.state.is_token_equal_to_prim(&token, Primitive::Month) {
+            IntegerVariable::Parameter(IntegerParameter::Month)
+        } else if self.state.is_token_equal_to_prim(&token, Primitive::Year) {
+            IntegerVariable::Parameter(IntegerParameter::Year)
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::AdjDemerits)
+        {
             IntegerVariable::Parameter(IntegerParameter::AdjDemerits)
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::HangAfter)
+        {
+            IntegerVariable::Parameter(IntegerParameter::HangAfter)
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::Looseness)
+        {
+            IntegerVariable::Parameter(IntegerParameter::Looseness)
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::PrevGraf)
+        {
+            IntegerVariable::Parameter(IntegerParameter::PrevGraf)
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::EndLineChar)
+        {
+            IntegerVariable::Parameter(IntegerParameter::EndLineChar)
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::TracingPages)
+        {
+            IntegerVariable::Parameter(IntegerParameter::TracingPages)
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::EscapeChar)
+        {
+            IntegerVariable::Parameter(IntegerParameter::EscapeChar)
         } else {
             panic!("unimplemented");
         }
     }
 
     pub fn is_dimen_variable_head(&mut self) -> bool {
-        self.is_next_expanded_token_in_set_of_primitives(&[
-            "wd", "ht", "dp", "hsize",
-        ])
+        self.is_register_def_head(RegisterKind::Dimen)
+            || self.is_next_expanded_token_in_set_of_primitives(&[
+                Primitive::Dimen,
+                Primitive::Wd,
+                Primitive::Ht,
+                Primitive::Dp,
+                Primitive::HSize,
+                Primitive::VSize,
+                Primitive::MaxDepth,
+                Primitive::SplitMaxDepth,
+                Primitive::HangIndent,
+                Primitive::ParIndent,
+                Primitive::HFuzz,
+                Primitive::OverfullRule,
+            ])
     }
 
     pub fn parse_dimen_variable(&mut self) -> DimenVariable {
         let token = self.lex_expanded_token().unwrap();
 
-        if self.state.is_token_equal_to_prim(&token, "wd") {
+        if let Some((RegisterKind::Dimen, index)) =
+            self.state.get_register_def(&token)
+        {
+            DimenVariable::DimenRegister(index)
+        } else if self.state.is_token_equal_to_prim(&token, Primitive::Dimen) {
+            let index = self.parse_8bit_number();
+            DimenVariable::DimenRegister(index)
+        } else if self.state.is_token_equal_to_prim(&token, Primitive::Wd) {
             let index = self.parse_8bit_number();
             DimenVariable::BoxWidth(index)
-        } else if self.state.is_token_equal_to_prim(&token, "ht") {
+        } else if self.state.is_token_equal_to_prim(&token, Primitive::Ht) {
             let index = self.parse_8bit_number();
             DimenVariable::BoxHeight(index)
-        } else if self.state.is_token_equal_to_prim(&token, "dp") {
+        } else if self.state.is_token_equal_to_prim(&token, Primitive::Dp) {
             let index = self.parse_8bit_number();
             DimenVariable::BoxDepth(index)
-        } else if self.state.is_token_equal_to_prim(&token, "hsize") {
+        } else if self.state.is_token_equal_to_prim(&token, Primitive::HSize) {
             DimenVariable::Parameter(DimenParameter::HSize)
+        } else if self.state.is_token_equal_to_prim(&token, Primitive::VSize) {
+            DimenVariable::Parameter(DimenParameter::VSize)
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::MaxDepth)
+        {
+            DimenVariable::Parameter(DimenParameter::MaxDepth)
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::SplitMaxDepth)
+        {
+            DimenVariable::Parameter(DimenParameter::SplitMaxDepth)
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::HangIndent)
+        {
+            DimenVariable::Parameter(DimenParameter::HangIndent)
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::ParIndent)
+        {
+            DimenVariable::Parameter(DimenParameter::ParIndent)
+        } else if self.state.is_token_equal_to_prim(&token, Primitive::HFuzz) {
+            DimenVariable::Parameter(DimenParameter::HFuzz)
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::OverfullRule)
+        {
+            DimenVariable::Parameter(DimenParameter::OverfullRule)
         } else {
             panic!("unimplemented");
         }
     }
 
     pub fn is_glue_variable_head(&mut self) -> bool {
-        self.is_next_expanded_token_in_set_of_primitives(&[
-            "parskip",
-            "spaceskip",
-            "parfillskip",
-        ])
+        self.is_register_def_head(RegisterKind::Skip)
+            || self.is_next_expanded_token_in_set_of_primitives(&[
+                Primitive::Skip,
+                Primitive::ParSkip,
+                Primitive::SpaceSkip,
+                Primitive::ParFillSkip,
+                Primitive::LeftSkip,
+                Primitive::RightSkip,
+                Primitive::TabSkip,
+            ])
     }
 
     pub fn parse_glue_variable(&mut self) -> GlueVariable {
         let token = self.lex_expanded_token().unwrap();
 
-        if self.state.is_token_equal_to_prim(&token, "parskip") {
+        if let Some((RegisterKind::Skip, index)) =
+            self.state.get_register_def(&token)
+        {
+            GlueVariable::SkipRegister(index)
+        } else if self.state.is_token_equal_to_prim(&token, Primitive::Skip) {
+            let index = self.parse_8bit_number();
+            GlueVariable::SkipRegister(index)
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::ParSkip)
+        {
             GlueVariable::Parameter(GlueParameter::ParSkip)
-        } else if self.state.is_token_equal_to_prim(&token, "spaceskip") {
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::SpaceSkip)
+        {
             GlueVariable::Parameter(GlueParameter::SpaceSkip)
-        } else if self.state.is_token_equal_to_prim(&token, "parfillskip") {
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::ParFillSkip)
+        {
             GlueVariable::Parameter(GlueParameter::ParFillSkip)
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::LeftSkip)
+        {
+            GlueVariable::Parameter(GlueParameter::LeftSkip)
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::RightSkip)
+        {
+            GlueVariable::Parameter(GlueParameter::RightSkip)
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::TabSkip)
+        {
+            GlueVariable::Parameter(GlueParameter::TabSkip)
+        } else {
+            panic!("unimplemented");
+        }
+    }
+
+    pub fn is_token_list_variable_head(&mut self) -> bool {
+        self.is_next_expanded_token_in_set_of_primitives(&[
+            Primitive::Toks,
+            Primitive::EveryPar,
+            Primitive::EveryHBox,
+            Primitive::EveryVBox,
+            Primitive::EveryMath,
+        ])
+    }
+
+    pub fn parse_token_list_variable(&mut self) -> TokenListVariable {
+        let token = self.lex_expanded_token().unwrap();
+
+        if self.state.is_token_equal_to_prim(&token, Primitive::Toks) {
+            let index = self.parse_8bit_number();
+            TokenListVariable::ToksRegister(index)
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::EveryPar)
+        {
+            TokenListVariable::Parameter(TokenListParameter::EveryPar)
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::EveryHBox)
+        {
+            TokenListVariable::Parameter(TokenListParameter::EveryHBox)
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::EveryVBox)
+        {
+            TokenListVariable::Parameter(TokenListParameter::EveryVBox)
+        } else if self
+            .state
+            .is_token_equal_to_prim(&token, Primitive::EveryMath)
+        {
+            TokenListVariable::Parameter(TokenListParameter::EveryMath)
         } else {
             panic!("unimplemented");
         }
@@ -94,7 +351,7 @@ mod tests {
         with_parser(
             &["\\let\\x=\\count%", "\\count0%", "\\count255%", "\\x255%"],
             |parser| {
-                parser.parse_assignment(None);
+                parser.parse_assignment();
 
                 assert!(parser.is_integer_variable_head());
                 assert_eq!(
@@ -138,6 +395,23 @@ mod tests {
         });
     }
 
+    #[test]
+    fn it_parses_dimen_register_variables() {
+        with_parser(&[r"\dimen0%", r"\dimen255%"], |parser| {
+            assert!(parser.is_dimen_variable_head());
+            assert_eq!(
+                parser.parse_dimen_variable(),
+                DimenVariable::DimenRegister(0)
+            );
+
+            assert!(parser.is_dimen_variable_head());
+            assert_eq!(
+                parser.parse_dimen_variable(),
+                DimenVariable::DimenRegister(255)
+            );
+        });
+    }
+
     #[test]
     fn it_parses_other_dimen_variables() {
         with_parser(&["\\hsize%"], |parser| {
@@ -166,6 +440,73 @@ mod tests {
         });
     }
 
+    #[test]
+    fn it_parses_skip_register_variables() {
+        with_parser(&[r"\skip0%", r"\skip255%"], |parser| {
+            assert!(parser.is_glue_variable_head());
+            assert_eq!(
+                parser.parse_glue_variable(),
+                GlueVariable::SkipRegister(0),
+            );
+
+            assert!(parser.is_glue_variable_head());
+            assert_eq!(
+                parser.parse_glue_variable(),
+                GlueVariable::SkipRegister(255),
+            );
+        });
+    }
+
+    #[test]
+    fn it_parses_toks_register_variables() {
+        with_parser(&[r"\toks0%", r"\toks255%"], |parser| {
+            assert!(parser.is_token_list_variable_head());
+            assert_eq!(
+                parser.parse_token_list_variable(),
+                TokenListVariable::ToksRegister(0),
+            );
+
+            assert!(parser.is_token_list_variable_head());
+            assert_eq!(
+                parser.parse_token_list_variable(),
+                TokenListVariable::ToksRegister(255),
+            );
+        });
+    }
+
+    #[test]
+    fn it_parses_register_defs_as_variables() {
+        with_parser(
+            &[r"\countdef\a=1 \dimendef\b=2 \skipdef\c=3 %", r"\a\b\c%"],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                assert!(parser.is_integer_variable_head());
+                assert!(!parser.is_dimen_variable_head());
+                assert_eq!(
+                    parser.parse_integer_variable(),
+                    IntegerVariable::CountRegister(1),
+                );
+
+                assert!(parser.is_dimen_variable_head());
+                assert!(!parser.is_glue_variable_head());
+                assert_eq!(
+                    parser.parse_dimen_variable(),
+                    DimenVariable::DimenRegister(2),
+                );
+
+                assert!(parser.is_glue_variable_head());
+                assert!(!parser.is_integer_variable_head());
+                assert_eq!(
+                    parser.parse_glue_variable(),
+                    GlueVariable::SkipRegister(3),
+                );
+            },
+        );
+    }
+
     #[test]
     fn it_parses_integer_parameter_variables() {
         with_parser(&[r"\tolerance%", r"\pretolerance%"], |parser| {
@@ -1,16 +1,93 @@
 use crate::category::Category;
-use crate::dimension::{Dimen, Unit};
+use crate::dimension::{Dimen, FilDimen, FilKind, SpringDimen, Unit};
 use crate::glue::Glue;
+use crate::limits::ResourceLimit;
 use crate::line_breaking::{
     break_horizontal_list_to_lines_with_params, LineBreakingParams,
 };
 use crate::list::{HorizontalListElem, VerticalListElem};
-use crate::parser::assignment::SpecialVariables;
+use crate::parser::mode::Mode;
 use crate::parser::Parser;
-use crate::state::{DimenParameter, GlueParameter, IntegerParameter};
+use crate::primitive::Primitive;
+use crate::state::{
+    DimenParameter, GlueParameter, IntegerParameter, TokenListParameter,
+};
 use crate::token::Token;
 
+/// The primitives for vertical material, and for the horizontal material
+/// that starts a paragraph.
+#[cfg(test)]
+pub const HANDLED_PRIMITIVES: &[Primitive] = &[
+    Primitive::HSkip,
+    Primitive::Char,
+    Primitive::VRule,
+    Primitive::VAlign,
+    Primitive::Discretionary,
+    Primitive::DiscretionaryHyphen,
+    Primitive::End,
+    Primitive::Par,
+    Primitive::VSkip,
+    Primitive::VFil,
+    Primitive::VFill,
+    Primitive::Kern,
+    Primitive::Penalty,
+    Primitive::Raise,
+    Primitive::Lower,
+    Primitive::MoveLeft,
+    Primitive::MoveRight,
+    Primitive::Indent,
+    Primitive::NoIndent,
+];
+
 impl<'a> Parser<'a> {
+    // Figures out the shape of the current paragraph, as a list of the
+    // indentation and width of the lines in the paragraph, following the
+    // format of LineBreakingParams::par_shape. A non-empty \parshape takes
+    // precedence over \hangindent and \hangafter. A paragraph can't have
+    // more than `max_lines` lines, so the shape doesn't need to describe more
+    // lines than that even if \hangafter is huge.
+    fn get_paragraph_shape(&self, max_lines: usize) -> Vec<(Dimen, Dimen)> {
+        let par_shape = self.state.get_par_shape();
+        if !par_shape.is_empty() {
+            return par_shape;
+        }
+
+        let hang_indent =
+            self.state.get_dimen_parameter(&DimenParameter::HangIndent);
+        if hang_indent == Dimen::zero() {
+            return Vec::new();
+        }
+
+        let hsize = self.state.get_dimen_parameter(&DimenParameter::HSize);
+        let hang_after = self
+            .state
+            .get_integer_parameter(&IntegerParameter::HangAfter);
+
+        // A positive \hangindent indents the lines on the left, and a negative
+        // one indents them on the right.
+        let normal_line = (Dimen::zero(), hsize);
+        let hanging_line = if hang_indent > Dimen::zero() {
+            (hang_indent, hsize - hang_indent)
+        } else {
+            (Dimen::zero(), hsize + hang_indent)
+        };
+
+        // With a non-negative \hangafter, the lines after the first
+        // \hangafter lines are indented. With a negative \hangafter, the
+        // first -\hangafter lines are indented instead.
+        let num_first_lines =
+            (hang_after.unsigned_abs() as usize).min(max_lines);
+        if hang_after >= 0 {
+            let mut shape = vec![normal_line; num_first_lines];
+            shape.push(hanging_line);
+            shape
+        } else {
+            let mut shape = vec![hanging_line; num_first_lines];
+            shape.push(normal_line);
+            shape
+        }
+    }
+
     /// Handle generating an optionally indented horizontal mode box by
     /// entering horizontal mode and parsing the box there.
     fn handle_enter_horizontal_mode(
@@ -18,6 +95,19 @@ impl<'a> Parser<'a> {
         indent: bool,
     ) -> Vec<VerticalListElem> {
         // TODO(xymostech): Add \parskip glue before the box if the vertical list is empty.
+
+        self.state
+            .set_integer_parameter(false, &IntegerParameter::PrevGraf, 0);
+
+        // The \everypar tokens are read at the start of the paragraph, after
+        // the indentation box (if any) has been added. Since the indentation
+        // is added before parse_horizontal_list() reads any tokens, we can
+        // just put the tokens in the input here.
+        let every_par = self
+            .state
+            .get_token_list_parameter(&TokenListParameter::EveryPar);
+        self.add_upcoming_tokens(every_par);
+
         let mut list = self.parse_horizontal_list(false, indent);
 
         if let Some(HorizontalListElem::HSkip(_)) = list.last() {
@@ -36,6 +126,15 @@ impl<'a> Parser<'a> {
             &list,
             LineBreakingParams {
                 hsize: self.state.get_dimen_parameter(&DimenParameter::HSize),
+                // Every line ends at a different element of the list, so
+                // there can't be more lines than elements.
+                par_shape: self.get_paragraph_shape(list.len()),
+                left_skip: self
+                    .state
+                    .get_glue_parameter(&GlueParameter::LeftSkip),
+                right_skip: self
+                    .state
+                    .get_glue_parameter(&GlueParameter::RightSkip),
                 tolerance: self
                     .state
                     .get_integer_parameter(&IntegerParameter::Tolerance),
@@ -49,17 +148,41 @@ impl<'a> Parser<'a> {
             self.state,
         );
 
-        if let Some(boxes) = maybe_boxes {
+        let boxes = if let Some(boxes) = maybe_boxes {
             boxes
-                .into_iter()
-                .map(|tex_box| VerticalListElem::Box {
-                    tex_box: tex_box,
-                    shift: Dimen::zero(),
-                })
-                .collect()
         } else {
             panic!("No valid line breaking found");
-        }
+        };
+
+        // Once a paragraph is finished, \prevgraf is set to the number of lines
+        // in it, and the parameters that affect the shape of the paragraph are
+        // reset so that they only apply to one paragraph.
+        self.state.set_integer_parameter(
+            false,
+            &IntegerParameter::PrevGraf,
+            boxes.len() as i32,
+        );
+        self.state.set_integer_parameter(
+            false,
+            &IntegerParameter::Looseness,
+            0,
+        );
+        self.state.set_dimen_parameter(
+            false,
+            &DimenParameter::HangIndent,
+            &Dimen::zero(),
+        );
+        self.state.set_integer_parameter(
+            false,
+            &IntegerParameter::HangAfter,
+            1,
+        );
+        self.state.set_par_shape(false, &[]);
+
+        boxes
+            .into_iter()
+            .map(|(tex_box, shift)| VerticalListElem::Box { tex_box, shift })
+            .collect()
     }
 
     /// Checks if a token is the start of something that only is valid in
@@ -72,8 +195,17 @@ impl<'a> Parser<'a> {
             _ => {}
         }
 
-        if self.state.is_token_equal_to_prim(tok, "hskip")
-            || self.state.is_token_equal_to_prim(tok, "char")
+        if self.state.is_token_equal_to_prim(tok, Primitive::HSkip)
+            || self.state.is_token_equal_to_prim(tok, Primitive::Char)
+            || self.state.get_chardef(tok).is_some()
+            || self.state.is_token_equal_to_prim(tok, Primitive::VRule)
+            || self.state.is_token_equal_to_prim(tok, Primitive::VAlign)
+            || self
+                .state
+                .is_token_equal_to_prim(tok, Primitive::Discretionary)
+            || self
+                .state
+                .is_token_equal_to_prim(tok, Primitive::DiscretionaryHyphen)
         {
             return true;
         }
@@ -81,10 +213,9 @@ impl<'a> Parser<'a> {
         false
     }
 
-    fn parse_vertical_list_elems(
+    pub fn parse_vertical_list_elems(
         &mut self,
         group_level: &mut usize,
-        prev_depth: &mut Dimen,
         internal: bool,
     ) -> Option<Vec<VerticalListElem>> {
         let expanded_token = self.peek_expanded_token();
@@ -103,21 +234,13 @@ impl<'a> Parser<'a> {
             Some(Token::Char(_, cat)) => match cat {
                 Category::Space => {
                     self.lex_expanded_token();
-                    self.parse_vertical_list_elems(
-                        group_level,
-                        prev_depth,
-                        internal,
-                    )
+                    self.parse_vertical_list_elems(group_level, internal)
                 }
                 Category::BeginGroup => {
                     self.lex_expanded_token();
                     *group_level += 1;
                     self.state.push_state();
-                    self.parse_vertical_list_elems(
-                        group_level,
-                        prev_depth,
-                        internal,
-                    )
+                    self.parse_vertical_list_elems(group_level, internal)
                 }
                 Category::EndGroup => {
                     if *group_level == 0 {
@@ -129,41 +252,83 @@ impl<'a> Parser<'a> {
                     } else {
                         self.lex_expanded_token();
                         *group_level -= 1;
-                        self.state.pop_state();
-                        self.parse_vertical_list_elems(
-                            group_level,
-                            prev_depth,
-                            internal,
-                        )
+                        self.end_group();
+                        self.parse_vertical_list_elems(group_level, internal)
                     }
                 }
                 _ => panic!("unimplemented"),
             },
-            Some(ref tok) if self.state.is_token_equal_to_prim(tok, "end") => {
+            Some(ref tok)
+                if self.state.is_token_equal_to_prim(tok, Primitive::End) =>
+            {
                 if internal {
-                    panic!(r"You can't use \end in internal vertical mode")
+                    self.report_illegal_case(&[Primitive::End]);
                 }
                 self.lex_expanded_token();
                 None
             }
-            Some(ref tok) if self.state.is_token_equal_to_prim(tok, "par") => {
+            Some(ref tok)
+                if self.state.is_token_equal_to_prim(tok, Primitive::Par) =>
+            {
                 // \par is completely ignored
                 self.lex_expanded_token();
-                self.parse_vertical_list_elems(
-                    group_level,
-                    prev_depth,
-                    internal,
-                )
+                self.parse_vertical_list_elems(group_level, internal)
             }
             Some(ref tok)
-                if self.state.is_token_equal_to_prim(tok, "vskip") =>
+                if self.state.is_token_equal_to_prim(tok, Primitive::VSkip) =>
             {
                 self.lex_expanded_token();
                 let glue = self.parse_glue();
                 Some(vec![VerticalListElem::VSkip(glue)])
             }
             Some(ref tok)
-                if self.state.is_token_equal_to_prim(tok, "moveleft") =>
+                if self.state.is_token_equal_to_prim(tok, Primitive::VFil)
+                    || self
+                        .state
+                        .is_token_equal_to_prim(tok, Primitive::VFill) =>
+            {
+                let tok = self.lex_expanded_token().unwrap();
+                let kind =
+                    if self.state.is_token_equal_to_prim(&tok, Primitive::VFil)
+                    {
+                        FilKind::Fil
+                    } else {
+                        FilKind::Fill
+                    };
+                Some(vec![VerticalListElem::VSkip(Glue {
+                    space: Dimen::zero(),
+                    stretch: SpringDimen::FilDimen(FilDimen::new(kind, 1.0)),
+                    shrink: SpringDimen::Dimen(Dimen::zero()),
+                })])
+            }
+            Some(ref tok)
+                if self.state.is_token_equal_to_prim(tok, Primitive::Kern) =>
+            {
+                self.lex_expanded_token();
+                let dimen = self.parse_dimen();
+                Some(vec![VerticalListElem::Kern(dimen)])
+            }
+            Some(ref tok)
+                if self
+                    .state
+                    .is_token_equal_to_prim(tok, Primitive::Penalty) =>
+            {
+                self.lex_expanded_token();
+                let penalty = self.parse_number();
+                Some(vec![VerticalListElem::Penalty(penalty)])
+            }
+            Some(ref tok)
+                if self.state.is_token_equal_to_prim(tok, Primitive::Raise)
+                    || self
+                        .state
+                        .is_token_equal_to_prim(tok, Primitive::Lower) =>
+            {
+                self.report_illegal_case(&[Primitive::Raise, Primitive::Lower]);
+            }
+            Some(ref tok)
+                if self
+                    .state
+                    .is_token_equal_to_prim(tok, Primitive::MoveLeft) =>
             {
                 self.lex_expanded_token();
                 let shift = self.parse_dimen();
@@ -173,45 +338,59 @@ impl<'a> Parser<'a> {
                         shift: shift * -1,
                     }])
                 } else {
-                    self.parse_vertical_list_elems(
-                        group_level,
-                        prev_depth,
-                        internal,
-                    )
+                    self.parse_vertical_list_elems(group_level, internal)
                 }
             }
             Some(ref tok)
-                if self.state.is_token_equal_to_prim(tok, "moveright") =>
+                if self
+                    .state
+                    .is_token_equal_to_prim(tok, Primitive::MoveRight) =>
             {
                 self.lex_expanded_token();
                 let shift = self.parse_dimen();
                 if let Some(tex_box) = self.parse_box() {
                     Some(vec![VerticalListElem::Box { tex_box, shift }])
                 } else {
-                    self.parse_vertical_list_elems(
-                        group_level,
-                        prev_depth,
-                        internal,
-                    )
+                    self.parse_vertical_list_elems(group_level, internal)
                 }
             }
             _ => {
                 if self.is_assignment_head() {
-                    self.parse_assignment(Some(SpecialVariables {
-                        prev_depth: Some(prev_depth),
-                    }));
-                    self.parse_vertical_list_elems(
-                        group_level,
-                        prev_depth,
-                        internal,
-                    )
+                    self.parse_assignment();
+                    self.parse_vertical_list_elems(group_level, internal)
+                } else if self.is_output_file_command_head() {
+                    match self.parse_output_file_command() {
+                        Some(operation) => {
+                            Some(vec![VerticalListElem::Whatsit(operation)])
+                        }
+                        None => self
+                            .parse_vertical_list_elems(group_level, internal),
+                    }
+                } else if self.is_input_file_command_head() {
+                    self.parse_input_file_command();
+                    self.parse_vertical_list_elems(group_level, internal)
+                } else if self.is_message_head() {
+                    self.parse_message();
+                    self.parse_vertical_list_elems(group_level, internal)
+                } else if self.is_shipout_head() {
+                    self.parse_shipout();
+                    self.parse_vertical_list_elems(group_level, internal)
+                } else if self.is_semi_simple_group_head() {
+                    self.parse_semi_simple_group_head();
+                    self.parse_vertical_list_elems(group_level, internal)
                 } else if self.is_next_expanded_token_in_set_of_primitives(&[
-                    "indent", "noindent",
+                    Primitive::Indent,
+                    Primitive::NoIndent,
                 ]) {
                     let tok = self.lex_expanded_token().unwrap();
-                    let indent =
-                        self.state.is_token_equal_to_prim(&tok, "indent");
+                    let indent = self
+                        .state
+                        .is_token_equal_to_prim(&tok, Primitive::Indent);
                     Some(self.handle_enter_horizontal_mode(indent))
+                } else if self.is_horizontal_rule_head() {
+                    Some(vec![self.parse_horizontal_rule()])
+                } else if self.is_alignment_head() {
+                    Some(self.parse_alignment())
                 } else if self.is_box_head() {
                     let maybe_tex_box = self.parse_box();
                     if let Some(tex_box) = maybe_tex_box {
@@ -221,14 +400,10 @@ impl<'a> Parser<'a> {
                             shift: Dimen::zero(),
                         }])
                     } else {
-                        self.parse_vertical_list_elems(
-                            group_level,
-                            prev_depth,
-                            internal,
-                        )
+                        self.parse_vertical_list_elems(group_level, internal)
                     }
                 } else {
-                    panic!("unimplemented");
+                    self.fail_on_unexpected_token();
                 }
             }
         }
@@ -238,10 +413,18 @@ impl<'a> Parser<'a> {
         &mut self,
         internal: bool,
     ) -> Vec<VerticalListElem> {
+        self.push_mode(if internal {
+            Mode::InternalVertical
+        } else {
+            Mode::Vertical
+        });
+
         let mut result = Vec::new();
 
-        // The depth of the most recent box.
-        let mut prev_depth = Dimen::from_unit(-1000.0, Unit::Point);
+        // The depth of the most recent box, which is what \prevdepth refers
+        // to while this list is being built.
+        self.prev_depth_stack
+            .push(Dimen::from_unit(-1000.0, Unit::Point));
 
         // TODO(xymostech): Store these as \baselineskip, \lineskiplimit,
         // \lineskip, and \topskip parameters
@@ -252,12 +435,22 @@ impl<'a> Parser<'a> {
         let topskip = Glue::from_dimen(Dimen::from_unit(10.0, Unit::Point));
 
         let mut group_level = 0;
-        while let Some(elems) = self.parse_vertical_list_elems(
-            &mut group_level,
-            &mut prev_depth,
-            internal,
-        ) {
+        loop {
+            // The list is kept on the parser while the elements are being
+            // parsed, so that \lastbox can remove boxes from it.
+            self.vertical_list_stack.push(result);
+            let maybe_elems =
+                self.parse_vertical_list_elems(&mut group_level, internal);
+            result = self.vertical_list_stack.pop().unwrap();
+
+            let elems = match maybe_elems {
+                Some(elems) => elems,
+                None => break,
+            };
+
             for elem in elems {
+                self.state.use_resource(ResourceLimit::ListElements, 1);
+
                 // Handle box elements specially so we can add interline glue
                 if let VerticalListElem::Box {
                     ref tex_box,
@@ -278,6 +471,7 @@ impl<'a> Parser<'a> {
                     }
 
                     // If prev_depth is -1000pt, don't add interline glue
+                    let prev_depth = self.get_prev_depth();
                     if prev_depth != Dimen::from_unit(-1000.0, Unit::Point) {
                         // Calculate how much interline glue we'd add if we just
                         // take into account baselineskip - prev_depth - box.height
@@ -298,12 +492,21 @@ impl<'a> Parser<'a> {
                     }
 
                     // Keep track of the depth of the most recent box
-                    prev_depth = *tex_box.depth();
+                    self.set_prev_depth(*tex_box.depth());
+                }
+
+                // No interline glue is added after rules.
+                if let VerticalListElem::Rule { .. } = elem {
+                    self.set_prev_depth(Dimen::from_unit(-1000.0, Unit::Point));
                 }
 
                 if !internal {
-                    if let VerticalListElem::VSkip(_) = elem {
-                        // Glue disappears at a page break.
+                    if let VerticalListElem::VSkip(_)
+                    | VerticalListElem::Kern(_)
+                    | VerticalListElem::Penalty(_) = elem
+                    {
+                        // Glue, kerns, and penalties disappear at a page
+                        // break.
                         if !result.is_empty() {
                             result.push(elem);
                         }
@@ -316,8 +519,29 @@ impl<'a> Parser<'a> {
             }
         }
 
+        self.prev_depth_stack.pop();
+        self.pop_mode();
+
         result
     }
+
+    /// Returns the current value of \prevdepth. This is only available while
+    /// building a vertical list.
+    pub fn get_prev_depth(&self) -> Dimen {
+        match (self.get_mode(), self.prev_depth_stack.last()) {
+            (Mode::Vertical, Some(prev_depth))
+            | (Mode::InternalVertical, Some(prev_depth)) => *prev_depth,
+            _ => panic!("Improper \\prevdepth"),
+        }
+    }
+
+    pub fn set_prev_depth(&mut self, prev_depth: Dimen) {
+        match (self.get_mode(), self.prev_depth_stack.last_mut()) {
+            (Mode::Vertical, Some(current))
+            | (Mode::InternalVertical, Some(current)) => *current = prev_depth,
+            _ => panic!("Improper \\prevdepth"),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -327,8 +551,10 @@ mod tests {
     use once_cell::sync::Lazy;
 
     use crate::boxes::{GlueSetRatio, GlueSetRatioKind, TeXBox, VerticalBox};
-    use crate::dimension::{FilDimen, FilKind, SpringDimen};
     use crate::font::Font;
+    use crate::page_breaking::{
+        break_vertical_list_into_pages, PageBreakingParams,
+    };
     use crate::testing::with_parser;
 
     static CMR10: Lazy<Font> = Lazy::new(|| Font {
@@ -373,6 +599,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_parses_vertical_kerns() {
+        assert_parses_to(
+            &[r"\kern 1pt\vskip 2pt\kern-3pt%"],
+            &[
+                VerticalListElem::Kern(Dimen::from_unit(1.0, Unit::Point)),
+                VerticalListElem::VSkip(Glue::from_dimen(Dimen::from_unit(
+                    2.0,
+                    Unit::Point,
+                ))),
+                VerticalListElem::Kern(Dimen::from_unit(-3.0, Unit::Point)),
+            ],
+        );
+    }
+
     #[test]
     fn it_ignores_spaces() {
         assert_parses_to(
@@ -469,6 +710,14 @@ mod tests {
         assert_parses_to(&[r"\vskip 0pt\end%"], &[]);
     }
 
+    #[test]
+    #[should_panic(
+        expected = r"You can't use \raise in internal vertical mode"
+    )]
+    fn it_should_fail_with_raise_in_vertical_mode() {
+        assert_parses_to(&[r"\raise 2pt\hbox{a}%"], &[]);
+    }
+
     #[test]
     fn it_skips_glue_at_the_beginning_of_non_internal_vertical_mode() {
         assert_parses_to_non_internal(&[r"\vskip 0pt\vskip 1pt\end%"], &[]);
@@ -565,6 +814,106 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_uses_vboxes_in_lines_for_topskip() {
+        with_parser(&[r"\noindent\vbox{\hbox{a}}\par\end%"], |parser| {
+            let metrics = parser.state.get_metrics_for_font(&CMR10).unwrap();
+
+            let list = parser.parse_vertical_list(false);
+            assert_eq!(list.len(), 2);
+            assert_eq!(
+                list[0],
+                VerticalListElem::VSkip(Glue::from_dimen(
+                    Dimen::from_unit(10.0, Unit::Point)
+                        - metrics.get_height('a')
+                ))
+            );
+            assert_eq!(list[1].get_size().0.space, metrics.get_height('a'));
+        });
+
+        // If the vbox is taller than \topskip, no glue is added
+        with_parser(&[r"\noindent\vbox to15pt{}\par\end%"], |parser| {
+            let list = parser.parse_vertical_list(false);
+            assert_eq!(list.len(), 1);
+            assert_eq!(
+                list[0].get_size().0.space,
+                Dimen::from_unit(15.0, Unit::Point)
+            );
+        });
+    }
+
+    #[test]
+    fn it_uses_vbox_depths_in_lines_for_interline_glue() {
+        with_parser(
+            &[
+                r"\noindent\vbox{\hbox{a}\hbox{g}}\par%",
+                r"\noindent a\par\end%",
+            ],
+            |parser| {
+                let metrics =
+                    parser.state.get_metrics_for_font(&CMR10).unwrap();
+
+                let list = parser.parse_vertical_list(false);
+
+                // The vbox is taller than \topskip, so the first line comes
+                // first.
+                assert_eq!(list.len(), 3);
+                assert_eq!(list[0].get_size().1, metrics.get_depth('g'));
+                assert_eq!(
+                    list[1],
+                    VerticalListElem::VSkip(Glue::from_dimen(
+                        Dimen::from_unit(12.0, Unit::Point)
+                            - metrics.get_depth('g')
+                            - metrics.get_height('a')
+                    ))
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_uses_shifted_vboxes_in_lines_for_interline_glue() {
+        with_parser(
+            &[
+                r"\noindent\raise1pt\vbox{\hbox{g}}\par%",
+                r"\noindent a\par\end%",
+            ],
+            |parser| {
+                let metrics =
+                    parser.state.get_metrics_for_font(&CMR10).unwrap();
+                let shift = Dimen::from_unit(1.0, Unit::Point);
+
+                let list = parser.parse_vertical_list(false);
+
+                assert_eq!(list.len(), 4);
+                assert_eq!(
+                    list[0],
+                    VerticalListElem::VSkip(Glue::from_dimen(
+                        Dimen::from_unit(10.0, Unit::Point)
+                            - metrics.get_height('g')
+                            - shift
+                    ))
+                );
+                assert_eq!(
+                    list[1].get_size(),
+                    (
+                        Glue::from_dimen(metrics.get_height('g') + shift),
+                        metrics.get_depth('g') - shift,
+                        Dimen::from_unit(6.5, Unit::Inch),
+                    )
+                );
+                assert_eq!(
+                    list[2],
+                    VerticalListElem::VSkip(Glue::from_dimen(
+                        Dimen::from_unit(12.0, Unit::Point)
+                            - (metrics.get_depth('g') - shift)
+                            - metrics.get_height('a')
+                    ))
+                );
+            },
+        );
+    }
+
     #[test]
     #[should_panic(expected = "Too many }'s")]
     fn it_should_fail_with_too_many_end_groups() {
@@ -573,6 +922,61 @@ mod tests {
         });
     }
 
+    #[test]
+    fn it_expands_mode_conditionals_in_each_mode() {
+        with_parser(
+            &[
+                r"\def\m{\ifvmode1\fi\ifhmode2\fi\ifmmode3\fi}%",
+                r"\count1=\m %",
+                r"\setbox0=\hbox{\global\count2=\m $\global\count3=\m $}%",
+                r"\setbox0=\vbox{\global\count4=\m }%",
+                r"\end%",
+            ],
+            |parser| {
+                parser.parse_vertical_list(false);
+
+                assert_eq!(parser.state.get_count(1), 1);
+                assert_eq!(parser.state.get_count(2), 2);
+                assert_eq!(parser.state.get_count(3), 3);
+                assert_eq!(parser.state.get_count(4), 1);
+            },
+        );
+    }
+
+    #[test]
+    fn it_scopes_assignments_in_semi_simple_groups() {
+        with_parser(
+            &[
+                r"\count1=1 %",
+                r"\begingroup\count1=2 \begingroup\global\count2=3 \endgroup%",
+                r"\count3=\count1 \endgroup\end%",
+            ],
+            |parser| {
+                parser.parse_vertical_list(false);
+
+                assert_eq!(parser.state.get_count(1), 1);
+                assert_eq!(parser.state.get_count(2), 3);
+                assert_eq!(parser.state.get_count(3), 0);
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r"Extra }, or forgotten \endgroup")]
+    fn it_should_fail_when_braces_end_semi_simple_groups() {
+        with_parser(&[r"{\begingroup}\end%"], |parser| {
+            parser.parse_vertical_list(false);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = r"Extra \endgroup")]
+    fn it_should_fail_when_endgroup_ends_brace_groups() {
+        with_parser(&[r"\begingroup{\endgroup}\end%"], |parser| {
+            parser.parse_vertical_list(false);
+        });
+    }
+
     #[test]
     #[should_panic(expected = r"EOF found before \end")]
     fn it_should_fail_with_no_end() {
@@ -581,6 +985,51 @@ mod tests {
         });
     }
 
+    #[test]
+    fn it_forces_page_breaks_at_penalties() {
+        with_parser(
+            &[r"\hbox{a}\penalty-10000\hbox{b}\penalty10000 \end%"],
+            |parser| {
+                let list = parser.parse_vertical_list(false);
+                let penalties: Vec<&VerticalListElem> = list
+                    .iter()
+                    .filter(|elem| matches!(elem, VerticalListElem::Penalty(_)))
+                    .collect();
+                assert_eq!(
+                    penalties,
+                    vec![
+                        &VerticalListElem::Penalty(-10000),
+                        &VerticalListElem::Penalty(10000)
+                    ]
+                );
+
+                let pages = break_vertical_list_into_pages(
+                    &list,
+                    &PageBreakingParams {
+                        vsize: Dimen::from_unit(100.0, Unit::Point),
+                        max_depth: Dimen::from_unit(2.0, Unit::Point),
+                        should_log: false,
+                    },
+                    &mut Vec::new(),
+                );
+                let page_chars: Vec<Vec<char>> = pages
+                    .iter()
+                    .map(|page| {
+                        page.iter()
+                            .flat_map(|elem| match elem {
+                                VerticalListElem::Box { tex_box, .. } => {
+                                    tex_box.to_chars()
+                                }
+                                _ => vec![],
+                            })
+                            .collect()
+                    })
+                    .collect();
+                assert_eq!(page_chars, vec![vec!['a'], vec!['b']]);
+            },
+        );
+    }
+
     #[test]
     fn it_parses_box_elements() {
         with_parser(
@@ -594,8 +1043,8 @@ mod tests {
                 r"\box2",
             ],
             |parser| {
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
+                parser.parse_assignment();
+                parser.parse_assignment();
 
                 let box0 = parser.state.get_box(0).unwrap();
                 let box1 = parser.state.get_box(1).unwrap();
@@ -643,9 +1092,9 @@ mod tests {
                 r"\noindent g\par%",
             ],
             |parser| {
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
 
                 let box0 = parser.state.get_box(0).unwrap();
                 let box1 = parser.state.get_box(1).unwrap();
@@ -695,11 +1144,11 @@ mod tests {
                 r"\indent g\par%",
             ],
             |parser| {
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
 
                 let box0 = parser.state.get_box(0).unwrap();
                 let box1 = parser.state.get_box(1).unwrap();
@@ -734,6 +1183,64 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_adds_everypar_tokens_without_indentation_after_noindent() {
+        with_parser(
+            &[
+                r"\hsize=1000pt%",
+                r"\setbox0=\hbox to1000pt{xa\hskip 0pt plus1fil}%",
+                r"\everypar={x}%",
+                r"\noindent a\par%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                let box0 = parser.state.get_box(0).unwrap();
+
+                assert_eq!(
+                    parser.parse_vertical_list(true),
+                    &[VerticalListElem::Box {
+                        tex_box: box0,
+                        shift: Dimen::zero()
+                    }]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_adds_everypar_tokens_after_indentation() {
+        with_parser(
+            &[
+                r"\hsize=1000pt%",
+                r"\setbox2=\hbox{}%",
+                r"\wd2=20pt%",
+                r"\setbox0=\hbox to1000pt{\copy2 xa\hskip 0pt plus1fil}%",
+                r"\everypar={x}%",
+                r"a\par%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                let box0 = parser.state.get_box(0).unwrap();
+
+                assert_eq!(
+                    parser.parse_vertical_list(true),
+                    &[VerticalListElem::Box {
+                        tex_box: box0,
+                        shift: Dimen::zero()
+                    }]
+                );
+            },
+        );
+    }
+
     #[test]
     fn it_enters_horizontal_mode_after_horizontal_material() {
         with_parser(
@@ -755,14 +1262,14 @@ mod tests {
                 r"\char 97\par%",
             ],
             |parser| {
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
 
                 let box1 = parser.state.get_box(1).unwrap();
                 let box2 = parser.state.get_box(2).unwrap();
@@ -839,13 +1346,13 @@ mod tests {
                 r"\copy2%",
             ],
             |parser| {
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
+                parser.parse_assignment();
 
                 assert_eq!(
                     parser.parse_vertical_list(true),
@@ -878,6 +1385,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_does_not_add_interline_glue_around_rules() {
+        with_parser(
+            &[
+                r"\setbox0=\hbox{}%",
+                r"\dp0=5pt%",
+                r"\copy0%",
+                r"\hrule%",
+                r"\copy0%",
+            ],
+            |parser| {
+                parser.parse_assignment();
+                parser.parse_assignment();
+
+                assert_eq!(
+                    parser.parse_vertical_list(true),
+                    &[
+                        VerticalListElem::Box {
+                            tex_box: parser.state.get_box_copy(0).unwrap(),
+                            shift: Dimen::zero()
+                        },
+                        VerticalListElem::Rule {
+                            height: Some(Dimen::from_unit(0.4, Unit::Point)),
+                            depth: Some(Dimen::zero()),
+                            width: None,
+                        },
+                        VerticalListElem::Box {
+                            tex_box: parser.state.get_box_copy(0).unwrap(),
+                            shift: Dimen::zero()
+                        },
+                    ]
+                );
+            },
+        );
+    }
+
     #[test]
     fn it_ignores_par() {
         with_parser(&[r"\vskip1pt", r"", r"\vskip1pt%"], |parser| {
@@ -962,6 +1505,35 @@ mod tests {
         });
     }
 
+    #[test]
+    fn it_reads_prev_depth_values() {
+        with_parser(
+            &[
+                r"\setbox0=\hbox{}%",
+                r"\dp0=3pt%",
+                r"\setbox1=\hbox{}%",
+                r"\setbox2=\hbox{}%",
+                r"\wd1=\prevdepth%",
+                r"\copy0%",
+                r"\wd2=\prevdepth%",
+                r"\ifdim\prevdepth=3pt \count1=1 \fi%",
+            ],
+            |parser| {
+                parser.parse_vertical_list(true);
+
+                assert_eq!(
+                    parser.state.get_box_copy(1).unwrap().width(),
+                    &Dimen::from_unit(-1000.0, Unit::Point)
+                );
+                assert_eq!(
+                    parser.state.get_box_copy(2).unwrap().width(),
+                    &Dimen::from_unit(3.0, Unit::Point)
+                );
+                assert_eq!(parser.state.get_count(1), 1);
+            },
+        );
+    }
+
     #[test]
     fn it_uses_updated_prev_depth_values() {
         with_parser(
@@ -1052,4 +1624,164 @@ mod tests {
             },
         );
     }
+
+    // Returns the width and shift of each of the lines in a vertical list.
+    fn get_line_widths_and_shifts(
+        list: &[VerticalListElem],
+    ) -> Vec<(Dimen, Dimen)> {
+        list.iter()
+            .filter_map(|elem| match elem {
+                VerticalListElem::Box { tex_box, shift } => {
+                    Some((*tex_box.width(), *shift))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn it_uses_parshape_over_hangindent() {
+        with_parser(
+            &[
+                r"\setbox1=\hbox to20pt{}%",
+                r"\def\a{\copy1}%",
+                r"\def\s{\hskip5pt plus20pt}%",
+                r"\hsize=100pt%",
+                r"\hangindent=30pt \hangafter=0 %",
+                r"\parshape 2 10pt 45pt 5pt 70pt %",
+                r"\noindent\a\s\a\s\a\s\a%",
+            ],
+            |parser| {
+                let list = parser.parse_vertical_list(true);
+
+                assert_eq!(
+                    get_line_widths_and_shifts(&list),
+                    vec![
+                        (
+                            Dimen::from_unit(45.0, Unit::Point),
+                            Dimen::from_unit(10.0, Unit::Point)
+                        ),
+                        (
+                            Dimen::from_unit(70.0, Unit::Point),
+                            Dimen::from_unit(5.0, Unit::Point)
+                        ),
+                    ]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_uses_hangindent_for_the_paragraph_shape() {
+        with_parser(
+            &[
+                r"\setbox1=\hbox to20pt{}%",
+                r"\def\a{\copy1}%",
+                r"\def\s{\hskip5pt plus20pt}%",
+                r"\hsize=100pt%",
+                r"\hangindent=-30pt \hangafter=-1 %",
+                r"\noindent\a\s\a\s\a\s\a%",
+            ],
+            |parser| {
+                let list = parser.parse_vertical_list(true);
+
+                assert_eq!(
+                    get_line_widths_and_shifts(&list),
+                    vec![
+                        (Dimen::from_unit(70.0, Unit::Point), Dimen::zero()),
+                        (Dimen::from_unit(100.0, Unit::Point), Dimen::zero()),
+                    ]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_handles_huge_hangafter_values() {
+        with_parser(
+            &[
+                r"\setbox1=\hbox to20pt{}%",
+                r"\def\a{\copy1}%",
+                r"\def\s{\hskip5pt plus20pt}%",
+                r"\hsize=100pt%",
+                r"\hangindent=30pt \hangafter=2147483647 %",
+                r"\noindent\a\s\a\s\a\s\a\par%",
+                r"\hangindent=30pt \hangafter=-2147483647 %",
+                r"\noindent\a\s\a\s\a\s\a%",
+            ],
+            |parser| {
+                let list = parser.parse_vertical_list(true);
+
+                assert_eq!(
+                    get_line_widths_and_shifts(&list),
+                    vec![
+                        (Dimen::from_unit(100.0, Unit::Point), Dimen::zero()),
+                        (
+                            Dimen::from_unit(70.0, Unit::Point),
+                            Dimen::from_unit(30.0, Unit::Point)
+                        ),
+                        (
+                            Dimen::from_unit(70.0, Unit::Point),
+                            Dimen::from_unit(30.0, Unit::Point)
+                        ),
+                    ]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_resets_paragraph_shape_parameters_after_each_paragraph() {
+        with_parser(
+            &[
+                r"\hsize=100pt%",
+                r"\looseness=1 \hangindent=10pt \hangafter=-2 %",
+                r"\parshape 1 5pt 50pt %",
+                r"\noindent a\par%",
+                r"\count1=\looseness \count2=\hangafter %",
+                r"\count3=\parshape \count4=\prevgraf %",
+                r"\noindent b%",
+            ],
+            |parser| {
+                parser.parse_vertical_list(true);
+
+                assert_eq!(parser.state.get_count(1), 0);
+                assert_eq!(parser.state.get_count(2), 1);
+                assert_eq!(parser.state.get_count(3), 0);
+                assert_eq!(parser.state.get_count(4), 1);
+                assert_eq!(
+                    parser
+                        .state
+                        .get_dimen_parameter(&DimenParameter::HangIndent),
+                    Dimen::zero()
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_indents_paragraphs_by_parindent() {
+        with_parser(
+            &[
+                r"\hsize=100pt \parindent=0pt %",
+                r"\indent ab\par\noindent ab%",
+            ],
+            |parser| {
+                let list = parser.parse_vertical_list(true);
+                let boxes: Vec<&TeXBox> = list
+                    .iter()
+                    .filter_map(|elem| match elem {
+                        VerticalListElem::Box { tex_box, .. } => Some(tex_box),
+                        _ => None,
+                    })
+                    .collect();
+
+                assert_eq!(boxes.len(), 2);
+                assert_eq!(boxes[0].width(), boxes[1].width());
+                assert_eq!(boxes[0].height(), boxes[1].height());
+                assert_eq!(boxes[0].depth(), boxes[1].depth());
+                assert_eq!(boxes[0].to_chars(), boxes[1].to_chars());
+            },
+        );
+    }
 }
@@ -1,5 +1,6 @@
 use once_cell::sync::Lazy;
 /// Module for finding paths to useful files
+use std::path::Path;
 use std::sync::Mutex;
 
 // We store a single shared reference to the kpathsea library so that we
@@ -14,23 +15,41 @@ use std::sync::Mutex;
 static SHARED_KPATHS: Lazy<Mutex<Option<kpathsea::Kpaths>>> =
     Lazy::new(|| Mutex::new(None));
 
-/// Given a font name (like "cmr10"), returns a path to the font if it can be
-/// found.
-pub fn get_path_to_font(font_name: &str) -> Option<String> {
+// Looks up a file using kpathsea, which knows where TeX files like fonts and
+// macro packages are installed.
+fn find_file_with_kpathsea(file_name: &str) -> Option<String> {
     let mut maybe_kpse = SHARED_KPATHS.lock().unwrap();
 
     if let Some(ref kpse) = *maybe_kpse {
-        kpse.find_file(font_name)
+        kpse.find_file(file_name)
     } else {
         match kpathsea::Kpaths::new() {
             Ok(kpse) => {
-                let result = kpse.find_file(font_name);
+                let result = kpse.find_file(file_name);
                 *maybe_kpse = Some(kpse);
                 result
             }
             // If we can't initialize kpathsea successfully, just say we
-            // couldn't find the font.
+            // couldn't find the file.
             Err(_) => None,
         }
     }
 }
+
+/// Given a font name (like "cmr10"), returns a path to the font if it can be
+/// found.
+pub fn get_path_to_font(font_name: &str) -> Option<String> {
+    find_file_with_kpathsea(font_name)
+}
+
+/// Given the name of a file to \input (like "plain.tex"), returns a path to
+/// the file if it can be found. Like TeX, we look relative to the current
+/// directory first, and then in the places where TeX's own input files are
+/// installed.
+pub fn get_path_to_input_file(file_name: &str) -> Option<String> {
+    if Path::new(file_name).is_file() {
+        Some(file_name.to_string())
+    } else {
+        find_file_with_kpathsea(file_name)
+    }
+}
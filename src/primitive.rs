@@ -0,0 +1,215 @@
+// Defines the Primitive enum along with the name of each primitive control
+// sequence, so that checking whether a token is a primitive can be done with
+// an enum variant instead of a string that might have a typo in it.
+macro_rules! define_primitives {
+    ($($variant:ident => $name:expr,)*) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum Primitive {
+            $($variant,)*
+        }
+
+        impl Primitive {
+            /// Every primitive, which are all defined when TeX starts so that
+            /// other control sequences can be \let equal to them.
+            pub const ALL: &'static [Primitive] = &[$(Primitive::$variant,)*];
+
+            /// The name of the control sequence for this primitive, without an
+            /// escape character.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Primitive::$variant => $name,)*
+                }
+            }
+        }
+    };
+}
+
+define_primitives! {
+    IfTrue => "iftrue",
+    IfFalse => "iffalse",
+    Fi => "fi",
+    Else => "else",
+    Def => "def",
+    Let => "let",
+    Global => "global",
+    Count => "count",
+    IfNum => "ifnum",
+    Advance => "advance",
+    Multiply => "multiply",
+    Divide => "divide",
+    Number => "number",
+    Par => "par",
+    HSkip => "hskip",
+    HBox => "hbox",
+    Relax => "relax",
+    SetBox => "setbox",
+    Wd => "wd",
+    Ht => "ht",
+    Dp => "dp",
+    Box => "box",
+    VSkip => "vskip",
+    VFil => "vfil",
+    VFill => "vfill",
+    End => "end",
+    Indent => "indent",
+    NoIndent => "noindent",
+    Copy => "copy",
+    VSplit => "vsplit",
+    VCenter => "vcenter",
+    LastBox => "lastbox",
+    VBox => "vbox",
+    MathCharDef => "mathchardef",
+    UMathCharDef => "Umathchardef",
+    UMathChar => "Umathchar",
+    MathCode => "mathcode",
+    DisplayStyle => "displaystyle",
+    TextStyle => "textstyle",
+    ScriptStyle => "scriptstyle",
+    ScriptScriptStyle => "scriptscriptstyle",
+    Font => "font",
+    Raise => "raise",
+    Lower => "lower",
+    MoveLeft => "moveleft",
+    MoveRight => "moveright",
+    PrevDepth => "prevdepth",
+    Char => "char",
+    Over => "over",
+    Atop => "atop",
+    Above => "above",
+    OverWithDelims => "overwithdelims",
+    AtopWithDelims => "atopwithdelims",
+    AboveWithDelims => "abovewithdelims",
+    HSize => "hsize",
+    VSize => "vsize",
+    MaxDepth => "maxdepth",
+    SplitMaxDepth => "splitmaxdepth",
+    ParSkip => "parskip",
+    SpaceSkip => "spaceskip",
+    ParFillSkip => "parfillskip",
+    LeftSkip => "leftskip",
+    RightSkip => "rightskip",
+    Pretolerance => "pretolerance",
+    Tolerance => "tolerance",
+    TracingParagraphs => "tracingparagraphs",
+    AdjDemerits => "adjdemerits",
+    Kern => "kern",
+    Penalty => "penalty",
+    DisplayLimits => "displaylimits",
+    Limits => "limits",
+    NoLimits => "nolimits",
+    IfX => "ifx",
+    IfDim => "ifdim",
+    EveryPar => "everypar",
+    LcCode => "lccode",
+    UcCode => "uccode",
+    Lowercase => "lowercase",
+    Uppercase => "uppercase",
+    FontDimen => "fontdimen",
+    The => "the",
+    ShowThe => "showthe",
+    Show => "show",
+    ShowBox => "showbox",
+    IfOdd => "ifodd",
+    IfCase => "ifcase",
+    IfCat => "ifcat",
+    Or => "or",
+    HRule => "hrule",
+    VRule => "vrule",
+    JobName => "jobname",
+    OpenOut => "openout",
+    CloseOut => "closeout",
+    Write => "write",
+    Message => "message",
+    OpenIn => "openin",
+    CloseIn => "closein",
+    Read => "read",
+    ShipOut => "shipout",
+    Immediate => "immediate",
+    CsName => "csname",
+    EndCsName => "endcsname",
+    HAlign => "halign",
+    VAlign => "valign",
+    Cr => "cr",
+    TabSkip => "tabskip",
+    NoAlign => "noalign",
+    Discretionary => "discretionary",
+    DiscretionaryHyphen => "-",
+    IfInner => "ifinner",
+    IfVMode => "ifvmode",
+    IfHMode => "ifhmode",
+    IfMMode => "ifmmode",
+    IfEOF => "ifeof",
+    String => "string",
+    Meaning => "meaning",
+    HangIndent => "hangindent",
+    HangAfter => "hangafter",
+    ParShape => "parshape",
+    ExpandAfter => "expandafter",
+    Looseness => "looseness",
+    PrevGraf => "prevgraf",
+    ParIndent => "parindent",
+    NoExpand => "noexpand",
+    HFuzz => "hfuzz",
+    OverfullRule => "overfullrule",
+    Badness => "badness",
+    TracingStats => "tracingstats",
+    EDef => "edef",
+    Time => "time",
+    Day => "day",
+    Month => "month",
+    Year => "year",
+    GDef => "gdef",
+    XDef => "xdef",
+    EveryHBox => "everyhbox",
+    EveryVBox => "everyvbox",
+    EveryMath => "everymath",
+    Dimen => "dimen",
+    FutureLet => "futurelet",
+    Skip => "skip",
+    CharDef => "chardef",
+    Toks => "toks",
+    CountDef => "countdef",
+    DimenDef => "dimendef",
+    SkipDef => "skipdef",
+    CatCode => "catcode",
+    Input => "input",
+    EndInput => "endinput",
+    EndLineChar => "endlinechar",
+    TracingPages => "tracingpages",
+    AfterAssignment => "afterassignment",
+    AfterGroup => "aftergroup",
+    BeginGroup => "begingroup",
+    EndGroup => "endgroup",
+    TextFont => "textfont",
+    ScriptFont => "scriptfont",
+    ScriptScriptFont => "scriptscriptfont",
+    EscapeChar => "escapechar",
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashSet;
+
+    #[test]
+    fn it_has_unique_primitive_names() {
+        let names: HashSet<&str> =
+            Primitive::ALL.iter().map(|prim| prim.name()).collect();
+        assert_eq!(names.len(), Primitive::ALL.len());
+    }
+
+    #[test]
+    fn it_handles_every_primitive_somewhere() {
+        let handled: HashSet<Primitive> = crate::parser::HANDLED_PRIMITIVES
+            .iter()
+            .flat_map(|prims| prims.iter().copied())
+            .collect();
+        let all: HashSet<Primitive> = Primitive::ALL.iter().copied().collect();
+
+        let mut unhandled: Vec<&str> =
+            all.difference(&handled).map(|prim| prim.name()).collect();
+        unhandled.sort();
+        assert_eq!(handled, all, "Unhandled primitives: {:?}", unhandled);
+    }
+}
@@ -1,97 +1,54 @@
-use std::cell::{Ref, RefCell};
-use std::collections::HashMap;
+use std::cell::{Cell, Ref, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 
 use crate::boxes::TeXBox;
 use crate::category::Category;
+use crate::date::get_date_and_time;
+use crate::diagnostics::{DiagnosticKind, Diagnostics};
 use crate::dimension::{Dimen, FilDimen, FilKind, SpringDimen, Unit};
 use crate::font::Font;
 use crate::font_metrics::FontMetrics;
 use crate::glue::Glue;
+use crate::input_files::InputFiles;
+use crate::limits::{ResourceLimit, ResourceLimits};
 use crate::makro::Macro;
 use crate::math_code::MathCode;
-use crate::token::Token;
-
-// A list of all primitive control sequences, used so that we can \let other
-// control sequences equal to them.
-const ALL_PRIMITIVES: &[&str] = &[
-    "iftrue",
-    "iffalse",
-    "fi",
-    "else",
-    "def",
-    "let",
-    "global",
-    "count",
-    "ifnum",
-    "advance",
-    "multiply",
-    "divide",
-    "number",
-    "par",
-    "hskip",
-    "hbox",
-    "relax",
-    "setbox",
-    "wd",
-    "ht",
-    "dp",
-    "box",
-    "vskip",
-    "end",
-    "indent",
-    "noindent",
-    "copy",
-    "vbox",
-    "mathchardef",
-    "mathcode",
-    "displaystyle",
-    "textstyle",
-    "scriptstyle",
-    "scriptscriptstyle",
-    "font",
-    "raise",
-    "lower",
-    "moveleft",
-    "moveright",
-    "prevdepth",
-    "char",
-    "over",
-    "atop",
-    "above",
-    "overwithdelims",
-    "atopwithdelims",
-    "abovewithdelims",
-    "hsize",
-    "parskip",
-    "spaceskip",
-    "parfillskip",
-    "pretolerance",
-    "tolerance",
-    "tracingparagraphs",
-    "adjdemerits",
-];
-
-fn is_primitive(maybe_prim: &str) -> bool {
-    for prim in ALL_PRIMITIVES {
-        if *prim == maybe_prim {
-            return true;
-        }
-    }
-    false
-}
+use crate::math_list::MathStyle;
+use crate::output_files::OutputFiles;
+use crate::primitive::Primitive;
+use crate::stats::Stats;
+use crate::token::{escape_name, Token};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum IntegerParameter {
     Pretolerance,
     Tolerance,
     TracingParagraphs,
+    TracingStats,
+    Time,
+    Day,
+    Month,
+    Year,
     AdjDemerits,
+    HangAfter,
+    Looseness,
+    PrevGraf,
+    EndLineChar,
+    TracingPages,
+    EscapeChar,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum DimenParameter {
     HSize,
+    VSize,
+    MaxDepth,
+    SplitMaxDepth,
+    HangIndent,
+    ParIndent,
+    HFuzz,
+    OverfullRule,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -99,14 +56,42 @@ pub enum GlueParameter {
     ParSkip,
     SpaceSkip,
     ParFillSkip,
+    LeftSkip,
+    RightSkip,
+    TabSkip,
 }
 
-#[derive(Clone)]
+// These are named after TeX's \everypar, \everyhbox, etc., which all happen
+// to share a prefix.
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenListParameter {
+    EveryPar,
+    EveryHBox,
+    EveryVBox,
+    EveryMath,
+}
+
+// The kinds of registers that a control sequence can refer to after \countdef,
+// \dimendef, or \skipdef.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegisterKind {
+    Count,
+    Dimen,
+    Skip,
+}
+
+// Two definitions are equal when they have the same meaning, which is what
+// \ifx compares. Notably, macros are compared by their parameter text and
+// replacement text rather than by where they were defined.
+#[derive(Clone, PartialEq)]
 enum TokenDefinition {
     Macro(Rc<Macro>),
     Token(Token),
     MathCode(MathCode),
-    Primitive(&'static str),
+    CharDef(u8),
+    Register(RegisterKind, u8),
+    Primitive(Primitive),
     Font(Font),
 }
 
@@ -121,6 +106,13 @@ pub struct TeXStateInner {
     // with. Set and retrieved with \mathcode, only used in math mode.
     math_code_map: HashMap<char, MathCode>,
 
+    // Maps of individual characters to their lowercase and uppercase
+    // equivalents. Set and retrieved with \lccode and \uccode, used by
+    // \lowercase and \uppercase. Characters missing from these maps have a
+    // code of 0, which means they aren't changed.
+    lc_code_map: HashMap<char, u8>,
+    uc_code_map: HashMap<char, u8>,
+
     // There are several ways to redefine what a given token means, with \def,
     // \let, \chardef, etc. This map contains the definition of each redefined
     // token.
@@ -132,6 +124,16 @@ pub struct TeXStateInner {
     // close track of that).
     count_registers: [i32; 256],
 
+    // TeX's 256 dimen registers.
+    dimen_registers: [Dimen; 256],
+
+    // TeX's 256 skip registers. Missing skips are treated as zero.
+    skip_registers: HashMap<u8, Glue>,
+
+    // TeX's 256 token list registers. Missing token lists are treated as
+    // empty.
+    toks_registers: HashMap<u8, Vec<Token>>,
+
     // TeX's explicit integer parameter registers, like \tolerance or
     // \linepenalty. Missing integers are treated as zero. Similar to the count
     // registers, the values here should be between 2147483647 and -2147483647.
@@ -145,6 +147,10 @@ pub struct TeXStateInner {
     // Missing glues are treated as zero.
     glue_parameter_registers: HashMap<GlueParameter, Glue>,
 
+    // TeX's explicit token list parameter registers, like \everypar. Missing
+    // token lists are treated as empty.
+    token_list_parameter_registers: HashMap<TokenListParameter, Vec<Token>>,
+
     // TeX's 256 box registers. The values are designed such that:
     //  * When entering a new group, we don't make a copy of a box by making
     //    the values Rc.
@@ -161,6 +167,15 @@ pub struct TeXStateInner {
     // We keep track of the name of the current font. Metrics and other
     // information about the font are stored elsewhere.
     current_font: Font,
+
+    // The fonts used for each math family, set with \textfont, \scriptfont,
+    // and \scriptscriptfont. The sizes are keyed by TextStyle, ScriptStyle,
+    // and ScriptScriptStyle. Missing families use \nullfont.
+    math_fonts: HashMap<(MathStyle, u8), Font>,
+
+    // The indentation and width of the lines of paragraphs, set by
+    // \parshape. Empty when there isn't a shape.
+    par_shape: Vec<(Dimen, Dimen)>,
 }
 
 impl TeXStateInner {
@@ -178,6 +193,7 @@ impl TeXStateInner {
         initial_categories.insert('\u{0000}', Category::Ignored);
         initial_categories.insert('\u{00ff}', Category::Invalid);
         initial_categories.insert('\n', Category::EndOfLine);
+        initial_categories.insert('\r', Category::EndOfLine);
         initial_categories.insert('\\', Category::Escape);
         initial_categories.insert('%', Category::Comment);
         initial_categories.insert(' ', Category::Space);
@@ -190,6 +206,7 @@ impl TeXStateInner {
         initial_categories.insert('}', Category::EndGroup);
         initial_categories.insert('#', Category::Parameter);
         initial_categories.insert('$', Category::MathShift);
+        initial_categories.insert('&', Category::AlignmentTab);
 
         let mut initial_math_codes = HashMap::new();
         for i in 0..255 {
@@ -203,6 +220,19 @@ impl TeXStateInner {
             }
         }
 
+        // INITEX sets the lowercase and uppercase codes of letters to the
+        // lowercase and uppercase versions of that letter.
+        let mut initial_lc_codes = HashMap::new();
+        let mut initial_uc_codes = HashMap::new();
+        for ch in 'a'..='z' {
+            let upper = ch.to_ascii_uppercase();
+
+            initial_lc_codes.insert(ch, ch as u8);
+            initial_lc_codes.insert(upper, ch as u8);
+            initial_uc_codes.insert(ch, upper as u8);
+            initial_uc_codes.insert(upper, upper as u8);
+        }
+
         let mut initial_integer_registers = HashMap::new();
         // TODO(emily): INITEX actually sets \tolerance to 10000, but it is
         // reset to 200 in plain.tex. Remove this once we run that.
@@ -210,11 +240,72 @@ impl TeXStateInner {
         initial_integer_registers.insert(IntegerParameter::Pretolerance, 100);
         // TODO(emily): This is set in plain.tex. Remove this once we run that.
         initial_integer_registers.insert(IntegerParameter::AdjDemerits, 10000);
+        initial_integer_registers.insert(IntegerParameter::HangAfter, 1);
+        initial_integer_registers.insert(IntegerParameter::EndLineChar, 13);
+        initial_integer_registers
+            .insert(IntegerParameter::EscapeChar, '\\' as i32);
+        // These are the values that TeX uses when it can't tell what the date
+        // is. The real date is set with set_date_and_time().
+        initial_integer_registers.insert(IntegerParameter::Time, 12 * 60);
+        initial_integer_registers.insert(IntegerParameter::Day, 4);
+        initial_integer_registers.insert(IntegerParameter::Month, 7);
+        initial_integer_registers.insert(IntegerParameter::Year, 1776);
 
         let mut initial_dimen_registers = HashMap::new();
         // TODO(emily): This is set in plain.tex. Remove this once we run that.
         initial_dimen_registers
             .insert(DimenParameter::HSize, Dimen::from_unit(6.5, Unit::Inch));
+        // TODO(emily): This is set in plain.tex. Remove this once we run that.
+        initial_dimen_registers
+            .insert(DimenParameter::VSize, Dimen::from_unit(8.9, Unit::Inch));
+        // TODO(emily): This is set in plain.tex. Remove this once we run that.
+        initial_dimen_registers.insert(
+            DimenParameter::MaxDepth,
+            Dimen::from_unit(4.0, Unit::Point),
+        );
+        // TODO(emily): This is set in plain.tex. Remove this once we run that.
+        initial_dimen_registers.insert(
+            DimenParameter::SplitMaxDepth,
+            Dimen::from_scaled_points(0o7777777777),
+        );
+        // TODO(emily): This is set in plain.tex. Remove this once we run that.
+        initial_dimen_registers.insert(
+            DimenParameter::ParIndent,
+            Dimen::from_unit(20.0, Unit::Point),
+        );
+        // TODO(emily): These are set in plain.tex. Remove this once we run
+        // that.
+        initial_dimen_registers
+            .insert(DimenParameter::HFuzz, Dimen::from_unit(0.1, Unit::Point));
+        initial_dimen_registers.insert(
+            DimenParameter::OverfullRule,
+            Dimen::from_unit(5.0, Unit::Point),
+        );
+
+        // These are the fonts that plain TeX sets up for math mode.
+        let initial_math_fonts = [
+            (MathStyle::TextStyle, 0, "cmr10", 10.0),
+            (MathStyle::ScriptStyle, 0, "cmr7", 7.0),
+            (MathStyle::ScriptScriptStyle, 0, "cmr5", 5.0),
+            (MathStyle::TextStyle, 1, "cmmi10", 10.0),
+            (MathStyle::ScriptStyle, 1, "cmmi7", 7.0),
+            (MathStyle::ScriptScriptStyle, 1, "cmmi5", 5.0),
+            (MathStyle::TextStyle, 2, "cmsy10", 10.0),
+            (MathStyle::ScriptStyle, 2, "cmsy7", 7.0),
+            (MathStyle::ScriptScriptStyle, 2, "cmsy5", 5.0),
+            (MathStyle::TextStyle, 3, "cmex10", 10.0),
+            (MathStyle::ScriptStyle, 3, "cmex10", 10.0),
+            (MathStyle::ScriptScriptStyle, 3, "cmex10", 10.0),
+        ]
+        .iter()
+        .map(|(style, family, font_name, size)| {
+            let font = Font {
+                font_name: font_name.to_string(),
+                scale: Dimen::from_unit(*size, Unit::Point),
+            };
+            ((style.clone(), *family), font)
+        })
+        .collect();
 
         let initial_glue_registers = HashMap::from([
             (
@@ -243,27 +334,39 @@ impl TeXStateInner {
 
         let mut token_definitions = HashMap::new();
 
-        for primitive in ALL_PRIMITIVES {
+        for primitive in Primitive::ALL {
             token_definitions.insert(
-                Token::ControlSequence(primitive.to_string()),
-                TokenDefinition::Primitive(primitive),
+                Token::ControlSequence(primitive.name().to_string()),
+                TokenDefinition::Primitive(*primitive),
             );
         }
+        token_definitions.insert(
+            Token::ControlSequence("nullfont".to_string()),
+            TokenDefinition::Font(Font::null_font()),
+        );
 
         TeXStateInner {
             category_map: initial_categories,
             math_code_map: initial_math_codes,
+            lc_code_map: initial_lc_codes,
+            uc_code_map: initial_uc_codes,
             token_definition_map: token_definitions,
             count_registers: [0; 256],
+            dimen_registers: [Dimen::zero(); 256],
+            skip_registers: HashMap::new(),
+            toks_registers: HashMap::new(),
             integer_parameter_registers: initial_integer_registers,
             dimen_parameter_registers: initial_dimen_registers,
             glue_parameter_registers: initial_glue_registers,
+            token_list_parameter_registers: HashMap::new(),
             box_registers: HashMap::new(),
             current_font: Font {
                 // TODO(xymostech): This should initially be "nullfont"
                 font_name: "cmr10".to_string(),
                 scale: Dimen::from_unit(10.0, Unit::Point),
             },
+            math_fonts: initial_math_fonts,
+            par_shape: Vec::new(),
         }
     }
 
@@ -274,7 +377,6 @@ impl TeXStateInner {
         }
     }
 
-    #[cfg(test)]
     fn set_category(&mut self, ch: char, cat: Category) {
         self.category_map.insert(ch, cat);
     }
@@ -330,6 +432,45 @@ impl TeXStateInner {
             .insert(*glue_parameter, glue.clone());
     }
 
+    fn get_token_list_parameter(
+        &self,
+        token_list_parameter: &TokenListParameter,
+    ) -> Vec<Token> {
+        self.token_list_parameter_registers
+            .get(token_list_parameter)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn set_token_list_parameter(
+        &mut self,
+        token_list_parameter: &TokenListParameter,
+        tokens: &[Token],
+    ) {
+        self.token_list_parameter_registers
+            .insert(*token_list_parameter, tokens.to_vec());
+    }
+
+    fn get_par_shape(&self) -> Vec<(Dimen, Dimen)> {
+        self.par_shape.clone()
+    }
+
+    fn set_par_shape(&mut self, par_shape: &[(Dimen, Dimen)]) {
+        self.par_shape = par_shape.to_vec();
+    }
+
+    fn get_math_font(&self, style: &MathStyle, family: u8) -> Font {
+        self.math_fonts
+            .get(&(style.clone(), family))
+            .cloned()
+            .unwrap_or_else(Font::null_font)
+    }
+
+    fn set_math_font(&mut self, style: &MathStyle, family: u8, font: &Font) {
+        self.math_fonts
+            .insert((style.clone(), family), font.clone());
+    }
+
     fn get_math_code(&self, ch: char) -> MathCode {
         match self.math_code_map.get(&ch) {
             Some(mathcode) => mathcode.clone(),
@@ -341,6 +482,22 @@ impl TeXStateInner {
         self.math_code_map.insert(ch, mathcode.clone());
     }
 
+    fn get_lc_code(&self, ch: char) -> u8 {
+        *self.lc_code_map.get(&ch).unwrap_or(&0)
+    }
+
+    fn set_lc_code(&mut self, ch: char, code: u8) {
+        self.lc_code_map.insert(ch, code);
+    }
+
+    fn get_uc_code(&self, ch: char) -> u8 {
+        *self.uc_code_map.get(&ch).unwrap_or(&0)
+    }
+
+    fn set_uc_code(&mut self, ch: char, code: u8) {
+        self.uc_code_map.insert(ch, code);
+    }
+
     fn get_math_chardef(&self, token: &Token) -> Option<MathCode> {
         if let Some(TokenDefinition::MathCode(math_code)) =
             self.token_definition_map.get(token)
@@ -358,6 +515,41 @@ impl TeXStateInner {
         );
     }
 
+    fn get_chardef(&self, token: &Token) -> Option<u8> {
+        if let Some(TokenDefinition::CharDef(char_code)) =
+            self.token_definition_map.get(token)
+        {
+            Some(*char_code)
+        } else {
+            None
+        }
+    }
+
+    fn set_chardef(&mut self, token: &Token, char_code: u8) {
+        self.token_definition_map
+            .insert(token.clone(), TokenDefinition::CharDef(char_code));
+    }
+
+    fn get_register_def(&self, token: &Token) -> Option<(RegisterKind, u8)> {
+        if let Some(TokenDefinition::Register(kind, index)) =
+            self.token_definition_map.get(token)
+        {
+            Some((*kind, *index))
+        } else {
+            None
+        }
+    }
+
+    fn set_register_def(
+        &mut self,
+        token: &Token,
+        kind: RegisterKind,
+        index: u8,
+    ) {
+        self.token_definition_map
+            .insert(token.clone(), TokenDefinition::Register(kind, index));
+    }
+
     fn get_macro(&self, token: &Token) -> Option<Rc<Macro>> {
         if let Some(TokenDefinition::Macro(makro)) =
             self.token_definition_map.get(token)
@@ -383,16 +575,16 @@ impl TeXStateInner {
         }
     }
 
-    fn set_let(&mut self, set_token: &Token, to_token: &Token) {
+    // Returns the definition that a token gets when it is \let to to_token,
+    // or None if it would be undefined.
+    fn get_let_definition(&self, to_token: &Token) -> Option<TokenDefinition> {
         if let Some(token_definition) = self.token_definition_map.get(to_token)
         {
             // If to_token already has a definition, we use that for the value
             // we're setting.
-            let cloned_token = token_definition.clone();
-            self.token_definition_map
-                .insert(set_token.clone(), cloned_token);
-        } else if let Token::Char(_, cat) = to_token {
-            if cat != &Category::Active {
+            Some(token_definition.clone())
+        } else {
+            match to_token {
                 // Otherwise, if to_token is a char token with a non-active
                 // category, we create a new definition for that character.
                 // TODO(xymostech): Figure out if this is the correct behavior
@@ -400,29 +592,54 @@ impl TeXStateInner {
                 // behavior is based on trying
                 // \catcode`@=13 \let\a=@ \def@{x} \show\a
                 // and seeing that it gives \a=undefined
-                self.token_definition_map.insert(
-                    set_token.clone(),
-                    TokenDefinition::Token(to_token.clone()),
-                );
+                Token::Char(_, cat) if cat != &Category::Active => {
+                    Some(TokenDefinition::Token(to_token.clone()))
+                }
+                // Letting a token be an undefined token makes it undefined,
+                // even if it had a definition before.
+                _ => None,
+            }
+        }
+    }
+
+    fn set_token_definition(
+        &mut self,
+        set_token: &Token,
+        definition: Option<TokenDefinition>,
+    ) {
+        match definition {
+            Some(definition) => {
+                self.token_definition_map
+                    .insert(set_token.clone(), definition);
+            }
+            None => {
+                self.token_definition_map.remove(set_token);
             }
         }
     }
 
-    fn is_token_equal_to_prim(&self, token: &Token, prim: &str) -> bool {
-        if cfg!(debug_assertions) && !is_primitive(prim) {
-            panic!("Testing invalid primitive: {}", prim);
+    // The control sequences made by \csname mean \relax if they weren't
+    // defined before.
+    fn set_relax_if_undefined(&mut self, token: &Token) {
+        if !self.token_definition_map.contains_key(token) {
+            self.token_definition_map.insert(
+                token.clone(),
+                TokenDefinition::Primitive(Primitive::Relax),
+            );
         }
+    }
 
+    fn is_token_equal_to_prim(&self, token: &Token, prim: Primitive) -> bool {
         if let Token::ControlSequence(real_cs) = token {
-            if real_cs == prim {
+            if real_cs == prim.name() {
                 return true;
             }
         }
 
-        if let Some(TokenDefinition::Primitive(prim_cs)) =
+        if let Some(TokenDefinition::Primitive(token_prim)) =
             self.token_definition_map.get(token)
         {
-            if prim_cs == &prim {
+            if *token_prim == prim {
                 return true;
             }
         }
@@ -430,6 +647,96 @@ impl TeXStateInner {
         false
     }
 
+    // Returns what a token means, or None if it is undefined. Non-active
+    // characters that haven't been redefined just mean themselves.
+    fn get_meaning(&self, token: &Token) -> Option<TokenDefinition> {
+        match self.token_definition_map.get(token) {
+            Some(definition) => Some(definition.clone()),
+            None => match token {
+                Token::Char(_, Category::Active) => None,
+                Token::Char(_, _) => {
+                    Some(TokenDefinition::Token(token.clone()))
+                }
+                Token::ControlSequence(_) => None,
+            },
+        }
+    }
+
+    fn is_meaning_equal(&self, left: &Token, right: &Token) -> bool {
+        self.get_meaning(left) == self.get_meaning(right)
+    }
+
+    fn is_token_defined(&self, token: &Token) -> bool {
+        self.get_meaning(token).is_some()
+    }
+
+    // Returns the description of a token's meaning that \meaning produces.
+    fn get_meaning_string(&self, token: &Token) -> String {
+        let escapechar =
+            self.get_integer_parameter(&IntegerParameter::EscapeChar);
+
+        match self.get_meaning(token) {
+            None => "undefined".to_string(),
+            Some(TokenDefinition::Macro(makro)) => {
+                makro.to_meaning_string(escapechar)
+            }
+            Some(TokenDefinition::Token(Token::Char(ch, cat))) => {
+                let description = match cat {
+                    Category::BeginGroup => "begin-group character",
+                    Category::EndGroup => "end-group character",
+                    Category::MathShift => "math shift character",
+                    Category::AlignmentTab => "alignment tab character",
+                    Category::Parameter => "macro parameter character",
+                    Category::Superscript => "superscript character",
+                    Category::Subscript => "subscript character",
+                    Category::Space => "blank space",
+                    Category::Letter => "the letter",
+                    Category::Other => "the character",
+                    _ => panic!("Invalid character token category: {:?}", cat),
+                };
+                format!("{} {}", description, ch)
+            }
+            Some(TokenDefinition::Token(Token::ControlSequence(name))) => {
+                panic!("Invalid renamed control sequence: {}", name)
+            }
+            Some(TokenDefinition::MathCode(math_code))
+                if !math_code.is_narrow() =>
+            {
+                format!(
+                    "{}\"{:X}\"{:02X}\"{:06X}",
+                    escape_name("Umathchar", escapechar),
+                    math_code.class.clone() as u32,
+                    math_code.family,
+                    math_code.position
+                )
+            }
+            Some(TokenDefinition::MathCode(math_code)) => {
+                format!(
+                    "{}\"{:X}",
+                    escape_name("mathchar", escapechar),
+                    math_code.to_number()
+                )
+            }
+            Some(TokenDefinition::CharDef(char_code)) => {
+                format!("{}\"{:X}", escape_name("char", escapechar), char_code)
+            }
+            Some(TokenDefinition::Register(kind, index)) => {
+                let register_name = match kind {
+                    RegisterKind::Count => "count",
+                    RegisterKind::Dimen => "dimen",
+                    RegisterKind::Skip => "skip",
+                };
+                format!("{}{}", escape_name(register_name, escapechar), index)
+            }
+            Some(TokenDefinition::Primitive(prim)) => {
+                escape_name(prim.name(), escapechar)
+            }
+            Some(TokenDefinition::Font(font)) => {
+                format!("select font {}", font.font_name)
+            }
+        }
+    }
+
     fn get_count(&self, register_index: u8) -> i32 {
         self.count_registers[register_index as usize]
     }
@@ -442,6 +749,36 @@ impl TeXStateInner {
         self.count_registers[register_index as usize] = value;
     }
 
+    fn get_dimen(&self, register_index: u8) -> Dimen {
+        self.dimen_registers[register_index as usize]
+    }
+
+    fn set_dimen(&mut self, register_index: u8, dimen: &Dimen) {
+        self.dimen_registers[register_index as usize] = *dimen;
+    }
+
+    fn get_skip(&self, register_index: u8) -> Glue {
+        self.skip_registers
+            .get(&register_index)
+            .cloned()
+            .unwrap_or_else(Glue::zero)
+    }
+
+    fn set_skip(&mut self, register_index: u8, glue: &Glue) {
+        self.skip_registers.insert(register_index, glue.clone());
+    }
+
+    fn get_toks(&self, register_index: u8) -> Vec<Token> {
+        self.toks_registers
+            .get(&register_index)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn set_toks(&mut self, register_index: u8, tokens: &[Token]) {
+        self.toks_registers.insert(register_index, tokens.to_vec());
+    }
+
     fn get_current_font(&self) -> Font {
         self.current_font.clone()
     }
@@ -477,6 +814,18 @@ impl TeXStateInner {
             .and_then(|box_refcell| (*box_refcell.borrow()).clone())
     }
 
+    // Changes what is in a box register without making a new local value for
+    // it, like TeX does when \vsplit leaves the rest of a box in its
+    // register.
+    fn replace_box_contents(&self, box_index: u8, tex_box: Option<TeXBox>) {
+        match self.box_registers.get(&box_index) {
+            Some(box_refcell) => {
+                box_refcell.replace(tex_box);
+            }
+            None => panic!("Box register {} was never set", box_index),
+        }
+    }
+
     fn set_box(&mut self, box_index: u8, tex_box: Rc<RefCell<Option<TeXBox>>>) {
         self.box_registers.insert(box_index, tex_box);
     }
@@ -504,6 +853,14 @@ impl TeXStateInner {
 // level of the stack using \global.
 struct TeXStateStack {
     state_stack: Vec<TeXStateInner>,
+
+    // The tokens saved with \aftergroup for each level of the stack, which are
+    // inserted back into the input when the group ends.
+    after_group_tokens: Vec<Vec<Token>>,
+
+    // Whether each level of the stack was started with \begingroup, in which
+    // case it can only be ended with \endgroup.
+    semi_simple_groups: Vec<bool>,
 }
 
 // Since we're mostly want to just be calling the same-named functions from
@@ -543,20 +900,43 @@ impl TeXStateStack {
     fn new() -> TeXStateStack {
         TeXStateStack {
             state_stack: vec![TeXStateInner::new()],
+            after_group_tokens: vec![Vec::new()],
+            semi_simple_groups: vec![false],
         }
     }
 
     fn push_state(&mut self) {
         let top_state = self.state_stack[self.state_stack.len() - 1].clone();
         self.state_stack.push(top_state);
+        self.after_group_tokens.push(Vec::new());
+        self.semi_simple_groups.push(false);
+    }
+
+    fn push_semi_simple_state(&mut self) {
+        self.push_state();
+        *self.semi_simple_groups.last_mut().unwrap() = true;
     }
 
-    fn pop_state(&mut self) {
+    fn is_in_semi_simple_group(&self) -> bool {
+        *self.semi_simple_groups.last().unwrap()
+    }
+
+    // Returns the tokens that were saved with \aftergroup in the group that
+    // was popped.
+    fn pop_state(&mut self) -> Vec<Token> {
         self.state_stack.pop().unwrap();
+        self.semi_simple_groups.pop().unwrap();
+        self.after_group_tokens.pop().unwrap()
+    }
+
+    fn add_after_group_token(&mut self, token: Token) {
+        // Like TeX, we ignore \aftergroup outside of any groups.
+        if self.after_group_tokens.len() > 1 {
+            self.after_group_tokens.last_mut().unwrap().push(token);
+        }
     }
 
     generate_inner_func!(fn get_category(ch: char) -> Category);
-    #[cfg(test)]
     generate_inner_global_func!(fn set_category(global: bool, ch: char, cat: Category));
     generate_inner_func!(fn get_integer_parameter(integer_parameter: &IntegerParameter) -> i32);
     generate_inner_global_func!(fn set_integer_parameter(global: bool, integer_parameter: &IntegerParameter, value: i32));
@@ -564,29 +944,86 @@ impl TeXStateStack {
     generate_inner_global_func!(fn set_dimen_parameter(global: bool, dimen_parameter: &DimenParameter, dimen: &Dimen));
     generate_inner_func!(fn get_glue_parameter(glue_parameter: &GlueParameter) -> Glue);
     generate_inner_global_func!(fn set_glue_parameter(global: bool, glue_parameter: &GlueParameter, glue: &Glue));
+    generate_inner_func!(fn get_token_list_parameter(token_list_parameter: &TokenListParameter) -> Vec<Token>);
+    generate_inner_global_func!(fn set_token_list_parameter(global: bool, token_list_parameter: &TokenListParameter, tokens: &[Token]));
+    generate_inner_func!(fn get_par_shape() -> Vec<(Dimen, Dimen)>);
+    generate_inner_func!(fn get_math_font(style: &MathStyle, family: u8) -> Font);
+    generate_inner_global_func!(fn set_math_font(global: bool, style: &MathStyle, family: u8, font: &Font));
+    generate_inner_global_func!(fn set_par_shape(global: bool, par_shape: &[(Dimen, Dimen)]));
     generate_inner_func!(fn get_math_code(ch: char) -> MathCode);
     generate_inner_global_func!(fn set_math_code(global: bool, ch: char, mathcode: &MathCode));
+    generate_inner_func!(fn get_lc_code(ch: char) -> u8);
+    generate_inner_global_func!(fn set_lc_code(global: bool, ch: char, code: u8));
+    generate_inner_func!(fn get_uc_code(ch: char) -> u8);
+    generate_inner_global_func!(fn set_uc_code(global: bool, ch: char, code: u8));
     generate_inner_func!(fn get_math_chardef(token: &Token) -> Option<MathCode>);
     generate_inner_global_func!(fn set_math_chardef(global: bool, token: &Token, mathcode: &MathCode));
+    generate_inner_func!(fn get_chardef(token: &Token) -> Option<u8>);
+    generate_inner_global_func!(fn set_chardef(global: bool, token: &Token, char_code: u8));
+    generate_inner_func!(fn get_register_def(token: &Token) -> Option<(RegisterKind, u8)>);
+    generate_inner_global_func!(fn set_register_def(global: bool, token: &Token, kind: RegisterKind, index: u8));
     generate_inner_func!(fn get_macro(token: &Token) -> Option<Rc<Macro>>);
     generate_inner_global_func!(fn set_macro(global: bool, token: &Token, makro: &Rc<Macro>));
     generate_inner_func!(fn get_renamed_token(token: &Token) -> Option<Token>);
-    generate_inner_global_func!(fn set_let(global: bool, set_token: &Token, to_token: &Token));
-    generate_inner_func!(fn is_token_equal_to_prim(token: &Token, cs: &str) -> bool);
+    generate_inner_global_func!(fn set_relax_if_undefined(global: bool, token: &Token));
+    generate_inner_func!(fn is_token_equal_to_prim(token: &Token, prim: Primitive) -> bool);
+    generate_inner_func!(fn is_meaning_equal(left: &Token, right: &Token) -> bool);
+    generate_inner_func!(fn is_token_defined(token: &Token) -> bool);
+    generate_inner_func!(fn get_meaning_string(token: &Token) -> String);
     generate_inner_func!(fn get_count(register_index: u8) -> i32);
     generate_inner_global_func!(fn set_count(global: bool, register_index: u8, value: i32));
+    generate_inner_func!(fn get_dimen(register_index: u8) -> Dimen);
+    generate_inner_global_func!(fn set_dimen(global: bool, register_index: u8, dimen: &Dimen));
+    generate_inner_func!(fn get_skip(register_index: u8) -> Glue);
+    generate_inner_global_func!(fn set_skip(global: bool, register_index: u8, glue: &Glue));
+    generate_inner_func!(fn get_toks(register_index: u8) -> Vec<Token>);
+    generate_inner_global_func!(fn set_toks(global: bool, register_index: u8, tokens: &[Token]));
     generate_inner_func!(fn get_current_font() -> Font);
     generate_inner_global_func!(fn set_current_font(global: bool, font: &Font));
     generate_inner_global_func!(fn set_fontdef(global: bool, token: &Token, font: &Font));
     generate_inner_func!(fn get_fontdef(token: &Token) -> Option<Font>);
     generate_inner_func!(fn get_box(box_index: u8) -> Option<TeXBox>);
     generate_inner_func!(fn get_box_copy(box_index: u8) -> Option<TeXBox>);
+    generate_inner_func!(fn replace_box_contents(box_index: u8, tex_box: Option<TeXBox>));
+
+    // The meaning that \let copies has to be looked up in the innermost group
+    // and then copied into every level for global assignments. Since
+    // to_token might mean something different (or nothing) in the outer
+    // levels, we can't generate this with `generate_inner_global_func!()`.
+    fn set_let(&mut self, global: bool, set_token: &Token, to_token: &Token) {
+        let len = self.state_stack.len();
+        let definition = self.state_stack[len - 1].get_let_definition(to_token);
+
+        if global {
+            for state in &mut self.state_stack {
+                state.set_token_definition(set_token, definition.clone());
+            }
+        } else {
+            self.state_stack[len - 1]
+                .set_token_definition(set_token, definition);
+        }
+    }
 
     // Because globally setting boxes means that we should share references
     // between the different stack levels, we can't handle generating this
     // function automatically with `generate_inner_global_func!()`.
     fn set_box(&mut self, global: bool, box_index: u8, tex_box: TeXBox) {
-        let wrapped_box = Rc::new(RefCell::new(Some(tex_box)));
+        self.set_box_register(global, box_index, Some(tex_box));
+    }
+
+    // Makes a box register void, like \setbox0=\box1 does when \box1 is
+    // void.
+    fn clear_box(&mut self, global: bool, box_index: u8) {
+        self.set_box_register(global, box_index, None);
+    }
+
+    fn set_box_register(
+        &mut self,
+        global: bool,
+        box_index: u8,
+        tex_box: Option<TeXBox>,
+    ) {
+        let wrapped_box = Rc::new(RefCell::new(tex_box));
         if global {
             for state in &mut self.state_stack {
                 state.set_box(box_index, wrapped_box.clone());
@@ -618,6 +1055,40 @@ pub struct TeXState {
     // in the `TeXStateInner` because loading the font metrics is global and
     // isn't affected by grouping.
     font_metrics: RefCell<HashMap<Font, FontMetrics>>,
+
+    // The name of the current job, used for \jobname. TeX uses "texput" when
+    // there isn't an input file to name the job after.
+    job_name: String,
+
+    // The files opened with \openin and \openout, which also aren't affected
+    // by grouping.
+    input_files: RefCell<InputFiles>,
+    output_files: RefCell<OutputFiles>,
+
+    // Where warnings and errors are reported.
+    diagnostics: RefCell<Diagnostics>,
+
+    // The badness of the most recently built box, used for \badness. Like in
+    // TeX, this isn't affected by grouping.
+    last_badness: Cell<u64>,
+
+    // Counts of things that happen during the job, for \tracingstats.
+    stats: RefCell<Stats>,
+
+    // The limits on how much of each resource the job can use, along with
+    // how much of the resources that are used up over time have been used so
+    // far.
+    resource_limits: RefCell<ResourceLimits>,
+    resource_usage: RefCell<HashMap<ResourceLimit, usize>>,
+
+    // The control sequence that each font was most recently defined with
+    // using \font, which is what \the produces for a font. Like in TeX, this
+    // isn't affected by grouping.
+    font_identifiers: RefCell<HashMap<Font, Token>>,
+
+    // The fonts and families that we've already reported as not having enough
+    // parameters to be used in math, so that we only report them once.
+    invalid_math_fonts: RefCell<HashSet<(Font, u8)>>,
 }
 
 // Since we're mostly want to just be calling the same-named functions from
@@ -639,6 +1110,16 @@ impl TeXState {
         TeXState {
             state_stack: RefCell::new(TeXStateStack::new()),
             font_metrics: RefCell::new(HashMap::new()),
+            job_name: "texput".to_string(),
+            input_files: RefCell::new(InputFiles::new()),
+            output_files: RefCell::new(OutputFiles::new()),
+            diagnostics: RefCell::new(Diagnostics::new()),
+            last_badness: Cell::new(0),
+            stats: RefCell::new(Stats::new()),
+            resource_limits: RefCell::new(ResourceLimits::unlimited()),
+            resource_usage: RefCell::new(HashMap::new()),
+            font_identifiers: RefCell::new(HashMap::new()),
+            invalid_math_fonts: RefCell::new(HashSet::new()),
         }
     }
 
@@ -653,10 +1134,12 @@ impl TeXState {
     }
 
     generate_stack_func!(fn push_state());
-    generate_stack_func!(fn pop_state());
+    generate_stack_func!(fn pop_state() -> Vec<Token>);
+    generate_stack_func!(fn push_semi_simple_state());
+    generate_stack_func!(fn is_in_semi_simple_group() -> bool);
+    generate_stack_func!(fn add_after_group_token(token: Token));
 
     generate_stack_func!(fn get_category(ch: char) -> Category);
-    #[cfg(test)]
     generate_stack_func!(fn set_category(global: bool, ch: char, cat: Category));
     generate_stack_func!(fn get_integer_parameter(integer_parameter: &IntegerParameter) -> i32);
     generate_stack_func!(fn set_integer_parameter(global: bool, integer_parameter: &IntegerParameter, value: i32));
@@ -664,24 +1147,50 @@ impl TeXState {
     generate_stack_func!(fn set_dimen_parameter(global: bool, dimen_parameter: &DimenParameter, dimen: &Dimen));
     generate_stack_func!(fn get_glue_parameter(glue_parameter: &GlueParameter) -> Glue);
     generate_stack_func!(fn set_glue_parameter(global: bool, glue_parameter: &GlueParameter, glue: &Glue));
+    generate_stack_func!(fn get_token_list_parameter(token_list_parameter: &TokenListParameter) -> Vec<Token>);
+    generate_stack_func!(fn set_token_list_parameter(global: bool, token_list_parameter: &TokenListParameter, tokens: &[Token]));
+    generate_stack_func!(fn get_par_shape() -> Vec<(Dimen, Dimen)>);
+    generate_stack_func!(fn get_math_font(style: &MathStyle, family: u8) -> Font);
+    generate_stack_func!(fn set_math_font(global: bool, style: &MathStyle, family: u8, font: &Font));
+    generate_stack_func!(fn set_par_shape(global: bool, par_shape: &[(Dimen, Dimen)]));
     generate_stack_func!(fn get_math_code(ch: char) -> MathCode);
     generate_stack_func!(fn set_math_code(global: bool, ch: char, mathcode: &MathCode));
+    generate_stack_func!(fn get_lc_code(ch: char) -> u8);
+    generate_stack_func!(fn set_lc_code(global: bool, ch: char, code: u8));
+    generate_stack_func!(fn get_uc_code(ch: char) -> u8);
+    generate_stack_func!(fn set_uc_code(global: bool, ch: char, code: u8));
     generate_stack_func!(fn get_math_chardef(token: &Token) -> Option<MathCode>);
     generate_stack_func!(fn set_math_chardef(global: bool, token: &Token, mathcode: &MathCode));
+    generate_stack_func!(fn get_chardef(token: &Token) -> Option<u8>);
+    generate_stack_func!(fn set_chardef(global: bool, token: &Token, char_code: u8));
+    generate_stack_func!(fn get_register_def(token: &Token) -> Option<(RegisterKind, u8)>);
+    generate_stack_func!(fn set_register_def(global: bool, token: &Token, kind: RegisterKind, index: u8));
     generate_stack_func!(fn get_macro(token: &Token) -> Option<Rc<Macro>>);
     generate_stack_func!(fn set_macro(global: bool, token: &Token, makro: &Rc<Macro>));
     generate_stack_func!(fn get_renamed_token(token: &Token) -> Option<Token>);
     generate_stack_func!(fn set_let(global: bool, set_token: &Token, to_token: &Token));
-    generate_stack_func!(fn is_token_equal_to_prim(token: &Token, cs: &str) -> bool);
+    generate_stack_func!(fn set_relax_if_undefined(global: bool, token: &Token));
+    generate_stack_func!(fn is_token_equal_to_prim(token: &Token, prim: Primitive) -> bool);
+    generate_stack_func!(fn is_meaning_equal(left: &Token, right: &Token) -> bool);
+    generate_stack_func!(fn is_token_defined(token: &Token) -> bool);
+    generate_stack_func!(fn get_meaning_string(token: &Token) -> String);
     generate_stack_func!(fn get_count(register_index: u8) -> i32);
     generate_stack_func!(fn set_count(global: bool, register_index: u8, value: i32));
+    generate_stack_func!(fn get_dimen(register_index: u8) -> Dimen);
+    generate_stack_func!(fn set_dimen(global: bool, register_index: u8, dimen: &Dimen));
+    generate_stack_func!(fn get_skip(register_index: u8) -> Glue);
+    generate_stack_func!(fn set_skip(global: bool, register_index: u8, glue: &Glue));
+    generate_stack_func!(fn get_toks(register_index: u8) -> Vec<Token>);
+    generate_stack_func!(fn set_toks(global: bool, register_index: u8, tokens: &[Token]));
     generate_stack_func!(fn get_current_font() -> Font);
     generate_stack_func!(fn set_current_font(global: bool, font: &Font));
     generate_stack_func!(fn set_fontdef(global: bool, token: &Token, font: &Font));
     generate_stack_func!(fn get_fontdef(token: &Token) -> Option<Font>);
     generate_stack_func!(fn get_box(box_index: u8) -> Option<TeXBox>);
     generate_stack_func!(fn get_box_copy(box_index: u8) -> Option<TeXBox>);
+    generate_stack_func!(fn replace_box_contents(box_index: u8, tex_box: Option<TeXBox>));
     generate_stack_func!(fn set_box(global: bool, box_index: u8, tex_box: TeXBox));
+    generate_stack_func!(fn clear_box(global: bool, box_index: u8));
 
     /// Run a function on a mutable reference to a Box in a given Box register.
     /// This allows access and mutations to the boxes without removing or
@@ -690,6 +1199,10 @@ impl TeXState {
     ///
     /// Note that this currently only runs on the top box of the state stack;
     /// there is no way to access or mutate boxes in other parts of the stack.
+    /// Since the levels of the stack share the same box until it is replaced,
+    /// mutations made inside of a group are still visible after the group
+    /// ends, which matches TeX's rule that changes to box dimensions ignore
+    /// grouping.
     pub fn with_box<T, F>(&self, box_index: u8, func: F) -> Option<T>
     where
         F: FnOnce(&mut TeXBox) -> T,
@@ -707,12 +1220,31 @@ impl TeXState {
         &self,
         font: &Font,
     ) -> Option<Ref<FontMetrics>> {
+        self.with_stats(|stats| stats.font_metric_lookups += 1);
+
+        // \nullfont has no metrics file, so there's nothing to load (or to
+        // complain about).
+        if font.is_null_font() {
+            return None;
+        }
+
         let has_metrics = self.font_metrics.borrow().contains_key(font);
 
         if !has_metrics {
             let mut font_metrics_mut = self.font_metrics.borrow_mut();
-            font_metrics_mut
-                .insert(font.clone(), FontMetrics::from_font(font)?);
+            let metrics = match FontMetrics::from_font(font) {
+                Some(metrics) => metrics,
+                None => {
+                    self.with_diagnostics(|diagnostics| {
+                        diagnostics.report(DiagnosticKind::MissingFont {
+                            control_sequence: None,
+                            font_name: font.font_name.clone(),
+                        })
+                    });
+                    return None;
+                }
+            };
+            font_metrics_mut.insert(font.clone(), metrics);
         }
 
         Some(Ref::map(self.font_metrics.borrow(), |x| {
@@ -732,6 +1264,143 @@ impl TeXState {
             None => None,
         }
     }
+
+    /// Checks that a font has enough parameters to be used for the given math
+    /// family. Family 2 (the symbol font) needs 22 parameters and family 3
+    /// (the extension font) needs 13. An error is reported the first time that
+    /// each font is found to be missing parameters.
+    pub fn is_valid_math_font(&self, font: &Font, family: u8) -> bool {
+        let required_params = match family {
+            2 => 22,
+            3 => 13,
+            _ => 0,
+        };
+        let num_params = self
+            .with_metrics_for_font(font, |metrics| {
+                metrics.get_num_font_dimensions()
+            })
+            .unwrap_or(0);
+        if num_params >= required_params {
+            return true;
+        }
+
+        let is_new = self
+            .invalid_math_fonts
+            .borrow_mut()
+            .insert((font.clone(), family));
+        if is_new {
+            self.with_diagnostics(|diagnostics| {
+                diagnostics.report(DiagnosticKind::InsufficientMathFont {
+                    font_name: font.font_name.clone(),
+                    family,
+                })
+            });
+        }
+        false
+    }
+
+    pub fn get_font_identifier(&self, font: &Font) -> Option<Token> {
+        self.font_identifiers.borrow().get(font).cloned()
+    }
+
+    pub fn set_font_identifier(&self, font: &Font, token: &Token) {
+        self.font_identifiers
+            .borrow_mut()
+            .insert(font.clone(), token.clone());
+    }
+
+    pub fn get_job_name(&self) -> String {
+        self.job_name.clone()
+    }
+
+    /// Calls a callback with the input files, which can be used to open,
+    /// close, and read from files.
+    pub fn with_input_files<T, F>(&self, func: F) -> T
+    where
+        F: FnOnce(&mut InputFiles) -> T,
+    {
+        let mut input_files = self.input_files.borrow_mut();
+        func(&mut input_files)
+    }
+
+    /// Calls a callback with the output files, which can be used to open and
+    /// close files or to change where files are allowed to be written.
+    pub fn with_output_files<T, F>(&self, func: F) -> T
+    where
+        F: FnOnce(&mut OutputFiles) -> T,
+    {
+        let mut output_files = self.output_files.borrow_mut();
+        func(&mut output_files)
+    }
+
+    /// Returns the number of different fonts whose metrics have been loaded.
+    pub fn get_num_loaded_fonts(&self) -> usize {
+        self.font_metrics.borrow().len()
+    }
+
+    /// Calls a callback with the statistics for the job, which can be used to
+    /// update them.
+    pub fn with_stats<T, F>(&self, func: F) -> T
+    where
+        F: FnOnce(&mut Stats) -> T,
+    {
+        let mut stats = self.stats.borrow_mut();
+        func(&mut stats)
+    }
+
+    // Like ResourceLimits::with_max(), this is only used by tests for now.
+    #[allow(dead_code)]
+    pub fn set_resource_limits(&self, limits: ResourceLimits) {
+        *self.resource_limits.borrow_mut() = limits;
+    }
+
+    /// Records that some of a resource that is used up over time (like the
+    /// number of pages) has been used, and stops the job if that goes over the
+    /// limit for that resource.
+    pub fn use_resource(&self, limit: ResourceLimit, amount: usize) {
+        let mut usage = self.resource_usage.borrow_mut();
+        let total = usage.entry(limit).or_insert(0);
+        *total += amount;
+        self.check_resource_level(limit, *total);
+    }
+
+    /// Stops the job if the current level of a resource that can go back down
+    /// (like the box nesting depth) is over the limit for that resource. The
+    /// job panics with the ResourceLimitExceeded itself, so that a program
+    /// embedding the engine can catch it and see which limit was hit.
+    pub fn check_resource_level(&self, limit: ResourceLimit, level: usize) {
+        if let Err(err) = self.resource_limits.borrow().check(limit, level) {
+            std::panic::panic_any(err);
+        }
+    }
+
+    /// Sets \time, \day, \month, and \year from a time in seconds since the
+    /// Unix epoch.
+    pub fn set_date_and_time(&self, epoch_seconds: i64) {
+        let (year, month, day, time) = get_date_and_time(epoch_seconds);
+        self.set_integer_parameter(true, &IntegerParameter::Time, time);
+        self.set_integer_parameter(true, &IntegerParameter::Day, day);
+        self.set_integer_parameter(true, &IntegerParameter::Month, month);
+        self.set_integer_parameter(true, &IntegerParameter::Year, year);
+    }
+
+    pub fn get_last_badness(&self) -> u64 {
+        self.last_badness.get()
+    }
+
+    pub fn set_last_badness(&self, badness: u64) {
+        self.last_badness.set(badness);
+    }
+
+    /// Calls a callback with the diagnostics, which is used to report
+    /// warnings and errors.
+    pub fn with_diagnostics<T, F>(&self, func: F) -> T
+    where
+        F: FnOnce(&mut Diagnostics) -> T,
+    {
+        let mut diagnostics = self.diagnostics.borrow_mut();
+        func(&mut diagnostics)
+    }
 }
 
 #[cfg(test)]
@@ -803,7 +1472,7 @@ mod tests {
 
         assert!(state.is_token_equal_to_prim(
             &Token::ControlSequence("let".to_string()),
-            "let"
+            Primitive::Let
         ));
 
         state.set_let(
@@ -814,7 +1483,7 @@ mod tests {
 
         assert!(state.is_token_equal_to_prim(
             &Token::ControlSequence("boo".to_string()),
-            "let"
+            Primitive::Let
         ));
     }
 
@@ -1005,6 +1674,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_gets_and_sets_chardefs_correctly() {
+        let state = TeXState::new();
+
+        state.set_chardef(
+            false,
+            &Token::ControlSequence("hello".to_string()),
+            65,
+        );
+        assert_eq!(
+            state.get_chardef(&Token::ControlSequence("hello".to_string())),
+            Some(65)
+        );
+        assert_eq!(
+            state
+                .get_math_chardef(&Token::ControlSequence("hello".to_string())),
+            None
+        );
+    }
+
     #[test]
     fn it_does_not_throw_when_accessing_invalid_fonts() {
         let state = TeXState::new();
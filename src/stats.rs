@@ -0,0 +1,80 @@
+//! Statistics about the current job, which are printed at the end of the job
+//! when \tracingstats is positive.
+use std::io;
+use std::io::Write;
+
+use crate::boxes::TeXBox;
+use crate::state::{IntegerParameter, TeXState};
+
+pub struct Stats {
+    // The number of tokens read from the input, not counting tokens that are
+    // read again after being put back or that come from macro expansions.
+    pub tokens_processed: usize,
+    pub boxes_built: usize,
+    pub pages_shipped: usize,
+    pub characters_shipped: usize,
+    // The number of times font metrics were looked up. This isn't included in
+    // the report, but is useful for keeping track of how much work layout is
+    // doing.
+    pub font_metric_lookups: usize,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Stats {
+            tokens_processed: 0,
+            boxes_built: 0,
+            pages_shipped: 0,
+            characters_shipped: 0,
+            font_metric_lookups: 0,
+        }
+    }
+
+    /// Records that a page was shipped out, along with all of the characters
+    /// that were typeset on it.
+    pub fn add_shipped_page(&mut self, page: &TeXBox) {
+        self.pages_shipped += 1;
+        self.characters_shipped += page.glyphs().count();
+    }
+
+    fn get_report_lines(&self, fonts_loaded: usize) -> Vec<String> {
+        let plural = |count: usize, singular: &str, plural: &str| {
+            format!(" {} {}", count, if count == 1 { singular } else { plural })
+        };
+
+        vec![
+            "Here is how much work this job took:".to_string(),
+            plural(
+                self.tokens_processed,
+                "token processed",
+                "tokens processed",
+            ),
+            plural(self.boxes_built, "box built", "boxes built"),
+            plural(self.pages_shipped, "page shipped out", "pages shipped out"),
+            plural(
+                self.characters_shipped,
+                "character shipped out",
+                "characters shipped out",
+            ),
+            plural(fonts_loaded, "font loaded", "fonts loaded"),
+        ]
+    }
+}
+
+/// Writes the statistics for the job to the writer if \tracingstats is
+/// positive. This should be called once the job is over.
+pub fn report_stats_at_end(
+    state: &TeXState,
+    writer: &mut dyn Write,
+) -> io::Result<()> {
+    if state.get_integer_parameter(&IntegerParameter::TracingStats) <= 0 {
+        return Ok(());
+    }
+
+    let fonts_loaded = state.get_num_loaded_fonts();
+    let lines = state.with_stats(|stats| stats.get_report_lines(fonts_loaded));
+    for line in lines {
+        writeln!(writer, "{}", line)?;
+    }
+    Ok(())
+}
@@ -1,3 +1,11 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::rc::Rc;
+
+use crate::layout_tree::horizontal_list_to_layout_tree;
+use crate::limits::ResourceLimitExceeded;
+use crate::list::HorizontalListElem;
 use crate::parser::Parser;
 use crate::state::TeXState;
 
@@ -12,3 +20,45 @@ where
     cb(&mut parser);
     assert_eq!(parser.lex_unexpanded_token(), None);
 }
+
+/// Runs the callback, which should go over one of the job's resource limits,
+/// and returns the ResourceLimitExceeded that the job stopped with.
+#[cfg(test)]
+pub fn get_resource_limit_exceeded<T>(cb: T) -> ResourceLimitExceeded
+where
+    T: FnOnce(),
+{
+    let payload = catch_unwind(AssertUnwindSafe(cb))
+        .expect_err("Expected a resource limit to be exceeded");
+    match payload.downcast::<ResourceLimitExceeded>() {
+        Ok(err) => *err,
+        Err(_) => panic!("Expected a ResourceLimitExceeded panic"),
+    }
+}
+
+/// Dumps a horizontal list in a \showbox-like format, one element per line
+/// with a `.` per level of box nesting, so that tests can compare against a
+/// golden string instead of building the expected list by hand.
+#[cfg(test)]
+pub fn debug_format_horizontal_list(
+    list: &[HorizontalListElem],
+    state: &TeXState,
+) -> String {
+    horizontal_list_to_layout_tree(list, state)
+}
+
+/// A writer that collects everything written to it into a shared buffer, so
+/// that tests can look at what was written after handing the writer off.
+#[cfg(test)]
+pub struct SharedWriter(pub Rc<RefCell<Vec<u8>>>);
+
+#[cfg(test)]
+impl Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -1,7 +1,24 @@
 /// Integration tests to ensure that high-level expectations hold
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::rc::Rc;
+
 use crate::box_to_dvi::DVIFileWriter;
-use crate::dvi::{interpret_dvi_file, DVIFile};
-use crate::testing::with_parser;
+use crate::boxes::TeXBox;
+use crate::dimension::{Dimen, Unit};
+use crate::dvi::interpreter::DVIOutputElement;
+use crate::dvi::{interpret_dvi_file, DVICommand, DVIFile};
+use crate::font::Font;
+use crate::layout_tree::{diff_layout_trees, horizontal_list_to_layout_tree};
+use crate::limits::{ResourceLimit, ResourceLimitExceeded, ResourceLimits};
+use crate::math_list::MathStyle;
+use crate::parser::Parser;
+use crate::state::TeXState;
+use crate::stats::report_stats_at_end;
+use crate::testing::{get_resource_limit_exceeded, with_parser, SharedWriter};
+
+mod conformance;
 
 /// This test ensures that we pass the stage #2 goals.
 #[test]
@@ -154,3 +171,408 @@ fn it_passes_stage_5_goals() {
         include_bytes!("../examples/math.dvi"),
     );
 }
+
+/// Runs every `.tex` file in the math_corpus directory as a text style math
+/// list, and compares the resulting horizontal list against the layout tree in
+/// the `.expected` file next to it.
+///
+/// When the UPDATE_MATH_CORPUS environment variable is set, the layout trees
+/// are written out instead of being compared, so that they can be reviewed and
+/// committed. Otherwise, a case without an `.expected` file is a failure.
+#[test]
+fn it_matches_the_math_corpus() {
+    let corpus_dir =
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("math_corpus");
+    let update = std::env::var_os("UPDATE_MATH_CORPUS").is_some();
+
+    let mut inputs: Vec<std::path::PathBuf> = std::fs::read_dir(&corpus_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "tex"))
+        .collect();
+    inputs.sort();
+    assert!(!inputs.is_empty(), "No math corpus cases found");
+
+    let mut failures = Vec::new();
+    for input_path in inputs {
+        let input = std::fs::read_to_string(&input_path).unwrap();
+        let lines: Vec<&str> = input.lines().collect();
+
+        let mut actual = String::new();
+        with_parser(&lines, |parser| {
+            let math_list = parser.parse_math_list();
+            let horizontal_list = parser.convert_math_list_to_horizontal_list(
+                math_list,
+                MathStyle::TextStyle,
+            );
+            actual =
+                horizontal_list_to_layout_tree(&horizontal_list, parser.state);
+        });
+
+        let expected_path = input_path.with_extension("expected");
+        if update {
+            println!("Writing {}", expected_path.display());
+            std::fs::write(&expected_path, actual).unwrap();
+            continue;
+        }
+
+        match std::fs::read_to_string(&expected_path) {
+            Ok(expected) => {
+                if let Some(diff) = diff_layout_trees(&expected, &actual) {
+                    failures.push(format!(
+                        "{}:\n{}",
+                        input_path.display(),
+                        diff
+                    ));
+                }
+            }
+            Err(_) => {
+                failures.push(format!(
+                    "{}: missing {}",
+                    input_path.display(),
+                    expected_path.display()
+                ));
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        panic!(
+            "{} math corpus case(s) didn't match (set UPDATE_MATH_CORPUS to \
+             write new .expected files):\n{}",
+            failures.len(),
+            failures.join("\n")
+        );
+    }
+}
+
+/// Runs documents that trigger each kind of diagnostic, and checks what gets
+/// written to the JSON diagnostics stream.
+#[test]
+fn it_writes_diagnostics_as_json_lines() {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let state = TeXState::new();
+    state.with_diagnostics(|diagnostics| {
+        diagnostics.set_json_writer(Box::new(SharedWriter(output.clone())))
+    });
+
+    // Errors stop the parsing, so each one needs its own document.
+    let documents: &[&[&str]] = &[
+        &[r"\setbox0=\hbox to1pt{aa}%", r"\setbox0=\hbox{\char200}%"],
+        &[r"%", r"\font\x=nonexistentfont\relax%"],
+        &[r"%", r"%", r"\undefined %"],
+    ];
+    for lines in documents {
+        let _ = catch_unwind(AssertUnwindSafe(|| {
+            let mut parser = Parser::new(lines, &state);
+            parser.parse_vertical_list(true);
+        }));
+    }
+
+    let overfull_by = state
+        .get_metrics_for_font(&Font {
+            font_name: "cmr10".to_string(),
+            scale: Dimen::from_unit(10.0, Unit::Point),
+        })
+        .unwrap()
+        .get_width('a')
+        * 2
+        - Dimen::from_unit(1.0, Unit::Point);
+
+    let output = String::from_utf8(output.borrow().clone()).unwrap();
+    assert_eq!(
+        output.lines().collect::<Vec<&str>>(),
+        vec![
+            format!(
+                concat!(
+                    r#"{{"severity":"warning","kind":"overfull_hbox","#,
+                    r#""message":"Overfull \\hbox ({} too wide)","#,
+                    r#""file":null,"line":1,"overfull_by_sp":{}}}"#,
+                ),
+                overfull_by.to_pt_string(),
+                overfull_by.as_scaled_points()
+            ),
+            concat!(
+                r#"{"severity":"warning","kind":"missing_character","#,
+                r#""message":"Missing character: There is no È in font cmr10!","#,
+                r#""file":null,"line":2,"character":"È","font":"cmr10"}"#,
+            )
+            .to_string(),
+            concat!(
+                r#"{"severity":"error","kind":"missing_font","#,
+                r#""message":"Font \\x=nonexistentfont not loadable: Metric (TFM) file not found","#,
+                r#""file":null,"line":2,"font":"nonexistentfont","control_sequence":"x"}"#,
+            )
+            .to_string(),
+            concat!(
+                r#"{"severity":"error","kind":"undefined_control_sequence","#,
+                r#""message":"Undefined control sequence \\undefined","#,
+                r#""file":null,"line":3,"control_sequence":"undefined"}"#,
+            )
+            .to_string(),
+        ]
+    );
+}
+
+/// A font whose metrics can't be found is reported, and the rest of the job
+/// carries on without it: nothing typeset in it makes it into the DVI file.
+#[test]
+fn it_keeps_going_after_fonts_that_cannot_be_loaded() {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let state = TeXState::new();
+    state.with_diagnostics(|diagnostics| {
+        diagnostics.set_json_writer(Box::new(SharedWriter(output.clone())))
+    });
+
+    let lines = &[
+        r"\font\tenrm=nonexistentfont %",
+        r"\font\good=cmr10 %",
+        r"\hbox{\tenrm ab\good cd}\end%",
+    ];
+    let mut parser = Parser::new(lines, &state);
+    let mut file_writer = DVIFileWriter::new();
+    file_writer.start(
+        (25400000, 473628672),
+        1000,
+        b"Made by XymosTeX".to_vec(),
+    );
+    let page = parser.parse_outer_vertical_box();
+    file_writer.add_page(&page.list, &None, [1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    file_writer.end();
+
+    let commands = file_writer.to_file().commands;
+    let font_names: Vec<&str> = commands
+        .iter()
+        .filter_map(|command| match command {
+            DVICommand::FntDef4 { font_name, .. } => Some(font_name.as_str()),
+            _ => None,
+        })
+        .collect();
+    let chars: Vec<u8> = commands
+        .iter()
+        .filter_map(|command| match command {
+            DVICommand::SetCharN(chr) => Some(*chr),
+            _ => None,
+        })
+        .collect();
+    // The font definitions are repeated in the postamble.
+    assert_eq!(font_names, vec!["cmr10", "cmr10"]);
+    assert_eq!(chars, b"cd".to_vec());
+
+    let output = String::from_utf8(output.borrow().clone()).unwrap();
+    let diagnostics: Vec<&str> = output.lines().collect();
+    assert_eq!(diagnostics.len(), 3);
+    assert!(diagnostics[0].contains(
+        r#""message":"Font \\tenrm=nonexistentfont not loadable: Metric (TFM) file not found""#
+    ));
+    // The characters typeset in \nullfont are dropped like any other missing
+    // characters.
+    assert!(diagnostics[1].contains(r#""character":"a","font":"nullfont""#));
+    assert!(diagnostics[2].contains(r#""character":"b","font":"nullfont""#));
+}
+
+/// Runs a job the way main() does, and checks the statistics that are
+/// reported at the end.
+#[test]
+fn it_reports_stats_at_the_end_of_the_job() {
+    let run_job = |lines: &[&str]| {
+        let state = TeXState::new();
+        let mut parser = Parser::new(lines, &state);
+        let mut file_writer = DVIFileWriter::new();
+
+        let page = TeXBox::VerticalBox(parser.parse_outer_vertical_box());
+        file_writer.add_box_page(&page, [1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        state.with_stats(|stats| stats.add_shipped_page(&page));
+
+        let mut output = Vec::new();
+        report_stats_at_end(&state, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    };
+
+    assert_eq!(run_job(&[r"\hbox{a}\end%"]), "");
+
+    let output = run_job(&[r"\tracingstats=1 %", r"\hbox{a}\end%"]);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines[0], "Here is how much work this job took:");
+    assert!(lines.contains(&" 1 page shipped out"));
+    assert!(lines.contains(&" 1 character shipped out"));
+    // One box is the \hbox, and the other is the box around the page.
+    assert!(lines.contains(&" 2 boxes built"));
+    assert!(lines.contains(&" 1 font loaded"));
+}
+
+/// Runs jobs with the same resource limits one after another, like a program
+/// embedding the engine would, and checks that the limits apply to each job
+/// separately.
+#[test]
+fn it_applies_resource_limits_to_each_job() {
+    let limits = ResourceLimits::unlimited().with_max(ResourceLimit::Pages, 3);
+    // This would ship out pages forever, counting them in \count1.
+    let unbounded_pages = [
+        r"\def\loop{\advance\count1 by1 \shipout\hbox{a}\loop}%",
+        r"\loop",
+    ];
+
+    for _ in 0..2 {
+        let state = TeXState::new();
+        state.set_resource_limits(limits.clone());
+
+        let err = get_resource_limit_exceeded(|| {
+            Parser::new(&unbounded_pages, &state).parse_pages();
+        });
+        assert_eq!(
+            err,
+            ResourceLimitExceeded {
+                limit: ResourceLimit::Pages,
+                max: 3,
+            }
+        );
+        // Each job gets to ship out three pages, and stops at the fourth.
+        assert_eq!(state.get_count(1), 4);
+    }
+}
+
+/// Typesets a paragraph made out of rules, and returns the horizontal extent
+/// of the rules on each line of the resulting page, from top to bottom.
+fn get_line_extents_of_rule_paragraph(setup: &str) -> Vec<(i32, i32)> {
+    let mut file_writer = DVIFileWriter::new();
+    file_writer.start(
+        (25400000, 473628672),
+        1000,
+        b"Made by XymosTeX".to_vec(),
+    );
+
+    with_parser(
+        &[
+            r"\hsize=100pt%",
+            r"\def\w#1{\vrule width#1pt height1pt depth0pt}%",
+            r"\def\s{\hskip10pt plus10pt}%",
+            setup,
+            r"\noindent\w{40}\s\w{40}\s\w{40}\s\w{20}\par%",
+            r"\end%",
+        ],
+        |parser| {
+            let page = parser.parse_outer_vertical_box();
+            file_writer.add_page(
+                &page.list,
+                &None,
+                [1, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            );
+        },
+    );
+    file_writer.end();
+
+    let pages = interpret_dvi_file(file_writer.to_file());
+    let mut extents: BTreeMap<i32, (i32, i32)> = BTreeMap::new();
+    for ((h, v), elems) in pages[0].iter() {
+        for elem in elems {
+            if let DVIOutputElement::Rule { width, .. } = elem {
+                let extent = extents.entry(*v).or_insert((*h, *h + *width));
+                extent.0 = extent.0.min(*h);
+                extent.1 = extent.1.max(*h + *width);
+            }
+        }
+    }
+    extents.into_values().collect()
+}
+
+/// The margin glue and \parfillskip can be combined to get the common
+/// paragraph layouts.
+#[test]
+fn it_lays_out_justified_ragged_and_centered_paragraphs() {
+    let pt =
+        |points: f64| Dimen::from_unit(points, Unit::Point).as_scaled_points();
+
+    let justified = get_line_extents_of_rule_paragraph("%");
+    let left = justified[0].0;
+    assert_eq!(
+        justified,
+        vec![(left, left + pt(100.0)), (left, left + pt(70.0))]
+    );
+
+    // With no \parfillskip, the last line has to be justified too.
+    assert_eq!(
+        get_line_extents_of_rule_paragraph(
+            r"\tolerance=10000 \parfillskip=0pt%"
+        ),
+        vec![(left, left + pt(100.0)), (left, left + pt(100.0))]
+    );
+
+    assert_eq!(
+        get_line_extents_of_rule_paragraph(r"\rightskip=0pt plus1fil%"),
+        vec![(left, left + pt(90.0)), (left, left + pt(70.0))]
+    );
+
+    assert_eq!(
+        get_line_extents_of_rule_paragraph(
+            r"\leftskip=0pt plus1fil \rightskip=0pt plus1fil \parfillskip=0pt%"
+        ),
+        vec![
+            (left + pt(5.0), left + pt(95.0)),
+            (left + pt(15.0), left + pt(85.0))
+        ]
+    );
+}
+
+/// Typesets a page with a 10pt tall rule in a box on it, and returns how far
+/// down the page the bottom of the rule is.
+fn get_rule_position_on_page(above: &str, below: &str) -> i32 {
+    let mut file_writer = DVIFileWriter::new();
+    file_writer.start(
+        (25400000, 473628672),
+        1000,
+        b"Made by XymosTeX".to_vec(),
+    );
+
+    let line = format!(
+        r"{}\hbox{{\vrule width1pt height10pt depth0pt}}{}\end%",
+        above, below
+    );
+    with_parser(&[r"\vsize=100pt%", &line], |parser| {
+        let page = parser.parse_outer_vertical_box();
+        file_writer.add_page(
+            &page.list,
+            &page.glue_set_ratio,
+            [1, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        );
+    });
+    file_writer.end();
+
+    let pages = interpret_dvi_file(file_writer.to_file());
+    let rule_positions: Vec<i32> = pages[0]
+        .iter()
+        .filter(|(_, elems)| {
+            elems
+                .iter()
+                .any(|elem| matches!(elem, DVIOutputElement::Rule { .. }))
+        })
+        .map(|((_, v), _)| *v)
+        .collect();
+    assert_eq!(rule_positions.len(), 1);
+    rule_positions[0]
+}
+
+#[test]
+fn it_stretches_page_glue_to_fill_the_vsize() {
+    let pt =
+        |points: f64| Dimen::from_unit(points, Unit::Point).as_scaled_points();
+
+    // Like in TeX, glue at the top of the page is discarded, so we put an
+    // invisible rule above it to keep it there.
+    let top = get_rule_position_on_page("", r"\vfil");
+    assert_eq!(get_rule_position_on_page(r"\vfil", ""), top);
+    assert_eq!(
+        get_rule_position_on_page(r"\hrule height0pt\vfil", r"\vfil"),
+        top + pt(45.0)
+    );
+    assert_eq!(
+        get_rule_position_on_page(r"\hrule height0pt\vfil", ""),
+        top + pt(90.0)
+    );
+
+    // Infinite glue of a higher order takes all of the stretch.
+    assert_eq!(
+        get_rule_position_on_page(r"\hrule height0pt\vfill", r"\vfil"),
+        top + pt(90.0)
+    );
+}
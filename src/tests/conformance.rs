@@ -0,0 +1,953 @@
+// Conformance tests, each of which checks a single rule from the TeXbook by
+// running a small document and looking at what it produced. Every test names
+// the chapter (or appendix rule) that it comes from, so that when one fails
+// it's clear which rule broke.
+//
+// Rules that aren't implemented yet are still listed here, with their tests
+// marked #[ignore]. it_summarizes_conformance turns the list into a markdown
+// table of which rules are implemented, which gives a map of what's missing.
+use std::fs;
+use std::path::Path;
+
+use crate::boxes::{HorizontalBox, TeXBox, VerticalBox};
+use crate::dimension::{Dimen, Unit};
+use crate::list::{HorizontalListElem, VerticalListElem};
+use crate::math_list::MathStyle;
+use crate::state::TeXState;
+use crate::testing::with_parser;
+
+struct ConformanceRule {
+    // Where the rule comes from, like "Chapter 12" or "Appendix G, rule 18a".
+    source: &'static str,
+    description: &'static str,
+    test_name: &'static str,
+    implemented: bool,
+}
+
+// Defines the conformance tests along with the list of the rules that they
+// check. Each test is written as
+//
+//     rule("Chapter 12", "Description of the rule");
+//     #[ignore = "Reason it isn't implemented"]
+//     fn test_name() { ... }
+//
+// where the attributes are optional. A rule is counted as implemented unless
+// its test is ignored.
+macro_rules! conformance_rules {
+    ($(
+        rule($source:expr, $description:expr $(,)?);
+        $(#[$attr:meta])*
+        fn $name:ident() $body:block
+    )*) => {
+        $(
+            #[test]
+            $(#[$attr])*
+            fn $name() $body
+        )*
+
+        fn get_rules() -> Vec<ConformanceRule> {
+            vec![$(ConformanceRule {
+                source: $source,
+                description: $description,
+                test_name: stringify!($name),
+                implemented: ![$(stringify!($attr)),*]
+                    .iter()
+                    .any(|attr: &&str| attr.starts_with("ignore")),
+            }),*]
+        }
+    };
+}
+
+fn pt(points: f64) -> Dimen {
+    Dimen::from_unit(points, Unit::Point)
+}
+
+fn cmr10_width(chr: char) -> Dimen {
+    let state = TeXState::new();
+    state
+        .with_metrics_for_font(&state.get_current_font(), |metrics| {
+            metrics.get_width(chr)
+        })
+        .unwrap()
+}
+
+// Runs a document in internal vertical mode, and then calls the callback with
+// the state that the document left behind.
+fn run_document<T>(lines: &[&str], check: T)
+where
+    T: FnOnce(&TeXState),
+{
+    with_parser(lines, |parser| {
+        parser.parse_vertical_list(true);
+        check(parser.state);
+    });
+}
+
+fn get_hbox(state: &TeXState, index: u8) -> HorizontalBox {
+    match state.get_box_copy(index) {
+        Some(TeXBox::HorizontalBox(hbox)) => hbox,
+        other => panic!("Expected \\box{} to be an hbox: {:?}", index, other),
+    }
+}
+
+fn get_vbox(state: &TeXState, index: u8) -> VerticalBox {
+    match state.get_box_copy(index) {
+        Some(TeXBox::VerticalBox(vbox)) => vbox,
+        other => panic!("Expected \\box{} to be a vbox: {:?}", index, other),
+    }
+}
+
+// Returns the width that each piece of glue in an hbox ended up with.
+fn get_set_glue_widths(hbox: &HorizontalBox) -> Vec<Dimen> {
+    hbox.list
+        .iter()
+        .filter_map(|elem| match elem {
+            HorizontalListElem::HSkip(glue) => {
+                Some(match &hbox.glue_set_ratio {
+                    Some(ratio) => ratio.apply_to_glue(glue),
+                    None => glue.space,
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+// Returns the lines of the paragraphs in a vbox, along with how far each of
+// them is shifted to the right.
+fn get_lines(vbox: &VerticalBox) -> Vec<(HorizontalBox, Dimen)> {
+    vbox.list
+        .iter()
+        .filter_map(|elem| match elem {
+            VerticalListElem::Box {
+                tex_box: TeXBox::HorizontalBox(hbox),
+                shift,
+            } => Some((hbox.clone(), *shift)),
+            _ => None,
+        })
+        .collect()
+}
+
+// Converts a math list to a horizontal list, starting in text style.
+fn convert_math(lines: &[&str]) -> Vec<HorizontalListElem> {
+    let mut result = Vec::new();
+    with_parser(lines, |parser| {
+        let math_list = parser.parse_math_list();
+        result = parser.convert_math_list_to_horizontal_list(
+            math_list,
+            MathStyle::TextStyle,
+        );
+    });
+    result
+}
+
+fn has_glue(list: &[HorizontalListElem]) -> bool {
+    list.iter()
+        .any(|elem| matches!(elem, HorizontalListElem::HSkip(_)))
+}
+
+conformance_rules! {
+    rule(
+        "Chapter 10",
+        "Physical units convert to scaled points like in TeX",
+    );
+    fn physical_units_convert_exactly() {
+        run_document(
+            &[
+                r"\dimen0=1in\dimen1=1cm\dimen2=1bp %",
+                r"\dimen3=1dd\dimen4=1cc\dimen5=1pc %",
+            ],
+            |state| {
+                assert_eq!(
+                    state.get_dimen(0),
+                    Dimen::from_scaled_points(4736286)
+                );
+                assert_eq!(
+                    state.get_dimen(1),
+                    Dimen::from_scaled_points(1864679)
+                );
+                assert_eq!(
+                    state.get_dimen(2),
+                    Dimen::from_scaled_points(65781)
+                );
+                assert_eq!(
+                    state.get_dimen(3),
+                    Dimen::from_scaled_points(70124)
+                );
+                assert_eq!(
+                    state.get_dimen(4),
+                    Dimen::from_scaled_points(841489)
+                );
+                assert_eq!(
+                    state.get_dimen(5),
+                    Dimen::from_scaled_points(786432)
+                );
+            },
+        );
+    }
+
+    rule(
+        "Chapter 10",
+        "Dimensions can be multiples of internal dimensions",
+    );
+    fn dimensions_multiply_internal_dimensions() {
+        run_document(&[r"\dimen0=2pt\dimen1=-1.5\dimen0 %"], |state| {
+            assert_eq!(state.get_dimen(1), pt(-3.0));
+        });
+    }
+
+    rule(
+        "Chapter 10",
+        "The em and ex units come from the current font",
+    );
+    #[ignore = "em and ex units aren't implemented"]
+    fn em_and_ex_units_use_the_current_font() {
+        run_document(&[r"\dimen0=1em\dimen1=\fontdimen6\font%"], |state| {
+            assert_eq!(state.get_dimen(0), state.get_dimen(1));
+        });
+    }
+
+    rule(
+        "Chapter 12",
+        "An hbox's natural width is the sum of its contents' widths",
+    );
+    fn hbox_natural_width_is_the_sum_of_widths() {
+        run_document(
+            &[r"\setbox0=\hbox{\hskip3pt\hbox to4pt{}\kern2pt}%"],
+            |state| {
+                assert_eq!(get_hbox(state, 0).width, pt(9.0));
+            },
+        );
+    }
+
+    rule(
+        "Chapter 12",
+        "An hbox's height and depth are the largest of its contents'",
+    );
+    fn hbox_height_and_depth_are_maximums() {
+        run_document(
+            &[
+                r"\setbox0=\hbox{\vrule height2pt depth1pt\vrule height5pt depth0pt}%",
+            ],
+            |state| {
+                let hbox = get_hbox(state, 0);
+                assert_eq!(hbox.height, pt(5.0));
+                assert_eq!(hbox.depth, pt(1.0));
+            },
+        );
+    }
+
+    rule(
+        "Chapter 12",
+        "Stretch is distributed in proportion to stretchability",
+    );
+    fn glue_stretches_in_proportion() {
+        run_document(
+            &[r"\setbox0=\hbox to10pt{\hskip0pt plus1pt\hskip0pt plus3pt}%"],
+            |state| {
+                assert_eq!(
+                    get_set_glue_widths(&get_hbox(state, 0)),
+                    vec![pt(2.5), pt(7.5)]
+                );
+            },
+        );
+    }
+
+    rule(
+        "Chapter 12",
+        "Shrink is distributed in proportion to shrinkability",
+    );
+    fn glue_shrinks_in_proportion() {
+        run_document(
+            &[
+                r"\setbox0=\hbox to16pt{\hskip10pt minus1pt\hskip10pt minus3pt}%",
+            ],
+            |state| {
+                assert_eq!(
+                    get_set_glue_widths(&get_hbox(state, 0)),
+                    vec![pt(9.0), pt(7.0)]
+                );
+            },
+        );
+    }
+
+    rule(
+        "Chapter 12",
+        "Glue never shrinks by more than its shrinkability",
+    );
+    fn glue_doesnt_shrink_past_its_shrinkability() {
+        run_document(
+            &[
+                r"\setbox0=\hbox to10pt{\hskip10pt minus1pt\hskip10pt minus1pt}%",
+            ],
+            |state| {
+                let hbox = get_hbox(state, 0);
+                assert_eq!(hbox.width, pt(10.0));
+                assert_eq!(get_set_glue_widths(&hbox), vec![pt(9.0), pt(9.0)]);
+            },
+        );
+    }
+
+    rule("Chapter 12", "Glue can stretch arbitrarily far");
+    fn glue_stretches_past_its_stretchability() {
+        run_document(
+            &[r"\setbox0=\hbox to30pt{\hskip0pt plus1pt\hskip10pt plus1pt}%"],
+            |state| {
+                assert_eq!(
+                    get_set_glue_widths(&get_hbox(state, 0)),
+                    vec![pt(10.0), pt(20.0)]
+                );
+            },
+        );
+    }
+
+    rule(
+        "Chapter 12",
+        "Infinite stretchability overrides finite stretchability",
+    );
+    fn infinite_glue_overrides_finite_glue() {
+        run_document(
+            &[r"\setbox0=\hbox to20pt{\hskip0pt plus1fil\hskip0pt plus100pt}%"],
+            |state| {
+                assert_eq!(
+                    get_set_glue_widths(&get_hbox(state, 0)),
+                    vec![pt(20.0), pt(0.0)]
+                );
+            },
+        );
+    }
+
+    rule(
+        "Chapter 12",
+        "Higher orders of infinity override lower ones",
+    );
+    fn fill_glue_overrides_fil_glue() {
+        run_document(
+            &[r"\setbox0=\hbox to10pt{\hskip0pt plus1fil\hskip0pt plus2fill}%"],
+            |state| {
+                assert_eq!(
+                    get_set_glue_widths(&get_hbox(state, 0)),
+                    vec![pt(0.0), pt(10.0)]
+                );
+            },
+        );
+    }
+
+    rule(
+        "Chapter 12",
+        "A spread box is wider than its natural width by the spread",
+    );
+    fn spread_adds_to_the_natural_width() {
+        run_document(
+            &[r"\setbox0=\hbox spread5pt{\hskip2pt plus1pt\hbox to3pt{}}%"],
+            |state| {
+                let hbox = get_hbox(state, 0);
+                assert_eq!(hbox.width, pt(10.0));
+                assert_eq!(get_set_glue_widths(&hbox), vec![pt(7.0)]);
+            },
+        );
+    }
+
+    rule(
+        "Chapter 12",
+        "Interword glue comes from the font's space parameters",
+    );
+    fn interword_glue_comes_from_the_font() {
+        run_document(
+            &[r"\setbox0=\hbox{a a}\dimen0=\fontdimen2\font %"],
+            |state| {
+                assert_eq!(
+                    get_hbox(state, 0).width,
+                    cmr10_width('a') * 2 + state.get_dimen(0)
+                );
+            },
+        );
+    }
+
+    rule(
+        "Chapter 12",
+        "A nonzero \\spaceskip replaces the interword glue",
+    );
+    #[ignore = "\\spaceskip isn't used for interword glue"]
+    fn spaceskip_replaces_interword_glue() {
+        run_document(&[r"\spaceskip=7pt\setbox0=\hbox{a a}%"], |state| {
+            assert_eq!(
+                get_hbox(state, 0).width,
+                cmr10_width('a') * 2 + pt(7.0)
+            );
+        });
+    }
+
+    rule("Chapter 12", "\\hfil is \\hskip 0pt plus 1fil");
+    #[ignore = "\\hfil isn't implemented"]
+    fn hfil_is_fil_glue() {
+        run_document(
+            &[r"\setbox0=\hbox to10pt{\hfil\hskip0pt plus1pt}%"],
+            |state| {
+                assert_eq!(
+                    get_set_glue_widths(&get_hbox(state, 0)),
+                    vec![pt(10.0), pt(0.0)]
+                );
+            },
+        );
+    }
+
+    rule(
+        "Chapter 12",
+        "The depth of a vbox is the depth of its last box",
+    );
+    fn vbox_depth_is_the_last_depth() {
+        run_document(
+            &[
+                r"\setbox0=\vbox{\hbox{\vrule height2pt depth3pt}%",
+                r"\hbox{\vrule height2pt depth1pt}}%",
+            ],
+            |state| {
+                assert_eq!(get_vbox(state, 0).depth, pt(1.0));
+            },
+        );
+    }
+
+    rule("Chapter 12", "A vbox's height includes its vertical glue");
+    fn vbox_height_includes_glue() {
+        run_document(&[r"\setbox0=\vbox{\vskip3pt\kern4pt}%"], |state| {
+            let vbox = get_vbox(state, 0);
+            assert_eq!(vbox.height, pt(7.0));
+            assert_eq!(vbox.depth, pt(0.0));
+        });
+    }
+
+    rule("Chapter 12", "Stretching glue all the way has badness 100");
+    fn badness_of_full_stretch_is_100() {
+        run_document(
+            &[r"\setbox0=\hbox to11pt{\hskip10pt plus1pt}\count0=\badness %"],
+            |state| {
+                assert_eq!(state.get_count(0), 100);
+            },
+        );
+    }
+
+    rule(
+        "Chapter 12",
+        "A box that needs to stretch without stretchability has badness 10000",
+    );
+    fn badness_without_stretch_is_infinite() {
+        run_document(
+            &[r"\setbox0=\hbox to11pt{\hskip10pt}\count0=\badness %"],
+            |state| {
+                assert_eq!(state.get_count(0), 10000);
+            },
+        );
+    }
+
+    rule("Chapter 14", "Lines of a paragraph are \\hsize wide");
+    fn paragraph_lines_are_hsize_wide() {
+        run_document(
+            &[r"\hsize=100pt\setbox0=\vbox{\noindent a\par}%"],
+            |state| {
+                let lines = get_lines(&get_vbox(state, 0));
+                assert_eq!(lines.len(), 1);
+                assert_eq!(lines[0].0.width, pt(100.0));
+            },
+        );
+    }
+
+    rule(
+        "Chapter 14",
+        "\\parfillskip fills out the last line of a paragraph",
+    );
+    fn parfillskip_fills_the_last_line() {
+        run_document(
+            &[r"\hsize=100pt\setbox0=\vbox{\noindent a\par}%"],
+            |state| {
+                let lines = get_lines(&get_vbox(state, 0));
+                assert_eq!(
+                    get_set_glue_widths(&lines[0].0).last(),
+                    Some(&(pt(100.0) - cmr10_width('a')))
+                );
+            },
+        );
+    }
+
+    rule(
+        "Chapter 14",
+        "\\indent starts a paragraph with a \\parindent box",
+    );
+    fn indent_adds_a_parindent_box() {
+        run_document(
+            &[r"\parindent=15pt\setbox0=\vbox{\indent a\par}%"],
+            |state| {
+                let lines = get_lines(&get_vbox(state, 0));
+                match lines[0].0.list.first() {
+                    Some(HorizontalListElem::Box { tex_box, .. }) => {
+                        assert_eq!(*tex_box.width(), pt(15.0));
+                    }
+                    other => panic!("Expected an indent box, got {:?}", other),
+                }
+            },
+        );
+    }
+
+    rule(
+        "Chapter 14",
+        "\\noindent starts a paragraph without an indent box",
+    );
+    fn noindent_doesnt_add_an_indent_box() {
+        run_document(
+            &[r"\parindent=15pt\setbox0=\vbox{\noindent a\par}%"],
+            |state| {
+                let lines = get_lines(&get_vbox(state, 0));
+                assert!(matches!(
+                    lines[0].0.list.first(),
+                    Some(HorizontalListElem::Char { chr: 'a', .. })
+                ));
+            },
+        );
+    }
+
+    rule(
+        "Chapter 14",
+        "A positive \\hangindent indents lines on the left",
+    );
+    fn hangindent_indents_on_the_left() {
+        run_document(
+            &[
+                r"\hsize=100pt\hangindent=10pt\hangafter=0 %",
+                r"\setbox0=\vbox{\noindent a\par}%",
+            ],
+            |state| {
+                let lines = get_lines(&get_vbox(state, 0));
+                assert_eq!(lines[0].0.width, pt(90.0));
+                assert_eq!(lines[0].1, pt(10.0));
+            },
+        );
+    }
+
+    rule(
+        "Chapter 14",
+        "A negative \\hangindent indents lines on the right",
+    );
+    fn negative_hangindent_indents_on_the_right() {
+        run_document(
+            &[
+                r"\hsize=100pt\hangindent=-10pt\hangafter=0 %",
+                r"\setbox0=\vbox{\noindent a\par}%",
+            ],
+            |state| {
+                let lines = get_lines(&get_vbox(state, 0));
+                assert_eq!(lines[0].0.width, pt(90.0));
+                assert_eq!(lines[0].1, pt(0.0));
+            },
+        );
+    }
+
+    rule(
+        "Chapter 14",
+        "\\hangafter counts lines that aren't indented",
+    );
+    fn hangafter_skips_the_first_lines() {
+        run_document(
+            &[
+                r"\hsize=100pt\hangindent=10pt\hangafter=1 %",
+                r"\setbox0=\vbox{\noindent a\par}%",
+            ],
+            |state| {
+                let lines = get_lines(&get_vbox(state, 0));
+                assert_eq!(lines[0].0.width, pt(100.0));
+                assert_eq!(lines[0].1, pt(0.0));
+            },
+        );
+    }
+
+    rule("Chapter 14", "\\parshape overrides \\hangindent");
+    fn parshape_overrides_hangindent() {
+        run_document(
+            &[
+                r"\hsize=100pt\hangindent=10pt\hangafter=0 %",
+                r"\parshape 1 5pt 50pt %",
+                r"\setbox0=\vbox{\noindent a\par}%",
+            ],
+            |state| {
+                let lines = get_lines(&get_vbox(state, 0));
+                assert_eq!(lines[0].0.width, pt(50.0));
+                assert_eq!(lines[0].1, pt(5.0));
+            },
+        );
+    }
+
+    rule(
+        "Chapter 14",
+        "Breaks are infeasible when their badness exceeds \\tolerance",
+    );
+    fn tolerance_limits_line_badness() {
+        let paragraph = [
+            r"\hsize=110pt\adjdemerits=0 %",
+            r"\def\sp{\hskip 1pt plus3pt{}}%",
+            r"\def\x{\hbox to50pt{a}}%",
+            r"\setbox0=\vbox{\noindent\x\sp\x\sp\x\sp\x\sp\x\par}%",
+        ];
+
+        let mut loose = vec![r"\tolerance=2700 %"];
+        loose.extend(&paragraph);
+        run_document(&loose, |state| {
+            assert_eq!(get_lines(&get_vbox(state, 0)).len(), 3);
+        });
+
+        let mut tight = vec![r"\tolerance=2600 %"];
+        tight.extend(&paragraph);
+        run_document(&tight, |state| {
+            assert_eq!(get_lines(&get_vbox(state, 0)).len(), 2);
+        });
+    }
+
+    rule(
+        "Chapter 14",
+        "\\prevgraf is the number of lines in the last paragraph",
+    );
+    fn prevgraf_counts_lines() {
+        run_document(
+            &[
+                r"\hsize=110pt\tolerance=10000 %",
+                r"\def\sp{\hskip 1pt plus3pt{}}%",
+                r"\def\x{\hbox to50pt{a}}%",
+                r"\setbox0=\vbox{\noindent\x\sp\x\sp\x\sp\x\sp\x\par",
+                r"\global\count0=\prevgraf}%",
+            ],
+            |state| {
+                assert_eq!(
+                    state.get_count(0),
+                    get_lines(&get_vbox(state, 0)).len() as i32
+                );
+                assert!(state.get_count(0) > 1);
+            },
+        );
+    }
+
+    rule(
+        "Chapter 14",
+        "Unbroken discretionaries contribute their no-break text",
+    );
+    fn unbroken_discretionaries_use_no_break_text() {
+        run_document(
+            &[r"\setbox0=\hbox{a\discretionary{b}{c}{dd}a}%"],
+            |state| {
+                assert_eq!(
+                    get_hbox(state, 0).width,
+                    cmr10_width('a') * 2 + cmr10_width('d') * 2
+                );
+            },
+        );
+    }
+
+    rule("Chapter 14", "Lines can be broken at \\penalty items");
+    #[ignore = "\\penalty isn't implemented"]
+    fn lines_break_at_penalties() {
+        run_document(
+            &[r"\hsize=100pt\setbox0=\vbox{\noindent a\penalty-10000 b\par}%"],
+            |state| {
+                assert_eq!(get_lines(&get_vbox(state, 0)).len(), 2);
+            },
+        );
+    }
+
+    rule(
+        "Chapter 5",
+        "Local assignments are undone at the end of a group",
+    );
+    fn groups_undo_local_assignments() {
+        run_document(&[r"\count0=1 {\count0=2 \count1=3 }%"], |state| {
+            assert_eq!(state.get_count(0), 1);
+            assert_eq!(state.get_count(1), 0);
+        });
+    }
+
+    rule(
+        "Chapter 5",
+        "\\global assignments survive the end of a group",
+    );
+    fn global_assignments_survive_groups() {
+        run_document(&[r"{\global\count0=2 \global\dimen0=1pt}%"], |state| {
+            assert_eq!(state.get_count(0), 2);
+            assert_eq!(state.get_dimen(0), pt(1.0));
+        });
+    }
+
+    rule("Chapter 7", "\\uppercase converts characters with \\uccode");
+    fn uppercase_uses_uccodes() {
+        run_document(
+            &[r"\uppercase{\count0=`a}\uccode`a=`z \uppercase{\count1=`a}%"],
+            |state| {
+                assert_eq!(state.get_count(0), 'A' as i32);
+                assert_eq!(state.get_count(1), 'z' as i32);
+            },
+        );
+    }
+
+    rule(
+        "Chapter 20",
+        "Undelimited parameters take one token or group, without braces",
+    );
+    fn undelimited_parameters_strip_braces() {
+        run_document(&[r"\def\a#1#2{#2#1}\count0=\a{12}3 %"], |state| {
+            assert_eq!(state.get_count(0), 312);
+        });
+    }
+
+    rule(
+        "Chapter 20",
+        "Delimited parameters extend up to their delimiters",
+    );
+    fn delimited_parameters_end_at_delimiters() {
+        run_document(&[r"\def\a#1.#2.{#2#1}\count0=\a12.34. %"], |state| {
+            assert_eq!(state.get_count(0), 3412);
+        });
+    }
+
+    rule(
+        "Chapter 20",
+        "\\edef expands its replacement text when it is defined",
+    );
+    fn edef_expands_when_defined() {
+        run_document(
+            &[r"\count1=5 \edef\a{\number\count1}\count1=6 \count0=\a %"],
+            |state| {
+                assert_eq!(state.get_count(0), 5);
+            },
+        );
+    }
+
+    rule(
+        "Chapter 20",
+        "\\expandafter expands the token after the next one",
+    );
+    fn expandafter_expands_the_second_token() {
+        run_document(
+            &[
+                r"\def\a{\b}\def\b{4}\def\c#1{\ifx#1\b 1\else 2\fi}%",
+                r"\count0=\expandafter\c\a %",
+                r"\count1=\c\a %",
+            ],
+            |state| {
+                assert_eq!(state.get_count(0), 1);
+                assert_eq!(state.get_count(1), 2);
+            },
+        );
+    }
+
+    rule("Chapter 20", "\\ifnum compares integers");
+    fn ifnum_compares_integers() {
+        run_document(
+            &[r"\count0=\ifnum1<2 5\else6\fi\count1=\ifnum-1>0 5\else6\fi%"],
+            |state| {
+                assert_eq!(state.get_count(0), 5);
+                assert_eq!(state.get_count(1), 6);
+            },
+        );
+    }
+
+    rule("Chapter 20", "\\ifdim compares dimensions");
+    fn ifdim_compares_dimensions() {
+        run_document(&[r"\count0=\ifdim1in>72pt 5\else6\fi%"], |state| {
+            assert_eq!(state.get_count(0), 5);
+        });
+    }
+
+    rule(
+        "Chapter 20",
+        "\\ifcase selects the case with the given number",
+    );
+    fn ifcase_selects_cases() {
+        run_document(
+            &[
+                r"\count0=\ifcase2 4\or5\or6\else7\fi\count1=\ifcase9 4\or5\else7\fi%",
+            ],
+            |state| {
+                assert_eq!(state.get_count(0), 6);
+                assert_eq!(state.get_count(1), 7);
+            },
+        );
+    }
+
+    rule("Chapter 20", "\\ifx compares the meanings of macros");
+    fn ifx_compares_macro_meanings() {
+        run_document(
+            &[
+                r"\def\a{x}\def\b{x}\def\c{y}%",
+                r"\count0=\ifx\a\b1\else2\fi\count1=\ifx\a\c1\else2\fi%",
+            ],
+            |state| {
+                assert_eq!(state.get_count(0), 1);
+                assert_eq!(state.get_count(1), 2);
+            },
+        );
+    }
+
+    rule("Chapter 20", "\\ifcat compares category codes");
+    fn ifcat_compares_categories() {
+        run_document(
+            &[r"\count0=\ifcat ab1\else2\fi\count1=\ifcat a11\else2\fi%"],
+            |state| {
+                assert_eq!(state.get_count(0), 1);
+                assert_eq!(state.get_count(1), 2);
+            },
+        );
+    }
+
+    rule(
+        "Appendix G, rule 5",
+        "A Bin atom at the start of a list becomes Ord",
+    );
+    fn leading_bin_atoms_become_ord() {
+        // An Ord next to an Ord gets no space, but a Bin gets medium spaces.
+        let plus = r#"\mathcode`+="202B%"#;
+        assert!(!has_glue(&convert_math(&[plus, r"+a%"])));
+        assert!(has_glue(&convert_math(&[plus, r"a+a%"])));
+    }
+
+    rule(
+        "Appendix G, rule 13",
+        "Op atoms in display style use the next larger glyph",
+    );
+    fn display_ops_use_successors() {
+        let find_chars = |list: &[HorizontalListElem]| -> Vec<char> {
+            list.iter()
+                .filter_map(|elem| match elem {
+                    HorizontalListElem::Box { tex_box, .. } => {
+                        tex_box.glyphs().next().map(|(chr, _)| chr)
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let sum = r#"\mathchardef\sum="1350%"#;
+        assert_eq!(find_chars(&convert_math(&[sum, r"\sum%"])), vec!['\x50']);
+        assert_eq!(
+            find_chars(&convert_math(&[sum, r"\displaystyle\sum%"])),
+            vec!['\x58']
+        );
+    }
+
+    rule(
+        "Appendix G, rule 15",
+        "Fractions stack the numerator over the denominator",
+    );
+    fn fractions_are_stacked() {
+        let list = convert_math(&[r"{a\atop b}%"]);
+        assert!(list.iter().any(|elem| matches!(
+            elem,
+            HorizontalListElem::Box {
+                tex_box: TeXBox::VerticalBox(_),
+                ..
+            }
+        )));
+    }
+
+    rule(
+        "Appendix G, rule 15",
+        "\\over draws a rule between the numerator and denominator",
+    );
+    #[ignore = "\\over isn't implemented"]
+    fn over_draws_fraction_rules() {
+        let list = convert_math(&[r"{a\over b}%"]);
+        assert_eq!(list.len(), 1);
+    }
+
+    rule(
+        "Appendix G, rule 18",
+        "Superscripts are raised above the baseline",
+    );
+    fn superscripts_are_raised() {
+        let list = convert_math(&[r"a^b%"]);
+        let shifts: Vec<Dimen> = list
+            .iter()
+            .filter_map(|elem| match elem {
+                HorizontalListElem::Box { shift, .. } => Some(*shift),
+                _ => None,
+            })
+            .collect();
+        assert!(shifts.iter().any(|shift| *shift < Dimen::zero()));
+    }
+
+    rule(
+        "Appendix G, rule 20",
+        "Relations get no space in script styles",
+    );
+    fn script_style_relations_have_no_space() {
+        let equals = r#"\mathcode`=="303D%"#;
+        assert!(has_glue(&convert_math(&[equals, r"a=b%"])));
+        assert!(!has_glue(&convert_math(&[equals, r"\scriptstyle a=b%"])));
+    }
+
+    rule(
+        "Appendix G, rule 19",
+        "\\left and \\right delimiters grow to cover the formula",
+    );
+    #[ignore = "\\left and \\right aren't implemented"]
+    fn left_and_right_delimiters_grow() {
+        let list = convert_math(&[r"\left(a\over b\right)%"]);
+        assert_eq!(list.len(), 3);
+    }
+
+    rule(
+        "Appendix G, rule 11",
+        "Radicals are drawn over their nucleus",
+    );
+    #[ignore = "\\radical isn't implemented"]
+    fn radicals_cover_the_nucleus() {
+        let list = convert_math(&[r#"\radical"270370 a%"#]);
+        assert_eq!(list.len(), 1);
+    }
+
+    rule(
+        "Appendix G, rule 9",
+        "\\overline puts a rule over the nucleus",
+    );
+    #[ignore = "\\overline isn't implemented"]
+    fn overlines_add_rules() {
+        let list = convert_math(&[r"\overline a%"]);
+        assert_eq!(list.len(), 1);
+    }
+}
+
+// Formats the rules as a markdown table, followed by a count of how many of
+// them are implemented.
+fn format_conformance_summary(rules: &[ConformanceRule]) -> String {
+    let mut summary = String::new();
+    summary.push_str("| Source | Rule | Test | Implemented |\n");
+    summary.push_str("| --- | --- | --- | --- |\n");
+    for rule in rules {
+        summary.push_str(&format!(
+            "| {} | {} | `{}` | {} |\n",
+            rule.source,
+            rule.description,
+            rule.test_name,
+            if rule.implemented { "yes" } else { "no" },
+        ));
+    }
+
+    let num_implemented = rules.iter().filter(|rule| rule.implemented).count();
+    summary.push_str(&format!(
+        "\n{} of {} rules implemented\n",
+        num_implemented,
+        rules.len()
+    ));
+    summary
+}
+
+/// Writes the table of which rules are implemented to
+/// target/conformance.md.
+#[test]
+fn it_summarizes_conformance() {
+    let rules = get_rules();
+    let summary = format_conformance_summary(&rules);
+
+    let lines: Vec<&str> = summary.lines().collect();
+    assert_eq!(lines.len(), rules.len() + 4);
+    assert!(lines[2].contains("`physical_units_convert_exactly` | yes"));
+    assert!(summary.contains("`hfil_is_fil_glue` | no"));
+
+    let target_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target");
+    fs::create_dir_all(&target_dir).unwrap();
+    fs::write(target_dir.join("conformance.md"), summary).unwrap();
+}
@@ -1,5 +1,10 @@
+use std::collections::HashMap;
+
 use crate::dimension::{Dimen, Unit};
-use crate::tfm::{CharInfoEntry, CharKind, TFMFile};
+use crate::tfm::{
+    CharInfoEntry, CharKind, ExtensibleRecipe, LigKernAction, LigKernKind,
+    LigKernStep, TFMFile,
+};
 
 impl TFMFile {
     pub fn get_design_size(&self) -> f64 {
@@ -18,6 +23,15 @@ impl TFMFile {
         &self.char_infos[char_info_index]
     }
 
+    /// Returns whether the font has metrics for the given character. Like in
+    /// TeX, characters with a zero width index don't exist.
+    pub fn has_char(&self, chr: char) -> bool {
+        let char_index = chr as usize;
+        self.first_char <= char_index
+            && char_index <= self.last_char
+            && self.char_infos[char_index - self.first_char].width_index != 0
+    }
+
     pub fn get_width(&self, chr: char) -> Dimen {
         let char_info = self.get_char_info(chr);
 
@@ -49,6 +63,10 @@ impl TFMFile {
         self.header.checksum
     }
 
+    pub fn get_num_font_dimensions(&self) -> usize {
+        self.font_parameters.len()
+    }
+
     pub fn get_font_dimension(&self, dimen_number: usize) -> Dimen {
         Dimen::from_unit(
             self.header.design_size * self.font_parameters[dimen_number - 1],
@@ -64,6 +82,120 @@ impl TFMFile {
             _ => chr,
         }
     }
+
+    pub fn get_extensible_recipe(&self, chr: char) -> Option<ExtensibleRecipe> {
+        let char_info = self.get_char_info(chr);
+
+        match char_info.kind {
+            CharKind::Extensible { ext_recipe_index } => {
+                let recipe = &self.ext_recipes[ext_recipe_index];
+                // A piece of 0 means that the piece is absent
+                let piece = |chr: usize| {
+                    if chr == 0 {
+                        None
+                    } else {
+                        Some(chr as u8 as char)
+                    }
+                };
+
+                Some(ExtensibleRecipe {
+                    top: piece(recipe.top),
+                    mid: piece(recipe.mid),
+                    bot: piece(recipe.bot),
+                    rep: recipe.ext as u8 as char,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn get_lig_kern_action(&self, step: &LigKernStep) -> LigKernAction {
+        match step.kind {
+            LigKernKind::Ligature { substitution } => {
+                LigKernAction::Ligature(substitution as u8 as char)
+            }
+            LigKernKind::Kern { kern_index } => {
+                LigKernAction::Kern(Dimen::from_unit(
+                    self.header.design_size * self.kerns[kern_index],
+                    Unit::Point,
+                ))
+            }
+        }
+    }
+
+    /// Runs the lig/kern program for every character in the font, returning
+    /// the action for each pair of characters that has one. Only the first
+    /// step for a given pair applies, so later ones are ignored.
+    pub fn get_lig_kern_table(&self) -> HashMap<(char, char), LigKernAction> {
+        let mut table = HashMap::new();
+
+        for (i, char_info) in self.char_infos.iter().enumerate() {
+            let left = (self.first_char + i) as u8 as char;
+
+            let mut index = match char_info.kind {
+                CharKind::LigKern { ligkern_index } => ligkern_index,
+                _ => continue,
+            };
+
+            loop {
+                let step = &self.lig_kern_steps[index];
+                table
+                    .entry((left, step.next_char as u8 as char))
+                    .or_insert_with(|| self.get_lig_kern_action(step));
+                if step.stop {
+                    break;
+                }
+                index += 1;
+            }
+        }
+
+        table
+    }
+
+    // Finds the step in the lig/kern program for `left` that applies when it
+    // is followed by `right`, if there is one. FontMetrics uses the table from
+    // get_lig_kern_table() instead of walking the program for every pair, so
+    // this is only used to check that the table matches the program.
+    #[cfg(test)]
+    fn get_lig_kern_step(
+        &self,
+        left: char,
+        right: char,
+    ) -> Option<&LigKernStep> {
+        let char_info = self.get_char_info(left);
+
+        let mut index = match char_info.kind {
+            CharKind::LigKern { ligkern_index } => ligkern_index,
+            _ => return None,
+        };
+
+        loop {
+            let step = &self.lig_kern_steps[index];
+            if step.next_char == right as usize {
+                return Some(step);
+            }
+            if step.stop {
+                return None;
+            }
+            index += 1;
+        }
+    }
+
+    #[cfg(test)]
+    pub fn get_ligature(&self, left: char, right: char) -> Option<char> {
+        match self.get_lig_kern_action(self.get_lig_kern_step(left, right)?) {
+            LigKernAction::Ligature(chr) => Some(chr),
+            LigKernAction::Kern(_) => None,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn get_kern(&self, left: char, right: char) -> Option<Dimen> {
+        match self.get_lig_kern_action(self.get_lig_kern_step(left, right)?) {
+            LigKernAction::Ligature(_) => None,
+            LigKernAction::Kern(kern) => Some(kern),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -71,6 +203,7 @@ mod tests {
     use super::*;
 
     use crate::tfm::test_data::{BASIC_TFM, CMR10_TFM};
+    use crate::tfm::ExtRecipe;
 
     #[test]
     fn get_basic_dimensions() {
@@ -138,4 +271,90 @@ mod tests {
             Dimen::from_scaled_points(72818)
         );
     }
+
+    #[test]
+    fn get_cmr10_ligatures() {
+        let font_metrics = TFMFile::new(CMR10_TFM).unwrap();
+
+        assert_eq!(font_metrics.get_ligature('f', 'f'), Some(11 as char));
+        assert_eq!(font_metrics.get_ligature('f', 'i'), Some(12 as char));
+        assert_eq!(font_metrics.get_ligature('f', 'l'), Some(13 as char));
+        assert_eq!(
+            font_metrics.get_ligature(11 as char, 'i'),
+            Some(14 as char)
+        );
+        assert_eq!(font_metrics.get_ligature('-', '-'), Some(123 as char));
+        assert_eq!(
+            font_metrics.get_ligature(123 as char, '-'),
+            Some(124 as char)
+        );
+
+        assert_eq!(font_metrics.get_ligature('a', 'b'), None);
+        // This pair has a kern, but no ligature
+        assert_eq!(font_metrics.get_ligature('A', 'V'), None);
+    }
+
+    #[test]
+    fn get_cmr10_kerns() {
+        let font_metrics = TFMFile::new(CMR10_TFM).unwrap();
+
+        assert!(font_metrics.get_kern('A', 'V').unwrap() < Dimen::zero());
+        assert!(font_metrics.get_kern('V', 'A').unwrap() < Dimen::zero());
+        assert!(font_metrics.get_kern('k', 'a').unwrap() < Dimen::zero());
+
+        assert_eq!(font_metrics.get_kern('a', 'b'), None);
+        // This pair has a ligature, but no kern
+        assert_eq!(font_metrics.get_kern('f', 'i'), None);
+    }
+
+    #[test]
+    fn it_builds_lig_kern_tables_matching_the_lig_kern_program() {
+        let font_metrics = TFMFile::new(CMR10_TFM).unwrap();
+        let table = font_metrics.get_lig_kern_table();
+
+        for left in 0..128u8 {
+            for right in 0..128u8 {
+                let (left, right) = (left as char, right as char);
+
+                let expected = match (
+                    font_metrics.get_ligature(left, right),
+                    font_metrics.get_kern(left, right),
+                ) {
+                    (Some(chr), None) => Some(LigKernAction::Ligature(chr)),
+                    (None, Some(kern)) => Some(LigKernAction::Kern(kern)),
+                    (None, None) => None,
+                    _ => panic!("Pair has both a ligature and a kern"),
+                };
+
+                assert_eq!(table.get(&(left, right)).copied(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn get_extensible_recipes() {
+        let mut font_metrics = TFMFile::new(&BASIC_TFM[..]).unwrap();
+        assert_eq!(font_metrics.get_extensible_recipe('a'), None);
+
+        // None of our test fonts have extensible characters, so we make one
+        font_metrics.ext_recipes.push(ExtRecipe {
+            top: 0,
+            mid: 0,
+            bot: 0x61,
+            ext: 0x61,
+        });
+        font_metrics.char_infos[0].kind = CharKind::Extensible {
+            ext_recipe_index: 0,
+        };
+
+        assert_eq!(
+            font_metrics.get_extensible_recipe('a'),
+            Some(ExtensibleRecipe {
+                top: None,
+                mid: None,
+                bot: Some('a'),
+                rep: 'a',
+            })
+        );
+    }
 }
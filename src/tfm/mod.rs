@@ -1,3 +1,23 @@
+use crate::dimension::Dimen;
+
+/// The result of running a font's lig/kern program on a pair of characters.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LigKernAction {
+    Ligature(char),
+    Kern(Dimen),
+}
+
+/// The pieces that a character with an extensible recipe is built from. The
+/// top, middle, and bottom pieces are optional, and the repeated piece is
+/// stacked as many times as needed to reach the desired size.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ExtensibleRecipe {
+    pub top: Option<char>,
+    pub mid: Option<char>,
+    pub bot: Option<char>,
+    pub rep: char,
+}
+
 #[derive(Debug, PartialEq)]
 struct TFMHeader {
     checksum: u32,
@@ -5,3 +5,194 @@ pub enum Token {
     ControlSequence(String),
     Char(char, Category),
 }
+
+// Whether a control sequence name is printed with a space after it. TeX puts
+// a space after control words so that they can be read back in correctly, but
+// not after control symbols like \$ or \ .
+//
+// TODO(xymostech): This should look at the current category of single
+// character names instead of assuming that only ASCII letters are letters.
+fn is_control_word(name: &str) -> bool {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) => ch.is_ascii_alphabetic(),
+        _ => true,
+    }
+}
+
+fn push_escaped_name(result: &mut String, name: &str, escapechar: i32) {
+    if (0..256).contains(&escapechar) {
+        result.push(escapechar as u8 as char);
+    }
+    result.push_str(name);
+}
+
+/// Returns the name of a control sequence prefixed with `escapechar`, like
+/// \string does.
+pub fn escape_name(name: &str, escapechar: i32) -> String {
+    let mut result = String::new();
+    push_escaped_name(&mut result, name, escapechar);
+    result
+}
+
+/// Converts a list of tokens to the string that TeX would print for them,
+/// like when showing the contents of a macro or writing to a file. Control
+/// sequences are prefixed with `escapechar`, or nothing if `escapechar` isn't
+/// a valid character code.
+pub fn stringify(tokens: &[Token], escapechar: i32) -> String {
+    let mut result = String::new();
+
+    for token in tokens {
+        match token {
+            // The empty control sequence can only be made with \csname, so
+            // it gets printed that way.
+            Token::ControlSequence(name) if name.is_empty() => {
+                push_escaped_name(&mut result, "csname", escapechar);
+                push_escaped_name(&mut result, "endcsname", escapechar);
+                result.push(' ');
+            }
+            Token::ControlSequence(name) => {
+                push_escaped_name(&mut result, name, escapechar);
+                if is_control_word(name) {
+                    result.push(' ');
+                }
+            }
+            // Parameter characters are doubled so that the result can be
+            // used as the body of a macro.
+            Token::Char(ch, Category::Parameter) => {
+                result.push(*ch);
+                result.push(*ch);
+            }
+            Token::Char(ch, _) => {
+                result.push(*ch);
+            }
+        }
+    }
+
+    result
+}
+
+/// Converts a list of character tokens to the string of their characters,
+/// like \csname does to build the name of a control sequence. Unlike
+/// stringify(), parameter characters aren't doubled.
+pub fn stringify_characters(tokens: &[Token]) -> String {
+    tokens
+        .iter()
+        .map(|token| match token {
+            Token::Char(ch, _) => *ch,
+            Token::ControlSequence(name) => {
+                panic!("Can't stringify control sequence {:?}", name)
+            }
+        })
+        .collect()
+}
+
+/// Converts a string into a list of tokens, like TeX does for the result of
+/// \string and \meaning. Every character becomes an Other token except for
+/// spaces, which become Space tokens.
+pub fn tokenize_other(string: &str) -> Vec<Token> {
+    string
+        .chars()
+        .map(|ch| {
+            if ch == ' ' {
+                Token::Char(ch, Category::Space)
+            } else {
+                Token::Char(ch, Category::Other)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_stringifies_characters() {
+        assert_eq!(
+            stringify(
+                &[
+                    Token::Char('a', Category::Letter),
+                    Token::Char('1', Category::Other),
+                    Token::Char(' ', Category::Space),
+                    Token::Char('{', Category::BeginGroup),
+                    Token::Char('}', Category::EndGroup),
+                    Token::Char('~', Category::Active),
+                ],
+                '\\' as i32
+            ),
+            "a1 {}~"
+        );
+    }
+
+    #[test]
+    fn it_stringifies_control_sequences_with_correct_spacing() {
+        assert_eq!(
+            stringify(
+                &[
+                    Token::ControlSequence("hbox".to_string()),
+                    Token::Char('{', Category::BeginGroup),
+                    Token::ControlSequence("a".to_string()),
+                    Token::ControlSequence("$".to_string()),
+                    Token::ControlSequence(" ".to_string()),
+                    Token::ControlSequence("".to_string()),
+                    Token::Char('}', Category::EndGroup),
+                ],
+                '\\' as i32
+            ),
+            "\\hbox {\\a \\$\\ \\csname\\endcsname }"
+        );
+    }
+
+    #[test]
+    fn it_doubles_parameter_tokens() {
+        assert_eq!(
+            stringify(
+                &[
+                    Token::Char('#', Category::Parameter),
+                    Token::Char('1', Category::Other),
+                ],
+                '\\' as i32
+            ),
+            "##1"
+        );
+    }
+
+    #[test]
+    fn it_stringifies_characters_without_doubling_parameters() {
+        assert_eq!(
+            stringify_characters(&[
+                Token::Char('a', Category::Letter),
+                Token::Char('#', Category::Parameter),
+                Token::Char(' ', Category::Space),
+            ]),
+            "a# "
+        );
+    }
+
+    #[test]
+    fn it_uses_escapechar_for_control_sequences() {
+        let tokens = [
+            Token::ControlSequence("relax".to_string()),
+            Token::ControlSequence("%".to_string()),
+        ];
+
+        assert_eq!(stringify(&tokens, '/' as i32), "/relax /%");
+        assert_eq!(stringify(&tokens, -1), "relax %");
+        assert_eq!(stringify(&tokens, 256), "relax %");
+    }
+
+    #[test]
+    fn it_tokenizes_strings_to_other_tokens() {
+        assert_eq!(
+            tokenize_other("\\a b#"),
+            vec![
+                Token::Char('\\', Category::Other),
+                Token::Char('a', Category::Other),
+                Token::Char(' ', Category::Space),
+                Token::Char('b', Category::Other),
+                Token::Char('#', Category::Other),
+            ]
+        );
+    }
+}
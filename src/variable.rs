@@ -1,6 +1,10 @@
 use crate::dimension::Dimen;
 use crate::glue::Glue;
-use crate::state::{DimenParameter, GlueParameter, IntegerParameter, TeXState};
+use crate::state::{
+    DimenParameter, GlueParameter, IntegerParameter, TeXState,
+    TokenListParameter,
+};
+use crate::token::Token;
 
 #[derive(PartialEq, Eq, Debug)]
 pub enum IntegerVariable {
@@ -32,6 +36,7 @@ impl IntegerVariable {
 
 #[derive(PartialEq, Eq, Debug)]
 pub enum DimenVariable {
+    DimenRegister(u8),
     BoxWidth(u8),
     BoxHeight(u8),
     BoxDepth(u8),
@@ -41,6 +46,7 @@ pub enum DimenVariable {
 impl DimenVariable {
     pub fn get(&self, state: &TeXState) -> Dimen {
         match self {
+            Self::DimenRegister(index) => state.get_dimen(*index),
             Self::BoxWidth(index) => state
                 .with_box(*index, |tex_box| *tex_box.width())
                 .unwrap_or_else(Dimen::zero),
@@ -56,6 +62,9 @@ impl DimenVariable {
 
     pub fn set(&self, state: &TeXState, global: bool, new_dimen: Dimen) {
         match self {
+            Self::DimenRegister(index) => {
+                state.set_dimen(global, *index, &new_dimen)
+            }
             Self::BoxWidth(index) => {
                 state.with_box(*index, |tex_box| {
                     *tex_box.mut_width() = new_dimen
@@ -80,18 +89,23 @@ impl DimenVariable {
 
 #[derive(PartialEq, Eq, Debug)]
 pub enum GlueVariable {
+    SkipRegister(u8),
     Parameter(GlueParameter),
 }
 
 impl GlueVariable {
     pub fn get(&self, state: &TeXState) -> Glue {
         match self {
+            Self::SkipRegister(index) => state.get_skip(*index),
             Self::Parameter(parameter) => state.get_glue_parameter(parameter),
         }
     }
 
     pub fn set(&self, state: &TeXState, global: bool, new_glue: Glue) {
         match self {
+            Self::SkipRegister(index) => {
+                state.set_skip(global, *index, &new_glue)
+            }
             Self::Parameter(parameter) => {
                 state.set_glue_parameter(global, parameter, &new_glue)
             }
@@ -99,6 +113,34 @@ impl GlueVariable {
     }
 }
 
+#[derive(PartialEq, Eq, Debug)]
+pub enum TokenListVariable {
+    ToksRegister(u8),
+    Parameter(TokenListParameter),
+}
+
+impl TokenListVariable {
+    pub fn get(&self, state: &TeXState) -> Vec<Token> {
+        match self {
+            Self::ToksRegister(index) => state.get_toks(*index),
+            Self::Parameter(parameter) => {
+                state.get_token_list_parameter(parameter)
+            }
+        }
+    }
+
+    pub fn set(&self, state: &TeXState, global: bool, tokens: Vec<Token>) {
+        match self {
+            Self::ToksRegister(index) => {
+                state.set_toks(global, *index, &tokens)
+            }
+            Self::Parameter(parameter) => {
+                state.set_token_list_parameter(global, parameter, &tokens)
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;